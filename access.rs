@@ -0,0 +1,263 @@
+//! Web server access logs (the Common and Combined formats of Apache and
+//! nginx) and the traffic analysis for `logger access`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::metrics::{self, Summary};
+use crate::useragent::{self, Agent};
+use crate::LogEntry;
+
+/// Parses `127.0.0.1 - frank [10/Oct/2024:13:55:36 -0700] "GET /a HTTP/1.1"
+/// 200 2326 "referer" "agent"`, with an optional response time after it
+/// (nginx's `$request_time`, in seconds). 5xx responses are ERROR, 4xx
+/// WARNING, everything else INFO. The User-Agent is also broken down into
+/// `browser`, `os` and `bot` fields.
+pub fn parse(line: &str) -> Option<LogEntry> {
+    static ACCESS: OnceLock<Regex> = OnceLock::new();
+    let regex = ACCESS.get_or_init(|| {
+        Regex::new(
+            r#"^(\S+) \S+ (\S+) \[([^\]]+)\] "([A-Z]+) (\S+)(?: [^"]*)?" (\d{3}) (\d+|-)(?: "([^"]*)" "([^"]*)")?(?: (\d+(?:\.\d+)?))?\s*$"#,
+        )
+        .unwrap()
+    });
+    let captures = regex.captures(line)?;
+    let text = |i: usize| captures.get(i).map_or("", |m| m.as_str());
+    let time = DateTime::parse_from_str(text(3), "%d/%b/%Y:%H:%M:%S %z").ok()?;
+    let status: u16 = text(6).parse().ok()?;
+    let mut fields = Map::new();
+    let mut set = |key: &str, value: Value| {
+        fields.insert(key.to_string(), value);
+    };
+    set("remote_addr", Value::from(text(1)));
+    if text(2) != "-" {
+        set("user", Value::from(text(2)));
+    }
+    set("method", Value::from(text(4)));
+    set("path", Value::from(text(5)));
+    set("status", Value::from(status));
+    if let Ok(bytes) = text(7).parse::<u64>() {
+        set("bytes", Value::from(bytes));
+    }
+    if !matches!(text(8), "" | "-") {
+        set("referer", Value::from(text(8)));
+    }
+    if !matches!(text(9), "" | "-") {
+        let agent = useragent::parse(text(9));
+        set("user_agent", Value::from(text(9)));
+        set("browser", Value::from(agent.browser));
+        set("os", Value::from(agent.os));
+        set("bot", Value::from(agent.bot));
+    }
+    if let Some(seconds) = captures
+        .get(10)
+        .and_then(|m| m.as_str().parse::<f64>().ok())
+    {
+        set("response_time", Value::from(seconds));
+    }
+    let level = match status {
+        500.. => "ERROR",
+        400..=499 => "WARNING",
+        _ => "INFO",
+    };
+    Some(LogEntry {
+        timestamp: time
+            .with_timezone(&Local)
+            .format(crate::TIMESTAMP_FORMAT)
+            .to_string(),
+        level: level.to_string(),
+        message: format!("{} {} {}", text(4), text(5), status),
+        fields,
+        source: None,
+    })
+}
+
+/// Response time in milliseconds: `response_time` or `request_time` in
+/// seconds (nginx), else `duration_ms` or `latency_ms`.
+fn latency_ms(entry: &LogEntry) -> Option<f64> {
+    let number = |key: &str| {
+        entry.fields.get(key).and_then(|value| match value {
+            Value::String(text) => text.parse().ok(),
+            other => other.as_f64(),
+        })
+    };
+    number("response_time")
+        .or_else(|| number("request_time"))
+        .map(|seconds| seconds * 1000.0)
+        .or_else(|| number("duration_ms"))
+        .or_else(|| number("latency_ms"))
+}
+
+/// The client of a request: the `browser`, `os` and `bot` fields, else
+/// parsed from a `user_agent` or `http_user_agent` field.
+pub fn agent(entry: &LogEntry) -> Option<Agent> {
+    let field = |key: &str| entry.fields.get(key);
+    if let (Some(Value::String(browser)), Some(Value::Bool(bot))) = (field("browser"), field("bot"))
+    {
+        return Some(Agent {
+            browser: browser.clone(),
+            os: field("os")
+                .and_then(Value::as_str)
+                .unwrap_or("Other")
+                .to_string(),
+            bot: *bot,
+        });
+    }
+    field("user_agent")
+        .or_else(|| field("http_user_agent"))
+        .and_then(Value::as_str)
+        .map(useragent::parse)
+}
+
+fn status(entry: &LogEntry) -> Option<u16> {
+    let value = entry.fields.get("status")?;
+    match value {
+        Value::String(text) => text.parse().ok(),
+        other => other.as_u64().and_then(|n| u16::try_from(n).ok()),
+    }
+}
+
+/// `/users/123/orders?page=2` as `/users/:id/orders`: the query is dropped
+/// and segments that look like IDs are folded, so one endpoint's requests
+/// count together.
+pub fn endpoint(path: &str) -> String {
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    let segments: Vec<&str> = path
+        .split('/')
+        .map(|segment| {
+            let digits = segment.chars().filter(char::is_ascii_digit).count();
+            let id_like = !segment.is_empty()
+                && (segment.chars().all(|c| c.is_ascii_digit())
+                    || (segment.len() >= 16
+                        && segment.chars().all(|c| c.is_ascii_hexdigit() || c == '-'))
+                    || digits * 2 > segment.len());
+            if id_like {
+                ":id"
+            } else {
+                segment
+            }
+        })
+        .collect();
+    segments.join("/")
+}
+
+pub struct Endpoint {
+    /// `GET /users/:id`.
+    pub name: String,
+    pub requests: usize,
+    /// Responses with a 5xx status.
+    pub errors: usize,
+    /// Latency in milliseconds, for requests that logged one.
+    pub latency: Option<Summary>,
+    pub p90: Option<f64>,
+}
+
+impl Endpoint {
+    pub fn error_rate(&self) -> f64 {
+        self.errors as f64 / self.requests.max(1) as f64
+    }
+}
+
+pub struct Traffic {
+    pub requests: usize,
+    pub errors: usize,
+    pub latency: Option<Summary>,
+    pub p90: Option<f64>,
+    /// Requests per status code.
+    pub statuses: BTreeMap<u16, usize>,
+    /// Busiest first.
+    pub endpoints: Vec<Endpoint>,
+    /// Requests per browser and per operating system, most first.
+    pub browsers: Vec<(String, usize)>,
+    pub systems: Vec<(String, usize)>,
+    /// Requests from bots, and from clients without a User-Agent.
+    pub bots: usize,
+}
+
+// Counts sorted by count, most first, then by name.
+fn ranked(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut ranked: Vec<(String, usize)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+fn summarize(latencies: &[f64]) -> (Option<Summary>, Option<f64>) {
+    let mut sorted = latencies.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let p90 = (!sorted.is_empty()).then(|| metrics::percentile(&sorted, 90.0));
+    (metrics::summarize(latencies), p90)
+}
+
+/// Traffic of the entries that have a status code, e.g. from `format =
+/// "access"` or JSON access logs with `status`, `method` and `path` (or
+/// `url`) fields. Without `bots`, requests from bots are left out.
+pub fn analyze(entries: &[LogEntry], bots: bool) -> Traffic {
+    let mut statuses = BTreeMap::new();
+    let mut browsers: HashMap<String, usize> = HashMap::new();
+    let mut systems: HashMap<String, usize> = HashMap::new();
+    let mut bot_requests = 0;
+    let mut all = Vec::new();
+    let mut errors = 0;
+    let mut groups: HashMap<String, (usize, usize, Vec<f64>)> = HashMap::new();
+    for entry in entries {
+        let Some(status) = status(entry) else {
+            continue;
+        };
+        match agent(entry) {
+            Some(agent) if agent.bot && !bots => continue,
+            Some(agent) => {
+                bot_requests += usize::from(agent.bot);
+                *browsers.entry(agent.browser).or_insert(0) += 1;
+                *systems.entry(agent.os).or_insert(0) += 1;
+            }
+            None => {}
+        }
+        *statuses.entry(status).or_insert(0) += 1;
+        let field = |key: &str| entry.fields.get(key).and_then(Value::as_str);
+        let path = field("path").or_else(|| field("url")).unwrap_or("?");
+        let name = match field("method") {
+            Some(method) => format!("{} {}", method, endpoint(path)),
+            None => endpoint(path),
+        };
+        let group = groups.entry(name).or_default();
+        group.0 += 1;
+        if status >= 500 {
+            group.1 += 1;
+            errors += 1;
+        }
+        if let Some(latency) = latency_ms(entry) {
+            group.2.push(latency);
+            all.push(latency);
+        }
+    }
+    let mut endpoints: Vec<Endpoint> = groups
+        .into_iter()
+        .map(|(name, (requests, errors, latencies))| {
+            let (latency, p90) = summarize(&latencies);
+            Endpoint {
+                name,
+                requests,
+                errors,
+                latency,
+                p90,
+            }
+        })
+        .collect();
+    endpoints.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.name.cmp(&b.name)));
+    let (latency, p90) = summarize(&all);
+    Traffic {
+        requests: statuses.values().sum(),
+        errors,
+        latency,
+        p90,
+        statuses,
+        endpoints,
+        browsers: ranked(browsers),
+        systems: ranked(systems),
+        bots: bot_requests,
+    }
+}
@@ -0,0 +1,241 @@
+//! Async following, receiving and sending on one tokio runtime, so many
+//! files and sockets can be collected without a thread each. Needs the
+//! `async` feature.
+//!
+//! Sources send their entries into a channel and `drain` writes them to a
+//! `Sink` in batches; the file reads and sink calls that block run on
+//! tokio's blocking pool. Followed files also send how far they have been
+//! read, which `drain` records once the entries before it are written.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::task::JoinSet;
+
+use crate::follow::{Follower, Offset, Offsets};
+use crate::listen::MAX_MESSAGE;
+use crate::sink::Sink;
+use crate::{LogAnalyzer, LogEntry};
+
+/// What sources send to `drain`.
+pub enum Received {
+    Entry(LogEntry),
+    /// How far a followed file has been read, after its entries.
+    Offset(String, Offset),
+}
+
+/// Where `collect` reads entries from.
+pub enum Source {
+    /// A file, from its start, then the lines appended to it.
+    File(String),
+    /// Syslog datagrams on this address.
+    Udp(String),
+    /// Syslog connections on this address.
+    Tcp(String),
+}
+
+/// Sends the entries of `path` after `offset`, then those appended to it,
+/// checking each second. Returns when reading fails or nobody receives any
+/// more.
+pub async fn follow(
+    path: String,
+    offset: Option<Offset>,
+    entries: Sender<Received>,
+) -> io::Result<()> {
+    let mut follower = Follower::resume(&path, offset.as_ref());
+    loop {
+        let (returned, polled) = tokio::task::spawn_blocking(move || {
+            let polled = follower.poll();
+            (follower, polled)
+        })
+        .await
+        .map_err(io::Error::other)?;
+        follower = returned;
+        let polled = polled?;
+        let idle = polled.is_empty();
+        let moved = !idle || follower.rewound;
+        let offset = moved.then(|| Received::Offset(path.clone(), follower.offset()));
+        for received in polled.into_iter().map(Received::Entry).chain(offset) {
+            if entries.send(received).await.is_err() {
+                return Ok(());
+            }
+        }
+        if idle {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+pub async fn listen_udp(address: String, entries: Sender<Received>) -> io::Result<()> {
+    let socket = UdpSocket::bind(&address).await?;
+    let mut buffer = vec![0; MAX_MESSAGE];
+    loop {
+        let (len, peer) = socket.recv_from(&mut buffer).await?;
+        let message = String::from_utf8_lossy(&buffer[..len]);
+        if let Some(entry) = crate::listen::entry(&message, peer.ip().to_string()) {
+            crate::emit(&entry);
+            if entries.send(Received::Entry(entry)).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+pub async fn listen_tcp(address: String, entries: Sender<Received>) -> io::Result<()> {
+    let listener = TcpListener::bind(&address).await?;
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let entries = entries.clone();
+        tokio::spawn(async move {
+            let peer = peer.ip().to_string();
+            let mut reader = BufReader::new(stream);
+            while let Ok(Some(message)) = read_frame(&mut reader).await {
+                if message.trim().is_empty() {
+                    continue;
+                }
+                if let Some(entry) = crate::listen::entry(&message, peer.clone()) {
+                    crate::emit(&entry);
+                    if entries.send(Received::Entry(entry)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+// `listen::read_frame`, for async readers.
+async fn read_frame(reader: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Option<String>> {
+    if reader.fill_buf().await?.is_empty() {
+        return Ok(None);
+    }
+    let mut frame = Vec::new();
+    if reader.fill_buf().await?[0].is_ascii_digit() {
+        reader.read_until(b' ', &mut frame).await?;
+        if let Ok(len) = String::from_utf8_lossy(&frame).trim_end().parse::<usize>() {
+            frame.clear();
+            reader
+                .take(len.min(MAX_MESSAGE) as u64)
+                .read_to_end(&mut frame)
+                .await?;
+            return Ok(Some(String::from_utf8_lossy(&frame).into_owned()));
+        }
+    }
+    if !frame.ends_with(b"\n") {
+        reader
+            .take(MAX_MESSAGE as u64)
+            .read_until(b'\n', &mut frame)
+            .await?;
+    }
+    Ok(Some(String::from_utf8_lossy(&frame).into_owned()))
+}
+
+/// Sends what arrives on `entries` to `sink`, `batch` at a time or every
+/// `flush`, whichever comes first, until every sender is gone. Offsets are
+/// saved to `offsets`, if given, once the entries before them are sent.
+pub async fn drain(
+    mut sink: Box<dyn Sink + Send>,
+    mut entries: Receiver<Received>,
+    batch: usize,
+    flush: Duration,
+    mut offsets: Option<(String, Offsets)>,
+) -> io::Result<()> {
+    let mut pending = Vec::new();
+    let mut reached = BTreeMap::new();
+    let mut ticks = tokio::time::interval(flush);
+    loop {
+        let closed = tokio::select! {
+            received = entries.recv() => match received {
+                Some(Received::Entry(entry)) => {
+                    pending.push(entry);
+                    if pending.len() < batch {
+                        continue;
+                    }
+                    false
+                }
+                Some(Received::Offset(path, offset)) => {
+                    reached.insert(path, offset);
+                    continue;
+                }
+                None => true,
+            },
+            _ = ticks.tick() => false,
+        };
+        if !pending.is_empty() {
+            let batch = std::mem::take(&mut pending);
+            let (returned, sent) = tokio::task::spawn_blocking(move || {
+                let sent = sink.send(&batch);
+                (sink, sent)
+            })
+            .await
+            .map_err(io::Error::other)?;
+            sink = returned;
+            sent?;
+        }
+        if let Some((file, saved)) = &mut offsets {
+            if !reached.is_empty() {
+                saved.0.append(&mut reached);
+                saved.save(file)?;
+            }
+        }
+        if closed {
+            return Ok(());
+        }
+    }
+}
+
+/// Appends to a log file, rotating it first if the config says so.
+pub struct LogFile(pub String);
+
+impl Sink for LogFile {
+    fn send(&mut self, entries: &[LogEntry]) -> io::Result<()> {
+        crate::rotate::before_append(&self.0)?;
+        LogAnalyzer::append_to_file(&self.0, entries)?;
+        crate::prometheus::record(entries, 0);
+        Ok(())
+    }
+}
+
+/// Runs every source and sends what they read to `sink` until one of
+/// them, or the sink, fails. At most `buffer` entries wait at a time; a
+/// source is held up while the queue is full. With an `offsets` file,
+/// followed files start where the last run left off.
+pub fn collect(
+    sources: Vec<Source>,
+    sink: Box<dyn Sink + Send>,
+    buffer: usize,
+    flush: Duration,
+    offsets: Option<String>,
+) -> io::Result<()> {
+    let offsets = match offsets {
+        Some(file) => Some((file.clone(), Offsets::load(&file)?)),
+        None => None,
+    };
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let (sender, received) = mpsc::channel(buffer);
+        let mut tasks = JoinSet::new();
+        for source in sources {
+            let sender = sender.clone();
+            match source {
+                Source::File(path) => {
+                    let offset = offsets.as_ref().and_then(|(_, saved)| saved.0.get(&path));
+                    tasks.spawn(follow(path, offset.cloned(), sender))
+                }
+                Source::Udp(address) => tasks.spawn(listen_udp(address, sender)),
+                Source::Tcp(address) => tasks.spawn(listen_tcp(address, sender)),
+            };
+        }
+        drop(sender);
+        tasks.spawn(drain(sink, received, buffer, flush, offsets));
+        // Returning drops the set, which stops the other tasks.
+        while let Some(done) = tasks.join_next().await {
+            done.map_err(io::Error::other)??;
+        }
+        Ok(())
+    })
+}
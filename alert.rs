@@ -0,0 +1,525 @@
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+
+use crate::http::url_encode;
+use crate::sink::post_json;
+use crate::smtp::Smtp;
+use crate::LogEntry;
+
+const MAX_SAMPLES: usize = 5;
+
+/// A fired condition, handed to every alert action.
+pub struct Alert {
+    pub rule: String,
+    pub count: usize,
+    /// Most severe level among the matching entries.
+    pub level: String,
+    /// Timestamps of the earliest and latest matching entries.
+    pub first: Option<String>,
+    pub last: Option<String>,
+    pub samples: Vec<LogEntry>,
+}
+
+// Higher is more severe; unknown levels rank with INFO.
+pub fn severity_rank(level: &str) -> u8 {
+    if let Some(rank) = crate::levels::custom_rank(level) {
+        return rank;
+    }
+    match crate::levels::normalize(level).as_str() {
+        "FATAL" | "CRITICAL" => 4,
+        "ERROR" => 3,
+        "WARNING" | "WARN" => 2,
+        "DEBUG" | "TRACE" => 0,
+        _ => 1,
+    }
+}
+
+impl Alert {
+    pub fn new(rule: &str, matches: &[LogEntry]) -> Alert {
+        let level = matches
+            .iter()
+            .map(|e| e.level.as_str())
+            .max_by_key(|level| severity_rank(level))
+            .unwrap_or("INFO");
+        let times = matches.iter().map(|e| e.timestamp.as_str());
+        Alert {
+            rule: rule.to_string(),
+            count: matches.len(),
+            level: level.to_uppercase(),
+            first: times.clone().min().map(String::from),
+            last: times.max().map(String::from),
+            samples: matches.iter().take(MAX_SAMPLES).cloned().collect(),
+        }
+    }
+
+    /// Stable key used to find an already open ticket for the same rule.
+    pub fn dedup_key(&self) -> String {
+        let slug: String = self
+            .rule
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        format!("logger-{}", slug.trim_matches('-'))
+    }
+
+    pub fn title(&self) -> String {
+        format!(
+            "[{}] {}: {} matching entries",
+            self.dedup_key(),
+            self.rule,
+            self.count
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let mut text = format!(
+            "Rule: {}\nMatching entries: {}\n\nSamples:\n",
+            self.rule, self.count
+        );
+        for entry in &self.samples {
+            text.push_str(&entry.to_line());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// A short message for chat and mail: the count, the time span of the
+    /// matches and sample messages. `status` is `fired` or `resolved`.
+    pub fn summary(&self, status: &str) -> String {
+        if status == "resolved" {
+            return format!("Resolved: {}", self.rule);
+        }
+        let mut text = format!(
+            "{} fired: {} matching {}",
+            self.rule,
+            self.count,
+            if self.count == 1 { "entry" } else { "entries" }
+        );
+        match (&self.first, &self.last) {
+            (Some(first), Some(last)) if first != last => {
+                text.push_str(&format!(" from {} to {}", first, last))
+            }
+            (Some(first), _) => text.push_str(&format!(" at {}", first)),
+            _ => {}
+        }
+        for entry in &self.samples {
+            text.push_str(&format!("\n{} {}", entry.level, entry.message));
+        }
+        text
+    }
+}
+
+pub enum IssueOutcome {
+    Created,
+    AlreadyOpen,
+}
+
+/// Generic REST request used to open a ticket. `{title}`, `{description}`
+/// and `{key}` are substituted into the strings of the JSON body; `{key}`
+/// into the search URL.
+pub struct IssueTemplate {
+    pub create_url: String,
+    pub search_url: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Value,
+}
+
+impl IssueTemplate {
+    pub fn jira(base_url: &str, project: &str, token: &str) -> IssueTemplate {
+        let base = base_url.trim_end_matches('/');
+        IssueTemplate {
+            create_url: format!("{}/rest/api/2/issue", base),
+            search_url: Some(format!(
+                "{}/rest/api/2/search?jql={}",
+                base,
+                encode_query(&format!(
+                    "project = {} AND summary ~ \"{{key}}\" AND statusCategory != Done",
+                    project
+                ))
+            )),
+            headers: vec![("Authorization".to_string(), format!("Bearer {}", token))],
+            body: serde_json::json!({
+                "fields": {
+                    "project": { "key": project },
+                    "issuetype": { "name": "Bug" },
+                    "summary": "{title}",
+                    "description": "{description}"
+                }
+            }),
+        }
+    }
+
+    pub fn github(repo: &str, token: &str) -> IssueTemplate {
+        IssueTemplate {
+            create_url: format!("https://api.github.com/repos/{}/issues", repo),
+            search_url: Some(format!(
+                "https://api.github.com/search/issues?q={}",
+                encode_query(&format!(
+                    "\"{{key}}\" repo:{} is:issue is:open in:title",
+                    repo
+                ))
+            )),
+            headers: vec![
+                ("Authorization".to_string(), format!("Bearer {}", token)),
+                (
+                    "Accept".to_string(),
+                    "application/vnd.github+json".to_string(),
+                ),
+            ],
+            body: serde_json::json!({ "title": "{title}", "body": "{description}" }),
+        }
+    }
+
+    /// Reads LOGGER_ISSUE_TRACKER (jira/github/rest) and friends.
+    pub fn from_env() -> Result<IssueTemplate, String> {
+        let var = |name: &str| env::var(name).map_err(|_| format!("{} is not set", name));
+        let token = var("LOGGER_ISSUE_TOKEN").unwrap_or_default();
+        let tracker = var("LOGGER_ISSUE_TRACKER")?;
+        match tracker.to_lowercase().as_str() {
+            "jira" => Ok(IssueTemplate::jira(
+                &var("LOGGER_ISSUE_URL")?,
+                &var("LOGGER_ISSUE_PROJECT")?,
+                &token,
+            )),
+            "github" => Ok(IssueTemplate::github(&var("LOGGER_ISSUE_URL")?, &token)),
+            "rest" => Ok(IssueTemplate {
+                create_url: var("LOGGER_ISSUE_URL")?,
+                search_url: var("LOGGER_ISSUE_SEARCH_URL").ok(),
+                headers: if token.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![("Authorization".to_string(), format!("Bearer {}", token))]
+                },
+                body: serde_json::from_str(&var("LOGGER_ISSUE_BODY")?)
+                    .map_err(|e| format!("LOGGER_ISSUE_BODY is not JSON: {}", e))?,
+            }),
+            _ => Err(format!(
+                "unknown LOGGER_ISSUE_TRACKER '{}'; use jira, github or rest",
+                tracker
+            )),
+        }
+    }
+
+    /// Opens a ticket for the alert unless one with its key in brackets, as
+    /// `{title}` starts, is still open; a bare key could be the start of
+    /// another rule's, e.g. `logger-db` of `logger-db-replica`.
+    pub fn create_issue(&self, alert: &Alert) -> io::Result<IssueOutcome> {
+        let key = alert.dedup_key();
+
+        if let Some(search_url) = &self.search_url {
+            let mut request = ureq::get(&search_url.replace("{key}", &url_encode(&key)));
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+            let found = request.call().map_err(io::Error::other)?.into_string()?;
+            if found.contains(&format!("[{}]", key)) {
+                return Ok(IssueOutcome::AlreadyOpen);
+            }
+        }
+
+        let values = [
+            ("{title}", alert.title()),
+            ("{description}", alert.description()),
+            ("{key}", key),
+        ];
+        post_json(&self.create_url, &self.headers, &fill(&self.body, &values))?;
+        Ok(IssueOutcome::Created)
+    }
+}
+
+/// On-call paging services. An alert triggers an incident keyed by its
+/// dedup key; resolving the same alert closes it again.
+pub enum Pager {
+    PagerDuty { routing_key: String },
+    Opsgenie { api_key: String },
+}
+
+impl Pager {
+    /// Reads LOGGER_PAGERDUTY_ROUTING_KEY or LOGGER_OPSGENIE_API_KEY.
+    pub fn from_env() -> Option<Pager> {
+        if let Ok(routing_key) = env::var("LOGGER_PAGERDUTY_ROUTING_KEY") {
+            return Some(Pager::PagerDuty { routing_key });
+        }
+        env::var("LOGGER_OPSGENIE_API_KEY")
+            .ok()
+            .map(|api_key| Pager::Opsgenie { api_key })
+    }
+
+    pub fn trigger(&self, alert: &Alert) -> io::Result<()> {
+        match self {
+            Pager::PagerDuty { routing_key } => post_json(
+                "https://events.pagerduty.com/v2/enqueue",
+                &[],
+                &serde_json::json!({
+                    "routing_key": routing_key,
+                    "event_action": "trigger",
+                    "dedup_key": alert.dedup_key(),
+                    "payload": {
+                        "summary": alert.title(),
+                        "source": "logger",
+                        "severity": pagerduty_severity(&alert.level),
+                        "custom_details": { "description": alert.description() }
+                    }
+                }),
+            ),
+            Pager::Opsgenie { api_key } => post_json(
+                "https://api.opsgenie.com/v2/alerts",
+                &[("Authorization".to_string(), format!("GenieKey {}", api_key))],
+                &serde_json::json!({
+                    "message": alert.title(),
+                    "alias": alert.dedup_key(),
+                    "description": alert.description(),
+                    "priority": opsgenie_priority(&alert.level),
+                    "source": "logger"
+                }),
+            ),
+        }
+    }
+
+    /// Triggers the incident when `status` is `fired`, resolves it when it
+    /// is `resolved`.
+    pub fn notify(&self, alert: &Alert, status: &str) -> io::Result<()> {
+        match status {
+            "resolved" => self.resolve(alert),
+            _ => self.trigger(alert),
+        }
+    }
+
+    /// Closes the incident opened for this alert's rule once it stops matching.
+    pub fn resolve(&self, alert: &Alert) -> io::Result<()> {
+        match self {
+            Pager::PagerDuty { routing_key } => post_json(
+                "https://events.pagerduty.com/v2/enqueue",
+                &[],
+                &serde_json::json!({
+                    "routing_key": routing_key,
+                    "event_action": "resolve",
+                    "dedup_key": alert.dedup_key()
+                }),
+            ),
+            Pager::Opsgenie { api_key } => post_json(
+                &format!(
+                    "https://api.opsgenie.com/v2/alerts/{}/close?identifierType=alias",
+                    url_encode(&alert.dedup_key())
+                ),
+                &[("Authorization".to_string(), format!("GenieKey {}", api_key))],
+                &serde_json::json!({ "source": "logger" }),
+            ),
+        }
+    }
+}
+
+/// What a rule does when it fires or resolves, besides being printed.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// A shell command, given the alert in LOGGER_ALERT_* variables and
+    /// the sample entries on stdin.
+    Command(String),
+    /// A URL the alert is POSTed to as JSON.
+    Webhook(String),
+    /// A Slack incoming webhook URL.
+    Slack(String),
+    /// A Discord webhook URL.
+    Discord(String),
+    /// Mail to these addresses through the rules file's `[smtp]` server.
+    Email(Box<Smtp>, Vec<String>),
+    /// A PagerDuty incident keyed by the alert's dedup key; the routing
+    /// key is read from this environment variable.
+    PagerDuty(String),
+    /// An Opsgenie alert aliased by the dedup key; the API key is read
+    /// from this environment variable.
+    Opsgenie(String),
+    /// A ticket in the tracker of the LOGGER_ISSUE_* variables, opened
+    /// when the rule fires unless one for its dedup key is still open.
+    Issue,
+}
+
+fn env_secret(var: &str) -> io::Result<String> {
+    env::var(var)
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, format!("${} is not set", var)))
+}
+
+// The summary's headline in bold and its sample lines as a code block,
+// in at most about `limit` characters.
+fn chat_text(alert: &Alert, status: &str, limit: usize) -> String {
+    let summary = alert.summary(status);
+    let (headline, samples) = summary.split_once('\n').unwrap_or((&summary, ""));
+    let mut text = format!("**{}**", headline);
+    if !samples.is_empty() {
+        let samples: String = samples
+            .replace("```", "` ` `")
+            .chars()
+            .take(limit.saturating_sub(headline.len() + 16))
+            .collect();
+        text.push_str(&format!("\n```\n{}\n```", samples));
+    }
+    text
+}
+
+impl Action {
+    /// Runs the action; `status` is `fired` or `resolved`.
+    pub fn run(&self, alert: &Alert, status: &str) -> io::Result<()> {
+        match self {
+            Action::Command(command) => {
+                let mut shell = if cfg!(windows) {
+                    let mut shell = Command::new("cmd");
+                    shell.arg("/C");
+                    shell
+                } else {
+                    let mut shell = Command::new("sh");
+                    shell.arg("-c");
+                    shell
+                };
+                let mut child = shell
+                    .arg(command)
+                    .env("LOGGER_ALERT_RULE", &alert.rule)
+                    .env("LOGGER_ALERT_STATUS", status)
+                    .env("LOGGER_ALERT_COUNT", alert.count.to_string())
+                    .env("LOGGER_ALERT_LEVEL", &alert.level)
+                    .env("LOGGER_ALERT_KEY", alert.dedup_key())
+                    .stdin(Stdio::piped())
+                    .spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    // A command that doesn't read its input closes the pipe.
+                    for entry in &alert.samples {
+                        if writeln!(stdin, "{}", entry.to_line()).is_err() {
+                            break;
+                        }
+                    }
+                }
+                let exit = child.wait()?;
+                if exit.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!("'{}' failed: {}", command, exit)))
+                }
+            }
+            Action::Webhook(url) => post_json(
+                url,
+                &[],
+                &serde_json::json!({
+                    "rule": alert.rule,
+                    "status": status,
+                    "key": alert.dedup_key(),
+                    "title": alert.title(),
+                    "count": alert.count,
+                    "level": alert.level,
+                    "samples": alert.samples.iter().map(|e| e.to_line()).collect::<Vec<_>>(),
+                }),
+            ),
+            // Slack's markdown bolds with single asterisks.
+            Action::Slack(url) => post_json(
+                url,
+                &[],
+                &serde_json::json!({ "text": chat_text(alert, status, 3000).replacen("**", "*", 2) }),
+            ),
+            // Discord rejects messages over 2000 characters.
+            Action::Discord(url) => post_json(
+                url,
+                &[],
+                &serde_json::json!({ "content": chat_text(alert, status, 2000) }),
+            ),
+            Action::Email(smtp, to) => {
+                let subject = match status {
+                    "resolved" => format!("[logger] Resolved: {}", alert.rule),
+                    _ => format!("[logger] {}: {} matching entries", alert.rule, alert.count),
+                };
+                smtp.send(to, &subject, &alert.summary(status))
+            }
+            Action::PagerDuty(var) => Pager::PagerDuty {
+                routing_key: env_secret(var)?,
+            }
+            .notify(alert, status),
+            Action::Opsgenie(var) => Pager::Opsgenie {
+                api_key: env_secret(var)?,
+            }
+            .notify(alert, status),
+            // Tickets are closed by people, not when the rule resolves.
+            Action::Issue if status == "resolved" => Ok(()),
+            Action::Issue => IssueTemplate::from_env()
+                .map_err(io::Error::other)?
+                .create_issue(alert)
+                .map(|_| ()),
+        }
+    }
+}
+
+fn pagerduty_severity(level: &str) -> &'static str {
+    match severity_rank(level) {
+        4 => "critical",
+        3 => "error",
+        2 => "warning",
+        _ => "info",
+    }
+}
+
+fn opsgenie_priority(level: &str) -> &'static str {
+    match severity_rank(level) {
+        4 => "P1",
+        3 => "P2",
+        2 => "P3",
+        _ => "P5",
+    }
+}
+
+// `template` with the placeholders in its strings replaced by `values`.
+fn fill(template: &Value, values: &[(&str, String)]) -> Value {
+    match template {
+        Value::String(text) => {
+            Value::String(values.iter().fold(text.clone(), |text, (name, value)| {
+                text.replace(name, value)
+            }))
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|item| fill(item, values)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(name, value)| (name.clone(), fill(value, values)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+// Percent-encodes a search query around its `{key}` placeholders, which
+// are substituted after encoding.
+fn encode_query(query: &str) -> String {
+    query
+        .split("{key}")
+        .map(url_encode)
+        .collect::<Vec<_>>()
+        .join("{key}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn issue_body_is_filled_as_json() {
+        let template = serde_json::json!({ "title": "{title}", "labels": ["x", "{key}"], "n": 1 });
+        let values = [
+            ("{title}", "say \"hi\"\n".to_string()),
+            ("{key}", "logger-say--hi".to_string()),
+        ];
+        assert_eq!(
+            fill(&template, &values),
+            serde_json::json!({ "title": "say \"hi\"\n", "labels": ["x", "logger-say--hi"], "n": 1 })
+        );
+    }
+
+    #[test]
+    fn dedup_key_and_title() {
+        let alert = Alert::new("Disk full!", &[]);
+        assert_eq!(alert.dedup_key(), "logger-disk-full");
+        assert!(alert.title().starts_with("[logger-disk-full] "));
+    }
+}
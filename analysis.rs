@@ -0,0 +1,890 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::LogEntry;
+
+/// SplitMix64: tiny, fast and stable across releases, so a given seed
+/// always reproduces the same sample.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in 0..bound (bound > 0), without modulo bias.
+    fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+/// Uniform random sample of `count` entries (reservoir sampling), returned
+/// in their original order. Without a seed the clock is used.
+pub fn sample(entries: &[LogEntry], count: usize, seed: Option<u64>) -> Vec<LogEntry> {
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+    });
+    sample_indices(entries.len(), count, &mut SplitMix64(seed))
+        .into_iter()
+        .map(|i| entries[i].clone())
+        .collect()
+}
+
+// Reservoir sampling of `count` indices out of 0..len, in ascending order.
+fn sample_indices(len: usize, count: usize, rng: &mut SplitMix64) -> Vec<usize> {
+    let mut reservoir: Vec<usize> = (0..count.min(len)).collect();
+    for index in reservoir.len()..len {
+        let slot = rng.below(index as u64 + 1) as usize;
+        if slot < count {
+            reservoir[slot] = index;
+        }
+    }
+    reservoir.sort_unstable();
+    reservoir
+}
+
+/// Cuts `entries` down to roughly `budget` for reports and exports: every
+/// ERROR-and-worse entry is kept (even past the budget), the rest of the
+/// budget is a reproducible sample of everything else, and a final entry
+/// says how many were omitted. Returns `entries` unchanged if they fit.
+pub fn within_budget(entries: &[LogEntry], budget: usize) -> Vec<LogEntry> {
+    if entries.len() <= budget {
+        return entries.to_vec();
+    }
+    let (mut kept, others): (Vec<usize>, Vec<usize>) =
+        (0..entries.len()).partition(|&i| crate::alert::severity_rank(&entries[i].level) >= 3);
+    let room = budget.saturating_sub(kept.len());
+    kept.extend(
+        sample_indices(others.len(), room, &mut SplitMix64(0))
+            .into_iter()
+            .map(|i| others[i]),
+    );
+    kept.sort_unstable();
+
+    let mut result: Vec<LogEntry> = kept.iter().map(|&i| entries[i].clone()).collect();
+    let omitted = entries.len() - kept.len();
+    let mut fields = serde_json::Map::new();
+    fields.insert("omitted".to_string(), serde_json::Value::from(omitted));
+    result.push(LogEntry {
+        timestamp: entries[entries.len() - 1].timestamp.clone(),
+        level: "INFO".to_string(),
+        message: format!("{} entries omitted (entry budget {})", omitted, budget),
+        fields,
+        source: None,
+    });
+    result
+}
+
+fn is_uuid(word: &str) -> bool {
+    let groups: Vec<&str> = word.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(g, len)| g.len() == len && g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn is_hex_id(word: &str) -> bool {
+    if let Some(rest) = word.strip_prefix("0x") {
+        return !rest.is_empty() && rest.chars().all(|c| c.is_ascii_hexdigit());
+    }
+    word.len() >= 8
+        && word.chars().all(|c| c.is_ascii_hexdigit())
+        && word.chars().any(|c| c.is_ascii_digit())
+}
+
+// Replaces each run of digits (with inner `.`/`:` separators, so decimals,
+// IPs and clock times collapse too) by `<num>`.
+fn mask_numbers(word: &str) -> String {
+    let chars: Vec<char> = word.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_ascii_digit() {
+            while i < chars.len()
+                && (chars[i].is_ascii_digit()
+                    || (matches!(chars[i], '.' | ':')
+                        && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())))
+            {
+                i += 1;
+            }
+            out.push_str("<num>");
+        } else {
+            out.push(chars[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Reduces a message to its template: UUIDs, hex IDs and numbers become
+/// placeholders so `took 12ms for id 0x1f` and `took 7ms for id 0xa0` match.
+pub fn normalize_message(message: &str) -> String {
+    message
+        .split_whitespace()
+        .map(normalize_word)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn normalize_word(word: &str) -> String {
+    // Only the value of `key=value` pairs varies.
+    if let Some((key, value)) = word.split_once('=') {
+        return format!("{}={}", key, normalize_word(value));
+    }
+    let core = word.trim_matches(|c: char| !c.is_alphanumeric());
+    if core.is_empty() {
+        word.to_string()
+    } else if is_uuid(core) {
+        word.replacen(core, "<uuid>", 1)
+    } else if is_hex_id(core) {
+        word.replacen(core, "<hex>", 1)
+    } else {
+        mask_numbers(word)
+    }
+}
+
+pub struct MessagePattern {
+    pub template: String,
+    pub count: usize,
+    pub example: String,
+}
+
+// Two templates of equal length differing in exactly one word.
+fn differs_in_one_word(a: &[String], b: &[String]) -> Option<usize> {
+    if a.len() != b.len() || a.len() < 2 {
+        return None;
+    }
+    let mut diff = a.iter().zip(b).enumerate().filter(|(_, (x, y))| x != y);
+    match (diff.next(), diff.next()) {
+        (Some((index, _)), None) => Some(index),
+        _ => None,
+    }
+}
+
+/// Groups messages by normalized template, merges near-duplicate templates
+/// (same shape, one differing word, replaced by `<*>`), and returns the
+/// `limit` most frequent patterns.
+pub fn top_patterns(entries: &[LogEntry], limit: usize) -> Vec<MessagePattern> {
+    let mut order: Vec<String> = Vec::new();
+    let mut groups: std::collections::HashMap<String, MessagePattern> =
+        std::collections::HashMap::new();
+    for entry in entries {
+        let template = normalize_message(&entry.message);
+        groups
+            .entry(template.clone())
+            .or_insert_with(|| {
+                order.push(template.clone());
+                MessagePattern {
+                    template,
+                    count: 0,
+                    example: entry.message.clone(),
+                }
+            })
+            .count += 1;
+    }
+
+    let mut patterns: Vec<MessagePattern> = order.iter().filter_map(|t| groups.remove(t)).collect();
+    patterns.sort_by_key(|p| std::cmp::Reverse(p.count));
+
+    let mut merged: Vec<(Vec<String>, MessagePattern)> = Vec::new();
+    for pattern in patterns {
+        let words: Vec<String> = pattern.template.split(' ').map(str::to_string).collect();
+        let target = merged.iter_mut().find_map(|(existing, group)| {
+            differs_in_one_word(existing, &words).map(|i| (existing, group, i))
+        });
+        match target {
+            Some((existing, group, index)) => {
+                existing[index] = "<*>".to_string();
+                group.template = existing.join(" ");
+                group.count += pattern.count;
+            }
+            None => merged.push((words, pattern)),
+        }
+    }
+
+    let mut top: Vec<MessagePattern> = merged.into_iter().map(|(_, p)| p).collect();
+    top.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.template.cmp(&b.template))
+    });
+    top.truncate(limit);
+    top
+}
+
+/// Collapses runs of consecutive entries with the same level and message
+/// (or the same normalized template) into the first entry of the run,
+/// marked with how often it repeated.
+pub fn collapse_repeats(entries: &[LogEntry], normalized: bool) -> Vec<LogEntry> {
+    let key = |entry: &LogEntry| {
+        if normalized {
+            (entry.level.clone(), normalize_message(&entry.message))
+        } else {
+            (entry.level.clone(), entry.message.clone())
+        }
+    };
+
+    let mut collapsed: Vec<LogEntry> = Vec::new();
+    let mut run = 0;
+    let mut last_key = None;
+    for entry in entries {
+        let current = key(entry);
+        if last_key.as_ref() == Some(&current) {
+            run += 1;
+            continue;
+        }
+        if let Some(previous) = collapsed.last_mut() {
+            mark_repeats(previous, run);
+        }
+        collapsed.push(entry.clone());
+        last_key = Some(current);
+        run = 1;
+    }
+    if let Some(previous) = collapsed.last_mut() {
+        mark_repeats(previous, run);
+    }
+    collapsed
+}
+
+fn mark_repeats(entry: &mut LogEntry, run: usize) {
+    if run > 1 {
+        entry.message = format!("{} [repeated {} times]", entry.message, run);
+    }
+}
+
+pub struct RatePoint {
+    pub start: chrono::NaiveDateTime,
+    pub total: usize,
+    /// Entries of the window that were counted, e.g. ERROR and worse.
+    pub errors: usize,
+}
+
+impl RatePoint {
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.total as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trend {
+    Increasing,
+    Decreasing,
+    Flat,
+}
+
+/// Error ratio (ERROR and worse over all entries) in windows of `window`
+/// length, each starting half a window after the previous one.
+pub fn error_rate_windows(entries: &[LogEntry], window: chrono::Duration) -> Vec<RatePoint> {
+    sliding_windows(entries, window, |e| {
+        crate::alert::severity_rank(&e.level) >= 3
+    })
+}
+
+/// How many entries `level` selects per `window`, over time: in windows
+/// each starting half a window after the previous one, from the first
+/// timestamp to the last. `ERROR+` per minute is the error rate a chart,
+/// a trend or an alert looks at.
+pub fn rate(
+    entries: &[LogEntry],
+    level: &crate::gate::LevelSelector,
+    window: chrono::Duration,
+) -> Vec<(chrono::NaiveDateTime, usize)> {
+    sliding_windows(entries, window, |e| level.matches(e))
+        .into_iter()
+        .map(|point| (point.start, point.errors))
+        .collect()
+}
+
+fn sliding_windows(
+    entries: &[LogEntry],
+    window: chrono::Duration,
+    counted: impl Fn(&LogEntry) -> bool,
+) -> Vec<RatePoint> {
+    let mut events: Vec<(chrono::NaiveDateTime, bool)> = entries
+        .iter()
+        .filter_map(|e| e.time().map(|t| (t, counted(e))))
+        .collect();
+    events.sort_by_key(|(t, _)| *t);
+    let (Some(first), Some(last)) = (events.first(), events.last()) else {
+        return Vec::new();
+    };
+    let (first, last) = (first.0, last.0);
+    let step = (window / 2).max(chrono::Duration::seconds(1));
+
+    let mut points = Vec::new();
+    let mut start = first;
+    let (mut lo, mut hi) = (0, 0);
+    loop {
+        let end = start + window;
+        while lo < events.len() && events[lo].0 < start {
+            lo += 1;
+        }
+        hi = hi.max(lo);
+        while hi < events.len() && events[hi].0 < end {
+            hi += 1;
+        }
+        points.push(RatePoint {
+            start,
+            total: hi - lo,
+            errors: events[lo..hi].iter().filter(|(_, e)| *e).count(),
+        });
+        if end > last {
+            break;
+        }
+        start += step;
+    }
+    points
+}
+
+/// Fits a least-squares line through the window ratios. Returns the
+/// direction and the change in percentage points across the whole series;
+/// less than one point either way counts as flat.
+pub fn classify_trend(points: &[RatePoint]) -> (Trend, f64) {
+    let n = points.len() as f64;
+    if points.len() < 2 {
+        return (Trend::Flat, 0.0);
+    }
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = points.iter().map(|p| p.ratio()).sum::<f64>() / n;
+    let (mut num, mut den) = (0.0, 0.0);
+    for (i, point) in points.iter().enumerate() {
+        let dx = i as f64 - mean_x;
+        num += dx * (point.ratio() - mean_y);
+        den += dx * dx;
+    }
+    let change = num / den * (n - 1.0) * 100.0;
+    let trend = if change >= 1.0 {
+        Trend::Increasing
+    } else if change <= -1.0 {
+        Trend::Decreasing
+    } else {
+        Trend::Flat
+    };
+    (trend, change)
+}
+
+pub struct Burst {
+    pub start: chrono::NaiveDateTime,
+    pub count: usize,
+    /// Mean count of the preceding windows.
+    pub baseline: f64,
+    pub top_messages: Vec<MessagePattern>,
+}
+
+/// Windows whose count exceeds `factor` times the mean of the `history`
+/// windows before them. Windows without enough history are never flagged.
+pub fn detect_bursts(
+    entries: &[LogEntry],
+    window: chrono::Duration,
+    history: usize,
+    factor: f64,
+) -> Vec<Burst> {
+    let counts = crate::report::time_histogram(entries, window);
+    let mut bursts = Vec::new();
+    for i in history.max(1)..counts.len() {
+        let previous = &counts[i.saturating_sub(history)..i];
+        let baseline = previous.iter().map(|(_, n)| *n as f64).sum::<f64>() / previous.len() as f64;
+        let (start, count) = counts[i];
+        // An empty baseline still needs a few entries to count as a burst.
+        if count as f64 > factor * baseline.max(1.0) {
+            let end = start + window;
+            let in_window: Vec<LogEntry> = entries
+                .iter()
+                .filter(|e| e.time().is_some_and(|t| t >= start && t < end))
+                .cloned()
+                .collect();
+            bursts.push(Burst {
+                start,
+                count,
+                baseline,
+                top_messages: top_patterns(&in_window, 3),
+            });
+        }
+    }
+    bursts
+}
+
+/// A stretch with no entries at all.
+pub struct Gap {
+    pub from: chrono::NaiveDateTime,
+    pub to: chrono::NaiveDateTime,
+}
+
+impl Gap {
+    pub fn length(&self) -> chrono::Duration {
+        self.to - self.from
+    }
+}
+
+/// Silences longer than `threshold` between consecutive timestamps.
+pub fn find_gaps(entries: &[LogEntry], threshold: chrono::Duration) -> Vec<Gap> {
+    let mut times: Vec<chrono::NaiveDateTime> = entries.iter().filter_map(|e| e.time()).collect();
+    times.sort();
+    times
+        .windows(2)
+        .filter(|pair| pair[1] - pair[0] > threshold)
+        .map(|pair| Gap {
+            from: pair[0],
+            to: pair[1],
+        })
+        .collect()
+}
+
+/// The overview `logger stats` prints.
+pub struct Stats {
+    pub total: usize,
+    /// Entries per level, most first.
+    pub levels: Vec<(String, usize)>,
+    /// Oldest and newest timestamp, of entries that have one.
+    pub first: Option<chrono::NaiveDateTime>,
+    pub last: Option<chrono::NaiveDateTime>,
+    /// The longest time between consecutive timestamps.
+    pub longest_quiet: Option<Gap>,
+}
+
+impl Stats {
+    /// Share of all entries in percent.
+    pub fn percent(&self, count: usize) -> f64 {
+        count as f64 / self.total.max(1) as f64 * 100.0
+    }
+
+    pub fn span(&self) -> Option<chrono::Duration> {
+        Some(self.last? - self.first?)
+    }
+
+    /// Entries per minute over the span; `None` when it is shorter than a
+    /// second.
+    pub fn per_minute(&self) -> Option<f64> {
+        let seconds = self.span()?.num_seconds();
+        (seconds > 0).then(|| self.total as f64 / seconds as f64 * 60.0)
+    }
+}
+
+pub fn statistics(entries: &[LogEntry]) -> Stats {
+    let mut counts: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+    for entry in entries {
+        *counts.entry(entry.level.as_str()).or_insert(0) += 1;
+    }
+    let mut levels: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(level, count)| (level.to_string(), count))
+        .collect();
+    levels.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let mut times: Vec<chrono::NaiveDateTime> = entries.iter().filter_map(|e| e.time()).collect();
+    times.sort();
+    let longest_quiet = times
+        .windows(2)
+        .max_by_key(|pair| pair[1] - pair[0])
+        .map(|pair| Gap {
+            from: pair[0],
+            to: pair[1],
+        });
+    Stats {
+        total: entries.len(),
+        levels,
+        first: times.first().copied(),
+        last: times.last().copied(),
+        longest_quiet,
+    }
+}
+
+/// Volume and error ratio of one period compared with the period before it.
+pub struct PeriodComparison {
+    pub label: &'static str,
+    pub current: RatePoint,
+    pub previous: RatePoint,
+}
+
+impl PeriodComparison {
+    /// Relative volume change in percent; `None` if the previous period was empty.
+    pub fn volume_change(&self) -> Option<f64> {
+        (self.previous.total > 0).then(|| {
+            (self.current.total as f64 - self.previous.total as f64) / self.previous.total as f64
+                * 100.0
+        })
+    }
+
+    /// Relative change of the error ratio in percent.
+    pub fn error_rate_change(&self) -> Option<f64> {
+        let previous = self.previous.ratio();
+        (previous > 0.0).then(|| (self.current.ratio() - previous) / previous * 100.0)
+    }
+}
+
+fn period(
+    entries: &[LogEntry],
+    start: chrono::NaiveDateTime,
+    end: chrono::NaiveDateTime,
+) -> RatePoint {
+    let mut point = RatePoint {
+        start,
+        total: 0,
+        errors: 0,
+    };
+    for entry in entries {
+        if entry.time().is_some_and(|t| t >= start && t < end) {
+            point.total += 1;
+            if crate::alert::severity_rank(&entry.level) >= 3 {
+                point.errors += 1;
+            }
+        }
+    }
+    point
+}
+
+/// Day-over-day and week-over-week comparisons, counting back from the
+/// newest entry so a log that stopped yesterday still compares full periods.
+pub fn seasonal_comparisons(entries: &[LogEntry]) -> Vec<PeriodComparison> {
+    let Some(latest) = entries.iter().filter_map(|e| e.time()).max() else {
+        return Vec::new();
+    };
+    let end = latest + chrono::Duration::seconds(1);
+    [
+        ("Day over day", chrono::Duration::days(1)),
+        ("Week over week", chrono::Duration::weeks(1)),
+    ]
+    .into_iter()
+    .map(|(label, length)| PeriodComparison {
+        label,
+        current: period(entries, end - length, end),
+        previous: period(entries, end - length * 2, end - length),
+    })
+    .collect()
+}
+
+/// Pearson correlation of per-window counts between the `limit` most
+/// frequent templates. A template that never varies correlates with nothing.
+pub struct CorrelationMatrix {
+    pub templates: Vec<String>,
+    pub values: Vec<Vec<f64>>,
+}
+
+impl CorrelationMatrix {
+    /// Distinct pairs ordered by strength of correlation, strongest first.
+    pub fn strongest_pairs(&self, count: usize) -> Vec<(usize, usize, f64)> {
+        let mut pairs = Vec::new();
+        for i in 0..self.templates.len() {
+            for j in i + 1..self.templates.len() {
+                pairs.push((i, j, self.values[i][j]));
+            }
+        }
+        pairs.sort_by(|a, b| b.2.abs().total_cmp(&a.2.abs()));
+        pairs.truncate(count);
+        pairs
+    }
+}
+
+fn pearson(xs: &[f64], ys: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let (mean_x, mean_y) = (xs.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        0.0
+    } else {
+        cov / (var_x * var_y).sqrt()
+    }
+}
+
+pub fn template_correlation(
+    entries: &[LogEntry],
+    window: chrono::Duration,
+    limit: usize,
+) -> CorrelationMatrix {
+    let mut totals: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let timed: Vec<(chrono::NaiveDateTime, String)> = entries
+        .iter()
+        .filter_map(|e| e.time().map(|t| (t, normalize_message(&e.message))))
+        .collect();
+    for (_, template) in &timed {
+        *totals.entry(template.clone()).or_insert(0) += 1;
+    }
+    let mut templates: Vec<(String, usize)> = totals.into_iter().collect();
+    templates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    templates.truncate(limit);
+    let templates: Vec<String> = templates.into_iter().map(|(t, _)| t).collect();
+
+    let width = window.num_seconds().max(1);
+    let first = timed
+        .iter()
+        .map(|(t, _)| crate::report::bucket_start(*t, width))
+        .min()
+        .unwrap_or(0);
+    let last = timed
+        .iter()
+        .map(|(t, _)| crate::report::bucket_start(*t, width))
+        .max()
+        .unwrap_or(0);
+    let windows = ((last - first) / width + 1) as usize;
+    let mut series = vec![vec![0.0; windows]; templates.len()];
+    for (time, template) in &timed {
+        if let Some(index) = templates.iter().position(|t| t == template) {
+            let slot = (crate::report::bucket_start(*time, width) - first) / width;
+            series[index][slot as usize] += 1.0;
+        }
+    }
+
+    let values = series
+        .iter()
+        .map(|a| series.iter().map(|b| pearson(a, b)).collect())
+        .collect();
+    CorrelationMatrix { templates, values }
+}
+
+/// Field names tried, in order, when no worker field is given.
+const WORKER_FIELDS: [&str; 6] = ["thread", "thread_id", "worker", "worker_id", "tid", "pid"];
+
+pub struct WorkerStats {
+    pub worker: String,
+    pub total: usize,
+    pub errors: usize,
+    pub last_seen: Option<chrono::NaiveDateTime>,
+}
+
+fn worker_of(entry: &LogEntry, field: Option<&crate::query::Field>) -> Option<String> {
+    match field {
+        Some(field) => field.lookup(entry).map(|v| v.into_owned()),
+        None => WORKER_FIELDS
+            .iter()
+            .find_map(|name| entry.fields.get(*name))
+            .map(crate::query::render),
+    }
+}
+
+/// Volume, errors and last activity per worker, busiest first. Entries
+/// without a worker ID are left out.
+pub fn worker_breakdown(
+    entries: &[LogEntry],
+    field: Option<&crate::query::Field>,
+) -> Vec<WorkerStats> {
+    let mut workers: std::collections::HashMap<String, WorkerStats> =
+        std::collections::HashMap::new();
+    for entry in entries {
+        let Some(worker) = worker_of(entry, field) else {
+            continue;
+        };
+        let stats = workers
+            .entry(worker.clone())
+            .or_insert_with(|| WorkerStats {
+                worker,
+                total: 0,
+                errors: 0,
+                last_seen: None,
+            });
+        stats.total += 1;
+        if crate::alert::severity_rank(&entry.level) >= 3 {
+            stats.errors += 1;
+        }
+        stats.last_seen = stats.last_seen.max(entry.time());
+    }
+    let mut workers: Vec<WorkerStats> = workers.into_values().collect();
+    workers.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.worker.cmp(&b.worker)));
+    workers
+}
+
+/// The worker that has been quiet the longest, measured up to the newest
+/// entry of any worker.
+pub fn longest_silent(workers: &[WorkerStats]) -> Option<(&WorkerStats, chrono::Duration)> {
+    let latest = workers.iter().filter_map(|w| w.last_seen).max()?;
+    workers
+        .iter()
+        .filter_map(|w| w.last_seen.map(|t| (w, latest - t)))
+        .max_by_key(|(_, silence)| *silence)
+}
+
+pub struct FunnelStage {
+    pub marker: String,
+    /// Requests that reached this stage after passing all earlier ones.
+    pub reached: usize,
+}
+
+/// Follows each correlation ID through the ordered `stages` (message
+/// substrings, case-insensitive). A stage only counts once every earlier
+/// stage was seen for that ID, in time order.
+pub fn funnel(
+    entries: &[LogEntry],
+    id_field: &crate::query::Field,
+    stages: &[String],
+) -> Vec<FunnelStage> {
+    let markers: Vec<String> = stages.iter().map(|s| s.to_lowercase()).collect();
+    let mut ordered: Vec<&LogEntry> = entries.iter().collect();
+    ordered.sort_by_key(|e| e.time());
+
+    let mut progress: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    for entry in ordered {
+        let Some(id) = id_field.lookup(entry) else {
+            continue;
+        };
+        let next = progress.entry(id.into_owned()).or_insert(0);
+        if *next < markers.len() && entry.message.to_lowercase().contains(&markers[*next]) {
+            *next += 1;
+        }
+    }
+
+    stages
+        .iter()
+        .enumerate()
+        .map(|(i, marker)| FunnelStage {
+            marker: marker.clone(),
+            reached: progress.values().filter(|&&n| n > i).count(),
+        })
+        .collect()
+}
+
+/// The values `field` takes and how many entries have each, most common
+/// first, and how many entries lack it. Each element of an array, such as
+/// the tags, counts on its own.
+pub fn distinct(
+    entries: &[LogEntry],
+    field: &crate::query::Field,
+) -> (Vec<(String, usize)>, usize) {
+    let mut counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let mut missing = 0;
+    for entry in entries {
+        let values = match field {
+            crate::query::Field::Path(path) => match path.resolve(entry) {
+                Some(serde_json::Value::Array(items)) => {
+                    items.iter().map(crate::query::render).collect()
+                }
+                Some(value) => vec![crate::query::render(&value)],
+                None => Vec::new(),
+            },
+            other => other
+                .lookup(entry)
+                .into_iter()
+                .map(|v| v.into_owned())
+                .collect(),
+        };
+        if values.is_empty() {
+            missing += 1;
+        }
+        for value in values {
+            *counts.entry(value).or_insert(0) += 1;
+        }
+    }
+    let mut ranked: Vec<(String, usize)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    (ranked, missing)
+}
+
+/// Entry numbers `A..B` as `View all logs` shows them (from 1, both ends
+/// included) as indexes into the entries; `A..` runs to the end and `..B`
+/// from the start.
+pub fn parse_range(text: &str) -> Result<std::ops::Range<usize>, String> {
+    let (start, end) = text
+        .split_once("..")
+        .ok_or_else(|| format!("expected A..B, got '{}'", text))?;
+    let number = |part: &str| match part.trim() {
+        "" => Ok(None),
+        part => match part.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("'{}' is not an entry number", part)),
+            Ok(n) => Ok(Some(n)),
+        },
+    };
+    let start = number(start)?.unwrap_or(1);
+    let end = number(end)?.unwrap_or(usize::MAX);
+    if end < start {
+        return Err(format!("{} ends before it starts", text));
+    }
+    Ok(start - 1..end)
+}
+
+/// What `sort_by` orders entries by.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum SortKey {
+    Timestamp,
+    /// Severity, DEBUG lowest.
+    Level,
+    Message,
+}
+
+impl SortKey {
+    pub fn parse(name: &str) -> Option<SortKey> {
+        match name.trim().to_lowercase().as_str() {
+            "timestamp" | "time" => Some(SortKey::Timestamp),
+            "level" | "severity" => Some(SortKey::Level),
+            "message" => Some(SortKey::Message),
+            _ => None,
+        }
+    }
+}
+
+/// Orders entries by `key`, ascending unless `descending`; ties keep their
+/// order. By timestamp, an entry without a parseable time (such as a
+/// continuation line) stays after the entry before it.
+pub fn sort_by<E: std::borrow::Borrow<LogEntry>>(
+    entries: &mut Vec<E>,
+    key: SortKey,
+    descending: bool,
+) {
+    let order = |ordering: std::cmp::Ordering| {
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    };
+    match key {
+        SortKey::Timestamp => {
+            let mut last = None;
+            let mut keyed: Vec<_> = entries
+                .drain(..)
+                .map(|entry| {
+                    last = entry.borrow().time().or(last);
+                    (last, entry)
+                })
+                .collect();
+            keyed.sort_by(|(a, _), (b, _)| order(a.cmp(b)));
+            entries.extend(keyed.into_iter().map(|(_, entry)| entry));
+        }
+        SortKey::Level => entries.sort_by(|a, b| {
+            let rank = |e: &E| crate::alert::severity_rank(&e.borrow().level);
+            order(rank(a).cmp(&rank(b)))
+        }),
+        SortKey::Message => {
+            entries.sort_by(|a, b| order(a.borrow().message.cmp(&b.borrow().message)))
+        }
+    }
+}
+
+/// Interleaves the entries of several logs by time, tagging each with the
+/// name of its log in `field` unless it already has one (e.g. from an
+/// earlier merge). Entries without a time stay after the entry before them
+/// in their own log, and ties keep the order the logs were given in.
+pub fn merge(logs: Vec<(String, Vec<LogEntry>)>, field: &str) -> Vec<LogEntry> {
+    let mut keyed = Vec::new();
+    for (name, entries) in logs {
+        let mut last = None;
+        for mut entry in entries {
+            last = entry.time().or(last);
+            entry
+                .fields
+                .entry(field.to_string())
+                .or_insert_with(|| serde_json::Value::from(name.clone()));
+            keyed.push((last, entry));
+        }
+    }
+    keyed.sort_by_key(|(time, _)| *time);
+    keyed.into_iter().map(|(_, entry)| entry).collect()
+}
@@ -0,0 +1,94 @@
+//! Moving old entries out of the working file into one file per day, e.g.
+//! `logs/2024-05-01.txt` for `logs.txt`, so the file stays small and the
+//! history stays readable.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{LogAnalyzer, LogEntry};
+
+/// `30d` for thirty days ago, or a date or time such as `2024-05-01`.
+pub fn parse_cutoff(text: &str) -> Result<NaiveDateTime, String> {
+    match crate::query::parse_interval(text) {
+        Ok(age) => crate::query::ago(age),
+        Err(_) => crate::query::parse_time(text),
+    }
+}
+
+/// The directory next to `file` named after it: `logs/` for `logs.txt`.
+pub fn default_dir(file: &str) -> PathBuf {
+    let path = Path::new(file);
+    let stem = path.file_stem().unwrap_or(path.as_os_str());
+    path.with_file_name(stem)
+}
+
+// `dir/2024-05-01.txt`, with the extension of the working file.
+fn day_file(dir: &Path, file: &str, day: NaiveDate) -> PathBuf {
+    let ext = Path::new(file)
+        .extension()
+        .map_or("log".into(), |e| e.to_string_lossy());
+    dir.join(format!("{}.{}", day.format("%Y-%m-%d"), ext))
+}
+
+/// Splits `entries` into those from before `cutoff`, by day, and the rest.
+/// An entry without a time belongs with the entry before it.
+pub fn split(
+    entries: Vec<LogEntry>,
+    cutoff: NaiveDateTime,
+) -> (BTreeMap<NaiveDate, Vec<LogEntry>>, Vec<LogEntry>) {
+    let mut days: BTreeMap<NaiveDate, Vec<LogEntry>> = BTreeMap::new();
+    let mut kept = Vec::new();
+    let mut last = None;
+    for entry in entries {
+        last = entry.time().or(last);
+        match last {
+            Some(time) if time < cutoff => days.entry(time.date()).or_default().push(entry),
+            _ => kept.push(entry),
+        }
+    }
+    (days, kept)
+}
+
+/// Appends `days` to their files in `dir`, creating it if needed.
+pub fn write_days(
+    dir: &Path,
+    file: &str,
+    days: &BTreeMap<NaiveDate, Vec<LogEntry>>,
+) -> io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    for (day, entries) in days {
+        LogAnalyzer::append_to_file(&day_file(dir, file, *day).to_string_lossy(), entries)?;
+    }
+    Ok(())
+}
+
+/// Moves the entries of `file` from before `cutoff` into per-day files in
+/// `dir`; returns how many went to each day. The day files are written
+/// before the working file is rewritten, so a failure loses nothing,
+/// though entries may then be in both. With `dry_run` nothing is written.
+pub fn archive(
+    file: &str,
+    cutoff: NaiveDateTime,
+    dir: &Path,
+    dry_run: bool,
+) -> io::Result<BTreeMap<NaiveDate, usize>> {
+    let _lock = crate::lock::exclusive(file)?;
+    // Entries are written back, so they must not be transformed again.
+    let mut analyzer = LogAnalyzer::builder().pipeline(false).build();
+    analyzer.load_from_file(file)?;
+    let (days, kept) = split(std::mem::take(&mut analyzer.entries), cutoff);
+    let counts = days
+        .iter()
+        .map(|(day, entries)| (*day, entries.len()))
+        .collect();
+    if dry_run || days.is_empty() {
+        return Ok(counts);
+    }
+    write_days(dir, file, &days)?;
+    analyzer.entries = kept;
+    analyzer.save_to_file(file)?;
+    Ok(counts)
+}
@@ -0,0 +1,160 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+
+use chrono::{Duration, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::alert::severity_rank;
+use crate::report;
+use crate::LogEntry;
+
+/// Mean and standard deviation of one measure.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Moments {
+    pub mean: f64,
+    pub stddev: f64,
+    pub samples: usize,
+}
+
+impl Moments {
+    fn from_samples(values: &[f64]) -> Moments {
+        if values.is_empty() {
+            return Moments::default();
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        Moments {
+            mean,
+            stddev: variance.sqrt(),
+            samples: values.len(),
+        }
+    }
+
+    /// Standard deviations between `value` and the mean. With little
+    /// history the deviation can be ~0, so it is floored at `floor`.
+    fn z_score(&self, value: f64, floor: f64) -> f64 {
+        (value - self.mean) / self.stddev.max(floor)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HourProfile {
+    pub volume: Moments,
+    pub error_share: Moments,
+}
+
+/// What a normal hour looks like, learned per hour of day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    pub learned_at: String,
+    pub hours: Vec<HourProfile>,
+}
+
+pub struct Anomaly {
+    pub start: NaiveDateTime,
+    pub volume: usize,
+    pub expected_volume: f64,
+    pub volume_z: f64,
+    pub error_share: f64,
+    pub expected_error_share: f64,
+    pub error_z: f64,
+}
+
+// Hourly buckets as (start, total, errors).
+fn hourly(entries: &[LogEntry]) -> Vec<(NaiveDateTime, usize, usize)> {
+    report::level_histogram(entries, Duration::hours(1))
+        .into_iter()
+        .map(|(start, levels)| {
+            let total = levels.values().sum();
+            let errors = levels
+                .iter()
+                .filter(|(level, _)| severity_rank(level) >= 3)
+                .map(|(_, n)| n)
+                .sum();
+            (start, total, errors)
+        })
+        .collect()
+}
+
+fn share(total: usize, errors: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        errors as f64 / total as f64
+    }
+}
+
+impl Baseline {
+    pub fn learn(entries: &[LogEntry]) -> Baseline {
+        let mut volumes: Vec<Vec<f64>> = vec![Vec::new(); 24];
+        let mut shares: Vec<Vec<f64>> = vec![Vec::new(); 24];
+        for (start, total, errors) in hourly(entries) {
+            let hour = start.hour() as usize;
+            volumes[hour].push(total as f64);
+            shares[hour].push(share(total, errors));
+        }
+        Baseline {
+            learned_at: chrono::Local::now()
+                .format(crate::TIMESTAMP_FORMAT)
+                .to_string(),
+            hours: volumes
+                .iter()
+                .zip(&shares)
+                .map(|(volume, error_share)| HourProfile {
+                    volume: Moments::from_samples(volume),
+                    error_share: Moments::from_samples(error_share),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(filename)?);
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)?;
+        out.flush()
+    }
+
+    pub fn load(filename: &str) -> io::Result<Baseline> {
+        let baseline: Baseline = serde_json::from_reader(BufReader::new(File::open(filename)?))?;
+        if baseline.hours.len() != 24 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "baseline must have 24 hourly profiles",
+            ));
+        }
+        Ok(baseline)
+    }
+
+    /// Scores each hour of `entries` against the profile for that hour of
+    /// day and returns the hours whose volume or error share is at least
+    /// `threshold` standard deviations away. Hours never seen while
+    /// learning are skipped.
+    pub fn score(&self, entries: &[LogEntry], threshold: f64) -> Vec<Anomaly> {
+        hourly(entries)
+            .into_iter()
+            .filter_map(|(start, total, errors)| {
+                let profile = &self.hours[start.hour() as usize];
+                if profile.volume.samples == 0 {
+                    return None;
+                }
+                // Poisson-like floor for volume, one percentage point for shares.
+                let volume_z = profile
+                    .volume
+                    .z_score(total as f64, profile.volume.mean.sqrt().max(1.0));
+                let error_share = share(total, errors);
+                let error_z = profile.error_share.z_score(error_share, 0.01);
+                (volume_z.abs() >= threshold || error_z >= threshold).then_some(Anomaly {
+                    start,
+                    volume: total,
+                    expected_volume: profile.volume.mean,
+                    volume_z,
+                    error_share,
+                    expected_error_share: profile.error_share.mean,
+                    error_z,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,167 @@
+//! `logger run FILE`: a saved analysis, one command per line, run without
+//! the menu. Commands work on the entries selected so far:
+//!
+//! ```text
+//! # Errors of the last day, newest first
+//! load app.log
+//! since 1d
+//! filter ERROR+
+//! search timeout
+//! where .status >= 500
+//! sort timestamp desc
+//! head 20
+//! print
+//! report
+//! export errors.csv
+//! fail-on count ERROR+ > 100
+//! ```
+
+use serde_json::Value;
+
+use crate::analysis::{self, SortKey};
+use crate::cli::{print_json, OutputFormat};
+use crate::gate::{self, LevelSelector};
+use crate::query::{self, CompiledQuery};
+use crate::{color, export, report, LogAnalyzer, LogEntry};
+
+pub const COMMANDS_HELP: &str = "\
+load FILE          add the entries of FILE and select them all
+reset              select every loaded entry again
+filter LEVEL       keep LEVEL entries; ERROR+ also keeps worse ones
+search TEXT        keep entries whose message contains TEXT
+where EXPR         keep entries matching a WHERE expression
+since INTERVAL     keep entries newer than e.g. 30m, 1h, 2d
+sort KEY [desc]    order by timestamp, level or message
+head N / tail N    keep the first or last N
+print              print the selected entries
+count              print how many are selected
+report             print statistics of the selected entries
+export FILE        write them, in the format the extension names
+fail-on CONDITION  exit with 1 if e.g. \"count ERROR+ > 10\" holds";
+
+struct Batch {
+    analyzer: LogAnalyzer,
+    selected: Vec<LogEntry>,
+    output: OutputFormat,
+    /// Whether a `fail-on` condition held.
+    failed: bool,
+}
+
+impl Batch {
+    fn run(&mut self, command: &str, argument: &str) -> Result<(), String> {
+        let required = || {
+            if argument.is_empty() {
+                Err(format!("{} needs an argument", command))
+            } else {
+                Ok(argument)
+            }
+        };
+        match command {
+            "load" => {
+                self.analyzer
+                    .load_from_file(required()?)
+                    .map_err(|e| e.to_string())?;
+                self.selected = self.analyzer.entries.clone();
+            }
+            "reset" => self.selected = self.analyzer.entries.clone(),
+            "filter" => {
+                let selector = LevelSelector::parse(required()?);
+                self.selected.retain(|entry| selector.matches(entry));
+            }
+            "search" => {
+                let needle = required()?.to_lowercase();
+                self.selected
+                    .retain(|entry| entry.message.to_lowercase().contains(&needle));
+            }
+            "where" => {
+                let query = CompiledQuery::compile(required()?)?;
+                self.selected.retain(|entry| query.matches(entry));
+            }
+            "since" => {
+                let since = query::ago(query::parse_interval(required()?)?)?;
+                self.selected
+                    .retain(|entry| entry.time().is_some_and(|t| t >= since));
+            }
+            "sort" => {
+                let mut words = required()?.split_whitespace();
+                let key = words
+                    .next()
+                    .and_then(SortKey::parse)
+                    .ok_or("sort by timestamp, level or message")?;
+                let descending = match words.next() {
+                    None | Some("asc") => false,
+                    Some("desc") => true,
+                    Some(other) => return Err(format!("expected asc or desc, got '{}'", other)),
+                };
+                analysis::sort_by(&mut self.selected, key, descending);
+            }
+            "head" | "tail" => {
+                let count: usize = required()?
+                    .parse()
+                    .map_err(|_| format!("invalid count '{}'", argument))?;
+                if command == "head" {
+                    self.selected.truncate(count);
+                } else {
+                    let start = self.selected.len().saturating_sub(count);
+                    self.selected.drain(..start);
+                }
+            }
+            "print" => match self.output {
+                OutputFormat::Text => {
+                    for entry in &self.selected {
+                        println!("{}", color::entry_line(entry, None));
+                    }
+                }
+                OutputFormat::Json => print_json(&Value::Array(
+                    self.selected.iter().map(export::entry_json).collect(),
+                )),
+            },
+            "count" => println!("{}", self.selected.len()),
+            "report" => {
+                let stats = analysis::statistics(&self.selected);
+                match self.output {
+                    OutputFormat::Text => report::print_stats(&stats),
+                    OutputFormat::Json => print_json(&report::stats_json(&stats)),
+                }
+            }
+            "export" => {
+                let file = required()?;
+                export::write_results(&self.selected, file)
+                    .map_err(|e| format!("could not write {}: {}", file, e))?;
+                eprintln!("Saved {} entries to {}", self.selected.len(), file);
+            }
+            "fail-on" => {
+                let outcomes = gate::evaluate_all(&self.selected, &[required()?.to_string()])?;
+                for outcome in outcomes.iter().filter(|o| o.passed) {
+                    eprintln!("FAIL  {} ({})", outcome.condition, outcome.actual);
+                    self.failed = true;
+                }
+            }
+            _ => return Err(format!("unknown command '{}'", command)),
+        }
+        Ok(())
+    }
+}
+
+/// Runs the commands of `path` in order, stopping at the first that
+/// fails. Returns whether a `fail-on` condition held.
+pub fn run(path: &str, output: OutputFormat) -> Result<bool, String> {
+    let script = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut batch = Batch {
+        analyzer: LogAnalyzer::new(),
+        selected: Vec::new(),
+        output,
+        failed: false,
+    };
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        batch
+            .run(command, argument.trim())
+            .map_err(|e| format!("{}:{}: {}", path, number + 1, e))?;
+    }
+    Ok(batch.failed)
+}
@@ -0,0 +1,309 @@
+//! A compact storage format for log files, smaller than the text lines and
+//! read without parsing them. It is written to files named `.lgb` (also
+//! `.lgb.gz` and `.lgb.zst`) and, with `--binary`, to the log file
+//! whatever its name; files in it are recognized by their first bytes.
+//!
+//! After the magic bytes come blocks, one per save or append: the levels
+//! used in the block, then its records. Each record is its length and then
+//! a flags byte, the index of its level, the timestamp (seconds since the
+//! previous record's, when it is in the native format, else the text), the
+//! message and, if it has any, the fields as JSON. Numbers are varints;
+//! strings are their length and UTF-8 bytes.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, NaiveDateTime};
+use serde_json::{Map, Value};
+
+use crate::LogEntry;
+
+pub const MAGIC: &[u8] = b"LGB\x01";
+
+// Record flags.
+const SECONDS: u8 = 1;
+const FIELDS: u8 = 2;
+
+static FORCED: AtomicBool = AtomicBool::new(false);
+
+/// Whether every log file is written in this format, like `--binary`.
+pub fn force(forced: bool) {
+    FORCED.store(forced, Ordering::Relaxed);
+}
+
+/// Whether `filename` is to be written in this format.
+pub fn wanted(filename: &str) -> bool {
+    let name = filename
+        .strip_suffix(".gz")
+        .or_else(|| filename.strip_suffix(".zst"))
+        .unwrap_or(filename);
+    FORCED.load(Ordering::Relaxed) || name.to_lowercase().ends_with(".lgb")
+}
+
+/// Whether `filename` holds entries in the other format than it is to be
+/// written in, so appending would mix both, or is a binary log ending in
+/// a record cut off, which appending would bury; either way it must be
+/// rewritten.
+pub fn needs_rewrite(filename: &str) -> io::Result<bool> {
+    let file = match std::fs::File::open(filename) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let mut reader = crate::export::decompress(crate::crypt::decrypt(BufReader::new(file))?)?;
+    let start = reader.fill_buf()?;
+    if start.is_empty() {
+        return Ok(false);
+    }
+    let binary = start.starts_with(MAGIC);
+    if binary != wanted(filename) {
+        return Ok(true);
+    }
+    if !binary {
+        return Ok(false);
+    }
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    Ok(decode_all(&data).is_ok_and(|(_, torn)| torn))
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_str(out: &mut Vec<u8>, text: &str) {
+    put_varint(out, text.len() as u64);
+    out.extend_from_slice(text.as_bytes());
+}
+
+// The seconds of a timestamp in the native format, if it reads back the
+// same from them.
+fn seconds(timestamp: &str) -> Option<i64> {
+    let time = NaiveDateTime::parse_from_str(timestamp, crate::TIMESTAMP_FORMAT).ok()?;
+    let canonical = time.format(crate::TIMESTAMP_FORMAT).to_string() == timestamp;
+    canonical.then(|| time.and_utc().timestamp())
+}
+
+/// `entries` as one block, after the magic bytes if `start` is set.
+pub fn encode(entries: &[LogEntry], start: bool) -> Vec<u8> {
+    let mut levels: BTreeMap<&str, usize> = BTreeMap::new();
+    for entry in entries {
+        let next = levels.len();
+        levels.entry(&entry.level).or_insert(next);
+    }
+    let mut out = Vec::new();
+    if start {
+        out.extend_from_slice(MAGIC);
+    }
+    let mut names: Vec<_> = levels.iter().collect();
+    names.sort_by_key(|(_, index)| **index);
+    put_varint(&mut out, names.len() as u64);
+    for (name, _) in names {
+        put_str(&mut out, name);
+    }
+    put_varint(&mut out, entries.len() as u64);
+    let mut previous = 0;
+    let mut record = Vec::new();
+    for entry in entries {
+        record.clear();
+        let seconds = seconds(&entry.timestamp);
+        let flags =
+            seconds.map_or(0, |_| SECONDS) | if entry.fields.is_empty() { 0 } else { FIELDS };
+        record.push(flags);
+        put_varint(&mut record, levels[entry.level.as_str()] as u64);
+        match seconds {
+            Some(seconds) => {
+                // Zigzag, so time going back also stays small.
+                let delta = seconds.wrapping_sub(previous);
+                put_varint(&mut record, ((delta << 1) ^ (delta >> 63)) as u64);
+                previous = seconds;
+            }
+            None => put_str(&mut record, &entry.timestamp),
+        }
+        put_str(&mut record, &entry.message);
+        if !entry.fields.is_empty() {
+            put_str(
+                &mut record,
+                &Value::Object(entry.fields.clone()).to_string(),
+            );
+        }
+        put_varint(&mut out, record.len() as u64);
+        out.extend_from_slice(&record);
+    }
+    out
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "truncated record")
+}
+
+// Reads from the front of a byte slice.
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.0.split_first().ok_or_else(truncated)?;
+            self.0 = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("number too long"))
+    }
+
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let (bytes, rest) = self.0.split_at_checked(len).ok_or_else(truncated)?;
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn str(&mut self) -> io::Result<String> {
+        let len = self.varint()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| invalid("text is not UTF-8"))
+    }
+}
+
+/// The entries of `reader`, which starts with the magic bytes. A record
+/// cut off at the end, as by a crash mid-append, is left out with a
+/// warning, like a partial last line of a text log.
+pub fn decode(mut reader: impl BufRead) -> io::Result<Vec<LogEntry>> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let (entries, torn) = decode_all(&data)?;
+    if torn {
+        eprintln!("Left out a record cut off at the end of a binary log");
+    }
+    Ok(entries)
+}
+
+// The entries of `data`, and whether it ends in a record cut off.
+fn decode_all(data: &[u8]) -> io::Result<(Vec<LogEntry>, bool)> {
+    let mut input = Cursor(
+        data.strip_prefix(MAGIC)
+            .ok_or_else(|| invalid("not a binary log"))?,
+    );
+    let mut entries = Vec::new();
+    while !input.0.is_empty() {
+        match decode_block(&mut input, &mut entries) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok((entries, true)),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((entries, false))
+}
+
+// Decodes one block onto `entries`, record by record.
+fn decode_block(input: &mut Cursor, entries: &mut Vec<LogEntry>) -> io::Result<()> {
+    let levels = (0..input.varint()?)
+        .map(|_| input.str())
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut previous = 0i64;
+    for _ in 0..input.varint()? {
+        let len = input.varint()? as usize;
+        // Cut off inside a complete record is damage, not a torn append.
+        let entry = decode_record(input.bytes(len)?, &levels, &mut previous).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                invalid("damaged record")
+            } else {
+                e
+            }
+        })?;
+        entries.push(entry);
+    }
+    Ok(())
+}
+
+fn decode_record(bytes: &[u8], levels: &[String], previous: &mut i64) -> io::Result<LogEntry> {
+    let mut record = Cursor(bytes);
+    let flags = record.bytes(1)?[0];
+    let level = levels
+        .get(record.varint()? as usize)
+        .ok_or_else(|| invalid("unknown level"))?;
+    let timestamp = if flags & SECONDS != 0 {
+        let zigzag = record.varint()?;
+        *previous = previous.wrapping_add((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64));
+        DateTime::from_timestamp(*previous, 0)
+            .ok_or_else(|| invalid("timestamp out of range"))?
+            .naive_utc()
+            .format(crate::TIMESTAMP_FORMAT)
+            .to_string()
+    } else {
+        record.str()?
+    };
+    let message = record.str()?;
+    let fields = if flags & FIELDS != 0 {
+        serde_json::from_str::<Map<String, Value>>(&record.str()?)?
+    } else {
+        Map::new()
+    };
+    Ok(LogEntry {
+        timestamp,
+        level: level.clone(),
+        message,
+        fields,
+        source: None,
+    })
+}
+
+/// Writes `entries` to `filename` in this format, like the other export
+/// formats; `.gz` or `.zst` compresses.
+pub fn write_file(entries: &[LogEntry], filename: &str) -> io::Result<()> {
+    use std::io::Write;
+
+    let entries = &*crate::redact::for_export(entries);
+    let mut out = crate::export::create_output(filename)?;
+    out.write_all(&encode(entries, true))?;
+    out.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: &str) -> LogEntry {
+        LogEntry::from_line(line).unwrap()
+    }
+
+    fn lines(entries: &[LogEntry]) -> Vec<String> {
+        entries.iter().map(LogEntry::to_line).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let entries = vec![
+            entry("2026-01-01 00:00:00|INFO|started"),
+            entry("2025-12-31 23:59:00|ERROR|clock went back"),
+            entry(r#"{"timestamp":"yesterday","level":"WARNING","message":"x","id":7}"#),
+        ];
+        let mut data = encode(&entries, true);
+        data.extend(encode(&entries[..1], false));
+        let decoded = decode(&data[..]).unwrap();
+        let mut expected = lines(&entries);
+        expected.push(entries[0].to_line());
+        assert_eq!(lines(&decoded), expected);
+    }
+
+    #[test]
+    fn torn_record_is_left_out() {
+        let first = vec![entry("2026-01-01 00:00:00|INFO|a")];
+        let mut data = encode(&first, true);
+        data.extend(encode(&[entry("2026-01-01 00:00:01|INFO|b")], false));
+        data.pop();
+        let (entries, torn) = decode_all(&data).unwrap();
+        assert!(torn);
+        assert_eq!(lines(&entries), lines(&first));
+    }
+}
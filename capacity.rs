@@ -0,0 +1,68 @@
+use std::io;
+use std::path::Path;
+
+use crate::LogEntry;
+
+pub struct CapacityForecast {
+    /// Size of the entries as they would be saved.
+    pub stored_bytes: u64,
+    pub bytes_per_day: f64,
+    pub available_bytes: u64,
+    /// `None` when the logs show no growth to extrapolate from.
+    pub days_until_full: Option<f64>,
+}
+
+/// Extrapolates the ingest rate seen between the oldest and newest entry
+/// against the free space of the filesystem holding `log_file`.
+pub fn forecast(entries: &[LogEntry], log_file: &str) -> io::Result<CapacityForecast> {
+    let dir = match Path::new(log_file).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let available_bytes = fs2::available_space(dir)?;
+    let stored_bytes: u64 = entries.iter().map(|e| e.to_line().len() as u64 + 1).sum();
+
+    let times = entries.iter().filter_map(|e| e.time());
+    let span = match (times.clone().min(), times.max()) {
+        (Some(first), Some(last)) => (last - first).num_seconds() as f64 / 86_400.0,
+        _ => 0.0,
+    };
+    let bytes_per_day = if span > 0.0 {
+        stored_bytes as f64 / span
+    } else {
+        0.0
+    };
+
+    Ok(CapacityForecast {
+        stored_bytes,
+        bytes_per_day,
+        available_bytes,
+        days_until_full: (bytes_per_day > 0.0).then(|| available_bytes as f64 / bytes_per_day),
+    })
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+pub fn print_forecast(forecast: &CapacityForecast) {
+    println!("\nCapacity:");
+    println!("Stored: {}", format_bytes(forecast.stored_bytes as f64));
+    println!("Growth: {}/day", format_bytes(forecast.bytes_per_day));
+    println!(
+        "Free on disk: {}",
+        format_bytes(forecast.available_bytes as f64)
+    );
+    match forecast.days_until_full {
+        Some(days) if days > 3650.0 => println!("Disk full in: more than 10 years"),
+        Some(days) => println!("Disk full in: {:.1} days", days),
+        None => println!("Disk full in: unknown (no growth observed)"),
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{normalize_message, MessagePattern};
+use crate::LogEntry;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateInfo {
+    pub first_seen: String,
+    pub last_seen: String,
+    pub count: usize,
+}
+
+/// Every message template seen so far, keyed by the normalized message.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateCatalog {
+    pub templates: BTreeMap<String, TemplateInfo>,
+}
+
+impl TemplateCatalog {
+    /// Loads the catalog, or starts an empty one if the file doesn't exist yet.
+    pub fn load(filename: &str) -> io::Result<TemplateCatalog> {
+        match File::open(filename) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(TemplateCatalog::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(filename)?);
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)?;
+        out.flush()
+    }
+
+    /// Adds the templates of `entries`; returns how many were new.
+    pub fn record(&mut self, entries: &[LogEntry]) -> usize {
+        let before = self.templates.len();
+        for entry in entries {
+            let info = self
+                .templates
+                .entry(normalize_message(&entry.message))
+                .or_insert_with(|| TemplateInfo {
+                    first_seen: entry.timestamp.clone(),
+                    last_seen: entry.timestamp.clone(),
+                    count: 0,
+                });
+            info.count += 1;
+            let Some(time) = entry.time() else {
+                continue;
+            };
+            // A stored time that does not parse is replaced by one that does.
+            if crate::parse_time(&info.first_seen).is_none_or(|first| time < first) {
+                info.first_seen = entry.timestamp.clone();
+            }
+            if crate::parse_time(&info.last_seen).is_none_or(|last| time > last) {
+                info.last_seen = entry.timestamp.clone();
+            }
+        }
+        self.templates.len() - before
+    }
+
+    /// Templates in `entries` the catalog has never seen, most frequent first.
+    pub fn unseen(&self, entries: &[LogEntry]) -> Vec<MessagePattern> {
+        let mut found: BTreeMap<String, MessagePattern> = BTreeMap::new();
+        for entry in entries {
+            let template = normalize_message(&entry.message);
+            if self.templates.contains_key(&template) {
+                continue;
+            }
+            found
+                .entry(template.clone())
+                .or_insert_with(|| MessagePattern {
+                    template,
+                    count: 0,
+                    example: entry.message.clone(),
+                })
+                .count += 1;
+        }
+        let mut patterns: Vec<MessagePattern> = found.into_values().collect();
+        patterns.sort_by_key(|p| std::cmp::Reverse(p.count));
+        patterns
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::LogEntry;
+
+/// Field holding the SHA-256 of the previous entry's stored line.
+pub const FIELD: &str = "prev_hash";
+
+/// `prev_hash` of the first entry in a file.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns on chaining for every later write (`hash_chain = true`).
+pub fn init(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn digest(entry: &LogEntry) -> String {
+    Sha256::digest(entry.to_line().as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Copies of `entries` linked to each other and to `previous`, the entry
+/// they follow in the file (`None` at the start of the file).
+pub fn link(previous: Option<&LogEntry>, entries: &[LogEntry]) -> Vec<LogEntry> {
+    let mut prev_hash = previous.map_or_else(|| GENESIS.to_string(), digest);
+    entries
+        .iter()
+        .map(|entry| {
+            let mut entry = entry.clone();
+            entry
+                .fields
+                .insert(FIELD.to_string(), Value::from(prev_hash.clone()));
+            prev_hash = digest(&entry);
+            entry
+        })
+        .collect()
+}
+
+/// Where the chain stops matching.
+#[derive(Debug)]
+pub struct Break {
+    /// 0-based index of the first entry whose link does not match.
+    pub index: usize,
+    pub reason: &'static str,
+}
+
+/// Checks every link. An entry that was changed, inserted or removed
+/// breaks the link of the entry after it (or its own); entries cut off
+/// the end of the file cannot be detected from the file alone.
+pub fn verify(entries: &[LogEntry]) -> Vec<Break> {
+    let mut breaks = Vec::new();
+    let mut expected = GENESIS.to_string();
+    for (index, entry) in entries.iter().enumerate() {
+        match entry.fields.get(FIELD).and_then(Value::as_str) {
+            None => breaks.push(Break {
+                index,
+                reason: "entry is not chained",
+            }),
+            Some(hash) if hash != expected => breaks.push(Break {
+                index,
+                reason: if index == 0 {
+                    "first entry does not start a chain"
+                } else {
+                    "previous entry was modified, inserted or removed"
+                },
+            }),
+            Some(_) => {}
+        }
+        expected = digest(entry);
+    }
+    breaks
+}
@@ -0,0 +1,104 @@
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum ChartStyle {
+    Bar,
+    Line,
+}
+
+const Y_LABEL_WIDTH: usize = 10;
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e9 {
+        format!("{}", value as i64)
+    } else if value.abs() >= 100.0 {
+        format!("{:.0}", value)
+    } else if value.abs() >= 1.0 {
+        format!("{:.1}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+/// Draws the series as a `height` rows tall chart with a labelled y axis
+/// scaled from zero to the largest value, and the first and last bucket
+/// under the x axis. Series longer than `width` columns are squeezed by
+/// keeping the largest value of each group of buckets, so peaks survive.
+pub fn render(
+    points: &[(NaiveDateTime, f64)],
+    style: ChartStyle,
+    height: usize,
+    width: usize,
+) -> String {
+    if points.is_empty() {
+        return "No data\n".to_string();
+    }
+    let height = height.max(2);
+    let per_column = points.len().div_ceil(width.max(1));
+    let columns: Vec<f64> = points
+        .chunks(per_column)
+        .map(|chunk| chunk.iter().map(|(_, v)| *v).fold(f64::MIN, f64::max))
+        .collect();
+    let max = columns.iter().copied().fold(0.0, f64::max);
+    let scale = if max > 0.0 { max } else { 1.0 };
+    // Row (0 = bottom) each column reaches.
+    let levels: Vec<usize> = columns
+        .iter()
+        .map(|v| ((v / scale) * (height - 1) as f64).round() as usize)
+        .collect();
+
+    let mut chart = String::new();
+    for row in (0..height).rev() {
+        let label = if row == height - 1 {
+            format_value(max)
+        } else if row == (height - 1) / 2 && height > 2 {
+            format_value(max * row as f64 / (height - 1) as f64)
+        } else if row == 0 {
+            "0".to_string()
+        } else {
+            String::new()
+        };
+        chart.push_str(&format!("{:>w$} |", label, w = Y_LABEL_WIDTH));
+        for (i, &level) in levels.iter().enumerate() {
+            let cell = match style {
+                ChartStyle::Bar if level >= row && (row > 0 || columns[i] > 0.0) => '#',
+                ChartStyle::Line if level == row => '*',
+                // Connect steep changes so the line reads as continuous.
+                ChartStyle::Line
+                    if i > 0 && {
+                        let previous = levels[i - 1];
+                        (previous.min(level) < row) && (row < previous.max(level))
+                    } =>
+                {
+                    '|'
+                }
+                _ => ' ',
+            };
+            chart.push(cell);
+        }
+        chart.push('\n');
+    }
+    chart.push_str(&format!(
+        "{:>w$} +{}\n",
+        "",
+        "-".repeat(levels.len()),
+        w = Y_LABEL_WIDTH
+    ));
+
+    let first = points[0].0.format("%Y-%m-%d %H:%M").to_string();
+    let last = points[points.len() - 1]
+        .0
+        .format("%Y-%m-%d %H:%M")
+        .to_string();
+    let gap = levels.len().saturating_sub(first.len() + last.len()).max(1);
+    chart.push_str(&format!(
+        "{:>w$}  {}{}{}\n",
+        "",
+        first,
+        " ".repeat(gap),
+        if points.len() > 1 { last.as_str() } else { "" },
+        w = Y_LABEL_WIDTH
+    ));
+    chart
+}
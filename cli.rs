@@ -0,0 +1,3400 @@
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::analysis::SortKey;
+use crate::chart::{self, ChartStyle};
+use crate::color::{self, ColorChoice};
+use crate::config::Config;
+use crate::directives::Directives;
+use crate::export::Compression;
+use crate::listen::Receiver;
+use crate::rules::Transition;
+use crate::script::Script;
+use crate::server::Server;
+use crate::sink::{self, Target};
+use crate::supervise::{RestartPolicy, Supervisor};
+use crate::{
+    access, alert, analysis, archive, batch, chain, compare, correlation, elastic, export, extract,
+    facade, fields, gate, generate, k8s, loki, metrics, pager, prometheus, query, redact, replay,
+    report, retention, rules, schedule, ssh, trace, tui, AnalyzerBuilder, LogAnalyzer, LogEntry,
+};
+
+/// Log analyzer. Run `logger repl` for the interactive menu.
+#[derive(Parser)]
+#[command(name = "logger", version, arg_required_else_help = true)]
+pub struct Cli {
+    /// Config file [default: ~/.config/logger/config.toml]
+    #[arg(long, global = true)]
+    pub config: Option<String>,
+    /// Output format for stats, search, workers, funnel and durations
+    /// [default: text]
+    #[arg(long, global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+    /// Color entries by level [default: auto]
+    #[arg(long, global = true, value_enum)]
+    pub color: Option<ColorChoice>,
+    /// Also print new entries (add, supervise, repl) to stdout as JSON lines
+    #[arg(long, global = true)]
+    pub emit: bool,
+    /// Show where each printed entry came from: file and line, and host
+    /// and pid when the input records them
+    #[arg(long, global = true)]
+    pub show_source: bool,
+    /// strftime format printed timestamps are shown in, e.g. "%H:%M:%S"
+    #[arg(long, global = true)]
+    pub time_format: Option<String>,
+    /// Timezone printed timestamps are shown in: local, utc or a name such
+    /// as Europe/Berlin
+    #[arg(long, global = true)]
+    pub tz: Option<String>,
+    /// Write log files in the compact binary format whatever their name,
+    /// converting text ones on the next write; `.lgb` files always are
+    #[arg(long, global = true)]
+    pub binary: bool,
+    /// Never pipe long output through $PAGER
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+    /// Encoding of input files, e.g. utf-16le, latin-1, shift_jis
+    /// [default: auto]
+    #[arg(long, global = true)]
+    pub encoding: Option<String>,
+    /// HTTP header for http(s):// inputs, e.g. "Authorization: Bearer
+    /// TOKEN"; repeatable
+    #[arg(long = "http-header", global = true)]
+    pub http_headers: Vec<String>,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Interactive menu
+    Repl {
+        /// Defaults to the log file from the config, else logs.txt
+        file: Option<String>,
+    },
+    /// Full-screen browser with live filtering
+    Tui {
+        /// Defaults to the log file from the config, else logs.txt
+        file: Option<String>,
+    },
+    /// Entry counts per level
+    Stats {
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Write an HTML report (totals, levels, a histogram, top errors and a
+    /// filterable table), or a Markdown summary with --markdown
+    Report {
+        #[arg(long)]
+        markdown: bool,
+        /// Destination, - for stdout [default: report.html, or report.md
+        /// with --markdown]
+        #[arg(long, short)]
+        out: Option<String>,
+        /// Hold at most about N entries in the HTML table; the summaries
+        /// still cover everything
+        #[arg(long, value_name = "N", conflicts_with = "markdown")]
+        budget: Option<usize>,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Run a SQL query, e.g. `logger query "SELECT level, count(*) FROM logs
+    /// GROUP BY level" app.log`
+    Query {
+        /// SELECT cols FROM logs [WHERE ...] [GROUP BY ...] [ORDER BY col
+        /// [ASC|DESC], ...] [LIMIT n]; columns are ts, level, message,
+        /// .paths, count(*), min(ts) and max(ts)
+        sql: String,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Print entries whose message contains TEXT (case-insensitive)
+    Search {
+        /// Only entries of these levels, e.g. ERROR,WARNING
+        #[arg(long, value_delimiter = ',')]
+        level: Vec<String>,
+        /// Only entries newer than this, e.g. 30m, 1h, 2d
+        #[arg(long)]
+        since: Option<String>,
+        /// Only entries with this tag (tags are attached in the repl)
+        #[arg(long)]
+        tag: Option<String>,
+        /// RUST_LOG-style levels per target, e.g. "myapp=debug,hyper=warn";
+        /// the target is the entry's target, source or logger field
+        #[arg(long)]
+        filter: Option<String>,
+        /// Only entries this Rhai script keeps: it sees `entry` (timestamp,
+        /// level, message, fields) and returns false to leave one out
+        #[arg(long)]
+        script: Option<String>,
+        /// Also write the results to FILE: CSV for .csv, JSON lines for
+        /// .json/.jsonl/.ndjson, else log lines; .gz or .zst compresses
+        #[arg(long, value_name = "FILE")]
+        save: Option<String>,
+        /// Compress the --save file, adding the extension to its name
+        #[arg(long, value_enum, requires = "save")]
+        compress: Option<Compression>,
+        /// Order the results by timestamp, level severity or message
+        /// [default: as in the file]
+        #[arg(long, value_enum)]
+        sort: Option<SortKey>,
+        /// With --sort, order descending: newest, most severe or Z first
+        #[arg(long, requires = "sort")]
+        desc: bool,
+        /// Only print how many entries match
+        #[arg(long, conflicts_with_all = ["save", "sort"])]
+        count: bool,
+        /// With --count, also count the matches per level
+        #[arg(long, requires = "count")]
+        per_level: bool,
+        /// Drop entries repeating the one before them exactly
+        #[arg(long)]
+        dedup: bool,
+        /// Use "" to match every message
+        text: String,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Print the first entries of a log
+    Head {
+        /// How many entries
+        #[arg(short = 'n', long, default_value_t = 10)]
+        lines: usize,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Print entries by number, e.g. `logger slice 1000..2000 app.log` to
+    /// bisect where a problem started; numbers start at 1 and both ends are
+    /// included, `1000..` runs to the end
+    Slice {
+        range: String,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Print a uniform random sample of N entries, in file order
+    Sample {
+        count: usize,
+        /// Seed for a repeatable sample [default: the clock]
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// List the values a field takes and how often, most common first, e.g.
+    /// `logger distinct host app.log` to see what an unfamiliar log holds
+    Distinct {
+        /// level, message, or a structured field such as host, .tags or
+        /// .http.status
+        field: String,
+        /// Only the N most common values
+        #[arg(long)]
+        top: Option<usize>,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Count, distinct values, and min/max/mean of a numeric field or the
+    /// most common values of a text one
+    FieldStats {
+        /// level, message, ts, or a structured field such as .latency_ms
+        field: String,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Append an entry stamped with the current time
+    Add {
+        level: String,
+        message: String,
+        /// Defaults to the log file from the config, else logs.txt
+        file: Option<String>,
+    },
+    /// Volume, errors and silence per thread or worker
+    Workers {
+        /// Worker ID field, e.g. .thread; by default thread, worker, tid
+        /// and similar fields are tried
+        #[arg(long)]
+        field: Option<String>,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// How many requests reached each stage, and where they dropped off
+    Funnel {
+        /// Correlation ID field, e.g. .request_id
+        #[arg(long)]
+        id: String,
+        /// Message marker of a stage, in order; repeat for each stage
+        #[arg(long = "stage", required = true)]
+        stages: Vec<String>,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Entries grouped into requests by correlation ID across logs: one
+    /// line per request, or every entry of one request in time order
+    Requests {
+        /// Show the whole lifecycle of this request
+        #[arg(long)]
+        id: Option<String>,
+        /// Where the ID is: a field such as .request_id, or /regex/ whose
+        /// `id` group or first capture is the ID [default: the config's
+        /// correlation_id, else request_id and similar fields or message
+        /// text]
+        #[arg(long)]
+        by: Option<String>,
+        /// Most requests to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Log files or - for stdin [default: piped stdin, else the log
+        /// file from the config]
+        files: Vec<String>,
+    },
+    /// Web traffic of an access log: latency percentiles, status codes,
+    /// error rate per endpoint, the slowest endpoints and the clients
+    Access {
+        /// How many endpoints and clients to list in each table
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Leave out requests from crawlers, monitors and scripts
+        #[arg(long)]
+        no_bots: bool,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Pair start and end events by ID and report their durations, the
+    /// slowest spans and starts that never ended
+    Durations {
+        /// Regex for start events; its `id` group or first capture is the ID
+        #[arg(long, required_unless_present = "span", requires = "end")]
+        start: Option<String>,
+        /// Regex for end events
+        #[arg(long, requires = "start")]
+        end: Option<String>,
+        /// Use the start and end patterns of this [[spans]] table in the
+        /// config
+        #[arg(long, conflicts_with_all = ["start", "end"])]
+        span: Option<String>,
+        /// Take the ID from this field instead of a capture
+        #[arg(long)]
+        id: Option<String>,
+        /// How many of the longest spans to list
+        #[arg(long, default_value_t = 5)]
+        slowest: usize,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Write a bucketed aggregation as tidy CSV (bucket,series,value)
+    Timeseries {
+        #[arg(long, value_enum, default_value_t = Metric::Counts)]
+        metric: Metric,
+        /// Bucket width, e.g. 5m or 1h
+        #[arg(long, default_value = "5m")]
+        bucket: String,
+        /// Regex with a capture group, for --metric extract
+        #[arg(long)]
+        pattern: Option<String>,
+        /// Destination, - for stdout; .gz/.zst compress
+        #[arg(long, short, default_value = "-")]
+        out: String,
+        /// Compress the --out file, adding the extension to its name
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Draw a bucketed series as a terminal chart
+    Chart {
+        #[arg(long, value_enum, default_value_t = Metric::Counts)]
+        metric: Metric,
+        /// Series to draw: a level or "total" for counts and rate,
+        /// p50/p95/p99/avg for extract
+        #[arg(long)]
+        series: Option<String>,
+        #[arg(long, value_enum, default_value_t = ChartStyle::Bar)]
+        style: ChartStyle,
+        /// Bucket width, e.g. 5m or 1h
+        #[arg(long, default_value = "5m")]
+        bucket: String,
+        /// Regex with a capture group, for --metric extract
+        #[arg(long)]
+        pattern: Option<String>,
+        /// Chart height in rows
+        #[arg(long, default_value_t = 15)]
+        height: usize,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Run a command, restart it on failure and log its output
+    Supervise {
+        #[arg(long, value_enum, default_value_t = RestartPolicy::OnFailure)]
+        restart: RestartPolicy,
+        /// Stop restarting after this many restarts
+        #[arg(long)]
+        max_restarts: Option<u32>,
+        /// Log file the output and lifecycle events are appended to
+        /// [default: the log file from the config, else logs.txt]
+        #[arg(long)]
+        log: Option<String>,
+        /// Command and arguments, after --
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Print a shell completion script, e.g. `logger completions bash`
+    Completions { shell: clap_complete::Shell },
+    /// Run a saved analysis: the commands of FILE, one per line; exits 1 if
+    /// a fail-on condition holds
+    #[command(after_help = batch::COMMANDS_HELP)]
+    Run { script: String },
+    /// Exit non-zero unless all conditions hold
+    #[command(after_help = gate::CONDITIONS_HELP)]
+    Assert {
+        /// Log file, or - for stdin
+        file: String,
+        #[arg(required = true)]
+        conditions: Vec<String>,
+    },
+    /// Scan a log, e.g. a CI test log, and exit 1 if it has problems: by
+    /// default any ERROR or worse
+    #[command(after_help = gate::CONDITIONS_HELP)]
+    Check {
+        /// Fail on more than this many ERROR-or-worse entries
+        #[arg(long, default_value_t = 0)]
+        max_errors: usize,
+        /// Fail on more than this many WARNING entries [default: any number]
+        #[arg(long)]
+        max_warnings: Option<usize>,
+        /// Fail if a message matches this regex; repeatable
+        #[arg(long = "pattern")]
+        patterns: Vec<String>,
+        /// Fail if this condition holds, e.g. "error rate > 1%"; repeatable
+        #[arg(long = "fail-on")]
+        fail_on: Vec<String>,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Compare a log to a saved stats baseline; exit 1 on regression
+    #[command(after_help = compare::FAIL_ON_HELP)]
+    CompareBaseline {
+        /// Stats baseline JSON written by --save
+        baseline: String,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        current: Option<String>,
+        /// What counts as a regression [default: new_error_templates > 0]
+        #[arg(long)]
+        fail_on: Option<String>,
+        /// Write the current stats as the new baseline instead of comparing
+        #[arg(long)]
+        save: bool,
+    },
+    /// Compare two runs, e.g. before and after a deploy: message templates
+    /// that appeared, disappeared or changed frequency; exit 1 if any did
+    Diff {
+        /// The earlier log, or - for stdin
+        before: String,
+        /// The later log, or - for stdin
+        after: String,
+        /// Report templates whose share of the entries changed by this
+        /// factor either way
+        #[arg(long, default_value_t = 2.0)]
+        factor: f64,
+        /// Ignore templates seen fewer times than this in both runs
+        #[arg(long, default_value_t = 2)]
+        min_count: usize,
+        /// Templates listed per kind of change
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Evaluate alert rules against the latest window; exit 1 if any fire
+    #[command(after_help = rules::RULES_HELP)]
+    Alerts {
+        /// TOML file of [[rule]] and [[silence]] tables
+        #[arg(long, default_value = "rules.toml")]
+        rules: String,
+        /// Where alerts suppressed by a silence are recorded
+        #[arg(long, default_value = "silenced.log")]
+        silenced_log: String,
+        /// File keeping which alerts are firing between runs, so an alert
+        /// notifies once (and again after its cooldown) and resolves once
+        #[arg(long, default_value = "alerts-state.json")]
+        state: String,
+        /// Keep running: evaluate every second as lines are appended, with
+        /// windows ending now, and report rules as they fire and resolve
+        #[arg(long)]
+        follow: bool,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Check the hash chain of a log written with `hash_chain = true`;
+    /// exit 1 if it is broken
+    Verify {
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Print the log with the [redaction] patterns from the config applied,
+    /// e.g. `logger redact app.log > shareable.log`
+    Redact {
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Permanently remove (or scrub) every entry matching a WHERE
+    /// expression, e.g. `logger purge ".user_id = 123" logs.txt old.log`.
+    /// Shows how many entries match in each file and asks before rewriting
+    Purge {
+        /// WHERE expression selecting the entries to purge
+        filter: String,
+        /// Keep the entries but replace their message with "[purged]" and
+        /// drop their fields, so counts and timelines stay intact
+        #[arg(long)]
+        scrub: bool,
+        /// Only show what matches
+        #[arg(long)]
+        dry_run: bool,
+        /// Apply without asking
+        #[arg(long)]
+        yes: bool,
+        /// Log files to rewrite [default: the log file from the config].
+        /// Backups and rotated files are only purged when listed
+        files: Vec<String>,
+    },
+    /// Build an [[extract]] rule interactively: try a regex with named
+    /// groups on a sample entry, preview the fields it produces, and save it
+    /// to the config file
+    Extract {
+        /// How many matches to preview
+        #[arg(long, default_value_t = 10)]
+        preview: usize,
+        /// Log file [default: the log file from the config]
+        file: Option<String>,
+    },
+    /// Send entries to a log server, e.g. `logger export --target syslog
+    /// --to logs.example.com:514 app.log`; with --follow keep sending lines
+    /// as they are appended
+    Export {
+        #[arg(long, value_enum)]
+        target: Target,
+        /// host:port of the server, or its URL for HTTP targets
+        #[arg(long)]
+        to: String,
+        /// Use TCP instead of UDP
+        #[arg(long)]
+        tcp: bool,
+        /// HTTP header to send, e.g. "Authorization: Bearer TOKEN"; repeatable
+        #[arg(long = "header")]
+        headers: Vec<String>,
+        /// Loki label: level, host, file, a field name, or name=value;
+        /// repeatable [default: level, host and file]
+        #[arg(long = "label")]
+        labels: Vec<String>,
+        /// Elasticsearch index, with strftime fields filled from each
+        /// entry's time
+        #[arg(long, default_value = elastic::DEFAULT_INDEX)]
+        index: String,
+        /// Times to resend a failed request, backing off from one second
+        #[arg(long, default_value_t = 3)]
+        retries: u32,
+        /// OpenTelemetry resource attribute, e.g. service.name=api;
+        /// repeatable
+        #[arg(long = "resource")]
+        resource: Vec<String>,
+        /// Entries per request
+        #[arg(long, default_value_t = 500)]
+        batch_size: usize,
+        /// Compress request bodies to the HTTP targets; Loki and
+        /// Elasticsearch take gzip, OpenTelemetry collectors either
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+        /// Keep running and send lines appended to the file
+        #[arg(long)]
+        follow: bool,
+        /// With --follow, serve Prometheus metrics on this address, e.g.
+        /// 127.0.0.1:9184, at /metrics
+        #[arg(long, requires = "follow")]
+        metrics: Option<String>,
+        /// With --follow, record in this file how far the log was sent and
+        /// carry on from there next time, even across a rotation
+        #[arg(long, requires = "follow")]
+        offsets: Option<String>,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Answer queries over HTTP: GET /entries (level, since, q, filter and
+    /// limit parameters), GET /stats, POST /entries with a JSON entry, and
+    /// GET /metrics
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+        /// Answer from only the newest N entries, bounding memory
+        #[arg(long)]
+        max_entries: Option<usize>,
+        /// Write a report of the entries added every interval, e.g. 1h
+        #[arg(long)]
+        report_every: Option<String>,
+        /// Where reports go: a file (strftime sequences allowed), an
+        /// http(s) webhook or mailto:ADDRESS; repeatable [default: stdout]
+        #[arg(long, requires = "report_every")]
+        report_to: Vec<String>,
+        /// Defaults to the log file from the config, else logs.txt
+        file: Option<String>,
+    },
+    /// Receive syslog messages over UDP, TCP or a Unix socket and append
+    /// them to the log file, e.g. `logger listen --udp 0.0.0.0:514 --tcp
+    /// 0.0.0.0:514`
+    Listen {
+        /// Address to receive UDP datagrams on
+        #[arg(long, required_unless_present_any = ["tcp", "unix"])]
+        udp: Option<String>,
+        /// Address to accept TCP connections on, newline or octet-counting
+        /// framed
+        #[arg(long)]
+        tcp: Option<String>,
+        /// Unix socket to create; local daemons write one line or JSON
+        /// object per entry
+        #[arg(long)]
+        unix: Option<String>,
+        /// Seconds between appends to the log file
+        #[arg(long, default_value_t = 5)]
+        flush: u64,
+        /// Entries held in memory before an early append; stream writers
+        /// are slowed down while it is full
+        #[arg(long, default_value_t = 10_000)]
+        buffer: usize,
+        /// Serve Prometheus metrics on this address, at /metrics
+        #[arg(long)]
+        metrics: Option<String>,
+        /// Write a report of the entries added every interval, e.g. 1h
+        #[arg(long)]
+        report_every: Option<String>,
+        /// Where reports go: a file (strftime sequences allowed), an
+        /// http(s) webhook or mailto:ADDRESS; repeatable [default: stdout]
+        #[arg(long, requires = "report_every")]
+        report_to: Vec<String>,
+        /// Defaults to the log file from the config, else logs.txt
+        file: Option<String>,
+    },
+    /// Follow several files and syslog sockets at once on one runtime and
+    /// append what they read to the log file, e.g. `logger collect --follow
+    /// app.log --follow db.log --udp 0.0.0.0:514`
+    #[cfg(feature = "async")]
+    Collect {
+        /// File to follow from its start, or from where --offsets says;
+        /// repeatable
+        #[arg(long = "follow", required_unless_present_any = ["udp", "tcp"])]
+        files: Vec<String>,
+        /// Address to receive syslog datagrams on
+        #[arg(long)]
+        udp: Option<String>,
+        /// Address to accept syslog TCP connections on
+        #[arg(long)]
+        tcp: Option<String>,
+        /// Seconds between appends to the log file
+        #[arg(long, default_value_t = 5)]
+        flush: u64,
+        /// Entries held in memory before an early append; sources are
+        /// slowed down while it is full
+        #[arg(long, default_value_t = 10_000)]
+        buffer: usize,
+        /// Record in this file how far each followed file was collected and
+        /// carry on from there next time, even across a rotation
+        #[arg(long)]
+        offsets: Option<String>,
+        /// Defaults to the log file from the config, else logs.txt
+        file: Option<String>,
+    },
+    /// Print entries from the systemd journal, newest last
+    #[cfg(feature = "journald")]
+    Journal {
+        /// Only this unit, e.g. nginx.service; repeatable
+        #[arg(long = "unit")]
+        units: Vec<String>,
+        /// Only this level and more severe ones
+        #[arg(long)]
+        priority: Option<String>,
+        /// Only entries newer than this, e.g. 30m, 1h, 2d
+        #[arg(long)]
+        since: Option<String>,
+        /// Only entries whose message contains TEXT (case-insensitive)
+        #[arg(long)]
+        grep: Option<String>,
+        /// Keep printing entries as they are written
+        #[arg(long)]
+        follow: bool,
+        /// Also append the entries to this log file
+        #[arg(long)]
+        save: Option<String>,
+    },
+    /// Print events from the Windows Event Log, oldest first
+    #[cfg(all(windows, feature = "eventlog"))]
+    EventLog {
+        /// Channel to read; repeatable [default: Application and System]
+        #[arg(long = "channel")]
+        channels: Vec<String>,
+        /// Only this level and more severe ones
+        #[arg(long)]
+        level: Option<String>,
+        /// Only events from this provider, e.g. "Service Control Manager"
+        #[arg(long)]
+        provider: Option<String>,
+        /// Only events newer than this, e.g. 30m, 1h, 2d
+        #[arg(long)]
+        since: Option<String>,
+        /// Also append the entries to this log file
+        #[arg(long)]
+        save: Option<String>,
+    },
+    /// Print a pod's logs from the Kubernetes API, e.g. `logger k8s
+    /// --selector app=api --follow`; by default through `kubectl proxy`
+    K8s {
+        #[arg(long, short = 'n', default_value = "default")]
+        namespace: String,
+        #[arg(long, required_unless_present = "selector")]
+        pod: Option<String>,
+        /// Label selector, for the logs of every matching pod
+        #[arg(long, short = 'l')]
+        selector: Option<String>,
+        /// Only this container [default: every container of the pods]
+        #[arg(long, short = 'c')]
+        container: Option<String>,
+        /// Only entries newer than this, e.g. 30m, 1h, 2d
+        #[arg(long)]
+        since: Option<String>,
+        /// Only the last N lines of each container
+        #[arg(long)]
+        tail: Option<usize>,
+        /// Keep printing entries as they are written
+        #[arg(long)]
+        follow: bool,
+        /// API server URL
+        #[arg(long, default_value = k8s::DEFAULT_SERVER)]
+        server: String,
+        /// Bearer token [default: the service account's, inside a pod]
+        #[arg(long)]
+        token: Option<String>,
+        /// Also append the entries to this log file
+        #[arg(long)]
+        save: Option<String>,
+    },
+    /// Follow a file and redraw its stats every few seconds: totals, rates
+    /// per level over the last window and the newest errors
+    Watch {
+        /// Seconds between redraws
+        #[arg(short = 'n', long, default_value_t = 2)]
+        interval: u64,
+        /// How far back the per-level rates look, e.g. 1m or 15m
+        #[arg(long, default_value = "1m")]
+        window: String,
+        /// How many of the newest errors to show
+        #[arg(long, default_value_t = 5)]
+        errors: usize,
+        /// Log file [default: the log file from the config]
+        file: Option<String>,
+    },
+    /// Print a log file on another machine, e.g. `logger ssh
+    /// web1:/var/log/app.log --follow`; other commands also read
+    /// ssh://host/path
+    Ssh {
+        /// [user@]host:/path or ssh://[user@]host[:port]/path
+        url: String,
+        /// Keep printing entries as they are appended
+        #[arg(long)]
+        follow: bool,
+        /// Also append the entries to this log file
+        #[arg(long)]
+        save: Option<String>,
+    },
+    /// Print fake log lines, e.g. `logger generate --rate 1000 --duration
+    /// 1h > big.log` to benchmark, or --realtime to feed a pipeline
+    #[command(after_help = generate::TEMPLATES_HELP)]
+    Generate {
+        /// Entries per second, on average
+        #[arg(long, default_value_t = 10.0)]
+        rate: f64,
+        /// Time span the entries cover
+        #[arg(long, default_value = "1m")]
+        duration: String,
+        /// Stop after this many entries instead
+        #[arg(long)]
+        count: Option<u64>,
+        /// Level weights
+        #[arg(long, default_value = "DEBUG=10,INFO=70,WARNING=12,ERROR=7,CRITICAL=1")]
+        levels: String,
+        /// Message template; repeatable [default: built-in web service
+        /// messages]
+        #[arg(long = "template")]
+        templates: Vec<String>,
+        /// Time of the first entry [default: --duration before now]
+        #[arg(long, conflicts_with = "realtime")]
+        start: Option<String>,
+        /// Print entries as they happen instead of all at once
+        #[arg(long)]
+        realtime: bool,
+        /// Seed for the same output every run
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Interleave logs by time on one timeline, each entry tagged with the
+    /// log it came from, e.g. `logger merge api.log db.log -o incident.log`
+    Merge {
+        /// Log files, or - for stdin
+        #[arg(required = true, num_args = 2..)]
+        files: Vec<String>,
+        /// Write the merged log here instead of printing it
+        #[arg(long, short)]
+        out: Option<String>,
+        /// Field naming each entry's log
+        #[arg(long, default_value = "origin")]
+        field: String,
+    },
+    /// Print a log again with the pauses between its entries, or send it
+    /// to a log server with --target, e.g. to reproduce timing issues or
+    /// feed a dashboard demo
+    Replay {
+        /// Replay this many times faster, e.g. 10, or 0.5 for half speed
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+        /// Cap pauses at this long, e.g. 5s
+        #[arg(long)]
+        max_gap: Option<String>,
+        /// Stamp entries with the time they are replayed at
+        #[arg(long)]
+        retime: bool,
+        /// Send to this kind of server instead of printing
+        #[arg(long, value_enum, requires = "to")]
+        target: Option<Target>,
+        /// host:port of the server, or its URL for HTTP targets
+        #[arg(long, requires = "target")]
+        to: Option<String>,
+        /// Use TCP instead of UDP
+        #[arg(long)]
+        tcp: bool,
+        /// Log file or - for stdin [default: piped stdin, else the log file
+        /// from the config]
+        file: Option<String>,
+    },
+    /// Rewrite a log in another storage format: the binary one for an
+    /// output named .lgb or with --binary, else text lines, e.g. `logger
+    /// convert logs.txt logs.lgb`
+    Convert {
+        /// Log to read, in any format
+        input: String,
+        /// File to write; may be the input, to convert it in place
+        #[arg(value_name = "OUTPUT")]
+        target: String,
+    },
+    /// Move entries older than a cutoff out of the log file into one file
+    /// per day, e.g. logs/2024-05-01.txt for logs.txt
+    Archive {
+        /// Entries before this are moved: an age such as 30d, or a date
+        #[arg(long)]
+        before: String,
+        /// Directory of the day files [default: next to the log file, named
+        /// after it]
+        #[arg(long)]
+        dir: Option<String>,
+        /// Only report what would be moved
+        #[arg(long)]
+        dry_run: bool,
+        /// Log file [default: from the config]
+        file: Option<String>,
+    },
+    /// Remove (or archive) entries older than the [retention] ages in the
+    /// config, and the oldest beyond its max_entries
+    Retention {
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+        /// Log file [default: from the config]
+        file: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Metric {
+    /// Entries per level
+    Counts,
+    /// Share of ERROR and worse
+    ErrorRate,
+    /// avg/p50/p95/p99 of a value captured by --pattern
+    Extract,
+    /// Entries per level and in total in sliding windows of the bucket
+    /// width, each starting half a bucket after the last
+    Rate,
+}
+
+fn time_series(
+    entries: &[LogEntry],
+    metric: Metric,
+    bucket: chrono::Duration,
+    pattern: Option<&regex::Regex>,
+) -> Vec<export::SeriesPoint> {
+    let point = |bucket, series: &str, value| export::SeriesPoint {
+        bucket,
+        series: series.to_string(),
+        value,
+    };
+    let mut points = Vec::new();
+    match metric {
+        Metric::Counts | Metric::ErrorRate => {
+            let histogram = report::level_histogram(entries, bucket);
+            // Every level gets a row in every bucket so plots show zeros.
+            let mut all_levels: Vec<&String> =
+                histogram.iter().flat_map(|(_, l)| l.keys()).collect();
+            all_levels.sort();
+            all_levels.dedup();
+            for (start, levels) in &histogram {
+                let start = *start;
+                if metric == Metric::ErrorRate {
+                    let total: usize = levels.values().sum();
+                    let errors: usize = levels
+                        .iter()
+                        .filter(|(level, _)| crate::alert::severity_rank(level) >= 3)
+                        .map(|(_, n)| n)
+                        .sum();
+                    let rate = if total == 0 {
+                        0.0
+                    } else {
+                        errors as f64 / total as f64
+                    };
+                    points.push(point(start, "error_rate", rate));
+                    continue;
+                }
+                for level in &all_levels {
+                    let count = levels.get(*level).copied().unwrap_or(0);
+                    points.push(point(start, level, count as f64));
+                }
+            }
+        }
+        Metric::Rate => {
+            let mut levels: Vec<&str> = entries.iter().map(|e| e.level.as_str()).collect();
+            levels.sort();
+            levels.dedup();
+            let series = levels
+                .into_iter()
+                .map(|level| (level, gate::LevelSelector::Exactly(level.to_string())))
+                .chain([("total", gate::LevelSelector::Any)]);
+            for (name, selector) in series {
+                for (start, count) in analysis::rate(entries, &selector, bucket) {
+                    points.push(point(start, name, count as f64));
+                }
+            }
+            points.sort_by_key(|p| p.bucket);
+        }
+        Metric::Extract => {
+            let Some(pattern) = pattern else {
+                return points;
+            };
+            let samples = metrics::extract(entries, pattern);
+            for (start, s) in metrics::summarize_by_bucket(&samples, bucket) {
+                points.push(point(start, "count", s.count as f64));
+                points.push(point(start, "avg", s.avg));
+                points.push(point(start, "p50", s.p50));
+                points.push(point(start, "p95", s.p95));
+                points.push(point(start, "p99", s.p99));
+            }
+        }
+    }
+    points
+}
+
+// Parses the bucket and pattern options shared by timeseries and chart.
+fn series_options(
+    metric: Metric,
+    bucket: &str,
+    pattern: Option<&str>,
+) -> Result<(chrono::Duration, Option<regex::Regex>), i32> {
+    let bucket = match query::parse_interval(bucket) {
+        Ok(bucket) if bucket > chrono::Duration::zero() => bucket,
+        _ => {
+            eprintln!("Invalid bucket size {}", bucket);
+            return Err(2);
+        }
+    };
+    match (metric, pattern.map(metrics::compile)) {
+        (_, Some(Ok(regex))) => Ok((bucket, Some(regex))),
+        (_, Some(Err(e))) => {
+            eprintln!("Invalid pattern: {}", e);
+            Err(2)
+        }
+        (Metric::Extract, None) => {
+            eprintln!("--metric extract needs --pattern");
+            Err(2)
+        }
+        (_, None) => Ok((bucket, None)),
+    }
+}
+
+/// Evaluates a rule set and reports the outcome: prints it, records
+/// silenced alerts and runs the rules' actions.
+struct Alerts<'a> {
+    set: &'a rules::RuleSet,
+    silenced_log: &'a str,
+    output: OutputFormat,
+    /// Only report rules that fired or resolved, as when following.
+    changes_only: bool,
+}
+
+impl Alerts<'_> {
+    /// Evaluates every rule at `now`; returns whether any fired. Rules in
+    /// `silenced` were silenced last time and aren't recorded again, and
+    /// it is updated with those silenced now.
+    fn evaluate(
+        &self,
+        state: &mut rules::AlertState,
+        entries: &[LogEntry],
+        now: chrono::NaiveDateTime,
+        silenced: &mut Vec<String>,
+    ) -> bool {
+        let set = self.set;
+        let results: Vec<_> = set
+            .rules
+            .iter()
+            .map(|rule| {
+                let evaluation = rule.evaluate(entries, now);
+                let silence = set.silenced(rule, now).filter(|_| evaluation.fired);
+                // A silenced alert leaves the state alone: it neither
+                // opens nor resolves anything.
+                let transition = match silence {
+                    Some(_) => None,
+                    None => Some(state.update(rule, evaluation.fired, now)),
+                };
+                (rule, evaluation, silence, transition)
+            })
+            .collect();
+
+        let suppressed: Vec<LogEntry> = results
+            .iter()
+            .filter(|(rule, ..)| !silenced.contains(&rule.name))
+            .filter_map(|(rule, e, silence, _)| {
+                let silence = silence.as_ref()?;
+                let mut fields = serde_json::Map::new();
+                fields.insert("rule".to_string(), json!(rule.name));
+                fields.insert("reason".to_string(), json!(silence.reason));
+                Some(LogEntry {
+                    timestamp: crate::now_timestamp(),
+                    level: "WARNING".to_string(),
+                    message: format!(
+                        "Silenced '{}' ({}): {}",
+                        rule.name,
+                        silence.reason,
+                        e.summary()
+                    ),
+                    fields,
+                    source: None,
+                })
+            })
+            .collect();
+        if !suppressed.is_empty() {
+            if let Err(e) = LogAnalyzer::append_to_file(self.silenced_log, &suppressed) {
+                eprintln!(
+                    "Could not record silenced alerts in {}: {}",
+                    self.silenced_log, e
+                );
+            }
+        }
+        *silenced = results
+            .iter()
+            .filter(|(_, _, silence, _)| silence.is_some())
+            .map(|(rule, ..)| rule.name.clone())
+            .collect();
+
+        let reported = results.iter().filter(|(_, _, silence, transition)| {
+            !self.changes_only
+                || (silence.is_none()
+                    && matches!(transition, Some(Transition::Fired | Transition::Resolved)))
+        });
+        let reported: Vec<_> = reported.collect();
+        if self.output == OutputFormat::Json {
+            let values = reported.iter().map(|(rule, e, silence, transition)| {
+                json!({
+                    "rule": rule.name,
+                    "fired": e.fired && silence.is_none(),
+                    "transition": transition.map(|t| t.name()),
+                    "silenced": silence.map(|s| &s.reason),
+                    "checks": e.checks.iter().map(|c| json!({
+                        "check": c.description,
+                        "held": c.held,
+                        "current": c.current,
+                        "previous": c.previous,
+                    })).collect::<Vec<_>>(),
+                })
+            });
+            if self.changes_only {
+                // One line per change, for piping the stream.
+                for value in values {
+                    println!("{}", value);
+                }
+            } else {
+                print_json(&Value::Array(values.collect()));
+            }
+        } else {
+            for (rule, e, silence, transition) in &reported {
+                let status = match transition {
+                    None => "SILENCED",
+                    Some(Transition::Fired) => "FIRED   ",
+                    Some(Transition::Firing) => "firing  ",
+                    Some(Transition::Resolved) => "RESOLVED",
+                    Some(Transition::Ok) => "ok      ",
+                };
+                if self.changes_only {
+                    print!("{}  ", crate::now_timestamp());
+                }
+                if let [check] = e.checks.as_slice() {
+                    println!("{}  {}: {}", status, rule.name, check.description);
+                } else {
+                    println!("{}  {}", status, rule.name);
+                    for check in &e.checks {
+                        let held = if check.held { "yes" } else { "no " };
+                        println!("    {} {}", held, check.description);
+                    }
+                }
+                if let Some(silence) = silence {
+                    println!("    {}", silence.reason);
+                } else if *transition == Some(Transition::Fired) {
+                    for entry in e.matches.iter().take(3) {
+                        println!("    {}", color::entry_line(entry, None));
+                    }
+                }
+            }
+        }
+
+        for (rule, e, _, transition) in &results {
+            let status = match transition {
+                Some(Transition::Fired) => "fired",
+                Some(Transition::Resolved) => "resolved",
+                _ => continue,
+            };
+            let alert = alert::Alert::new(&rule.name, &e.matches);
+            for action in &rule.actions {
+                if let Err(err) = action.run(&alert, status) {
+                    eprintln!("Alert action for '{}' failed: {}", rule.name, err);
+                }
+            }
+        }
+        results
+            .iter()
+            .any(|(_, e, silence, _)| e.fired && silence.is_none())
+    }
+}
+
+/// Evaluates the rules every second against the entries of `path` and
+/// those appended to it, with windows ending at the current time, until
+/// reading fails.
+fn follow_alerts(
+    set: &rules::RuleSet,
+    state: &mut rules::AlertState,
+    state_file: &str,
+    path: &str,
+    silenced_log: &str,
+    output: OutputFormat,
+) -> std::io::Result<()> {
+    let alerts = Alerts {
+        set,
+        silenced_log,
+        output,
+        changes_only: true,
+    };
+    let horizon = set
+        .rules
+        .iter()
+        .map(rules::Rule::horizon)
+        .max()
+        .unwrap_or_else(chrono::Duration::zero);
+    let mut follower = crate::follow::Follower::from_start(path);
+    let mut entries: Vec<LogEntry> = Vec::new();
+    let mut silenced = Vec::new();
+    loop {
+        entries.extend(follower.poll()?);
+        let now = chrono::Local::now().naive_local();
+        let start = now
+            .checked_sub_signed(horizon)
+            .unwrap_or(chrono::NaiveDateTime::MIN);
+        entries.retain(|e| e.time().is_some_and(|t| t > start));
+        alerts.evaluate(state, &entries, now, &mut silenced);
+        if let Err(e) = state.save(state_file) {
+            eprintln!("Could not save alert state to {}: {}", state_file, e);
+        }
+        std::io::stdout().flush()?;
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Starts `--report-every` reports of `file`, if asked for.
+fn start_reports(
+    every: Option<String>,
+    to: Vec<String>,
+    file: &str,
+    config: &Config,
+) -> Result<(), i32> {
+    let Some(every) = every else {
+        return Ok(());
+    };
+    let every = query::parse_interval(&every).map_err(|e| {
+        eprintln!("Invalid --report-every: {}", e);
+        2
+    })?;
+    let to = if to.is_empty() {
+        vec!["-".to_string()]
+    } else {
+        to
+    };
+    let reports = schedule::Reports {
+        every,
+        log_file: file.to_string(),
+        to: to.iter().map(|t| schedule::Destination::parse(t)).collect(),
+        smtp: config.smtp.clone(),
+    };
+    if let Err(e) = reports.validate() {
+        eprintln!("Invalid report settings: {}", e);
+        return Err(2);
+    }
+    reports.start();
+    Ok(())
+}
+
+// Loads a file that has to exist ("-" reads stdin); load_from_file treats
+// a missing file as empty, which would make searches and gates pass
+// vacuously.
+fn load_existing(file: &str) -> Result<LogAnalyzer, i32> {
+    load_with(file, LogAnalyzer::builder())
+}
+
+fn load_with(file: &str, builder: AnalyzerBuilder) -> Result<LogAnalyzer, i32> {
+    if file != "-" && !crate::is_remote(file) && !Path::new(file).exists() {
+        eprintln!("Log file {} does not exist", file);
+        return Err(2);
+    }
+    let mut analyzer = builder.build();
+    match analyzer.load_from_file(file) {
+        Ok(()) => Ok(analyzer),
+        Err(e) => {
+            eprintln!("Could not load log file: {}", e);
+            Err(2)
+        }
+    }
+}
+
+// --compress adds the extension `export::create_output` compresses by.
+fn compressed_path(path: String, compress: Option<Compression>) -> Result<String, i32> {
+    let Some(compression) = compress.filter(|&c| c != Compression::None) else {
+        return Ok(path);
+    };
+    if path == "-" {
+        eprintln!("--compress needs a file; pipe stdout through gzip or zstd instead");
+        return Err(2);
+    }
+    match Compression::from_path(&path) {
+        Compression::None => Ok(compression.apply_to(&path)),
+        suffix if suffix == compression => Ok(path),
+        _ => {
+            eprintln!("--compress disagrees with the extension of {}", path);
+            Err(2)
+        }
+    }
+}
+
+// A bare name is a structured field unless it names a column.
+fn field_argument(field: &str) -> Option<query::Field> {
+    query::parse_field(field)
+        .or_else(|| query::parse_field(&format!(".{}", field)))
+        .filter(|_| !field.trim_start_matches('.').is_empty())
+}
+
+pub(crate) fn print_json(value: &Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+/// Runs a non-interactive command and returns the process exit code:
+/// 0 on success, 1 when a check fails, 2 on usage or load errors.
+pub fn run(command: Command, output: OutputFormat, config: &Config) -> i32 {
+    match command {
+        Command::Repl { .. } => unreachable!("the menu is started by main"),
+        Command::Tui { file } => {
+            let file = config.log_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            match tui::run(&analyzer.entries) {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Terminal error: {}", e);
+                    2
+                }
+            }
+        }
+        Command::Stats { file } => {
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let stats = analyzer.get_statistics();
+            match output {
+                OutputFormat::Text => report::print_stats(&stats),
+                OutputFormat::Json => print_json(&report::stats_json(&stats)),
+            }
+            0
+        }
+        Command::Report {
+            markdown,
+            out,
+            budget,
+            file,
+        } => {
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let out = out
+                .unwrap_or_else(|| if markdown { "report.md" } else { "report.html" }.to_string());
+            let result = if markdown {
+                report::write_markdown(&analyzer.entries, &file, &out)
+            } else {
+                report::write_html(&analyzer.entries, &file, &out, budget)
+            };
+            match result {
+                Ok(()) if out == "-" => 0,
+                Ok(()) => {
+                    println!("Report written to {}", out);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Error writing report: {}", e);
+                    2
+                }
+            }
+        }
+        Command::Query { sql, file } => {
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            match analyzer.query(&sql) {
+                Ok((_, result)) => {
+                    match output {
+                        OutputFormat::Text => println!("{}", result),
+                        OutputFormat::Json => print_json(&result.to_json()),
+                    }
+                    0
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    2
+                }
+            }
+        }
+        Command::Search {
+            level,
+            since,
+            tag,
+            filter,
+            script,
+            save,
+            compress,
+            sort,
+            desc,
+            count,
+            per_level,
+            dedup,
+            text,
+            file,
+        } => {
+            let save = match save.map(|save| compressed_path(save, compress)).transpose() {
+                Ok(save) => save,
+                Err(code) => return code,
+            };
+            let file = config.input_file(file);
+            let filter = match filter.as_deref().map(Directives::parse) {
+                Some(Ok(filter)) => Some(filter),
+                Some(Err(e)) => {
+                    eprintln!("Invalid --filter: {}", e);
+                    return 2;
+                }
+                None => None,
+            };
+            let script = match script.as_deref().map(Script::load) {
+                Some(Ok(script)) => Some(script),
+                Some(Err(e)) => {
+                    eprintln!("Invalid --script: {}", e);
+                    return 2;
+                }
+                None => None,
+            };
+            let since = match since
+                .as_deref()
+                .map(|since| query::parse_interval(since).and_then(query::ago))
+            {
+                Some(Ok(since)) => Some(since),
+                Some(Err(e)) => {
+                    eprintln!("Invalid --since: {}", e);
+                    return 2;
+                }
+                None => None,
+            };
+            let analyzer = match load_with(&file, LogAnalyzer::builder().dedup(dedup)) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let levels: Vec<String> = match level.iter().map(|l| crate::levels::parse(l)).collect()
+            {
+                Ok(levels) => levels,
+                Err(e) => {
+                    eprintln!("Invalid --level: {}", e);
+                    return 2;
+                }
+            };
+            let selected = |entry: &&LogEntry| {
+                let level_ok = match (levels.is_empty(), &config.min_level) {
+                    (false, _) => levels.iter().any(|l| entry.level.eq_ignore_ascii_case(l)),
+                    (true, Some(min)) => {
+                        crate::alert::severity_rank(&entry.level)
+                            >= crate::alert::severity_rank(min)
+                    }
+                    (true, None) => true,
+                };
+                level_ok
+                    && since.is_none_or(|s| entry.time().is_some_and(|t| t >= s))
+                    && tag.as_ref().is_none_or(|tag| entry.has_tag(tag))
+                    && filter.as_ref().is_none_or(|f| f.matches(entry))
+                    && script.as_ref().is_none_or(|s| s.matches(entry))
+            };
+            if count {
+                // Counted in place; nothing is copied or printed per entry.
+                let mut total = 0;
+                let mut levels: std::collections::BTreeMap<&str, usize> = Default::default();
+                for entry in analyzer.iter_search(&text).filter(selected) {
+                    total += 1;
+                    if per_level {
+                        *levels.entry(entry.level.as_str()).or_insert(0) += 1;
+                    }
+                }
+                match output {
+                    OutputFormat::Text => {
+                        for (level, n) in &levels {
+                            println!("{}: {}", level, n);
+                        }
+                        println!("{}", total);
+                    }
+                    OutputFormat::Json if per_level => {
+                        print_json(&json!({ "count": total, "levels": levels }))
+                    }
+                    OutputFormat::Json => print_json(&json!({ "count": total })),
+                }
+                return 0;
+            }
+            // Borrowed from the analyzer; only a saved copy is cloned.
+            let mut matches: Vec<&LogEntry> =
+                analyzer.iter_search(&text).filter(selected).collect();
+            if let Some(sort) = sort {
+                analysis::sort_by(&mut matches, sort, desc);
+            }
+            if let Some(save) = &save {
+                let saved: Vec<LogEntry> = matches.iter().map(|&entry| entry.clone()).collect();
+                if let Err(e) = export::write_results(&saved, save) {
+                    eprintln!("Error writing {}: {}", save, e);
+                    return 2;
+                }
+                eprintln!("Saved {} entries to {}", matches.len(), save);
+            }
+            match output {
+                OutputFormat::Text => {
+                    let mut lines = String::new();
+                    for entry in &matches {
+                        lines.push_str(&color::entry_line(entry, Some(&text)));
+                        lines.push('\n');
+                    }
+                    pager::page(&lines);
+                }
+                OutputFormat::Json => print_json(&Value::Array(
+                    matches
+                        .iter()
+                        .map(|&entry| export::entry_json(entry))
+                        .collect(),
+                )),
+            }
+            0
+        }
+        Command::Head { lines, file } => {
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let first = analyzer.get_first(lines);
+            match output {
+                OutputFormat::Text => {
+                    for entry in &first {
+                        println!("{}", color::entry_line(entry, None));
+                    }
+                }
+                OutputFormat::Json => print_json(&Value::Array(
+                    first.iter().map(export::entry_json).collect(),
+                )),
+            }
+            0
+        }
+        Command::Slice { range, file } => {
+            let range = match analysis::parse_range(&range) {
+                Ok(range) => range,
+                Err(e) => {
+                    eprintln!("Invalid range: {}", e);
+                    return 2;
+                }
+            };
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let start = range.start;
+            let slice = analyzer.get_range(range);
+            match output {
+                OutputFormat::Text => {
+                    let mut lines = String::new();
+                    for (i, entry) in slice.iter().enumerate() {
+                        lines.push_str(&format!(
+                            "{:>5}  {}\n",
+                            start + i + 1,
+                            color::entry_line(entry, None)
+                        ));
+                    }
+                    pager::page(&lines);
+                }
+                OutputFormat::Json => print_json(&Value::Array(
+                    slice.iter().map(export::entry_json).collect(),
+                )),
+            }
+            0
+        }
+        Command::Sample { count, seed, file } => {
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let sampled = analysis::sample(&analyzer.entries, count, seed);
+            match output {
+                OutputFormat::Text => {
+                    for entry in &sampled {
+                        println!("{}", color::entry_line(entry, None));
+                    }
+                }
+                OutputFormat::Json => print_json(&Value::Array(
+                    sampled.iter().map(export::entry_json).collect(),
+                )),
+            }
+            0
+        }
+        Command::Distinct { field, top, file } => {
+            let Some(parsed) = field_argument(&field) else {
+                eprintln!("Unknown field '{}'", field);
+                return 2;
+            };
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let (mut values, missing) = analysis::distinct(&analyzer.entries, &parsed);
+            let total = values.len();
+            if let Some(top) = top {
+                values.truncate(top);
+            }
+            if output == OutputFormat::Json {
+                print_json(&json!({
+                    "field": parsed.name(),
+                    "distinct": total,
+                    "missing": missing,
+                    "values": values
+                        .iter()
+                        .map(|(value, count)| json!({ "value": value, "count": count }))
+                        .collect::<Vec<_>>(),
+                }));
+                return 0;
+            }
+            let entries = analyzer.entries.len().max(1) as f64;
+            let width = values
+                .iter()
+                .map(|(v, _)| v.len())
+                .max()
+                .unwrap_or(0)
+                .max(5);
+            for (value, count) in &values {
+                println!(
+                    "{:<width$} {:>8} {:>6.1}%",
+                    value,
+                    count,
+                    *count as f64 / entries * 100.0
+                );
+            }
+            println!(
+                "\n{} distinct value(s) of {}; {} of {} entries without it",
+                total,
+                parsed.name(),
+                missing,
+                analyzer.entries.len()
+            );
+            0
+        }
+        Command::FieldStats { field, file } => {
+            let Some(parsed) = field_argument(&field) else {
+                eprintln!("Unknown field '{}'", field);
+                return 2;
+            };
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let stats = fields::field_stats(&analyzer.entries, &parsed);
+            match output {
+                OutputFormat::Text => fields::print_field_stats(&stats),
+                OutputFormat::Json => print_json(&fields::field_stats_json(&stats)),
+            }
+            0
+        }
+        Command::Add {
+            level,
+            message,
+            file,
+        } => {
+            let level = match crate::levels::parse(&level) {
+                Ok(level) => level,
+                Err(e) => {
+                    eprintln!("Invalid level: {}", e);
+                    return 2;
+                }
+            };
+            let file = config.log_file(file);
+            let mut analyzer = LogAnalyzer::new();
+            analyzer.add_entry(level, message);
+            let result = crate::rotate::before_append(&file)
+                .and_then(|()| LogAnalyzer::append_to_file(&file, &analyzer.entries));
+            match result {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Error saving: {}", e);
+                    2
+                }
+            }
+        }
+        Command::Workers { field, file } => {
+            let file = config.input_file(file);
+            let field = match field.as_deref().map(query::parse_field) {
+                Some(Some(field)) => Some(field),
+                Some(None) => {
+                    eprintln!("Unknown field");
+                    return 2;
+                }
+                None => None,
+            };
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let workers = analysis::worker_breakdown(&analyzer.entries, field.as_ref());
+            if workers.is_empty() {
+                eprintln!("No entries carry a worker ID");
+                return 1;
+            }
+            let total_errors: usize = workers.iter().map(|w| w.errors).sum();
+            let silent = analysis::longest_silent(&workers);
+            if output == OutputFormat::Json {
+                let time = |t: Option<chrono::NaiveDateTime>| {
+                    t.map(|t| t.format(crate::TIMESTAMP_FORMAT).to_string())
+                };
+                print_json(&json!({
+                    "workers": workers.iter().map(|w| json!({
+                        "worker": w.worker,
+                        "entries": w.total,
+                        "errors": w.errors,
+                        "last_seen": time(w.last_seen),
+                    })).collect::<Vec<_>>(),
+                    "longest_silent": silent.map(|(w, silence)| json!({
+                        "worker": w.worker,
+                        "seconds": silence.num_seconds(),
+                    })),
+                }));
+                return 0;
+            }
+            println!(
+                "{:<20} {:>8} {:>8} {:>9}  last seen",
+                "worker", "entries", "errors", "of errors"
+            );
+            for w in &workers {
+                let share = if total_errors == 0 {
+                    0.0
+                } else {
+                    w.errors as f64 / total_errors as f64 * 100.0
+                };
+                let last_seen = w
+                    .last_seen
+                    .map(|t| t.format(crate::TIMESTAMP_FORMAT).to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                println!(
+                    "{:<20} {:>8} {:>8} {:>8.1}%  {}",
+                    w.worker, w.total, w.errors, share, last_seen
+                );
+            }
+            if let Some((worker, silence)) = silent {
+                println!(
+                    "\nLongest silent: {} (quiet for {}s before the newest entry)",
+                    worker.worker,
+                    silence.num_seconds()
+                );
+            }
+            0
+        }
+        Command::Funnel { id, stages, file } => {
+            let file = config.input_file(file);
+            let Some(id_field) = query::parse_field(&id) else {
+                eprintln!("Unknown field {}", id);
+                return 2;
+            };
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let funnel = analysis::funnel(&analyzer.entries, &id_field, &stages);
+            if output == OutputFormat::Json {
+                print_json(&Value::Array(
+                    funnel
+                        .iter()
+                        .map(|s| json!({ "stage": s.marker, "reached": s.reached }))
+                        .collect(),
+                ));
+                return 0;
+            }
+            let started = funnel.first().map_or(0, |s| s.reached);
+            let mut previous = started;
+            println!(
+                "{:<24} {:>8} {:>8} {:>8}",
+                "stage", "reached", "of start", "dropped"
+            );
+            for stage in &funnel {
+                let percent = if started == 0 {
+                    0.0
+                } else {
+                    stage.reached as f64 / started as f64 * 100.0
+                };
+                println!(
+                    "{:<24} {:>8} {:>7.1}% {:>8}",
+                    stage.marker,
+                    stage.reached,
+                    percent,
+                    previous - stage.reached
+                );
+                previous = stage.reached;
+            }
+            0
+        }
+        Command::Requests {
+            id,
+            by,
+            limit,
+            files,
+        } => {
+            let extractor = match correlation::Extractor::parse(
+                by.as_deref().or(config.correlation_id.as_deref()),
+            ) {
+                Ok(extractor) => extractor,
+                Err(e) => {
+                    eprintln!("Invalid correlation ID: {}", e);
+                    return 2;
+                }
+            };
+            let files = if files.is_empty() {
+                vec![config.input_file(None)]
+            } else {
+                files
+            };
+            let mut logs = Vec::new();
+            for file in &files {
+                match load_existing(file) {
+                    Ok(analyzer) => logs.push((file.clone(), analyzer.entries)),
+                    Err(code) => return code,
+                }
+            }
+            let several = logs.len() > 1;
+            let seconds =
+                |d: chrono::Duration| format!("{}s", d.num_milliseconds() as f64 / 1000.0);
+            let entries = analysis::merge(logs, "origin");
+            let requests = correlation::requests(&entries, &extractor);
+            let origin = |entry: &LogEntry| {
+                entry
+                    .fields
+                    .get("origin")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let summary = |request: &correlation::Request| {
+                json!({
+                    "id": request.id,
+                    "entries": request.entries.len(),
+                    "first": request.entries.first().map(|e| &e.timestamp),
+                    "last": request.entries.last().map(|e| &e.timestamp),
+                    "seconds": request.duration().map(|d| d.num_milliseconds() as f64 / 1000.0),
+                    "level": request.level(),
+                })
+            };
+            if let Some(id) = id {
+                let Some(request) = requests.iter().find(|r| r.id == id) else {
+                    eprintln!("No entries for request {}", id);
+                    return 1;
+                };
+                if output == OutputFormat::Json {
+                    let mut result = summary(request);
+                    result["log"] = Value::Array(
+                        request
+                            .entries
+                            .iter()
+                            .map(|e| export::entry_json(e))
+                            .collect(),
+                    );
+                    print_json(&result);
+                    return 0;
+                }
+                let mut logs: Vec<String> = request.entries.iter().map(|e| origin(e)).collect();
+                logs.sort();
+                logs.dedup();
+                print!(
+                    "Request {}: {} entries from {} log(s)",
+                    request.id,
+                    request.entries.len(),
+                    logs.len()
+                );
+                match (
+                    request.entries.first(),
+                    request.entries.last(),
+                    request.duration(),
+                ) {
+                    (Some(first), Some(last), Some(duration)) => println!(
+                        ", {} to {} ({})",
+                        first.timestamp,
+                        last.timestamp,
+                        seconds(duration)
+                    ),
+                    _ => println!(),
+                }
+                let width = files.iter().map(|f| f.len()).max().unwrap_or(0);
+                for entry in &request.entries {
+                    let line = color::entry_line(entry, None);
+                    if several {
+                        println!("{:<width$}  {}", origin(entry), line);
+                    } else {
+                        println!("{}", line);
+                    }
+                }
+                return 0;
+            }
+            if output == OutputFormat::Json {
+                print_json(&Value::Array(requests.iter().map(summary).collect()));
+                return 0;
+            }
+            if requests.is_empty() {
+                println!("No entries with a correlation ID");
+                return 0;
+            }
+            let width = requests
+                .iter()
+                .take(limit)
+                .map(|r| r.id.len())
+                .max()
+                .unwrap_or(0)
+                .max(2);
+            println!(
+                "{:<width$}  {:>7}  {:<19}  {:>9}  level",
+                "id", "entries", "first", "duration"
+            );
+            for request in requests.iter().take(limit) {
+                println!(
+                    "{:<width$}  {:>7}  {:<19}  {:>9}  {}",
+                    request.id,
+                    request.entries.len(),
+                    request.entries.first().map_or("", |e| e.timestamp.as_str()),
+                    request.duration().map(seconds).unwrap_or_default(),
+                    request.level()
+                );
+            }
+            if requests.len() > limit {
+                println!("... and {} more", requests.len() - limit);
+            }
+            0
+        }
+        Command::Access { top, no_bots, file } => {
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let traffic = access::analyze(&analyzer.entries, !no_bots);
+            let counts = |counts: &[(String, usize)]| {
+                counts
+                    .iter()
+                    .take(top)
+                    .map(|(name, count)| (name.clone(), json!(count)))
+                    .collect::<serde_json::Map<_, _>>()
+            };
+            let latency = |summary: &Option<metrics::Summary>, p90: Option<f64>| {
+                summary.as_ref().map(|s| {
+                    json!({
+                        "p50": s.p50, "p90": p90, "p99": s.p99,
+                        "avg": s.avg, "max": s.max,
+                    })
+                })
+            };
+            let mut slowest: Vec<&access::Endpoint> = traffic
+                .endpoints
+                .iter()
+                .filter(|e| e.p90.is_some())
+                .collect();
+            slowest.sort_by(|a, b| b.p90.unwrap_or(0.0).total_cmp(&a.p90.unwrap_or(0.0)));
+            slowest.truncate(top);
+            if output == OutputFormat::Json {
+                let endpoint = |e: &access::Endpoint| {
+                    json!({
+                        "endpoint": e.name,
+                        "requests": e.requests,
+                        "errors": e.errors,
+                        "error_rate": e.error_rate(),
+                        "latency_ms": latency(&e.latency, e.p90),
+                    })
+                };
+                print_json(&json!({
+                    "requests": traffic.requests,
+                    "errors": traffic.errors,
+                    "latency_ms": latency(&traffic.latency, traffic.p90),
+                    "statuses": traffic.statuses.iter()
+                        .map(|(status, count)| (status.to_string(), json!(count)))
+                        .collect::<serde_json::Map<_, _>>(),
+                    "endpoints": traffic.endpoints.iter().take(top).map(endpoint).collect::<Vec<_>>(),
+                    "slowest": slowest.iter().map(|e| endpoint(e)).collect::<Vec<_>>(),
+                    "bots": traffic.bots,
+                    "browsers": counts(&traffic.browsers),
+                    "os": counts(&traffic.systems),
+                }));
+                return 0;
+            }
+            if traffic.requests == 0 {
+                println!("No entries with a status code (set format = \"access\" for access logs)");
+                return 0;
+            }
+            println!(
+                "Requests: {}  5xx: {} ({:.2}%)",
+                traffic.requests,
+                traffic.errors,
+                traffic.errors as f64 / traffic.requests as f64 * 100.0
+            );
+            if let (Some(summary), Some(p90)) = (&traffic.latency, traffic.p90) {
+                println!(
+                    "Latency (ms)  n={} p50={:.1} p90={:.1} p99={:.1} max={:.1}",
+                    summary.count, summary.p50, p90, summary.p99, summary.max
+                );
+            }
+            let statuses: Vec<String> = traffic
+                .statuses
+                .iter()
+                .map(|(status, count)| format!("{} {}", status, count))
+                .collect();
+            println!("Status codes: {}", statuses.join(", "));
+            let width = traffic
+                .endpoints
+                .iter()
+                .map(|e| e.name.len())
+                .max()
+                .unwrap_or(0)
+                .max(8);
+            let ms = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.1}", v));
+            println!(
+                "\n{:<width$} {:>8} {:>7} {:>8} {:>8} {:>8}",
+                "endpoint", "requests", "5xx", "p50 ms", "p90 ms", "p99 ms"
+            );
+            for e in traffic.endpoints.iter().take(top) {
+                println!(
+                    "{:<width$} {:>8} {:>6.1}% {:>8} {:>8} {:>8}",
+                    e.name,
+                    e.requests,
+                    e.error_rate() * 100.0,
+                    ms(e.latency.as_ref().map(|s| s.p50)),
+                    ms(e.p90),
+                    ms(e.latency.as_ref().map(|s| s.p99))
+                );
+            }
+            if !slowest.is_empty() {
+                println!("\nSlowest by p90:");
+                for e in &slowest {
+                    println!(
+                        "{:<width$} {:>8} ms  ({} requests, max {} ms)",
+                        e.name,
+                        ms(e.p90),
+                        e.requests,
+                        ms(e.latency.as_ref().map(|s| s.max))
+                    );
+                }
+            }
+            let share = |count: usize| count as f64 / traffic.requests as f64 * 100.0;
+            if !traffic.browsers.is_empty() {
+                println!();
+            }
+            for (title, counts) in [("Browsers", &traffic.browsers), ("OS", &traffic.systems)] {
+                if counts.is_empty() {
+                    continue;
+                }
+                let listed: Vec<String> = counts
+                    .iter()
+                    .take(top)
+                    .map(|(name, count)| format!("{} {:.1}%", name, share(*count)))
+                    .collect();
+                println!("{}: {}", title, listed.join(", "));
+            }
+            if !no_bots && !traffic.browsers.is_empty() {
+                println!("Bots: {} ({:.1}%)", traffic.bots, share(traffic.bots));
+            }
+            0
+        }
+        Command::Durations {
+            start,
+            end,
+            span,
+            id,
+            slowest,
+            file,
+        } => {
+            let file = config.input_file(file);
+            let (start, end, id) = match span {
+                Some(name) => {
+                    let spans = config.spans.as_deref().unwrap_or_default();
+                    let Some(spec) = spans.iter().find(|s| s.name == name) else {
+                        eprintln!("No [[spans]] table named {} in the config", name);
+                        return 2;
+                    };
+                    (spec.start.clone(), spec.end.clone(), id.or(spec.id.clone()))
+                }
+                None => (start.unwrap_or_default(), end.unwrap_or_default(), id),
+            };
+            let field = match id.as_deref().map(query::parse_field) {
+                Some(Some(field)) => Some(field),
+                Some(None) => {
+                    eprintln!("Unknown field");
+                    return 2;
+                }
+                None => None,
+            };
+            let compile = |pattern: &str| match &field {
+                Some(_) => regex::Regex::new(pattern).map_err(|e| e.to_string()),
+                None => metrics::compile(pattern),
+            };
+            let (start, end) = match (compile(&start), compile(&end)) {
+                (Ok(start), Ok(end)) => (start, end),
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("Invalid pattern: {}", e);
+                    return 2;
+                }
+            };
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let pairing = metrics::pair_events(&analyzer.entries, &start, &end, field.as_ref());
+            let summary = metrics::summarize(&pairing.durations());
+            let slowest = pairing.slowest(slowest);
+            if output == OutputFormat::Json {
+                print_json(&json!({
+                    "paired": pairing.spans.len(),
+                    "seconds": summary.map(|s| json!({
+                        "min": s.min, "max": s.max, "avg": s.avg,
+                        "p50": s.p50, "p95": s.p95, "p99": s.p99,
+                    })),
+                    "slowest": slowest.iter()
+                        .map(|s| json!({ "id": s.id, "timestamp": s.start.timestamp, "seconds": s.seconds }))
+                        .collect::<Vec<_>>(),
+                    "unmatched_starts": pairing.unmatched_starts.iter()
+                        .map(|(id, e)| json!({ "id": id, "timestamp": e.timestamp }))
+                        .collect::<Vec<_>>(),
+                    "unmatched_ends": pairing.unmatched_ends,
+                }));
+                return 0;
+            }
+            match &summary {
+                Some(summary) => metrics::print_summary("Durations (s)", summary),
+                None => println!("No start/end pairs found"),
+            }
+            if !slowest.is_empty() {
+                println!("Slowest:");
+                for span in &slowest {
+                    println!(
+                        "  {}s  {}  {}",
+                        span.seconds,
+                        span.id,
+                        color::entry_line(&span.start, None)
+                    );
+                }
+            }
+            println!("Unmatched starts: {}", pairing.unmatched_starts.len());
+            for (id, entry) in &pairing.unmatched_starts {
+                println!("  {}  {}", id, color::entry_line(entry, None));
+            }
+            println!("Unmatched ends: {}", pairing.unmatched_ends);
+            0
+        }
+        Command::Timeseries {
+            metric,
+            bucket,
+            pattern,
+            out,
+            compress,
+            file,
+        } => {
+            let out = match compressed_path(out, compress) {
+                Ok(out) => out,
+                Err(code) => return code,
+            };
+            let file = config.input_file(file);
+            let (bucket, pattern) = match series_options(metric, &bucket, pattern.as_deref()) {
+                Ok(options) => options,
+                Err(code) => return code,
+            };
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let points = time_series(&analyzer.entries, metric, bucket, pattern.as_ref());
+            match export::write_timeseries_csv(&points, &out) {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Error writing time series: {}", e);
+                    2
+                }
+            }
+        }
+        Command::Chart {
+            metric,
+            series,
+            style,
+            bucket,
+            pattern,
+            height,
+            file,
+        } => {
+            let file = config.input_file(file);
+            let (bucket, pattern) = match series_options(metric, &bucket, pattern.as_deref()) {
+                Ok(options) => options,
+                Err(code) => return code,
+            };
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let series = series.unwrap_or_else(|| {
+                match metric {
+                    Metric::Counts | Metric::Rate => "total",
+                    Metric::ErrorRate => "error_rate",
+                    Metric::Extract => "p95",
+                }
+                .to_string()
+            });
+            let values: Vec<(chrono::NaiveDateTime, f64)> =
+                if metric == Metric::Counts && series == "total" {
+                    report::time_histogram(&analyzer.entries, bucket)
+                        .into_iter()
+                        .map(|(start, n)| (start, n as f64))
+                        .collect()
+                } else {
+                    time_series(&analyzer.entries, metric, bucket, pattern.as_ref())
+                        .into_iter()
+                        .filter(|p| p.series.eq_ignore_ascii_case(&series))
+                        .map(|p| (p.bucket, p.value))
+                        .collect()
+                };
+            let width = terminal_size::terminal_size()
+                .map(|(terminal_size::Width(w), _)| w as usize)
+                .filter(|&w| w > 20)
+                .unwrap_or(80)
+                - 13;
+            println!("{} per {}", series, report::bucket_label(bucket));
+            print!("{}", chart::render(&values, style, height, width));
+            0
+        }
+        Command::Supervise {
+            restart,
+            max_restarts,
+            log,
+            command,
+        } => {
+            let supervisor = Supervisor {
+                command,
+                policy: restart,
+                max_restarts,
+                log_file: config.log_file(log),
+            };
+            // Lifecycle events go through tracing; libraries' `log` records
+            // land in the same file. RUST_LOG narrows both.
+            let filter = std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
+            let filter = match Directives::parse(&filter) {
+                Ok(filter) => filter,
+                Err(e) => {
+                    eprintln!("Invalid RUST_LOG: {}", e);
+                    return 2;
+                }
+            };
+            let logger = facade::Logger::new(&supervisor.log_file, filter);
+            let subscriber =
+                tracing_subscriber::registry().with(trace::EntryLayer::new(logger.clone()));
+            if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+                eprintln!("Could not install the tracing layer: {}", e);
+                return 2;
+            }
+            if let Err(e) = logger.install() {
+                eprintln!("Could not install the logger: {}", e);
+                return 2;
+            }
+            match supervisor.run() {
+                Ok(code) => code,
+                Err(e) => {
+                    eprintln!("Could not supervise {}: {}", supervisor.command[0], e);
+                    2
+                }
+            }
+        }
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "logger", &mut std::io::stdout());
+            0
+        }
+        Command::Run { script } => match batch::run(&script, output) {
+            Ok(false) => 0,
+            Ok(true) => 1,
+            Err(e) => {
+                eprintln!("{}", e);
+                2
+            }
+        },
+        Command::Assert { file, conditions } => {
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            match gate::evaluate_all(&analyzer.entries, &conditions) {
+                Ok(outcomes) => {
+                    gate::print_report(&outcomes);
+                    if outcomes.iter().all(|o| o.passed) {
+                        0
+                    } else {
+                        1
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    2
+                }
+            }
+        }
+        Command::Check {
+            max_errors,
+            max_warnings,
+            patterns,
+            fail_on,
+            file,
+        } => {
+            let mut conditions = vec![format!("count ERROR+ > {}", max_errors)];
+            conditions.extend(max_warnings.map(|n| format!("count WARNING > {}", n)));
+            conditions.extend(patterns.iter().map(|p| format!("matches /{}/", p)));
+            conditions.extend(fail_on);
+            let analyzer = match load_existing(&config.input_file(file)) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            // A condition that holds is a problem found.
+            let outcomes = match gate::evaluate_all(&analyzer.entries, &conditions) {
+                Ok(outcomes) => outcomes,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+            };
+            let found = outcomes.iter().filter(|o| o.passed).count();
+            if output == OutputFormat::Json {
+                print_json(&json!({
+                    "entries": analyzer.entries.len(),
+                    "failed": found > 0,
+                    "conditions": outcomes.iter().map(|o| json!({
+                        "condition": o.condition,
+                        "failed": o.passed,
+                        "actual": o.actual,
+                    })).collect::<Vec<_>>(),
+                }));
+            } else {
+                for outcome in &outcomes {
+                    println!(
+                        "{}  {} ({})",
+                        if outcome.passed { "FAIL" } else { "ok  " },
+                        outcome.condition,
+                        outcome.actual
+                    );
+                }
+                println!(
+                    "{} of {} checks failed on {} entries",
+                    found,
+                    outcomes.len(),
+                    analyzer.entries.len()
+                );
+            }
+            if found > 0 {
+                1
+            } else {
+                0
+            }
+        }
+        Command::CompareBaseline {
+            baseline,
+            current,
+            fail_on,
+            save,
+        } => {
+            let fail_on = fail_on.as_deref().unwrap_or("new_error_templates > 0");
+            let gate = match compare::FailOn::parse(fail_on) {
+                Ok(gate) => gate,
+                Err(e) => {
+                    eprintln!("Invalid --fail-on: {}", e);
+                    return 2;
+                }
+            };
+            let analyzer = match load_existing(&config.input_file(current)) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let snapshot = compare::StatsSnapshot::from_entries(&analyzer.entries);
+            if save {
+                return match snapshot.save(&baseline) {
+                    Ok(()) => {
+                        println!(
+                            "Baseline of {} entries saved to {}",
+                            snapshot.entries, baseline
+                        );
+                        0
+                    }
+                    Err(e) => {
+                        eprintln!("Could not save {}: {}", baseline, e);
+                        2
+                    }
+                };
+            }
+            let stored = match compare::StatsSnapshot::load(&baseline) {
+                Ok(stored) => stored,
+                Err(e) => {
+                    eprintln!("Could not load baseline: {}", e);
+                    return 2;
+                }
+            };
+            let metrics = compare::metrics(&stored, &snapshot);
+            let failed = gate.fails(&metrics);
+            if output == OutputFormat::Json {
+                // Infinite changes are not valid JSON numbers.
+                let values: serde_json::Map<String, Value> = metrics
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = serde_json::Number::from_f64(*value)
+                            .map_or_else(|| Value::from("inf"), Value::Number);
+                        (name.to_string(), value)
+                    })
+                    .collect();
+                print_json(&json!({
+                    "baseline": baseline,
+                    "baseline_created": stored.created,
+                    "fail_on": fail_on,
+                    "failed": failed,
+                    "metrics": values,
+                }));
+            } else {
+                println!("Compared to {} ({}):", baseline, stored.created);
+                for (name, value) in &metrics {
+                    println!("  {:<22} {:.2}", name, value);
+                }
+                let verdict = if failed { "FAIL" } else { "PASS" };
+                println!("{}  (fails on {})", verdict, fail_on);
+            }
+            if failed {
+                1
+            } else {
+                0
+            }
+        }
+        Command::Diff {
+            before,
+            after,
+            factor,
+            min_count,
+            limit,
+        } => {
+            if factor <= 1.0 {
+                eprintln!("--factor must be above 1");
+                return 2;
+            }
+            let (old, new) = match (load_existing(&before), load_existing(&after)) {
+                (Ok(old), Ok(new)) => (old, new),
+                (Err(code), _) | (_, Err(code)) => return code,
+            };
+            let changes = compare::diff(&old.entries, &new.entries, factor, min_count);
+            let kinds = [
+                (compare::Change::Appeared, "Appeared"),
+                (compare::Change::Disappeared, "Disappeared"),
+                (compare::Change::Changed, "Changed frequency"),
+            ];
+            if output == OutputFormat::Json {
+                let list = |kind| {
+                    changes
+                        .iter()
+                        .filter(|c| c.change == kind)
+                        .take(limit)
+                        .map(|c| {
+                            json!({
+                                "level": c.level,
+                                "template": c.template,
+                                "before": c.before,
+                                "after": c.after,
+                                "factor": serde_json::Number::from_f64(c.factor),
+                                "example": c.example,
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                };
+                print_json(&json!({
+                    "before": { "file": before, "entries": old.entries.len() },
+                    "after": { "file": after, "entries": new.entries.len() },
+                    "appeared": list(compare::Change::Appeared),
+                    "disappeared": list(compare::Change::Disappeared),
+                    "changed": list(compare::Change::Changed),
+                }));
+            } else {
+                println!(
+                    "{} ({} entries) -> {} ({} entries)",
+                    before,
+                    old.entries.len(),
+                    after,
+                    new.entries.len()
+                );
+                for (kind, title) in kinds {
+                    let listed: Vec<_> = changes.iter().filter(|c| c.change == kind).collect();
+                    if listed.is_empty() {
+                        continue;
+                    }
+                    println!();
+                    println!("{} ({}):", title, listed.len());
+                    for c in listed.iter().take(limit) {
+                        let counts = match kind {
+                            compare::Change::Appeared => format!("+{}", c.after),
+                            compare::Change::Disappeared => format!("-{}", c.before),
+                            compare::Change::Changed => {
+                                format!("{} -> {} (x{:.1})", c.before, c.after, c.factor)
+                            }
+                        };
+                        println!("  {:<22} {:<8} {}", counts, c.level, c.template);
+                    }
+                    if listed.len() > limit {
+                        println!("  ... and {} more", listed.len() - limit);
+                    }
+                }
+                if changes.is_empty() {
+                    println!("No significant differences.");
+                }
+            }
+            if changes.is_empty() {
+                0
+            } else {
+                1
+            }
+        }
+        Command::Alerts {
+            rules,
+            silenced_log,
+            state: state_file,
+            follow,
+            file,
+        } => {
+            let set = match rules::RuleSet::load(&rules, config.smtp.as_ref()) {
+                Ok(set) => set,
+                Err(e) => {
+                    eprintln!("Could not load rules from {}: {}", rules, e);
+                    return 2;
+                }
+            };
+            let mut state = match rules::AlertState::load(&state_file) {
+                Ok(state) => state,
+                Err(e) => {
+                    eprintln!("Could not load alert state from {}: {}", state_file, e);
+                    return 2;
+                }
+            };
+            let file = config.input_file(file);
+            if follow {
+                if file == "-" {
+                    eprintln!("--follow needs a file");
+                    return 2;
+                }
+                return match follow_alerts(
+                    &set,
+                    &mut state,
+                    &state_file,
+                    &file,
+                    &silenced_log,
+                    output,
+                ) {
+                    Ok(()) => 0,
+                    Err(e) => {
+                        eprintln!("Could not follow {}: {}", file, e);
+                        1
+                    }
+                };
+            }
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            // Windows end at the newest entry, so old files evaluate the
+            // same way they would have when they were live.
+            let Some(now) = analyzer.entries.iter().filter_map(LogEntry::time).max() else {
+                println!("No timestamped entries.");
+                return 0;
+            };
+            let alerts = Alerts {
+                set: &set,
+                silenced_log: &silenced_log,
+                output,
+                changes_only: false,
+            };
+            let fired = alerts.evaluate(&mut state, &analyzer.entries, now, &mut Vec::new());
+            if let Err(e) = state.save(&state_file) {
+                eprintln!("Could not save alert state to {}: {}", state_file, e);
+            }
+            if fired {
+                1
+            } else {
+                0
+            }
+        }
+        Command::Verify { file } => {
+            // Hashes cover the entries as written, before any transform.
+            let builder = LogAnalyzer::builder().pipeline(false);
+            let analyzer = match load_with(&config.input_file(file), builder) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let breaks = chain::verify(&analyzer.entries);
+            if output == OutputFormat::Json {
+                let breaks: Vec<Value> = breaks
+                    .iter()
+                    .map(|b| {
+                        json!({
+                            "entry": b.index + 1,
+                            "timestamp": analyzer.entries[b.index].timestamp,
+                            "reason": b.reason,
+                        })
+                    })
+                    .collect();
+                print_json(&json!({
+                    "entries": analyzer.entries.len(),
+                    "intact": breaks.is_empty(),
+                    "breaks": breaks,
+                }));
+            } else if breaks.is_empty() {
+                println!("Chain intact ({} entries).", analyzer.entries.len());
+            } else {
+                println!("Chain broken ({} entries):", analyzer.entries.len());
+                for b in &breaks {
+                    println!(
+                        "  entry {} [{}]: {}",
+                        b.index + 1,
+                        analyzer.entries[b.index].timestamp,
+                        b.reason
+                    );
+                }
+            }
+            if breaks.is_empty() {
+                0
+            } else {
+                1
+            }
+        }
+        Command::Redact { file } => {
+            let Some(redactor) = redact::redactor() else {
+                eprintln!("No [redaction] table in the config.");
+                return 2;
+            };
+            let mut analyzer = match load_existing(&config.input_file(file)) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            for entry in &mut analyzer.entries {
+                redactor.redact_entry(entry);
+                println!("{}", entry.to_line());
+            }
+            0
+        }
+        Command::Purge {
+            filter,
+            scrub,
+            dry_run,
+            yes,
+            files,
+        } => {
+            let query = match query::CompiledQuery::compile(&filter) {
+                Ok(query) => query,
+                Err(e) => {
+                    eprintln!("Invalid filter: {}", e);
+                    return 2;
+                }
+            };
+            let files = if files.is_empty() {
+                vec![config.log_file(None)]
+            } else {
+                files
+            };
+            let mut plans = Vec::new();
+            for file in files {
+                if file == "-" {
+                    eprintln!("Cannot purge stdin; name the files to rewrite.");
+                    return 2;
+                }
+                let analyzer = match load_existing(&file) {
+                    Ok(analyzer) => analyzer,
+                    Err(code) => return code,
+                };
+                let matched = analyzer.entries.iter().filter(|e| query.matches(e)).count();
+                println!(
+                    "{}: {} of {} entries match",
+                    file,
+                    matched,
+                    analyzer.entries.len()
+                );
+                plans.push((file, analyzer, matched));
+            }
+            let total: usize = plans.iter().map(|(_, _, matched)| matched).sum();
+            let action = if scrub { "scrub" } else { "remove" };
+            if total == 0 || dry_run {
+                return 0;
+            }
+            if !yes {
+                if !std::io::stdin().is_terminal() {
+                    eprintln!(
+                        "Not a terminal; pass --yes to {} {} entries.",
+                        action, total
+                    );
+                    return 2;
+                }
+                print!("Permanently {} {} entries? [y/N] ", action, total);
+                let _ = std::io::stdout().flush();
+                let mut answer = String::new();
+                let _ = std::io::stdin().read_line(&mut answer);
+                if !answer.trim().eq_ignore_ascii_case("y") {
+                    println!("Nothing changed.");
+                    return 1;
+                }
+            }
+            for (file, mut analyzer, matched) in plans {
+                if matched == 0 {
+                    continue;
+                }
+                if scrub {
+                    for entry in analyzer.entries.iter_mut().filter(|e| query.matches(e)) {
+                        entry.message = "[purged]".to_string();
+                        entry.fields.clear();
+                    }
+                } else {
+                    analyzer.entries.retain(|e| !query.matches(e));
+                }
+                // No backup: the point is that the data is gone.
+                if let Err(e) = analyzer.save_to_file(&file) {
+                    eprintln!("Could not rewrite {}: {}", file, e);
+                    return 2;
+                }
+                let done = if scrub { "scrubbed" } else { "removed" };
+                println!("{}: {} {} entries", file, done, matched);
+            }
+            0
+        }
+        Command::Extract { preview, file } => {
+            let analyzer = match load_existing(&config.log_file(file)) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            if analyzer.entries.is_empty() {
+                println!("No entries.");
+                return 0;
+            }
+            extract::run_helper(&analyzer.entries, preview, config.path.as_deref());
+            0
+        }
+        Command::Export {
+            target,
+            to,
+            tcp,
+            headers,
+            labels,
+            index,
+            retries,
+            resource,
+            batch_size,
+            compress,
+            follow,
+            metrics,
+            offsets,
+            file,
+        } => {
+            let compression = compress.unwrap_or(Compression::None);
+            if compression != Compression::None
+                && !matches!(target, Target::Loki | Target::Elasticsearch | Target::Otlp)
+            {
+                eprintln!("--compress only applies to the loki, elasticsearch and otlp targets");
+                return 2;
+            }
+            let file = config.input_file(file);
+            if follow && file == "-" {
+                eprintln!("--follow needs a file");
+                return 2;
+            }
+            if let Some(address) = &metrics {
+                if let Err(e) = prometheus::serve(address) {
+                    eprintln!("Could not serve metrics on {}: {}", address, e);
+                    return 2;
+                }
+            }
+            let mut parsed = Vec::new();
+            for header in headers {
+                match header.split_once(':') {
+                    Some((name, value)) => {
+                        parsed.push((name.trim().to_string(), value.trim().to_string()))
+                    }
+                    None => {
+                        eprintln!("Invalid --header '{}': expected \"Name: value\"", header);
+                        return 2;
+                    }
+                }
+            }
+            let mut attributes = Vec::new();
+            for attribute in resource {
+                match attribute.split_once('=') {
+                    Some((key, value)) => attributes.push((key.to_string(), value.to_string())),
+                    None => {
+                        eprintln!("Invalid --resource '{}': expected key=value", attribute);
+                        return 2;
+                    }
+                }
+            }
+            let labels = if labels.is_empty() {
+                loki::DEFAULT_LABELS.map(String::from).to_vec()
+            } else {
+                labels
+            };
+            let options = sink::Options {
+                to,
+                tcp,
+                headers: parsed,
+                labels,
+                file: file.clone(),
+                index,
+                retries,
+                resource: attributes,
+                compression,
+            };
+            let mut sink = match sink::open(target, &options) {
+                Ok(sink) => sink,
+                Err(e) => {
+                    eprintln!("Could not connect to {}: {}", options.to, e);
+                    return 2;
+                }
+            };
+            let result = if follow {
+                sink::follow(sink.as_mut(), &file, batch_size, offsets.as_deref()).map(|()| 0)
+            } else {
+                let analyzer = match load_existing(&file) {
+                    Ok(analyzer) => analyzer,
+                    Err(code) => return code,
+                };
+                sink::send_all(sink.as_mut(), &analyzer.entries, batch_size)
+            };
+            match result {
+                Ok(sent) => {
+                    println!("Sent {} entries to {}", sent, options.to);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Could not send to {}: {}", options.to, e);
+                    1
+                }
+            }
+        }
+        Command::Serve {
+            listen,
+            max_entries,
+            report_every,
+            report_to,
+            file,
+        } => {
+            let file = config.log_file(file);
+            if let Err(code) = start_reports(report_every, report_to, &file, config) {
+                return code;
+            }
+            let listener = match std::net::TcpListener::bind(&listen) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("Could not listen on {}: {}", listen, e);
+                    return 2;
+                }
+            };
+            let server = match Server::new(file.clone(), max_entries) {
+                Ok(server) => server,
+                Err(e) => {
+                    eprintln!("Could not read {}: {}", file, e);
+                    return 1;
+                }
+            };
+            eprintln!("Serving {} on http://{}", file, listen);
+            server.run(listener);
+            0
+        }
+        Command::Listen {
+            udp,
+            tcp,
+            unix,
+            flush,
+            buffer,
+            metrics,
+            report_every,
+            report_to,
+            file,
+        } => {
+            if let Some(address) = &metrics {
+                if let Err(e) = prometheus::serve(address) {
+                    eprintln!("Could not serve metrics on {}: {}", address, e);
+                    return 2;
+                }
+            }
+            let file = config.log_file(file);
+            if let Err(code) = start_reports(report_every, report_to, &file, config) {
+                return code;
+            }
+            let receiver = Receiver {
+                udp,
+                tcp,
+                unix,
+                flush: std::time::Duration::from_secs(flush.max(1)),
+                buffer: buffer.max(1),
+                log_file: file,
+            };
+            match receiver.run() {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Listener error: {}", e);
+                    2
+                }
+            }
+        }
+        #[cfg(feature = "async")]
+        Command::Collect {
+            files,
+            udp,
+            tcp,
+            flush,
+            buffer,
+            offsets,
+            file,
+        } => {
+            let file = config.log_file(file);
+            if files.contains(&file) {
+                eprintln!("Cannot collect {} into itself", file);
+                return 2;
+            }
+            let sources = files
+                .into_iter()
+                .map(crate::aio::Source::File)
+                .chain(udp.map(crate::aio::Source::Udp))
+                .chain(tcp.map(crate::aio::Source::Tcp))
+                .collect();
+            let sink = Box::new(crate::aio::LogFile(file));
+            let flush = std::time::Duration::from_secs(flush.max(1));
+            match crate::aio::collect(sources, sink, buffer.max(1), flush, offsets) {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Collect error: {}", e);
+                    2
+                }
+            }
+        }
+        #[cfg(feature = "journald")]
+        Command::Journal {
+            units,
+            priority,
+            since,
+            grep,
+            follow,
+            save,
+        } => {
+            let since = match since
+                .as_deref()
+                .map(|since| query::parse_interval(since).and_then(query::ago))
+            {
+                Some(Ok(since)) => Some(since),
+                Some(Err(e)) => {
+                    eprintln!("Invalid --since: {}", e);
+                    return 2;
+                }
+                None => None,
+            };
+            let priority = match priority.as_deref().map(crate::levels::parse).transpose() {
+                Ok(priority) => priority,
+                Err(e) => {
+                    eprintln!("Invalid --priority: {}", e);
+                    return 2;
+                }
+            };
+            let journal = crate::journald::Journal {
+                units,
+                priority: priority.as_deref().map(crate::syslog::severity),
+                since,
+                follow,
+            };
+            let grep = grep.map(|text| text.to_lowercase());
+            let result = journal.read(|entry| {
+                if grep
+                    .as_ref()
+                    .is_some_and(|text| !entry.message.to_lowercase().contains(text))
+                {
+                    return Ok(());
+                }
+                match output {
+                    OutputFormat::Text => {
+                        println!("{}", color::entry_line(&entry, grep.as_deref()))
+                    }
+                    OutputFormat::Json => println!("{}", export::entry_json(&entry)),
+                }
+                match &save {
+                    Some(file) => crate::rotate::before_append(file)
+                        .and_then(|()| LogAnalyzer::append_to_file(file, &[entry])),
+                    None => Ok(()),
+                }
+            });
+            match result {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Journal error: {}", e);
+                    2
+                }
+            }
+        }
+        #[cfg(all(windows, feature = "eventlog"))]
+        Command::EventLog {
+            channels,
+            level,
+            provider,
+            since,
+            save,
+        } => {
+            let since = match since
+                .as_deref()
+                .map(|since| query::parse_interval(since).and_then(query::ago))
+            {
+                Some(Ok(since)) => Some(since),
+                Some(Err(e)) => {
+                    eprintln!("Invalid --since: {}", e);
+                    return 2;
+                }
+                None => None,
+            };
+            let channels = if channels.is_empty() {
+                vec!["Application".to_string(), "System".to_string()]
+            } else {
+                channels
+            };
+            let level = match level.as_deref().map(crate::levels::parse).transpose() {
+                Ok(level) => level,
+                Err(e) => {
+                    eprintln!("Invalid --level: {}", e);
+                    return 2;
+                }
+            };
+            let log = crate::eventlog::EventLog {
+                channels,
+                level: level.as_deref().map(crate::eventlog::windows_level),
+                provider,
+                since,
+            };
+            let result = log.read(|entry| {
+                match output {
+                    OutputFormat::Text => println!("{}", color::entry_line(&entry, None)),
+                    OutputFormat::Json => println!("{}", export::entry_json(&entry)),
+                }
+                match &save {
+                    Some(file) => crate::rotate::before_append(file)
+                        .and_then(|()| LogAnalyzer::append_to_file(file, &[entry])),
+                    None => Ok(()),
+                }
+            });
+            match result {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Event log error: {}", e);
+                    2
+                }
+            }
+        }
+        Command::K8s {
+            namespace,
+            pod,
+            selector,
+            container,
+            since,
+            tail,
+            follow,
+            server,
+            token,
+            save,
+        } => {
+            let since_seconds = match since.as_deref().map(query::parse_interval) {
+                Some(Ok(interval)) => Some(interval.num_seconds()),
+                Some(Err(e)) => {
+                    eprintln!("Invalid --since: {}", e);
+                    return 2;
+                }
+                None => None,
+            };
+            let source = k8s::Source {
+                server,
+                token,
+                namespace,
+                pod,
+                selector,
+                container,
+                since_seconds,
+                tail,
+                follow,
+            }
+            .with_default_token();
+            let result = source.read(|entry| {
+                match output {
+                    OutputFormat::Text => println!("{}", color::entry_line(&entry, None)),
+                    OutputFormat::Json => println!("{}", export::entry_json(&entry)),
+                }
+                match &save {
+                    Some(file) => crate::rotate::before_append(file)
+                        .and_then(|()| LogAnalyzer::append_to_file(file, &[entry])),
+                    None => Ok(()),
+                }
+            });
+            match result {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Kubernetes error: {}", e);
+                    2
+                }
+            }
+        }
+        Command::Watch {
+            interval,
+            window,
+            errors,
+            file,
+        } => {
+            let window = match query::parse_interval(&window) {
+                Ok(window) if window > chrono::Duration::zero() => window,
+                _ => {
+                    eprintln!("Invalid --window {}", window);
+                    return 2;
+                }
+            };
+            let file = config.log_file(file);
+            let options = crate::watch::Options {
+                interval,
+                window,
+                errors,
+            };
+            match crate::watch::run(&file, &options) {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Could not follow {}: {}", file, e);
+                    2
+                }
+            }
+        }
+        Command::Ssh { url, follow, save } => {
+            let Some(remote) = ssh::Remote::parse(&url) else {
+                eprintln!("Invalid remote file '{}': expected host:/path", url);
+                return 2;
+            };
+            let print = |entry: LogEntry| {
+                match output {
+                    OutputFormat::Text => println!("{}", color::entry_line(&entry, None)),
+                    OutputFormat::Json => println!("{}", export::entry_json(&entry)),
+                }
+                match &save {
+                    Some(file) => crate::rotate::before_append(file)
+                        .and_then(|()| LogAnalyzer::append_to_file(file, &[entry])),
+                    None => Ok(()),
+                }
+            };
+            let result = if follow {
+                remote.follow(print)
+            } else {
+                let mut analyzer = LogAnalyzer::new();
+                remote
+                    .open()
+                    .and_then(crate::encoding::decode)
+                    .and_then(|reader| analyzer.load_from_reader(reader))
+                    .and_then(|()| analyzer.entries.into_iter().try_for_each(print))
+            };
+            match result {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Could not read {}: {}", url, e);
+                    2
+                }
+            }
+        }
+        Command::Generate {
+            rate,
+            duration,
+            count,
+            levels,
+            templates,
+            start,
+            realtime,
+            seed,
+        } => {
+            if !(rate > 0.0 && rate.is_finite()) {
+                eprintln!("--rate must be positive");
+                return 2;
+            }
+            let duration = match query::parse_interval(&duration) {
+                Ok(duration) => duration,
+                Err(e) => {
+                    eprintln!("Invalid --duration: {}", e);
+                    return 2;
+                }
+            };
+            let now = chrono::Local::now().naive_local();
+            let start = match start.as_deref().map(crate::parse_time) {
+                Some(Some(start)) => start,
+                Some(None) => {
+                    eprintln!("Invalid --start");
+                    return 2;
+                }
+                None if realtime => now,
+                None => match now.checked_sub_signed(duration) {
+                    Some(start) => start,
+                    None => {
+                        eprintln!("Invalid --duration: too long");
+                        return 2;
+                    }
+                },
+            };
+            let generator = generate::parse_levels(&levels)
+                .and_then(|levels| generate::Generator::new(rate, levels, &templates, start, seed));
+            let mut generator = match generator {
+                Ok(generator) => generator,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+            };
+            let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+            let mut written = 0;
+            let result = loop {
+                let entry = generator.next_entry();
+                let done = match count {
+                    Some(count) => written >= count,
+                    None => generator.time() > start + duration,
+                };
+                if done {
+                    break out.flush();
+                }
+                if realtime {
+                    out.flush().ok();
+                    let wait = generator.time() - chrono::Local::now().naive_local();
+                    if let Ok(wait) = wait.to_std() {
+                        std::thread::sleep(wait);
+                    }
+                }
+                let line = match output {
+                    OutputFormat::Text => entry.to_line(),
+                    OutputFormat::Json => export::entry_json(&entry).to_string(),
+                };
+                if let Err(e) = writeln!(out, "{}", line) {
+                    break Err(e);
+                }
+                written += 1;
+            };
+            match result {
+                Ok(()) => 0,
+                // Stopped by e.g. `| head`.
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => 0,
+                Err(e) => {
+                    eprintln!("Could not write: {}", e);
+                    1
+                }
+            }
+        }
+        Command::Merge { files, out, field } => {
+            let mut logs = Vec::new();
+            for file in &files {
+                match load_existing(file) {
+                    Ok(analyzer) => logs.push((file.clone(), analyzer.entries)),
+                    Err(code) => return code,
+                }
+            }
+            let merged = analysis::merge(logs, &field);
+            if let Some(out) = out {
+                let mut analyzer = LogAnalyzer::new();
+                analyzer.entries = merged;
+                return match analyzer.save_to_file(&out) {
+                    Ok(()) => {
+                        println!(
+                            "Merged {} entries from {} logs into {}",
+                            analyzer.entries.len(),
+                            files.len(),
+                            out
+                        );
+                        0
+                    }
+                    Err(e) => {
+                        eprintln!("Could not write {}: {}", out, e);
+                        1
+                    }
+                };
+            }
+            let width = files.iter().map(|f| f.len()).max().unwrap_or(0);
+            for entry in &merged {
+                match output {
+                    OutputFormat::Text => {
+                        let origin = entry.fields.get(&field).and_then(Value::as_str);
+                        println!(
+                            "{:<width$}  {}",
+                            origin.unwrap_or(""),
+                            color::entry_line(entry, None)
+                        )
+                    }
+                    OutputFormat::Json => println!("{}", export::entry_json(entry)),
+                }
+            }
+            0
+        }
+        Command::Replay {
+            speed,
+            max_gap,
+            retime,
+            target,
+            to,
+            tcp,
+            file,
+        } => {
+            if !(speed > 0.0 && speed.is_finite()) {
+                eprintln!("--speed must be positive");
+                return 2;
+            }
+            let max_gap = match max_gap.as_deref().map(query::parse_interval) {
+                Some(Ok(gap)) => Some(gap),
+                Some(Err(e)) => {
+                    eprintln!("Invalid --max-gap: {}", e);
+                    return 2;
+                }
+                None => None,
+            };
+            let file = config.input_file(file);
+            let analyzer = match load_existing(&file) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            let replay = replay::Replay {
+                speed,
+                max_gap,
+                retime,
+            };
+            let result = match (target, to) {
+                (Some(target), Some(to)) => {
+                    let options = sink::Options {
+                        to,
+                        tcp,
+                        headers: Vec::new(),
+                        labels: loki::DEFAULT_LABELS.map(String::from).to_vec(),
+                        file,
+                        index: elastic::DEFAULT_INDEX.to_string(),
+                        retries: 3,
+                        resource: Vec::new(),
+                        compression: Compression::None,
+                    };
+                    let mut sink = match sink::open(target, &options) {
+                        Ok(sink) => sink,
+                        Err(e) => {
+                            eprintln!("Could not connect to {}: {}", options.to, e);
+                            return 2;
+                        }
+                    };
+                    replay.run(analyzer.entries, |entry| {
+                        sink::send_all(sink.as_mut(), &[entry], 1).map(|_| ())
+                    })
+                }
+                _ => replay.run(analyzer.entries, |entry| {
+                    match output {
+                        OutputFormat::Text => println!("{}", color::entry_line(&entry, None)),
+                        OutputFormat::Json => println!("{}", export::entry_json(&entry)),
+                    }
+                    Ok(())
+                }),
+            };
+            match result {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("Replay failed: {}", e);
+                    1
+                }
+            }
+        }
+        Command::Convert { input, target } => {
+            // Entries are written back as they are, so they skip the pipeline.
+            let analyzer = match load_with(&input, LogAnalyzer::builder().pipeline(false)) {
+                Ok(analyzer) => analyzer,
+                Err(code) => return code,
+            };
+            if let Err(e) = analyzer.save_to_file(&target) {
+                eprintln!("Could not write {}: {}", target, e);
+                return 2;
+            }
+            let format = if crate::binary::wanted(&target) {
+                "binary"
+            } else {
+                "text"
+            };
+            if output == OutputFormat::Json {
+                print_json(&json!({
+                    "input": input,
+                    "output": target,
+                    "format": format,
+                    "entries": analyzer.entries.len(),
+                }));
+            } else {
+                println!(
+                    "Wrote {} entries from {} to {} as {}",
+                    analyzer.entries.len(),
+                    input,
+                    target,
+                    format
+                );
+            }
+            0
+        }
+        Command::Archive {
+            before,
+            dir,
+            dry_run,
+            file,
+        } => {
+            let cutoff = match archive::parse_cutoff(&before) {
+                Ok(cutoff) => cutoff,
+                Err(e) => {
+                    eprintln!("Invalid --before: {}", e);
+                    return 2;
+                }
+            };
+            let file = config.log_file(file);
+            let dir = dir.map_or_else(|| archive::default_dir(&file), PathBuf::from);
+            let moved = match archive::archive(&file, cutoff, &dir, dry_run) {
+                Ok(moved) => moved,
+                Err(e) => {
+                    eprintln!("Could not archive {}: {}", file, e);
+                    return 2;
+                }
+            };
+            let total: usize = moved.values().sum();
+            if output == OutputFormat::Json {
+                print_json(&json!({
+                    "file": file,
+                    "dir": dir,
+                    "dry_run": dry_run,
+                    "archived": total,
+                    "by_day": moved
+                        .iter()
+                        .map(|(day, count)| (day.to_string(), *count))
+                        .collect::<std::collections::BTreeMap<_, _>>(),
+                }));
+            } else {
+                let verb = if dry_run { "Would move" } else { "Moved" };
+                println!(
+                    "{} {} entries from {} to {}",
+                    verb,
+                    total,
+                    file,
+                    dir.display()
+                );
+                for (day, count) in &moved {
+                    println!("  {}  {}", day, count);
+                }
+            }
+            0
+        }
+        Command::Retention { dry_run, file } => {
+            let Some(policy) = retention::policy() else {
+                eprintln!("No [retention] table in the config.");
+                return 2;
+            };
+            let file = config.log_file(file);
+            let removed = match retention::purge_file(policy, &file, &file, dry_run) {
+                Ok(removed) => removed,
+                Err(e) => {
+                    eprintln!("Could not apply retention to {}: {}", file, e);
+                    return 2;
+                }
+            };
+            let total: usize = removed.values().sum();
+            if output == OutputFormat::Json {
+                print_json(&json!({
+                    "file": file,
+                    "dry_run": dry_run,
+                    "removed": total,
+                    "by_level": removed,
+                }));
+            } else {
+                let verb = match (dry_run, policy.archive.is_some()) {
+                    (true, false) => "Would remove",
+                    (true, true) => "Would archive",
+                    (false, false) => "Removed",
+                    (false, true) => "Archived",
+                };
+                println!("{} {} expired entries from {}", verb, total, file);
+                for (level, count) in &removed {
+                    println!("  {:<8} {}", level, count);
+                }
+            }
+            0
+        }
+    }
+}
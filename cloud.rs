@@ -0,0 +1,129 @@
+//! Logs as the cloud CLIs print them: `heroku logs` and AWS CloudWatch
+//! (`aws logs tail`, and the JSON of `aws logs filter-log-events` or
+//! `get-log-events`).
+
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::LogEntry;
+
+// RFC 3339 (or a bare ISO 8601 time) in the native format and local time.
+fn native_time(text: &str) -> Option<String> {
+    let time = DateTime::parse_from_rfc3339(text)
+        .map(|t| t.with_timezone(&Local).naive_local())
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()?;
+    Some(time.format(crate::TIMESTAMP_FORMAT).to_string())
+}
+
+fn millis_time(millis: i64) -> Option<String> {
+    let time = DateTime::from_timestamp_millis(millis)?.with_timezone(&Local);
+    Some(time.format(crate::TIMESTAMP_FORMAT).to_string())
+}
+
+// The application's own line, if it logged JSON or logfmt; otherwise a
+// plain INFO message.
+fn inner_entry(timestamp: String, message: &str) -> LogEntry {
+    let inner = if message.trim_start().starts_with('{') {
+        LogEntry::from_json(message)
+    } else {
+        LogEntry::from_logfmt(message)
+    };
+    match inner {
+        Some(mut entry) => {
+            if entry.time().is_none() {
+                entry.timestamp = timestamp;
+            }
+            entry
+        }
+        None => LogEntry {
+            timestamp,
+            level: "INFO".to_string(),
+            message: message.to_string(),
+            fields: Map::new(),
+            source: None,
+        },
+    }
+}
+
+/// Parses `2024-05-01T12:00:00.123456+00:00 app[web.1]: message`. The
+/// source (`app`, `heroku`, ...) and dyno become fields; router lines take
+/// their level from `at=`.
+pub fn from_heroku(line: &str) -> Option<LogEntry> {
+    static HEROKU: OnceLock<Regex> = OnceLock::new();
+    let regex = HEROKU.get_or_init(|| {
+        Regex::new(r"^(\d{4}-\d\d-\d\dT[^\s|]+) ([\w-]+)\[([^\]]+)\]: ?(.*)$").unwrap()
+    });
+    let captures = regex.captures(line)?;
+    let timestamp = native_time(&captures[1])?;
+    let message = &captures[4];
+    let mut entry = inner_entry(timestamp, message);
+    if message.starts_with("at=") {
+        let mut fields = crate::logfmt_fields(message);
+        entry.level = match fields.remove("at").as_ref().and_then(Value::as_str) {
+            Some("error") => "ERROR",
+            Some("warning") => "WARNING",
+            _ => "INFO",
+        }
+        .to_string();
+        entry.fields = fields;
+    }
+    entry
+        .fields
+        .insert("source".to_string(), Value::from(&captures[2]));
+    entry
+        .fields
+        .insert("dyno".to_string(), Value::from(&captures[3]));
+    Some(entry)
+}
+
+/// Parses `aws logs tail` output: `<timestamp> <log stream> <message>`
+/// (or `<timestamp> <message>` with `--format short`). The stream becomes
+/// the `source` field; a second word without `/` or `[` is taken to be
+/// part of the message.
+pub fn from_cloudwatch(line: &str) -> Option<LogEntry> {
+    let (time, rest) = line.split_once(' ')?;
+    let timestamp = native_time(time)?;
+    // Stream names look like 2024/05/01/[$LATEST]abc or ecs/app/0abc.
+    let stream = rest
+        .split_once(' ')
+        .filter(|(stream, _)| stream.contains('/') || stream.contains('['));
+    let (stream, message) = match stream {
+        Some((stream, message)) => (Some(stream), message),
+        None => (None, rest),
+    };
+    let mut entry = inner_entry(timestamp, message.trim());
+    if let Some(stream) = stream {
+        entry
+            .fields
+            .insert("source".to_string(), Value::from(stream));
+    }
+    Some(entry)
+}
+
+/// Parses a whole `{"events": [...]}` document; `None` if `text` is not
+/// one, so it can be read line by line instead.
+pub fn from_cloudwatch_export(text: &str) -> Option<Vec<LogEntry>> {
+    let Ok(Value::Object(document)) = serde_json::from_str(text) else {
+        return None;
+    };
+    let events = document.get("events")?.as_array()?;
+    let entries = events
+        .iter()
+        .filter_map(|event| {
+            let timestamp = millis_time(event.get("timestamp")?.as_i64()?)?;
+            let message = event.get("message")?.as_str()?;
+            let mut entry = inner_entry(timestamp, message.trim_end());
+            if let Some(stream) = event.get("logStreamName").and_then(Value::as_str) {
+                entry
+                    .fields
+                    .insert("source".to_string(), Value::from(stream));
+            }
+            Some(entry)
+        })
+        .collect();
+    Some(entries)
+}
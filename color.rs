@@ -0,0 +1,125 @@
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::ValueEnum;
+
+use crate::alert::severity_rank;
+use crate::LogEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SHOW_SOURCE: AtomicBool = AtomicBool::new(false);
+
+const RESET: &str = "\x1b[0m";
+const HIGHLIGHT: &str = "\x1b[1;4m";
+
+/// Decides once whether to color stdout. `auto` colors only a terminal,
+/// and only while NO_COLOR is unset or empty; `always` ignores both.
+pub fn init(choice: ColorChoice) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => !no_color && std::io::stdout().is_terminal(),
+    };
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether `entry_line` ends with where the entry came from.
+pub fn show_source(show: bool) {
+    SHOW_SOURCE.store(show, Ordering::Relaxed);
+}
+
+// ` (app.log:12, host web1, pid 42)`, or nothing if showing is off.
+fn source_suffix(entry: &LogEntry) -> String {
+    let Some(source) = entry
+        .source
+        .as_ref()
+        .filter(|_| SHOW_SOURCE.load(Ordering::Relaxed))
+    else {
+        return String::new();
+    };
+    let mut parts = Vec::new();
+    match (&source.file, source.line) {
+        (Some(file), Some(line)) => parts.push(format!("{}:{}", file, line)),
+        (Some(file), None) => parts.push(file.clone()),
+        (None, Some(line)) => parts.push(format!("line {}", line)),
+        (None, None) => {}
+    }
+    if let Some(host) = &source.host {
+        parts.push(format!("host {}", host));
+    }
+    if let Some(pid) = source.pid {
+        parts.push(format!("pid {}", pid));
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", parts.join(", "))
+    }
+}
+
+fn level_style(level: &str) -> &'static str {
+    match severity_rank(level) {
+        4 => "\x1b[1;31m",
+        3 => "\x1b[31m",
+        2 => "\x1b[33m",
+        _ => "\x1b[2m",
+    }
+}
+
+// Wraps case-insensitive occurrences of `needle`, resuming `style` after each.
+fn highlight(text: &str, needle: &str, style: &str) -> String {
+    let lower = text.to_lowercase();
+    let needle = needle.to_lowercase();
+    // Lowercasing can change byte offsets outside ASCII; don't guess.
+    if needle.is_empty() || lower.len() != text.len() {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut last = 0;
+    for (start, _) in lower.match_indices(&needle) {
+        if start < last {
+            continue;
+        }
+        let end = start + needle.len();
+        out.push_str(&text[last..start]);
+        out.push_str(HIGHLIGHT);
+        out.push_str(&text[start..end]);
+        out.push_str(RESET);
+        out.push_str(style);
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// `[timestamp] LEVEL - message`, colored by level when enabled, with
+/// `matched` emphasized in the message and, with --show-source, where the
+/// entry came from after it.
+pub fn entry_line(entry: &LogEntry, matched: Option<&str>) -> String {
+    let source = source_suffix(entry);
+    let timestamp = crate::display::timestamp(entry);
+    if !ENABLED.load(Ordering::Relaxed) {
+        return format!(
+            "[{}] {} - {}{}",
+            timestamp, entry.level, entry.message, source
+        );
+    }
+    let style = level_style(&entry.level);
+    let message = match matched {
+        Some(needle) => highlight(&entry.message, needle, style),
+        None => entry.message.clone(),
+    };
+    format!(
+        "{}[{}] {} - {}{}\x1b[2m{}{}",
+        style, timestamp, entry.level, message, RESET, source, RESET
+    )
+}
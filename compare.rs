@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::alert::severity_rank;
+use crate::analysis::normalize_message;
+use crate::error::LoggerError;
+use crate::gate::{parse_number, parse_op};
+use crate::query::Op;
+use crate::LogEntry;
+
+pub const FAIL_ON_HELP: &str =
+    "--fail-on takes comparisons joined with `and`/`or` (and binds tighter), e.g.
+\"new_error_templates > 0 or error_rate_increase > 20%\". Metrics: entries,
+entries_change (%), errors, error_rate (%), error_rate_increase (% over the
+baseline rate), new_templates, new_error_templates.";
+
+/// The stats of a known-good run, saved with `compare-baseline --save`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub created: String,
+    pub entries: usize,
+    pub errors: usize,
+    pub levels: BTreeMap<String, usize>,
+    /// Normalized messages, see `analysis::normalize_message`.
+    pub templates: BTreeSet<String>,
+    pub error_templates: BTreeSet<String>,
+}
+
+fn is_error(entry: &LogEntry) -> bool {
+    severity_rank(&entry.level) >= 3
+}
+
+impl StatsSnapshot {
+    pub fn from_entries(entries: &[LogEntry]) -> StatsSnapshot {
+        let mut snapshot = StatsSnapshot {
+            created: crate::now_timestamp(),
+            entries: entries.len(),
+            ..StatsSnapshot::default()
+        };
+        for entry in entries {
+            *snapshot
+                .levels
+                .entry(entry.level.to_uppercase())
+                .or_insert(0) += 1;
+            let template = normalize_message(&entry.message);
+            if is_error(entry) {
+                snapshot.errors += 1;
+                snapshot.error_templates.insert(template.clone());
+            }
+            snapshot.templates.insert(template);
+        }
+        snapshot
+    }
+
+    pub fn load(filename: &str) -> Result<StatsSnapshot, LoggerError> {
+        let file = File::open(filename).map_err(|e| LoggerError::io(filename, e))?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+            if e.is_io() {
+                LoggerError::io(filename, e.into())
+            } else {
+                LoggerError::Parse {
+                    file: filename.to_string(),
+                    line: e.line(),
+                    message: e.to_string(),
+                }
+            }
+        })
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(filename)?);
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)?;
+        out.flush()
+    }
+
+    /// Errors as a percentage of all entries.
+    pub fn error_rate(&self) -> f64 {
+        if self.entries == 0 {
+            0.0
+        } else {
+            self.errors as f64 * 100.0 / self.entries as f64
+        }
+    }
+}
+
+// Percent change from `before` to `after`; any rise from zero is infinite.
+fn change(before: f64, after: f64) -> f64 {
+    if before == 0.0 {
+        if after == 0.0 {
+            0.0
+        } else {
+            f64::INFINITY
+        }
+    } else {
+        (after - before) * 100.0 / before
+    }
+}
+
+/// The metrics `--fail-on` can refer to, comparing `current` to `baseline`.
+pub fn metrics(baseline: &StatsSnapshot, current: &StatsSnapshot) -> BTreeMap<&'static str, f64> {
+    BTreeMap::from([
+        ("entries", current.entries as f64),
+        (
+            "entries_change",
+            change(baseline.entries as f64, current.entries as f64),
+        ),
+        ("errors", current.errors as f64),
+        ("error_rate", current.error_rate()),
+        (
+            "error_rate_increase",
+            change(baseline.error_rate(), current.error_rate()),
+        ),
+        (
+            "new_templates",
+            current.templates.difference(&baseline.templates).count() as f64,
+        ),
+        (
+            "new_error_templates",
+            current
+                .error_templates
+                .difference(&baseline.error_templates)
+                .count() as f64,
+        ),
+    ])
+}
+
+struct Comparison {
+    metric: String,
+    op: Op,
+    value: f64,
+}
+
+/// A parsed `--fail-on` expression: any group failing fails the gate; a
+/// group fails when all of its comparisons hold.
+pub struct FailOn {
+    groups: Vec<Vec<Comparison>>,
+}
+
+fn split_words<'a>(text: &'a str, word: &str) -> Vec<&'a str> {
+    let lower = text.to_ascii_lowercase();
+    let separator = format!(" {} ", word);
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while let Some(found) = lower[start..].find(&separator) {
+        parts.push(&text[start..start + found]);
+        start += found + separator.len();
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+impl FailOn {
+    pub fn parse(expression: &str) -> Result<FailOn, String> {
+        let known = metrics(&StatsSnapshot::default(), &StatsSnapshot::default());
+        let mut groups = Vec::new();
+        for group in split_words(expression, "or") {
+            let mut comparisons = Vec::new();
+            for comparison in split_words(group, "and") {
+                let comparison = comparison.trim();
+                let op_start = comparison
+                    .find(['<', '>', '=', '!'])
+                    .ok_or_else(|| format!("no comparison in '{}'", comparison))?;
+                let op_len = comparison[op_start..]
+                    .find(|c: char| !"<>=!".contains(c))
+                    .unwrap_or(comparison.len() - op_start);
+                let metric = comparison[..op_start].trim().to_lowercase();
+                if !known.contains_key(metric.as_str()) {
+                    return Err(format!(
+                        "unknown metric '{}' (known: {})",
+                        metric,
+                        known.keys().copied().collect::<Vec<_>>().join(", ")
+                    ));
+                }
+                comparisons.push(Comparison {
+                    metric,
+                    op: parse_op(&comparison[op_start..op_start + op_len])?,
+                    value: parse_number(comparison[op_start + op_len..].trim())?,
+                });
+            }
+            groups.push(comparisons);
+        }
+        Ok(FailOn { groups })
+    }
+
+    /// Whether the metrics count as a regression.
+    pub fn fails(&self, metrics: &BTreeMap<&'static str, f64>) -> bool {
+        self.groups.iter().any(|group| {
+            group
+                .iter()
+                .all(|c| c.op.holds(&metrics[c.metric.as_str()], &c.value))
+        })
+    }
+}
+
+/// How a message template's frequency differs between two runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Appeared,
+    Disappeared,
+    /// Its share of the run's entries grew or shrank by the factor asked for.
+    Changed,
+}
+
+pub struct TemplateChange {
+    pub change: Change,
+    pub level: String,
+    pub template: String,
+    pub before: usize,
+    pub after: usize,
+    /// Share of its run in `after` over share in `before`; infinite for
+    /// templates that appeared and zero for ones that disappeared.
+    pub factor: f64,
+    pub example: String,
+}
+
+/// Templates (per level) that appeared, disappeared or changed frequency
+/// by at least `factor` between two runs. Frequencies are shares of each
+/// run so runs of different length compare; templates seen fewer than
+/// `min_count` times in both runs are noise and left out.
+pub fn diff(
+    before: &[LogEntry],
+    after: &[LogEntry],
+    factor: f64,
+    min_count: usize,
+) -> Vec<TemplateChange> {
+    let mut counts: BTreeMap<(String, String), (usize, usize, String)> = BTreeMap::new();
+    for (run, entries) in [before, after].into_iter().enumerate() {
+        for entry in entries {
+            let key = (
+                entry.level.to_uppercase(),
+                normalize_message(&entry.message),
+            );
+            let counts = counts
+                .entry(key)
+                .or_insert_with(|| (0, 0, entry.message.clone()));
+            if run == 0 {
+                counts.0 += 1;
+            } else {
+                counts.1 += 1;
+            }
+        }
+    }
+    let share = |count: usize, total: usize| count as f64 / total.max(1) as f64;
+    let mut changes: Vec<TemplateChange> = counts
+        .into_iter()
+        .filter(|(_, (b, a, _))| (*b).max(*a) >= min_count.max(1))
+        .filter_map(|((level, template), (b, a, example))| {
+            let ratio = share(a, after.len()) / share(b, before.len());
+            let change = match (b, a) {
+                (0, _) => Change::Appeared,
+                (_, 0) => Change::Disappeared,
+                _ if ratio >= factor || ratio <= 1.0 / factor => Change::Changed,
+                _ => return None,
+            };
+            Some(TemplateChange {
+                change,
+                level,
+                template,
+                before: b,
+                after: a,
+                factor: ratio,
+                example,
+            })
+        })
+        .collect();
+    // Most severe first, then by how much they moved.
+    changes.sort_by(|x, y| {
+        severity_rank(&y.level)
+            .cmp(&severity_rank(&x.level))
+            .then_with(|| (y.before.abs_diff(y.after)).cmp(&x.before.abs_diff(x.after)))
+            .then_with(|| x.template.cmp(&y.template))
+    });
+    changes
+}
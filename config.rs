@@ -0,0 +1,157 @@
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::cli::OutputFormat;
+use crate::color::ColorChoice;
+use crate::crypt::Encryption;
+use crate::extract::Extraction;
+use crate::fetch::HttpAuth;
+use crate::geoip::GeoIp;
+use crate::levels::Levels;
+use crate::metrics::SpanSpec;
+use crate::pipeline::Stage;
+use crate::provenance::Provenance;
+use crate::redact::Redaction;
+use crate::retention::RetentionTable;
+use crate::rotate::Rotation;
+use crate::smtp::Smtp;
+use crate::InputFormat;
+
+/// Defaults from `~/.config/logger/config.toml`. Every key is optional and
+/// command-line flags win over the file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Used when a command is given no file and stdin is a terminal.
+    pub log_file: Option<String>,
+    pub format: Option<InputFormat>,
+    /// Append lines that fail to parse here, as `file:line: text`.
+    pub quarantine_file: Option<String>,
+    /// Encoding of input files, e.g. "utf-16le", "latin-1", "shift_jis",
+    /// like --encoding. Detected when unset.
+    pub encoding: Option<String>,
+    /// Layout for `format = "log4j"`, e.g. "%d [%t] %-5p %c - %m%n".
+    pub log4j_pattern: Option<String>,
+    /// strftime format for new entries, also tried first when parsing.
+    pub timestamp_format: Option<String>,
+    /// strftime format printed timestamps are shown in, like --time-format.
+    pub display_format: Option<String>,
+    /// Timezone printed timestamps are shown in, like --tz.
+    pub display_timezone: Option<String>,
+    /// Timezone stored timestamps without an offset are in, for
+    /// converting them: "local" (the default), "utc" or a name.
+    pub log_timezone: Option<String>,
+    /// Level aliases, e.g. `sev1 = "CRITICAL"`, and custom levels with
+    /// their severity, as a `[levels]` table.
+    pub levels: Option<Levels>,
+    pub color: Option<ColorChoice>,
+    pub output: Option<OutputFormat>,
+    pub pager: Option<bool>,
+    /// Show where printed entries came from, like --show-source.
+    pub show_source: Option<bool>,
+    /// Write log files in the binary format, like --binary.
+    pub binary: Option<bool>,
+    /// Print new entries to stdout as JSON lines, like --emit.
+    pub emit: Option<bool>,
+    /// How entries name their request for `logger requests`: a field such
+    /// as ".request_id", or "/regex/" with the ID as its first capture.
+    pub correlation_id: Option<String>,
+    /// Lowest level `search` shows when no --level is given, e.g. "WARNING".
+    pub min_level: Option<String>,
+    /// Rotation of the log file when entries are added to it.
+    pub rotation: Option<Rotation>,
+    /// Link every written entry to the one before it with a `prev_hash`
+    /// field, so `logger verify` can detect tampering.
+    pub hash_chain: Option<bool>,
+    /// Encrypt the log file (and its journal) at rest.
+    pub encryption: Option<Encryption>,
+    /// Patterns replaced with placeholders on ingest and/or export.
+    pub redaction: Option<Redaction>,
+    /// Keep the original line ("raw") or its SHA-256 ("hash") and the
+    /// applied transforms on entries that ingest or export changed.
+    pub provenance: Option<Provenance>,
+    /// Named start/end patterns for `logger durations --span`.
+    pub spans: Option<Vec<SpanSpec>>,
+    /// Fields pulled out of messages as entries are read.
+    pub extract: Option<Vec<Extraction>>,
+    /// Stages new entries go through after parsing, in order: filters
+    /// that drop entries, and transforms such as set, rename and levels.
+    pub pipeline: Option<Vec<Stage>>,
+    /// Country and city fields from a MaxMind database for the addresses
+    /// in entries.
+    pub geoip: Option<GeoIp>,
+    /// WASM modules with parsers for formats of their own, see `plugin`.
+    pub plugins: Option<Vec<String>>,
+    /// Headers for http(s):// inputs, per URL prefix.
+    pub http: Option<Vec<HttpAuth>>,
+    /// Mail server for scheduled reports, and for alert rules whose rules
+    /// file has no `[smtp]` of its own.
+    pub smtp: Option<Smtp>,
+    /// Where this config was read from (or would be, if it is missing).
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    /// Maximum age per level, e.g. `ERROR = "1y"`, plus `default`, an entry
+    /// limit, archiving and automatic enforcement.
+    pub retention: Option<RetentionTable>,
+}
+
+/// `$XDG_CONFIG_HOME/logger/config.toml`, else `~/.config/logger/config.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("logger").join("config.toml"))
+}
+
+impl Config {
+    /// The file to write to or browse: `file`, else the configured log
+    /// file, else logs.txt.
+    pub fn log_file(&self, file: Option<String>) -> String {
+        file.or_else(|| self.log_file.clone())
+            .unwrap_or_else(|| "logs.txt".to_string())
+    }
+
+    /// The file to read: `file`, else stdin when it is piped, else the
+    /// configured log file.
+    pub fn input_file(&self, file: Option<String>) -> String {
+        match file {
+            Some(file) => file,
+            None if !io::stdin().is_terminal() => "-".to_string(),
+            None => self.log_file(None),
+        }
+    }
+
+    /// Reads `path`, or the default location when `None`. Only an
+    /// explicitly given file has to exist.
+    pub fn load(path: Option<&str>) -> io::Result<Config> {
+        let (path, required) = match path {
+            Some(path) => (PathBuf::from(path), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Config {
+                    path: Some(path),
+                    ..Config::default()
+                })
+            }
+            Err(e) => return Err(e),
+        };
+        let mut config: Config = toml::from_str(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })?;
+        config.path = Some(path);
+        Ok(config)
+    }
+}
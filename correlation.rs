@@ -0,0 +1,128 @@
+//! Correlation IDs: finding the request an entry belongs to, and grouping
+//! a log into requests.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+use crate::alert::severity_rank;
+use crate::query::{self, Field};
+use crate::LogEntry;
+
+/// Fields tried, in order, when no ID is configured.
+const DEFAULT_FIELDS: [&str; 6] = [
+    "request_id",
+    "correlation_id",
+    "trace_id",
+    "req_id",
+    "requestId",
+    "correlationId",
+];
+
+/// Matches `request_id=abc`, `req-id: abc`, `correlation id abc` and the
+/// like in messages when no field holds the ID.
+const DEFAULT_PATTERN: &str =
+    r"(?i)\b(?:request|req|correlation|trace)[_-]?id\b[=:\s]\s*(?P<id>[\w.:-]+)";
+
+/// How the ID is found: `.request_id` reads a field, `/regex/` the `id`
+/// group or first capture of a match in the message.
+pub enum Extractor {
+    Field(Field),
+    Pattern(Regex),
+    /// The usual ID fields, then the usual message forms.
+    Default(Regex),
+}
+
+impl Extractor {
+    pub fn parse(spec: Option<&str>) -> Result<Extractor, String> {
+        let Some(spec) = spec else {
+            let regex = Regex::new(DEFAULT_PATTERN).expect("valid pattern");
+            return Ok(Extractor::Default(regex));
+        };
+        if let Some(pattern) = spec.strip_prefix('/').and_then(|s| s.strip_suffix('/')) {
+            let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+            if regex.captures_len() < 2 {
+                return Err(format!("/{}/ needs a capture group for the ID", pattern));
+            }
+            return Ok(Extractor::Pattern(regex));
+        }
+        query::parse_field(spec)
+            .map(Extractor::Field)
+            .ok_or_else(|| {
+                format!(
+                    "expected a field such as .request_id or /regex/, got '{}'",
+                    spec
+                )
+            })
+    }
+
+    pub fn id(&self, entry: &LogEntry) -> Option<String> {
+        let capture = |regex: &Regex| {
+            let captures = regex.captures(&entry.message)?;
+            captures
+                .name("id")
+                .or_else(|| captures.get(1))
+                .map(|m| m.as_str().to_string())
+        };
+        match self {
+            Extractor::Field(field) => field.lookup(entry).map(|v| v.into_owned()),
+            Extractor::Pattern(regex) => capture(regex),
+            Extractor::Default(regex) => DEFAULT_FIELDS
+                .iter()
+                .find_map(|name| entry.fields.get(*name).map(query::render))
+                .or_else(|| capture(regex)),
+        }
+        .filter(|id| !id.is_empty())
+    }
+}
+
+/// The entries of one request, in time order.
+pub struct Request<'a> {
+    pub id: String,
+    pub entries: Vec<&'a LogEntry>,
+}
+
+impl Request<'_> {
+    pub fn first(&self) -> Option<NaiveDateTime> {
+        self.entries.iter().find_map(|e| e.time())
+    }
+
+    pub fn last(&self) -> Option<NaiveDateTime> {
+        self.entries.iter().rev().find_map(|e| e.time())
+    }
+
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        Some(self.last()? - self.first()?)
+    }
+
+    /// The most severe level among the entries.
+    pub fn level(&self) -> &str {
+        self.entries
+            .iter()
+            .map(|e| e.level.as_str())
+            .max_by_key(|level| severity_rank(level))
+            .unwrap_or_default()
+    }
+}
+
+/// Groups entries by ID, ordered by each request's first entry. `entries`
+/// should already be in time order, as `analysis::merge` leaves them.
+pub fn requests<'a>(entries: &'a [LogEntry], extractor: &Extractor) -> Vec<Request<'a>> {
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut requests: Vec<Request> = Vec::new();
+    for entry in entries {
+        let Some(id) = extractor.id(entry) else {
+            continue;
+        };
+        let slot = *index.entry(id.clone()).or_insert_with(|| {
+            requests.push(Request {
+                id,
+                entries: Vec::new(),
+            });
+            requests.len() - 1
+        });
+        requests[slot].entries.push(entry);
+    }
+    requests
+}
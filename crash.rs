@@ -0,0 +1,47 @@
+use std::backtrace::Backtrace;
+use std::panic::{self, PanicHookInfo};
+
+use serde_json::{Map, Value};
+
+use crate::LogEntry;
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    let text = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string());
+    match info.location() {
+        Some(location) => format!("panic: {} at {}:{}", text, location.file(), location.line()),
+        None => format!("panic: {}", text),
+    }
+}
+
+/// Installs a panic hook that appends a FATAL entry to `filename` (the
+/// message and location, with the thread and backtrace as fields) before
+/// handing over to the previous hook, so crashes land in the same log.
+pub fn install_panic_hook(filename: &str) {
+    let filename = filename.to_string();
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let mut fields = Map::new();
+        if let Some(name) = std::thread::current().name() {
+            fields.insert("thread".to_string(), Value::from(name));
+        }
+        fields.insert(
+            "backtrace".to_string(),
+            Value::from(Backtrace::force_capture().to_string()),
+        );
+        let entry = LogEntry {
+            timestamp: crate::now_timestamp(),
+            level: "FATAL".to_string(),
+            message: panic_message(info),
+            fields,
+            source: None,
+        };
+        // Nothing sensible is left to do if the log itself can't be written.
+        let _ = crate::LogAnalyzer::append_to_file(&filename, std::slice::from_ref(&entry));
+        previous(info);
+    }));
+}
@@ -0,0 +1,272 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Cursor, Read, Seek, SeekFrom, Write};
+use std::sync::OnceLock;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::Deserialize;
+
+/// Encrypted files start with this, then a 16-byte salt, then records of
+/// a big-endian u32 ciphertext length, a 12-byte nonce and the ciphertext.
+/// Each save or append adds a record, so appending never has to decrypt.
+const MAGIC: &[u8] = b"LOGGER-ENC1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// The `[encryption]` table of the config file. The secret is run through
+/// Argon2id with a per-file salt to get the ChaCha20-Poly1305 key.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Encryption {
+    /// File holding the passphrase or key material.
+    pub key_file: Option<String>,
+    /// Environment variable holding the passphrase.
+    pub passphrase_env: Option<String>,
+}
+
+static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Enables encryption of everything written to log files from now on.
+pub fn init(encryption: Option<&Encryption>) -> Result<(), String> {
+    let Some(encryption) = encryption else {
+        return Ok(());
+    };
+    let secret = match (&encryption.key_file, &encryption.passphrase_env) {
+        (Some(path), None) => {
+            let mut secret =
+                std::fs::read(path).map_err(|e| format!("cannot read key file {}: {}", path, e))?;
+            while secret.last().is_some_and(|b| b.is_ascii_whitespace()) {
+                secret.pop();
+            }
+            secret
+        }
+        (None, Some(var)) => std::env::var(var)
+            .map_err(|_| format!("{} is not set", var))?
+            .into_bytes(),
+        _ => return Err("set exactly one of key_file and passphrase_env".to_string()),
+    };
+    if secret.is_empty() {
+        return Err("the passphrase is empty".to_string());
+    }
+    let _ = SECRET.set(secret);
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    SECRET.get().is_some()
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn cipher(salt: &[u8]) -> io::Result<ChaCha20Poly1305> {
+    let secret = SECRET
+        .get()
+        .ok_or_else(|| invalid("file is encrypted; configure [encryption] to read it"))?;
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|e| invalid(&e.to_string()))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+fn write_record(
+    out: &mut impl Write,
+    cipher: &ChaCha20Poly1305,
+    plaintext: &[u8],
+) -> io::Result<()> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| invalid("encryption failed"))?;
+    out.write_all(&(ciphertext.len() as u32).to_be_bytes())?;
+    out.write_all(&nonce)?;
+    out.write_all(&ciphertext)
+}
+
+/// Writes `plaintext` as a new encrypted file with a fresh salt.
+pub fn write_new(mut file: File, plaintext: &[u8]) -> io::Result<()> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let mut out = io::BufWriter::new(&mut file);
+    out.write_all(MAGIC)?;
+    out.write_all(&salt)?;
+    write_record(&mut out, &cipher(&salt)?, plaintext)?;
+    out.flush()
+}
+
+// Where the last complete record ends. A crash mid-append leaves part of
+// a record behind, which would hide every record appended after it.
+fn records_end(file: &mut File, len: u64) -> io::Result<u64> {
+    let mut end = (MAGIC.len() + SALT_LEN) as u64;
+    let mut length = [0u8; 4];
+    loop {
+        file.seek(SeekFrom::Start(end))?;
+        if file.read_exact(&mut length).is_err() {
+            return Ok(end);
+        }
+        let next = end + 4 + NONCE_LEN as u64 + u64::from(u32::from_be_bytes(length));
+        if next > len {
+            return Ok(end);
+        }
+        end = next;
+    }
+}
+
+/// Appends `plaintext` to the encrypted file `filename` as a new record,
+/// creating the file if it is missing or empty. A record cut off at the
+/// end is dropped first.
+pub fn append(filename: &str, plaintext: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(filename)?;
+    if file.metadata()?.len() == 0 {
+        return write_new(file, plaintext);
+    }
+    let mut header = [0u8; MAGIC.len() + SALT_LEN];
+    file.read_exact(&mut header)
+        .map_err(|_| invalid("not an encrypted log file"))?;
+    if !header.starts_with(MAGIC) {
+        return Err(invalid(&format!(
+            "{} is not encrypted; save it once in the repl to encrypt it",
+            filename
+        )));
+    }
+    let cipher = cipher(&header[MAGIC.len()..])?;
+    let len = file.metadata()?.len();
+    let end = records_end(&mut file, len)?;
+    if end < len {
+        file.set_len(end)?;
+    }
+    let mut out = io::BufWriter::new(file);
+    write_record(&mut out, &cipher, plaintext)?;
+    out.flush()
+}
+
+/// Whether a file starting with `head` is encrypted.
+pub fn encrypted(head: &[u8]) -> bool {
+    head.starts_with(MAGIC)
+}
+
+/// Whether encryption is on but `filename` still holds plaintext, so it
+/// has to be rewritten rather than appended to.
+pub fn needs_rewrite(filename: &str) -> bool {
+    if !enabled() {
+        return false;
+    }
+    let mut head = [0u8; MAGIC.len()];
+    match File::open(filename).and_then(|mut f| f.read_exact(&mut head)) {
+        Ok(()) => &head[..] != MAGIC,
+        // Missing or shorter than the header: plaintext only if not empty.
+        Err(_) => std::fs::metadata(filename).is_ok_and(|m| m.len() > 0),
+    }
+}
+
+/// Decrypts `reader` when it starts with the encrypted-file header and
+/// passes anything else through untouched. A record cut off at the end,
+/// as by a crash mid-append, is left out with a warning.
+pub fn decrypt<'a>(mut reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    if !reader.fill_buf()?.starts_with(MAGIC) {
+        return Ok(Box::new(reader));
+    }
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let (salt, mut records) = data[MAGIC.len()..]
+        .split_at_checked(SALT_LEN)
+        .ok_or_else(|| invalid("truncated encrypted file"))?;
+    let cipher = cipher(salt)?;
+    let mut plaintext = Vec::new();
+    while !records.is_empty() {
+        let record = records.split_at_checked(4).and_then(|(length, rest)| {
+            let length = u32::from_be_bytes(length.try_into().unwrap()) as usize;
+            let (nonce, rest) = rest.split_at_checked(NONCE_LEN)?;
+            let (ciphertext, rest) = rest.split_at_checked(length)?;
+            Some((nonce, ciphertext, rest))
+        });
+        let Some((nonce, ciphertext, rest)) = record else {
+            eprintln!("Left out a record cut off at the end of an encrypted log");
+            break;
+        };
+        plaintext.extend(
+            cipher
+                .decrypt(Nonce::from_slice(nonce), ciphertext)
+                .map_err(|_| invalid("wrong key, or the file was modified"))?,
+        );
+        records = rest;
+    }
+    Ok(Box::new(Cursor::new(plaintext)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("logger-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn init_key() {
+        let key_file = path("key");
+        std::fs::write(&key_file, "correct horse battery staple\n").unwrap();
+        init(Some(&Encryption {
+            key_file: Some(key_file),
+            passphrase_env: None,
+        }))
+        .unwrap();
+    }
+
+    fn read(filename: &str) -> String {
+        let file = io::BufReader::new(File::open(filename).unwrap());
+        let mut text = String::new();
+        decrypt(file).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn round_trip() {
+        init_key();
+        let filename = path("round-trip.log");
+        write_new(File::create(&filename).unwrap(), b"a\n").unwrap();
+        append(&filename, b"b\n").unwrap();
+        assert!(encrypted(&std::fs::read(&filename).unwrap()));
+        assert_eq!(read(&filename), "a\nb\n");
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn torn_record_is_dropped() {
+        init_key();
+        let filename = path("torn.log");
+        write_new(File::create(&filename).unwrap(), b"a\n").unwrap();
+        append(&filename, b"b\n").unwrap();
+        let len = std::fs::metadata(&filename).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&filename)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+        assert_eq!(read(&filename), "a\n");
+        append(&filename, b"c\n").unwrap();
+        assert_eq!(read(&filename), "a\nc\n");
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn plaintext_passes_through() {
+        let mut text = String::new();
+        decrypt(&b"a\n"[..])
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "a\n");
+    }
+}
@@ -0,0 +1,147 @@
+//! `RUST_LOG`-style filters such as `myapp=debug,hyper=warn`: a bare level
+//! sets the default, `target=level` the level for targets starting with
+//! `target`, the longest match winning. Without a default only errors pass,
+//! as with env_logger.
+
+use log::{Level, LevelFilter};
+use serde_json::Value;
+
+use crate::LogEntry;
+
+#[derive(Debug, Clone)]
+pub struct Directives {
+    default: LevelFilter,
+    /// Longest target first.
+    targets: Vec<(String, LevelFilter)>,
+}
+
+fn parse_level(text: &str) -> Result<LevelFilter, String> {
+    text.trim().parse().map_err(|_| {
+        format!(
+            "unknown level '{}' (off, error, warn, info, debug, trace)",
+            text
+        )
+    })
+}
+
+/// The `log` level of an entry level name; names this tool does not know
+/// count as info.
+pub fn entry_level(level: &str) -> Level {
+    match level.to_uppercase().as_str() {
+        "FATAL" | "CRITICAL" | "ERROR" => Level::Error,
+        "WARNING" | "WARN" => Level::Warn,
+        "DEBUG" => Level::Debug,
+        "TRACE" => Level::Trace,
+        _ => Level::Info,
+    }
+}
+
+impl Directives {
+    pub fn parse(spec: &str) -> Result<Directives, String> {
+        let mut directives = Directives {
+            default: LevelFilter::Error,
+            targets: Vec::new(),
+        };
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => directives
+                    .targets
+                    .push((target.trim().to_string(), parse_level(level)?)),
+                // A bare name is a level if it parses as one, else a target
+                // with everything enabled.
+                None => match parse_level(directive) {
+                    Ok(level) => directives.default = level,
+                    Err(_) => directives
+                        .targets
+                        .push((directive.to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+        directives
+            .targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Ok(directives)
+    }
+
+    /// The level enabled for `target`; `None` is an entry without one.
+    pub fn level_for(&self, target: Option<&str>) -> LevelFilter {
+        target
+            .and_then(|target| {
+                self.targets
+                    .iter()
+                    .find(|(prefix, _)| target.starts_with(prefix.as_str()))
+            })
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    pub fn allows(&self, target: Option<&str>, level: Level) -> bool {
+        level <= self.level_for(target)
+    }
+
+    /// The most verbose level any directive enables.
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Ord::max)
+    }
+
+    /// Whether a stored entry passes, taking its target from a `target`
+    /// field (as this tool's logger writes it), else `source` or `logger`.
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        let target = ["target", "source", "logger"]
+            .iter()
+            .find_map(|key| entry.fields.get(*key).and_then(Value::as_str));
+        self.allows(target, entry_level(&entry.level))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_target_wins() {
+        let directives = Directives::parse("info, app=debug, app::db=warn").unwrap();
+        assert_eq!(
+            directives.level_for(Some("app::db::pool")),
+            LevelFilter::Warn
+        );
+        assert_eq!(directives.level_for(Some("app::http")), LevelFilter::Debug);
+        assert_eq!(directives.level_for(Some("hyper")), LevelFilter::Info);
+        assert_eq!(directives.level_for(None), LevelFilter::Info);
+        assert_eq!(directives.max_level(), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn defaults_to_errors() {
+        let directives = Directives::parse("").unwrap();
+        assert!(directives.allows(None, Level::Error));
+        assert!(!directives.allows(None, Level::Warn));
+    }
+
+    #[test]
+    fn bare_target_enables_everything() {
+        let directives = Directives::parse("myapp").unwrap();
+        assert!(directives.allows(Some("myapp::x"), Level::Trace));
+        assert!(!directives.allows(Some("other"), Level::Info));
+    }
+
+    #[test]
+    fn bad_level() {
+        assert!(Directives::parse("app=loud").is_err());
+    }
+
+    #[test]
+    fn entries_by_target_field() {
+        let directives = Directives::parse("warn,app=debug").unwrap();
+        let mut entry = LogEntry::from_pipe("2026-01-01 00:00:00|DEBUG|x").unwrap();
+        assert!(!directives.matches(&entry));
+        entry
+            .fields
+            .insert("target".to_string(), Value::from("app::db"));
+        assert!(directives.matches(&entry));
+        entry.level = "TRACE".to_string();
+        assert!(!directives.matches(&entry));
+    }
+}
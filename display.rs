@@ -0,0 +1,103 @@
+//! How timestamps are shown, apart from how they are stored: another
+//! strftime format, and another timezone, so logs from UTC servers can be
+//! read in local time. Only printed entries change; files, exports and
+//! JSON output keep the stored text.
+
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+
+use crate::LogEntry;
+
+/// `local`, `utc` or an IANA name such as `Europe/Berlin`.
+#[derive(Debug, Clone, Copy)]
+pub enum Zone {
+    Local,
+    Utc,
+    Named(chrono_tz::Tz),
+}
+
+impl Zone {
+    pub fn parse(name: &str) -> Result<Zone, String> {
+        match name.to_lowercase().as_str() {
+            "local" => Ok(Zone::Local),
+            "utc" | "z" => Ok(Zone::Utc),
+            _ => name
+                .parse()
+                .map(Zone::Named)
+                .map_err(|_| format!("unknown timezone '{}'", name)),
+        }
+    }
+
+    // `time` read as a time in this zone; the earlier one when it occurs
+    // twice, as clocks go back.
+    fn locate(self, time: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Zone::Local => Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|t| t.fixed_offset()),
+            Zone::Utc => Some(Utc.from_utc_datetime(&time).fixed_offset()),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|t| t.fixed_offset()),
+        }
+    }
+
+    // `time` in this zone, formatted; `None` for a format chrono can't
+    // render, such as a bad `%` sequence.
+    fn render(self, time: DateTime<FixedOffset>, format: &str) -> Option<String> {
+        use std::fmt::Write;
+
+        let mut text = String::new();
+        let written = match self {
+            Zone::Local => write!(text, "{}", time.with_timezone(&Local).format(format)),
+            Zone::Utc => write!(text, "{}", time.with_timezone(&Utc).format(format)),
+            Zone::Named(tz) => write!(text, "{}", time.with_timezone(&tz).format(format)),
+        };
+        written.ok().map(|()| text)
+    }
+}
+
+struct Display {
+    format: Option<String>,
+    /// The zone to show times in.
+    zone: Option<Zone>,
+    /// The zone timestamps without an offset were written in.
+    stored: Zone,
+}
+
+static DISPLAY: OnceLock<Display> = OnceLock::new();
+
+/// Takes the display format and zones of the flags and config; a format
+/// that can't be rendered shows timestamps as stored.
+pub fn init(format: Option<&str>, zone: Option<&str>, stored: Option<&str>) -> Result<(), String> {
+    let display = Display {
+        format: format.map(String::from),
+        zone: zone.map(Zone::parse).transpose()?,
+        stored: stored.map(Zone::parse).transpose()?.unwrap_or(Zone::Local),
+    };
+    let _ = DISPLAY.set(display);
+    Ok(())
+}
+
+/// The timestamp of `entry` as it should be printed: the stored text
+/// unless a display format or zone is set and the timestamp parses.
+pub fn timestamp(entry: &LogEntry) -> Cow<'_, str> {
+    let Some(display) = DISPLAY.get() else {
+        return Cow::Borrowed(&entry.timestamp);
+    };
+    if display.format.is_none() && display.zone.is_none() {
+        return Cow::Borrowed(&entry.timestamp);
+    }
+    let time = DateTime::parse_from_rfc3339(&entry.timestamp)
+        .ok()
+        .or_else(|| display.stored.locate(entry.time()?));
+    let format = display.format.as_deref().unwrap_or(crate::TIMESTAMP_FORMAT);
+    match time.and_then(|time| display.zone.unwrap_or(display.stored).render(time, format)) {
+        Some(text) => Cow::Owned(text),
+        None => Cow::Borrowed(&entry.timestamp),
+    }
+}
@@ -0,0 +1,158 @@
+//! Elasticsearch's `_bulk` API.
+
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Local, TimeZone};
+use serde_json::{json, Value};
+
+use crate::export::Compression;
+use crate::sink::{self, Options, Sink};
+use crate::LogEntry;
+
+/// Index name used when no --index is given: one index per day.
+pub const DEFAULT_INDEX: &str = "logs-%Y.%m.%d";
+
+/// Indexes entries through `_bulk`, retrying throttled or failed requests.
+pub struct Indexer {
+    url: String,
+    headers: Vec<(String, String)>,
+    index: String,
+    retries: u32,
+    compression: Compression,
+}
+
+// The entry as a document, with an RFC 3339 `@timestamp` Elasticsearch
+// recognizes as a date.
+fn document(entry: &LogEntry) -> (Value, Option<chrono::DateTime<Local>>) {
+    let time = entry
+        .time()
+        .and_then(|t| Local.from_local_datetime(&t).earliest());
+    let mut document = crate::export::entry_json(entry);
+    if let (Some(time), Value::Object(object)) = (time, &mut document) {
+        object.insert("@timestamp".to_string(), Value::from(time.to_rfc3339()));
+    }
+    (document, time)
+}
+
+enum Outcome {
+    Done,
+    /// Worth sending again: throttling, server errors, connection errors.
+    /// `Some` lists the only documents to resend, the rest were indexed.
+    Retry(String, Option<Vec<usize>>),
+    Failed(String),
+}
+
+impl Indexer {
+    /// `options.to` is the cluster URL, e.g. http://localhost:9200.
+    /// `options.index` may hold strftime fields, filled from each entry's
+    /// time.
+    pub fn new(options: &Options) -> Indexer {
+        Indexer {
+            url: format!("{}/_bulk", options.to.trim_end_matches('/')),
+            headers: options.headers.clone(),
+            index: options.index.clone(),
+            retries: options.retries,
+            compression: options.compression,
+        }
+    }
+
+    fn index_name(&self, time: Option<chrono::DateTime<Local>>) -> String {
+        time.unwrap_or_else(Local::now)
+            .format(&self.index)
+            .to_string()
+    }
+
+    fn post(&self, body: &str) -> Outcome {
+        let mut request = ureq::post(&self.url).set("Content-Type", "application/x-ndjson");
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        let (request, body) = match sink::encode_body(request, body, self.compression) {
+            Ok(encoded) => encoded,
+            Err(e) => return Outcome::Failed(e.to_string()),
+        };
+        let response = match request.send_bytes(&body) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(code, response)) => {
+                let text = response.into_string().unwrap_or_default();
+                let message = format!("HTTP {}: {}", code, text.trim());
+                return if code == 429 || code >= 500 {
+                    Outcome::Retry(message, None)
+                } else {
+                    Outcome::Failed(message)
+                };
+            }
+            Err(e) => return Outcome::Retry(e.to_string(), None),
+        };
+        let result: Value = match response
+            .into_string()
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+        {
+            Ok(result) => result,
+            Err(e) => return Outcome::Failed(format!("unreadable response: {}", e)),
+        };
+        if result["errors"] != Value::Bool(true) {
+            return Outcome::Done;
+        }
+        // Throttled documents can be sent again; anything else, such as a
+        // mapping conflict, would fail again.
+        let items = result["items"].as_array().cloned().unwrap_or_default();
+        let failed: Vec<(usize, &Value)> = items
+            .iter()
+            .map(|item| &item["index"])
+            .enumerate()
+            .filter(|(_, item)| item["error"].is_object())
+            .collect();
+        let reason = failed
+            .first()
+            .map(|(_, item)| item["error"]["reason"].to_string())
+            .unwrap_or_default();
+        let message = format!(
+            "{} of {} entries rejected: {}",
+            failed.len(),
+            items.len(),
+            reason
+        );
+        if failed.iter().all(|(_, item)| item["status"] == 429) {
+            Outcome::Retry(message, Some(failed.iter().map(|&(i, _)| i).collect()))
+        } else {
+            Outcome::Failed(message)
+        }
+    }
+}
+
+impl Sink for Indexer {
+    /// Retries up to `retries` times, waiting 1s, 2s, 4s... in between.
+    fn send(&mut self, entries: &[LogEntry]) -> io::Result<()> {
+        let mut pending: Vec<String> = entries
+            .iter()
+            .map(|entry| {
+                let (document, time) = document(entry);
+                let action = json!({ "index": { "_index": self.index_name(time) } });
+                format!("{}\n{}\n", action, document)
+            })
+            .collect();
+        let mut backoff = Duration::from_secs(1);
+        let mut attempt = 0;
+        loop {
+            match self.post(&pending.concat()) {
+                Outcome::Done => return Ok(()),
+                Outcome::Retry(message, only) if attempt < self.retries => {
+                    eprintln!("{}; retrying in {}s", message, backoff.as_secs());
+                    if let Some(only) = only {
+                        pending = only.into_iter().map(|i| pending[i].clone()).collect();
+                    }
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Outcome::Retry(message, _) | Outcome::Failed(message) => {
+                    return Err(io::Error::other(message))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::OnceLock;
+
+use encoding_rs::{Decoder, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// Written at the start of rewritten files while converting.
+pub const BOM: &str = "\u{feff}";
+
+/// Unset means detect: UTF-16 by its byte order mark or NUL pattern, and
+/// per line UTF-8 where it is valid, else Latin-1.
+static INPUT_ENCODING: OnceLock<&'static Encoding> = OnceLock::new();
+
+/// Sets the encoding of input files from a label such as "utf-16le",
+/// "latin-1" or "shift_jis"; "auto" (the default) detects it.
+pub fn init(label: Option<&str>) -> Result<(), String> {
+    if let Some(label) = label.filter(|l| !l.trim().eq_ignore_ascii_case("auto")) {
+        // WHATWG labels; also accept the common "latin-1" spelling.
+        let encoding = Encoding::for_label(label.trim().as_bytes())
+            .or_else(|| Encoding::for_label(label.trim().replace('-', "").as_bytes()))
+            .ok_or_else(|| format!("unknown encoding '{}'", label))?;
+        let _ = INPUT_ENCODING.set(encoding);
+    }
+    Ok(())
+}
+
+// Decodes as the input is read, so large files are not held in memory.
+struct Decoding<R> {
+    inner: R,
+    decoder: Decoder,
+    output: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl<R: Read> Read for Decoding<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.output.len() && !self.done {
+            let mut chunk = [0; 8192];
+            let read = self.inner.read(&mut chunk)?;
+            self.done = read == 0;
+            let capacity = self
+                .decoder
+                .max_utf8_buffer_length(read)
+                .ok_or_else(|| io::Error::other("input too large to decode"))?;
+            self.output.resize(capacity, 0);
+            let (_, _, written, _) =
+                self.decoder
+                    .decode_to_utf8(&chunk[..read], &mut self.output, self.done);
+            self.output.truncate(written);
+            self.position = 0;
+        }
+        let count = buf.len().min(self.output.len() - self.position);
+        buf[..count].copy_from_slice(&self.output[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+// UTF-16 without a byte order mark: ASCII text has a NUL in every other
+// byte.
+fn sniff_utf16(start: &[u8]) -> Option<&'static Encoding> {
+    if let Some((encoding, _)) = Encoding::for_bom(start) {
+        return (encoding != UTF_8).then_some(encoding);
+    }
+    let pairs = start.len() / 2;
+    if pairs < 8 {
+        return None;
+    }
+    let nuls = |offset: usize| (0..pairs).filter(|i| start[i * 2 + offset] == 0).count();
+    if nuls(1) * 10 >= pairs * 8 && nuls(0) == 0 {
+        Some(UTF_16LE)
+    } else if nuls(0) * 10 >= pairs * 8 && nuls(1) == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+fn decoding<'a>(reader: impl BufRead + 'a, encoding: &'static Encoding) -> Box<dyn BufRead + 'a> {
+    Box::new(BufReader::new(Decoding {
+        inner: reader,
+        decoder: encoding.new_decoder(),
+        output: Vec::new(),
+        position: 0,
+        done: false,
+    }))
+}
+
+/// Wraps `reader` so it yields UTF-8 when another input encoding is
+/// configured or UTF-16 is detected. A byte order mark wins over the
+/// configured encoding, so UTF-16 files with a BOM read correctly either
+/// way.
+pub fn decode<'a>(mut reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    Ok(match INPUT_ENCODING.get() {
+        Some(&encoding) if encoding != UTF_8 => decoding(reader, encoding),
+        Some(_) => Box::new(reader),
+        None => match sniff_utf16(reader.fill_buf()?) {
+            Some(encoding) => decoding(reader, encoding),
+            None => Box::new(reader),
+        },
+    })
+}
+
+/// A line of decoded input as text. Invalid UTF-8 is read as Latin-1 when
+/// detecting and replaced with U+FFFD otherwise, so the line is never lost.
+pub fn decode_line(bytes: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(_) if INPUT_ENCODING.get().is_none() => {
+            WINDOWS_1252.decode_without_bom_handling(bytes).0
+        }
+        Err(_) => String::from_utf8_lossy(bytes),
+    }
+}
+
+/// Whether files are read as something other than UTF-8.
+pub fn converting() -> bool {
+    INPUT_ENCODING
+        .get()
+        .is_some_and(|&encoding| encoding != UTF_8)
+}
+
+/// Whether `filename` is still in the input encoding, so appending UTF-8
+/// to it would mix encodings. Files this program wrote start with a UTF-8
+/// byte order mark, which decoding honors; when detecting, only UTF-16
+/// files need converting, since each line is detected on its own.
+pub fn needs_rewrite(filename: &str) -> io::Result<bool> {
+    if !std::path::Path::new(filename).exists() {
+        return Ok(false);
+    }
+    let file = std::fs::File::open(filename)?;
+    let mut reader = crate::export::decompress(crate::crypt::decrypt(BufReader::new(file))?)?;
+    let start = reader.fill_buf()?;
+    Ok(if converting() {
+        !start.is_empty() && !start.starts_with(BOM.as_bytes())
+    } else {
+        INPUT_ENCODING.get().is_none() && sniff_utf16(start).is_some()
+    })
+}
@@ -0,0 +1,60 @@
+//! The entry type and the native pipe-format parser. This module only uses
+//! `core` and `alloc` (serde's derives and serde_json's `Map` build with
+//! their `alloc` features), so embedded collectors can include the file
+//! as-is in a `no_std` crate and parse exactly like the analyzer does.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Serializes with any serde format as `{timestamp, level, message,
+/// fields}`; `source` is not serialized. The timestamp stays the text it
+/// was logged with, since not every input's parses; `time()` reads it as
+/// a chrono time. The stored JSON lines, with the fields at the top
+/// level, are a separate format (`export::entry_json`, `from_json`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: String,
+    pub message: String,
+    /// Structured fields from JSON input; empty for pipe-format lines.
+    /// Reading them back needs a self-describing format such as JSON.
+    #[serde(default)]
+    pub fields: Map<String, Value>,
+    /// Where the entry was read from, set by the loader. It is not stored
+    /// with the entry, so it always describes the input at hand.
+    #[serde(skip)]
+    pub source: Option<Source>,
+}
+
+/// Where an entry came from: the input and line it was read from, and the
+/// host and process that logged it when the input says so.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Source {
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub host: Option<String>,
+    pub pid: Option<u64>,
+}
+
+impl LogEntry {
+    /// Parses `timestamp|level|message`. The message may itself contain `|`.
+    pub fn from_pipe(line: &str) -> Option<LogEntry> {
+        let mut parts = line.splitn(3, '|');
+        let (timestamp, level, message) = (parts.next()?, parts.next()?, parts.next()?);
+        Some(LogEntry {
+            timestamp: timestamp.trim().to_string(),
+            level: level.trim().to_string(),
+            message: message.trim().to_string(),
+            fields: Map::new(),
+            source: None,
+        })
+    }
+
+    /// The pipe-format line for this entry, ignoring structured fields.
+    pub fn to_pipe(&self) -> String {
+        format!("{}|{}|{}", self.timestamp, self.level, self.message)
+    }
+}
@@ -0,0 +1,58 @@
+use std::io;
+
+use thiserror::Error;
+
+/// Why loading, parsing or querying logs failed, with the file involved.
+#[derive(Debug, Error)]
+pub enum LoggerError {
+    /// Reading or writing `path` failed.
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    /// A document that has to parse as a whole (a baseline, an export) did
+    /// not. Log lines that fail to parse are skipped instead.
+    #[error("{file}:{line}: {message}")]
+    Parse {
+        file: String,
+        line: usize,
+        message: String,
+    },
+    /// `path` could be read but is not something this program reads: a
+    /// wrong passphrase, a corrupt archive, an unknown encryption header.
+    #[error("{path}: {message}")]
+    Format { path: String, message: String },
+    /// A query or WHERE expression that does not parse.
+    #[error("invalid query: {0}")]
+    Query(String),
+}
+
+impl LoggerError {
+    /// An I/O failure on `path`. Invalid data is reported as a format error,
+    /// which is what decryption and decompression fail with.
+    pub fn io(path: &str, source: io::Error) -> LoggerError {
+        let path = if path == "-" { "stdin" } else { path }.to_string();
+        if source.kind() == io::ErrorKind::InvalidData {
+            LoggerError::Format {
+                path,
+                message: source.to_string(),
+            }
+        } else {
+            LoggerError::Io { path, source }
+        }
+    }
+}
+
+/// For callers that still return `io::Result`; the message keeps the path.
+impl From<LoggerError> for io::Error {
+    fn from(error: LoggerError) -> io::Error {
+        let kind = match &error {
+            LoggerError::Io { source, .. } => source.kind(),
+            LoggerError::Parse { .. } | LoggerError::Format { .. } => io::ErrorKind::InvalidData,
+            LoggerError::Query(_) => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, error)
+    }
+}
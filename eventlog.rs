@@ -0,0 +1,358 @@
+//! The Windows Event Log, read with the Windows Event Log API (wevtapi).
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::io;
+use std::ptr;
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use serde_json::{Map, Value};
+
+use crate::xml::unescape;
+use crate::LogEntry;
+
+type Handle = isize;
+
+const QUERY_CHANNEL_PATH: u32 = 0x1;
+const QUERY_FORWARD_DIRECTION: u32 = 0x100;
+const RENDER_EVENT_XML: u32 = 1;
+const FORMAT_MESSAGE_EVENT: u32 = 1;
+const INFINITE: u32 = u32::MAX;
+const ERROR_INSUFFICIENT_BUFFER: i32 = 122;
+const ERROR_NO_MORE_ITEMS: i32 = 259;
+
+#[link(name = "wevtapi")]
+extern "system" {
+    fn EvtQuery(session: Handle, path: *const u16, query: *const u16, flags: u32) -> Handle;
+    fn EvtNext(
+        results: Handle,
+        size: u32,
+        events: *mut Handle,
+        timeout: u32,
+        flags: u32,
+        returned: *mut u32,
+    ) -> i32;
+    fn EvtRender(
+        context: Handle,
+        fragment: Handle,
+        flags: u32,
+        size: u32,
+        buffer: *mut c_void,
+        used: *mut u32,
+        properties: *mut u32,
+    ) -> i32;
+    fn EvtOpenPublisherMetadata(
+        session: Handle,
+        publisher: *const u16,
+        log_file: *const u16,
+        locale: u32,
+        flags: u32,
+    ) -> Handle;
+    fn EvtFormatMessage(
+        publisher: Handle,
+        event: Handle,
+        message_id: u32,
+        value_count: u32,
+        values: *const c_void,
+        flags: u32,
+        size: u32,
+        buffer: *mut u16,
+        used: *mut u32,
+    ) -> i32;
+    fn EvtClose(handle: Handle) -> i32;
+}
+
+// Closes the handle when dropped.
+struct Owned(Handle);
+
+impl Drop for Owned {
+    fn drop(&mut self) {
+        if self.0 != 0 {
+            // SAFETY: the handle came from the API and is closed only here.
+            unsafe { EvtClose(self.0) };
+        }
+    }
+}
+
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
+
+fn from_wide(buffer: &[u16]) -> String {
+    let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..end])
+}
+
+pub struct EventLog {
+    /// Channels such as Application or System.
+    pub channels: Vec<String>,
+    /// Only this Windows level and more severe ones: 1 critical, 2 error,
+    /// 3 warning, 4 information, 5 verbose.
+    pub level: Option<u8>,
+    /// Only events from this provider.
+    pub provider: Option<String>,
+    pub since: Option<NaiveDateTime>,
+}
+
+/// The Windows level for an entry level.
+pub fn windows_level(level: &str) -> u8 {
+    match crate::syslog::severity(level) {
+        0..=2 => 1,
+        3 => 2,
+        4 => 3,
+        7 => 5,
+        _ => 4,
+    }
+}
+
+fn level(windows_level: u8) -> &'static str {
+    match windows_level {
+        1 => "CRITICAL",
+        2 => "ERROR",
+        3 => "WARNING",
+        5 => "DEBUG",
+        _ => "INFO",
+    }
+}
+
+// The value of `attribute` on the first `<tag ...>` in `xml`.
+fn attribute(xml: &str, tag: &str, attribute: &str) -> Option<String> {
+    let start = xml.find(&format!("<{} ", tag))?;
+    let element = &xml[start..start + xml[start..].find('>')?];
+    let value = &element[element.find(&format!(" {}=", attribute))? + attribute.len() + 2..];
+    let quote = value.chars().next()?;
+    let value = &value[1..];
+    Some(unescape(&value[..value.find(quote)?]))
+}
+
+// The text of the first `<tag>...</tag>` or `<tag attributes>...</tag>`.
+fn element(xml: &str, tag: &str) -> Option<String> {
+    let start = [format!("<{}>", tag), format!("<{} ", tag)]
+        .iter()
+        .filter_map(|open| xml.find(open.as_str()))
+        .min()?;
+    let open_end = start + xml[start..].find('>')?;
+    if xml[..open_end].ends_with('/') {
+        return Some(String::new());
+    }
+    let body = &xml[open_end + 1..];
+    Some(unescape(&body[..body.find(&format!("</{}>", tag))?]))
+}
+
+// `<Data Name="x">value</Data>` pairs from the event data; unnamed data
+// is numbered.
+fn event_data(xml: &str) -> Vec<(String, String)> {
+    let Some(data) = element(xml, "EventData").or_else(|| element(xml, "UserData")) else {
+        return Vec::new();
+    };
+    data.split("<Data")
+        .skip(1)
+        .enumerate()
+        .filter_map(|(i, item)| {
+            let end = item.find('>')?;
+            let name = attribute(&format!("<Data {}", &item[..=end]), "Data", "Name")
+                .unwrap_or_else(|| format!("data{}", i));
+            let value = item[end + 1..].split("</Data>").next().unwrap_or("");
+            Some((name, unescape(value)))
+        })
+        .collect()
+}
+
+/// The entry for an event rendered as XML. `message` is the provider's
+/// formatted message, if it has one.
+pub fn entry(xml: &str, message: Option<String>) -> LogEntry {
+    let timestamp = attribute(xml, "TimeCreated", "SystemTime")
+        .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+        .map_or_else(crate::now_timestamp, |t| {
+            t.with_timezone(&Local)
+                .format(crate::timestamp_format())
+                .to_string()
+        });
+    let mut fields = Map::new();
+    let mut field = |name: &str, value: Option<String>| {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            fields.insert(name.to_string(), Value::from(value));
+        }
+    };
+    field("provider", attribute(xml, "Provider", "Name"));
+    field("event_id", element(xml, "EventID"));
+    field("channel", element(xml, "Channel"));
+    field("host", element(xml, "Computer"));
+    field("record_id", element(xml, "EventRecordID"));
+    field("pid", attribute(xml, "Execution", "ProcessID"));
+    let data = event_data(xml);
+    let message = message.filter(|m| !m.trim().is_empty()).unwrap_or_else(|| {
+        data.iter()
+            .map(|(_, value)| value.as_str())
+            .collect::<Vec<_>>()
+            .join("; ")
+    });
+    for (name, value) in data {
+        fields.entry(name).or_insert(Value::from(value));
+    }
+    let windows_level = element(xml, "Level")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(4);
+    LogEntry {
+        timestamp,
+        level: level(windows_level).to_string(),
+        message: message.trim_end().to_string(),
+        fields,
+        source: None,
+    }
+}
+
+fn last_error() -> io::Error {
+    io::Error::last_os_error()
+}
+
+fn render(event: Handle) -> io::Result<String> {
+    let (mut used, mut properties) = (0, 0);
+    let mut buffer: Vec<u16> = Vec::new();
+    loop {
+        // SAFETY: the buffer is as large as the size passed, in bytes.
+        let rendered = unsafe {
+            EvtRender(
+                0,
+                event,
+                RENDER_EVENT_XML,
+                (buffer.len() * 2) as u32,
+                buffer.as_mut_ptr().cast(),
+                &mut used,
+                &mut properties,
+            )
+        };
+        if rendered != 0 {
+            return Ok(from_wide(&buffer));
+        }
+        let error = last_error();
+        if error.raw_os_error() != Some(ERROR_INSUFFICIENT_BUFFER) {
+            return Err(error);
+        }
+        buffer = vec![0; (used as usize).div_ceil(2)];
+    }
+}
+
+fn format_message(publisher: Handle, event: Handle) -> Option<String> {
+    let mut used = 0;
+    let mut buffer: Vec<u16> = Vec::new();
+    loop {
+        // SAFETY: the buffer is as large as the size passed, in characters.
+        let formatted = unsafe {
+            EvtFormatMessage(
+                publisher,
+                event,
+                0,
+                0,
+                ptr::null(),
+                FORMAT_MESSAGE_EVENT,
+                buffer.len() as u32,
+                buffer.as_mut_ptr(),
+                &mut used,
+            )
+        };
+        if formatted != 0 {
+            return Some(from_wide(&buffer));
+        }
+        if last_error().raw_os_error() != Some(ERROR_INSUFFICIENT_BUFFER) {
+            return None;
+        }
+        buffer = vec![0; used as usize];
+    }
+}
+
+impl EventLog {
+    // An XPath filter on the System section, or every event.
+    fn query(&self) -> String {
+        let mut conditions = Vec::new();
+        if let Some(level) = self.level {
+            let levels: Vec<String> = (1..=level).map(|l| format!("Level={}", l)).collect();
+            conditions.push(format!("({})", levels.join(" or ")));
+        }
+        if let Some(provider) = &self.provider {
+            conditions.push(format!("Provider[@Name='{}']", provider.replace('\'', "")));
+        }
+        if let Some(since) = self.since {
+            let millis = (Local::now().naive_local() - since)
+                .num_milliseconds()
+                .max(0);
+            conditions.push(format!("TimeCreated[timediff(@SystemTime) <= {}]", millis));
+        }
+        if conditions.is_empty() {
+            "*".to_string()
+        } else {
+            format!("*[System[{}]]", conditions.join(" and "))
+        }
+    }
+
+    /// Calls `each` with the matching events of each channel, oldest first.
+    pub fn read(&self, mut each: impl FnMut(LogEntry) -> io::Result<()>) -> io::Result<()> {
+        let query = wide(&self.query());
+        let mut publishers: HashMap<String, Owned> = HashMap::new();
+        for channel in &self.channels {
+            let path = wide(channel);
+            // SAFETY: both strings are NUL-terminated and outlive the call.
+            let results = Owned(unsafe {
+                EvtQuery(
+                    0,
+                    path.as_ptr(),
+                    query.as_ptr(),
+                    QUERY_CHANNEL_PATH | QUERY_FORWARD_DIRECTION,
+                )
+            });
+            if results.0 == 0 {
+                let error = last_error();
+                return Err(io::Error::new(
+                    error.kind(),
+                    format!("could not query {}: {}", channel, error),
+                ));
+            }
+            loop {
+                let mut events: [Handle; 64] = [0; 64];
+                let mut returned = 0;
+                // SAFETY: `events` has room for the 64 handles asked for.
+                let next = unsafe {
+                    EvtNext(
+                        results.0,
+                        events.len() as u32,
+                        events.as_mut_ptr(),
+                        INFINITE,
+                        0,
+                        &mut returned,
+                    )
+                };
+                if next == 0 {
+                    let error = last_error();
+                    if error.raw_os_error() == Some(ERROR_NO_MORE_ITEMS) {
+                        break;
+                    }
+                    return Err(error);
+                }
+                let events: Vec<Owned> = events[..returned as usize]
+                    .iter()
+                    .map(|&event| Owned(event))
+                    .collect();
+                for event in &events {
+                    let xml = render(event.0)?;
+                    let provider = attribute(&xml, "Provider", "Name").unwrap_or_default();
+                    let publisher = publishers.entry(provider.clone()).or_insert_with(|| {
+                        let name = wide(&provider);
+                        // SAFETY: the name is NUL-terminated and outlives
+                        // the call.
+                        Owned(unsafe {
+                            EvtOpenPublisherMetadata(0, name.as_ptr(), ptr::null(), 0, 0)
+                        })
+                    });
+                    let message = (publisher.0 != 0)
+                        .then(|| format_message(publisher.0, event.0))
+                        .flatten();
+                    let entry = entry(&xml, message);
+                    if let Some(entry) = crate::ingest(entry, Some(&xml), Vec::new()) {
+                        each(entry)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,464 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::LogEntry;
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(entry: &LogEntry) -> String {
+    format!(
+        "{},{},{}",
+        csv_field(&entry.timestamp),
+        csv_field(&entry.level),
+        csv_field(&entry.message)
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_name(name: &str) -> Option<Compression> {
+        match name.to_lowercase().as_str() {
+            "" | "none" => Some(Compression::None),
+            "gzip" | "gz" => Some(Compression::Gzip),
+            "zstd" | "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(filename: &str) -> Compression {
+        if filename.ends_with(".gz") {
+            Compression::Gzip
+        } else if filename.ends_with(".zst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// The HTTP Content-Encoding name, `None` when uncompressed.
+    pub fn encoding(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gzip"),
+            Compression::Zstd => Some("zstd"),
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip => {
+                let mut gzip =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                gzip.write_all(data)?;
+                gzip.finish()
+            }
+            Compression::Zstd => zstd::encode_all(data, 0),
+        }
+    }
+
+    /// Adds the matching extension unless the name already carries it.
+    pub fn apply_to(self, filename: &str) -> String {
+        let extension = match self {
+            Compression::None => return filename.to_string(),
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        };
+        if filename == "-" || filename.ends_with(extension) {
+            filename.to_string()
+        } else {
+            format!("{}{}", filename, extension)
+        }
+    }
+}
+
+enum Sink {
+    Stdout(io::StdoutLock<'static>),
+    File(BufWriter<File>),
+    Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+/// An export destination. Call `finish` when done so compressed streams
+/// get their trailer written and any error is reported.
+pub struct Output(Sink);
+
+impl Output {
+    /// Writes to `file`, compressing with `compression`.
+    pub fn new(file: File, compression: Compression) -> io::Result<Output> {
+        let file = BufWriter::new(file);
+        Ok(Output(match compression {
+            Compression::None => Sink::File(file),
+            Compression::Gzip => Sink::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            )),
+            Compression::Zstd => Sink::Zstd(zstd::Encoder::new(file, 0)?),
+        }))
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self.0 {
+            Sink::Stdout(mut out) => out.flush(),
+            Sink::File(mut out) => out.flush(),
+            Sink::Gzip(out) => out.finish()?.flush(),
+            Sink::Zstd(out) => out.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.0 {
+            Sink::Stdout(out) => out.write(buf),
+            Sink::File(out) => out.write(buf),
+            Sink::Gzip(out) => out.write(buf),
+            Sink::Zstd(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.0 {
+            Sink::Stdout(out) => out.flush(),
+            Sink::File(out) => out.flush(),
+            Sink::Gzip(out) => out.flush(),
+            Sink::Zstd(out) => out.flush(),
+        }
+    }
+}
+
+/// Opens an export destination; `-` means stdout so output can be piped.
+/// Files ending in `.gz` or `.zst` are compressed on the fly.
+pub fn create_output(filename: &str) -> io::Result<Output> {
+    if filename == "-" {
+        return Ok(Output(Sink::Stdout(io::stdout().lock())));
+    }
+    Output::new(File::create(filename)?, Compression::from_path(filename))
+}
+
+const GZIP: &[u8] = &[0x1f, 0x8b];
+const ZSTD: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Whether a file starting with `head` is gzip or zstd compressed.
+pub fn compressed(head: &[u8]) -> bool {
+    head.starts_with(GZIP) || head.starts_with(ZSTD)
+}
+
+/// Wraps `reader` in a decompressor when its first bytes are a gzip or
+/// zstd header, so compressed logs (e.g. rotated app.log.1.gz) read like
+/// plain ones whatever they are named. Concatenated members are read too.
+pub fn decompress<'a>(mut reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    let head = reader.fill_buf()?;
+    if head.starts_with(GZIP) {
+        Ok(Box::new(BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(reader),
+        )))
+    } else if head.starts_with(ZSTD) {
+        Ok(Box::new(BufReader::new(
+            zstd::stream::read::Decoder::with_buffer(reader)?,
+        )))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+pub fn write_csv(entries: &[LogEntry], filename: &str) -> io::Result<()> {
+    let entries = &*crate::redact::for_export(entries);
+    let mut out = create_output(filename)?;
+    writeln!(out, "timestamp,level,message")?;
+    for entry in entries {
+        writeln!(out, "{}", csv_row(entry))?;
+    }
+    out.finish()
+}
+
+/// One value of a bucketed series; a row of the tidy time series CSV.
+pub struct SeriesPoint {
+    pub bucket: chrono::NaiveDateTime,
+    pub series: String,
+    pub value: f64,
+}
+
+pub fn write_timeseries_csv(points: &[SeriesPoint], filename: &str) -> io::Result<()> {
+    let mut out = create_output(filename)?;
+    writeln!(out, "bucket,series,value")?;
+    for point in points {
+        writeln!(
+            out,
+            "{},{},{}",
+            point.bucket.format(crate::TIMESTAMP_FORMAT),
+            csv_field(&point.series),
+            point.value
+        )?;
+    }
+    out.finish()
+}
+
+pub fn entry_json(entry: &LogEntry) -> serde_json::Value {
+    let mut object = entry.fields.clone();
+    object.insert("timestamp".to_string(), entry.timestamp.clone().into());
+    object.insert("level".to_string(), entry.level.clone().into());
+    object.insert("message".to_string(), entry.message.clone().into());
+    serde_json::Value::Object(object)
+}
+
+/// Writes entries as log file lines, which every command reads back.
+pub fn write_native(entries: &[LogEntry], filename: &str) -> io::Result<()> {
+    let entries = &*crate::redact::for_export(entries);
+    let mut out = create_output(filename)?;
+    for entry in entries {
+        writeln!(out, "{}", entry.to_line())?;
+    }
+    out.finish()
+}
+
+/// Writes query results in the format the file name asks for: CSV for
+/// `.csv`, JSON lines for `.json`, `.jsonl` and `.ndjson`, Parquet for
+/// `.parquet`, the binary format for `.lgb`, else log file lines. A `.gz`
+/// or `.zst` suffix compresses, except on Parquet: it is compressed
+/// internally, and readers don't accept an outer layer.
+pub fn write_results(entries: &[LogEntry], filename: &str) -> io::Result<()> {
+    let name = filename
+        .strip_suffix(".gz")
+        .or_else(|| filename.strip_suffix(".zst"))
+        .unwrap_or(filename)
+        .to_lowercase();
+    let extension = name.rsplit_once('.').map_or("", |(_, extension)| extension);
+    match extension {
+        "csv" => write_csv(entries, filename),
+        "json" | "jsonl" | "ndjson" => write_ndjson(entries, filename),
+        #[cfg(feature = "parquet")]
+        "parquet" => write_parquet(entries, filename),
+        #[cfg(not(feature = "parquet"))]
+        "parquet" => Err(io::Error::other("built without the parquet feature")),
+        "lgb" => crate::binary::write_file(entries, filename),
+        _ => write_native(entries, filename),
+    }
+}
+
+/// Writes one JSON object per line (NDJSON / JSON Lines).
+pub fn write_ndjson(entries: &[LogEntry], filename: &str) -> io::Result<()> {
+    let entries = &*crate::redact::for_export(entries);
+    let mut out = create_output(filename)?;
+    for entry in entries {
+        serde_json::to_writer(&mut out, &entry_json(entry))?;
+        writeln!(out)?;
+    }
+    out.finish()
+}
+
+// The line of the input the entry was read from, when the loader knows it.
+fn source_line(entry: &LogEntry) -> Option<usize> {
+    entry.source.as_ref().and_then(|source| source.line)
+}
+
+// GitHub workflow commands only know error/warning/notice.
+fn github_command(level: &str) -> &'static str {
+    match level.to_uppercase().as_str() {
+        "ERROR" | "FATAL" | "CRITICAL" => "error",
+        "WARNING" | "WARN" => "warning",
+        _ => "notice",
+    }
+}
+
+fn github_escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn github_escape_property(value: &str) -> String {
+    github_escape_data(value)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+/// Prints one `::error file=...::message` line per entry for GitHub Actions.
+pub fn print_github_annotations(entries: &[LogEntry], source: &str) {
+    let entries = &*crate::redact::for_export(entries);
+    for entry in entries {
+        let line = source_line(entry).map_or(String::new(), |line| format!(",line={}", line));
+        println!(
+            "::{} file={}{},title={}::{}",
+            github_command(&entry.level),
+            github_escape_property(source),
+            line,
+            github_escape_property(&format!("{} at {}", entry.level, entry.timestamp)),
+            github_escape_data(&entry.message)
+        );
+    }
+}
+
+fn code_quality_severity(level: &str) -> &'static str {
+    match level.to_uppercase().as_str() {
+        "FATAL" | "CRITICAL" => "critical",
+        "ERROR" => "major",
+        "WARNING" | "WARN" => "minor",
+        _ => "info",
+    }
+}
+
+// Stable across builds and Rust versions, so GitLab matches an issue with
+// the same one in earlier reports.
+fn fingerprint(entry: &LogEntry) -> String {
+    let mut hasher = Sha256::new();
+    for part in [&entry.timestamp, &entry.level, &entry.message] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Writes a GitLab Code Quality report (a JSON array of issues).
+pub fn write_code_quality(entries: &[LogEntry], source: &str, filename: &str) -> io::Result<()> {
+    let entries = &*crate::redact::for_export(entries);
+    let issues: Vec<serde_json::Value> = entries
+        .iter()
+        .map(|entry| {
+            serde_json::json!({
+                "description": format!("[{}] {}", entry.timestamp, entry.message),
+                "check_name": format!("log-{}", entry.level.to_lowercase()),
+                "fingerprint": fingerprint(entry),
+                "severity": code_quality_severity(&entry.level),
+                "location": {
+                    "path": source,
+                    // Code Quality requires a line; 1 stands in for none.
+                    "lines": { "begin": source_line(entry).unwrap_or(1) }
+                }
+            })
+        })
+        .collect();
+
+    let mut out = create_output(filename)?;
+    serde_json::to_writer_pretty(&mut out, &issues)?;
+    writeln!(out)?;
+    out.finish()
+}
+
+/// Writes entries as a Parquet file for DuckDB/Spark. Timestamps that do not
+/// parse are stored as nulls; structured fields go into a JSON `fields`
+/// column (null when an entry has none).
+#[cfg(feature = "parquet")]
+pub fn write_parquet(entries: &[LogEntry], filename: &str) -> io::Result<()> {
+    if Compression::from_path(filename) != Compression::None {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Parquet is compressed already; drop the .gz or .zst suffix",
+        ));
+    }
+    let entries = &*crate::redact::for_export(entries);
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, StringArray, TimestampMillisecondArray};
+    use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            true,
+        ),
+        Field::new("level", DataType::Utf8, false),
+        Field::new("message", DataType::Utf8, false),
+        Field::new("fields", DataType::Utf8, true),
+    ]));
+
+    let timestamps: TimestampMillisecondArray = entries
+        .iter()
+        .map(|e| e.time().map(|t| t.and_utc().timestamp_millis()))
+        .collect();
+    let levels: StringArray = entries.iter().map(|e| Some(e.level.as_str())).collect();
+    let messages: StringArray = entries.iter().map(|e| Some(e.message.as_str())).collect();
+    let fields: StringArray = entries
+        .iter()
+        .map(|e| {
+            (!e.fields.is_empty()).then(|| serde_json::Value::Object(e.fields.clone()).to_string())
+        })
+        .collect();
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(timestamps),
+        Arc::new(levels),
+        Arc::new(messages),
+        Arc::new(fields),
+    ];
+    let batch = RecordBatch::try_new(schema.clone(), columns).map_err(io::Error::other)?;
+
+    let file = File::create(filename)?;
+    let mut writer = ArrowWriter::try_new(file, schema, None).map_err(io::Error::other)?;
+    writer.write(&batch).map_err(io::Error::other)?;
+    writer.close().map_err(io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn csv_rows() {
+        let entry = LogEntry::from_pipe("2026-01-01 00:00:00|ERROR|disk full, retrying").unwrap();
+        assert_eq!(
+            csv_row(&entry),
+            "2026-01-01 00:00:00,ERROR,\"disk full, retrying\""
+        );
+    }
+
+    #[test]
+    fn code_quality_lines() {
+        let mut read = LogEntry::from_pipe("2026-01-01 00:00:00|ERROR|disk full").unwrap();
+        read.source = Some(crate::Source {
+            line: Some(42),
+            ..Default::default()
+        });
+        let added = LogEntry::from_pipe("2026-01-01 00:00:01|WARN|slow").unwrap();
+        let path = std::env::temp_dir().join(format!("logger-{}-quality.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        write_code_quality(&[read, added], "app.log", path).unwrap();
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(report[0]["location"]["lines"]["begin"], 42);
+        assert_eq!(report[1]["location"]["lines"]["begin"], 1);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn compressed_parquet_is_refused() {
+        let path =
+            std::env::temp_dir().join(format!("logger-{}-out.parquet.gz", std::process::id()));
+        let error = write_results(&[], path.to_str().unwrap()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
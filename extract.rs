@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::LogEntry;
+
+/// One `[[extract]]` table of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Extraction {
+    /// Regex whose named groups become fields, e.g.
+    /// `user=(?P<user>\w+) took (?P<ms>\d+)ms`.
+    pub pattern: String,
+}
+
+/// A compiled extraction rule.
+pub struct Extractor {
+    regex: Regex,
+}
+
+static EXTRACTORS: OnceLock<Vec<Extractor>> = OnceLock::new();
+
+impl Extractor {
+    pub fn compile(pattern: &str) -> Result<Extractor, String> {
+        let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+        if regex.capture_names().flatten().next().is_none() {
+            return Err("the pattern needs a named group such as (?P<user>\\w+)".to_string());
+        }
+        Ok(Extractor { regex })
+    }
+
+    /// The fields the pattern produces for `message`, typed like logfmt
+    /// values; `None` if it does not match.
+    pub fn fields(&self, message: &str) -> Option<Map<String, Value>> {
+        let captures = self.regex.captures(message)?;
+        let mut fields = Map::new();
+        for name in self.regex.capture_names().flatten() {
+            if let Some(found) = captures.name(name) {
+                fields.insert(
+                    name.to_string(),
+                    crate::logfmt_value(found.as_str().to_string()),
+                );
+            }
+        }
+        Some(fields)
+    }
+}
+
+pub fn init(extractions: &[Extraction]) -> Result<(), String> {
+    let extractors = extractions
+        .iter()
+        .map(|e| Extractor::compile(&e.pattern).map_err(|err| format!("{}: {}", e.pattern, err)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let _ = EXTRACTORS.set(extractors);
+    Ok(())
+}
+
+/// Adds the configured extracted fields to a newly read or added entry.
+/// Fields the entry already has are left alone. Whether any were added.
+pub fn apply(entry: &mut LogEntry) -> bool {
+    let mut added = false;
+    for extractor in EXTRACTORS.get().into_iter().flatten() {
+        if let Some(fields) = extractor.fields(&entry.message) {
+            for (name, value) in fields {
+                if !entry.fields.contains_key(&name) {
+                    entry.fields.insert(name, value);
+                    added = true;
+                }
+            }
+        }
+    }
+    added
+}
+
+fn preview(entries: &[LogEntry], extractor: &Extractor, limit: usize) {
+    let mut matched = 0;
+    let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in entries {
+        let Some(fields) = extractor.fields(&entry.message) else {
+            continue;
+        };
+        matched += 1;
+        for name in fields.keys() {
+            *seen.entry(name.clone()).or_insert(0) += 1;
+        }
+        if matched <= limit {
+            println!("  {}", entry.message);
+            println!("    -> {}", Value::Object(fields));
+        }
+    }
+    println!(
+        "\nMatches {} of {} entries (first {} shown).",
+        matched,
+        entries.len(),
+        matched.min(limit)
+    );
+    for (name, count) in seen {
+        println!("  {:<16} {} entries", name, count);
+    }
+}
+
+// Appends an [[extract]] table; a string written by toml is always valid.
+fn save_rule(path: &Path, pattern: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(
+        file,
+        "\n[[extract]]\npattern = {}",
+        toml::Value::String(pattern.to_string())
+    )
+}
+
+/// Interactive helper: pick a sample entry, try patterns against it and
+/// preview the fields across the first `limit` matches, then save the
+/// pattern to the config file at `config_path`.
+pub fn run_helper(entries: &[LogEntry], limit: usize, config_path: Option<&Path>) {
+    let needle = crate::prompt("Sample entry (text it contains, empty for the first): ");
+    let Some(sample) = entries
+        .iter()
+        .find(|e| e.message.to_lowercase().contains(&needle.to_lowercase()))
+    else {
+        println!("No entry contains {:?}", needle);
+        return;
+    };
+    println!("Sample: {}", sample.message);
+
+    loop {
+        let pattern = crate::prompt("Pattern with named groups (empty to quit): ");
+        if pattern.is_empty() {
+            return;
+        }
+        let extractor = match Extractor::compile(&pattern) {
+            Ok(extractor) => extractor,
+            Err(e) => {
+                println!("Invalid pattern: {}", e);
+                continue;
+            }
+        };
+        match extractor.fields(&sample.message) {
+            Some(fields) => println!("Sample -> {}", Value::Object(fields)),
+            None => println!("The pattern does not match the sample."),
+        }
+        println!();
+        preview(entries, &extractor, limit);
+
+        let Some(path) = config_path else {
+            println!("No config file location; add it as an [[extract]] table yourself.");
+            continue;
+        };
+        let answer = crate::prompt(&format!("Save to {}? [y/N] ", path.display()));
+        if answer.eq_ignore_ascii_case("y") {
+            match save_rule(path, &pattern) {
+                Ok(()) => println!("Saved; new entries get these fields when read."),
+                Err(e) => println!("Could not save: {}", e),
+            }
+            return;
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! A backend for the `log` crate: `log::info!` and friends become entries
+//! appended to a log file, so a program can write straight into the files
+//! this tool analyzes. Key-values (`log::info!(user = 7; "...")`) become
+//! fields.
+
+use log::kv::{self, Key, VisitSource};
+use log::{Log, Metadata, Record};
+use serde_json::{Map, Value};
+
+use crate::directives::Directives;
+use crate::LogEntry;
+
+#[derive(Clone)]
+pub struct Logger {
+    file: String,
+    filter: Directives,
+}
+
+/// The name this tool uses for a `log` level.
+pub fn level_name(level: log::Level) -> &'static str {
+    match level {
+        log::Level::Error => "ERROR",
+        log::Level::Warn => "WARNING",
+        log::Level::Info => "INFO",
+        log::Level::Debug => "DEBUG",
+        log::Level::Trace => "TRACE",
+    }
+}
+
+struct Fields(Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = serde_json::to_value(&value).unwrap_or_else(|_| Value::from(value.to_string()));
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
+
+impl Logger {
+    /// A logger appending the records `filter` allows to `file`.
+    pub fn new(file: &str, filter: Directives) -> Logger {
+        Logger {
+            file: file.to_string(),
+            filter,
+        }
+    }
+
+    /// Whether records from `target` at `level` are written.
+    pub fn allows(&self, target: &str, level: log::Level) -> bool {
+        self.filter.allows(Some(target), level)
+    }
+
+    /// Applies the ingest stages to `entry` and appends it. A logger has
+    /// nowhere to report its own failures but stderr.
+    pub fn write(&self, entry: LogEntry) {
+        let Some(entry) = crate::ingest(entry, None, Vec::new()) else {
+            return;
+        };
+        crate::emit(&entry);
+        let written = crate::rotate::before_append(&self.file).and_then(|()| {
+            crate::LogAnalyzer::append_to_file(&self.file, std::slice::from_ref(&entry))
+        });
+        if let Err(e) = written {
+            eprintln!("Could not write {}: {}", self.file, e);
+        }
+    }
+
+    /// Makes this the global `log` backend.
+    pub fn install(self) -> Result<(), log::SetLoggerError> {
+        log::set_max_level(self.filter.max_level());
+        log::set_boxed_logger(Box::new(self))
+    }
+
+    /// The entry for a record; the target goes in a `target` field.
+    fn entry(record: &Record) -> LogEntry {
+        let mut fields = Fields(Map::new());
+        let _ = record.key_values().visit(&mut fields);
+        let mut fields = fields.0;
+        fields.insert("target".to_string(), Value::from(record.target()));
+        LogEntry {
+            timestamp: crate::now_timestamp(),
+            level: level_name(record.level()).to_string(),
+            message: record.args().to_string(),
+            fields,
+            source: None,
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.allows(metadata.target(), metadata.level())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.write(Logger::entry(record));
+        }
+    }
+
+    // Every record is written as it is logged.
+    fn flush(&self) {}
+}
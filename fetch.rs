@@ -0,0 +1,92 @@
+//! http:// and https:// input: the response body is parsed as it arrives.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::{crypt, export};
+
+/// Headers sent to URLs starting with `prefix`, e.g. a token for a CI
+/// artifact store.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpAuth {
+    pub prefix: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Header name to the environment variable holding its value, to keep
+    /// tokens out of the config.
+    #[serde(default)]
+    pub headers_env: BTreeMap<String, String>,
+}
+
+enum HeaderValue {
+    Text(String),
+    Env(String),
+}
+
+static HEADERS: OnceLock<Vec<(String, String, HeaderValue)>> = OnceLock::new();
+
+/// Sets the headers for URL inputs: `global` ones ("Name: value", from
+/// --http-header) for every URL, then the configured ones per prefix.
+pub fn init(global: &[String], configured: &[HttpAuth]) -> Result<(), String> {
+    let mut headers = Vec::new();
+    for header in global {
+        let (name, value) = header.split_once(':').ok_or_else(|| {
+            format!(
+                "Invalid --http-header '{}': expected \"Name: value\"",
+                header
+            )
+        })?;
+        let value = HeaderValue::Text(value.trim().to_string());
+        headers.push((String::new(), name.trim().to_string(), value));
+    }
+    for auth in configured {
+        for (name, value) in &auth.headers {
+            let value = HeaderValue::Text(value.clone());
+            headers.push((auth.prefix.clone(), name.clone(), value));
+        }
+        for (name, var) in &auth.headers_env {
+            let value = HeaderValue::Env(var.clone());
+            headers.push((auth.prefix.clone(), name.clone(), value));
+        }
+    }
+    let _ = HEADERS.set(headers);
+    Ok(())
+}
+
+pub fn is_url(filename: &str) -> bool {
+    filename.starts_with("http://") || filename.starts_with("https://")
+}
+
+/// Opens `url`, decrypting and decompressing the body like a local file.
+pub fn open(url: &str) -> io::Result<Box<dyn BufRead>> {
+    let mut request = ureq::get(url);
+    let mut sent: Vec<String> = Vec::new();
+    // --http-header comes first and wins over the config.
+    for (prefix, name, value) in HEADERS.get().into_iter().flatten() {
+        if !url.starts_with(prefix.as_str()) || sent.contains(&name.to_lowercase()) {
+            continue;
+        }
+        sent.push(name.to_lowercase());
+        let value = match value {
+            HeaderValue::Text(text) => text.clone(),
+            HeaderValue::Env(var) => std::env::var(var).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("header {}: ${} is not set", name, var),
+                )
+            })?,
+        };
+        request = request.set(name, &value);
+    }
+    let response = request.call().map_err(|e| match e {
+        ureq::Error::Status(code, response) => {
+            io::Error::other(format!("HTTP {} {}", code, response.status_text()))
+        }
+        e => io::Error::other(e),
+    })?;
+    export::decompress(crypt::decrypt(BufReader::new(response.into_reader()))?)
+}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde_json::{Map, Value};
+
+use crate::query::{self, Field};
+use crate::LogEntry;
+
+const MAX_EXAMPLES: usize = 3;
+
+/// What was observed for one field path across all entries.
+pub struct FieldSchema {
+    pub path: String,
+    pub types: BTreeSet<&'static str>,
+    pub count: usize,
+    pub examples: Vec<String>,
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Appends `key` to a jq-style path, quoting keys that are not plain
+/// identifiers so the result can be pasted into a query.
+pub fn child_path(parent: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !key.starts_with(|c: char| c.is_ascii_digit());
+    if plain {
+        format!("{}.{}", parent, key)
+    } else {
+        format!("{}[{}]", parent, Value::String(key.to_string()))
+    }
+}
+
+fn walk(
+    fields: &Map<String, Value>,
+    parent: &str,
+    seen: &mut BTreeSet<String>,
+    schema: &mut HashMap<String, FieldSchema>,
+) {
+    for (key, value) in fields {
+        let path = child_path(parent, key);
+        let entry = schema.entry(path.clone()).or_insert_with(|| FieldSchema {
+            path: path.clone(),
+            types: BTreeSet::new(),
+            count: 0,
+            examples: Vec::new(),
+        });
+        entry.types.insert(type_name(value));
+        // Count each path once per log entry.
+        if seen.insert(path.clone()) {
+            entry.count += 1;
+        }
+        if let Value::Object(children) = value {
+            walk(children, &path, seen, schema);
+        } else {
+            let example = query::render(value);
+            if entry.examples.len() < MAX_EXAMPLES && !entry.examples.contains(&example) {
+                entry.examples.push(example);
+            }
+        }
+    }
+}
+
+/// Infers the structured-field schema of the loaded entries, nested
+/// objects flattened into jq-style paths, sorted by path.
+pub fn infer_schema(entries: &[LogEntry]) -> Vec<FieldSchema> {
+    let mut schema = HashMap::new();
+    for entry in entries {
+        let mut seen = BTreeSet::new();
+        walk(&entry.fields, "", &mut seen, &mut schema);
+    }
+    let mut fields: Vec<FieldSchema> = schema.into_values().collect();
+    fields.sort_by(|a, b| a.path.cmp(&b.path));
+    fields
+}
+
+pub fn print_schema(schema: &[FieldSchema], total: usize) {
+    if schema.is_empty() {
+        println!("\nNo structured fields found");
+        return;
+    }
+    let width = schema.iter().map(|f| f.path.len()).max().unwrap_or(0);
+    println!("\nFields ({} entries):", total);
+    for field in schema {
+        let types: Vec<&str> = field.types.iter().copied().collect();
+        println!(
+            "{:<width$}  {:<14} {:>5.1}%  {}",
+            field.path,
+            types.join("|"),
+            field.count as f64 * 100.0 / total.max(1) as f64,
+            field.examples.join(", "),
+            width = width
+        );
+    }
+}
+
+const TOP_VALUES: usize = 10;
+
+pub struct NumericStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+pub struct FieldStats {
+    pub field: String,
+    pub count: usize,
+    pub distinct: usize,
+    /// Set when every present value is a number.
+    pub numeric: Option<NumericStats>,
+    pub top: Vec<(String, usize)>,
+}
+
+/// Counts, distinct values and either min/max/mean (numeric fields) or the
+/// most frequent values (text fields). Entries lacking the field are skipped.
+pub fn field_stats(entries: &[LogEntry], field: &Field) -> FieldStats {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut numbers = Vec::new();
+    let mut all_numeric = true;
+    let mut count = 0;
+    for value in entries.iter().filter_map(|e| field.lookup(e)) {
+        count += 1;
+        match value.parse::<f64>() {
+            Ok(n) if all_numeric => numbers.push(n),
+            _ => all_numeric = false,
+        }
+        *counts.entry(value.into_owned()).or_insert(0) += 1;
+    }
+
+    let numeric = (all_numeric && !numbers.is_empty()).then(|| NumericStats {
+        min: numbers.iter().copied().fold(f64::INFINITY, f64::min),
+        max: numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        mean: numbers.iter().sum::<f64>() / numbers.len() as f64,
+    });
+    let distinct = counts.len();
+    let mut top: Vec<(String, usize)> = counts.into_iter().collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top.truncate(TOP_VALUES);
+
+    FieldStats {
+        field: field.name().to_string(),
+        count,
+        distinct,
+        numeric,
+        top,
+    }
+}
+
+pub fn field_stats_json(stats: &FieldStats) -> Value {
+    let mut json = serde_json::json!({
+        "field": stats.field,
+        "count": stats.count,
+        "distinct": stats.distinct,
+    });
+    match &stats.numeric {
+        Some(numeric) => {
+            json["min"] = numeric.min.into();
+            json["max"] = numeric.max.into();
+            json["mean"] = numeric.mean.into();
+        }
+        None => {
+            json["top"] = stats
+                .top
+                .iter()
+                .map(|(value, count)| serde_json::json!({ "value": value, "count": count }))
+                .collect();
+        }
+    }
+    json
+}
+
+pub fn print_field_stats(stats: &FieldStats) {
+    println!("\nField {}:", stats.field);
+    println!("Count: {}", stats.count);
+    println!("Distinct: {}", stats.distinct);
+    match &stats.numeric {
+        Some(numeric) => {
+            println!("Min: {}", numeric.min);
+            println!("Max: {}", numeric.max);
+            println!("Mean: {:.3}", numeric.mean);
+        }
+        None => {
+            println!("Top values:");
+            for (value, count) in &stats.top {
+                println!("{:>8}  {}", count, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::fs::{File, Metadata};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{LogAnalyzer, LogEntry};
+
+/// Reads lines appended to a file, like `tail -f`.
+pub struct Follower {
+    path: String,
+    position: u64,
+    // The start of a line whose newline has not been written yet.
+    partial: Vec<u8>,
+    // Lines read so far, to number new ones; unknown when starting at the end.
+    lines: Option<usize>,
+    /// Lines the last poll could not parse.
+    pub skipped: usize,
+    /// Whether the last poll started the file over.
+    pub rewound: bool,
+    // The file being read, to notice when another one takes its name.
+    identity: Option<(u64, u64)>,
+}
+
+impl Follower {
+    /// Starts at the beginning of `path`, which may not exist yet.
+    pub fn from_start(path: &str) -> Follower {
+        Follower {
+            path: path.to_string(),
+            position: 0,
+            partial: Vec::new(),
+            lines: Some(0),
+            skipped: 0,
+            rewound: false,
+            identity: None,
+        }
+    }
+
+    /// Starts at the current end of `path`, so only lines appended from
+    /// now on are read.
+    pub fn from_end(path: &str) -> Follower {
+        let mut follower = Follower::from_start(path);
+        match File::open(path) {
+            // Those are counted in entries, which takes reading them.
+            Ok(file) if whole(&file).unwrap_or(false) => {
+                let _ = follower.poll();
+            }
+            _ => {
+                follower.position = std::fs::metadata(path).map_or(0, |m| m.len());
+                follower.lines = None;
+            }
+        }
+        follower
+    }
+
+    /// Starts where `offset` says a follower of `path` got to, or at the
+    /// beginning without one.
+    pub fn resume(path: &str, offset: Option<&Offset>) -> Follower {
+        let mut follower = Follower::from_start(path);
+        if let Some(offset) = offset {
+            follower.position = offset.position;
+            follower.identity = offset.file;
+            follower.lines = offset.lines;
+        }
+        follower
+    }
+
+    /// Where the lines read so far end, a line not yet complete excluded.
+    pub fn offset(&self) -> Offset {
+        Offset {
+            position: self.position - self.partial.len() as u64,
+            file: self.identity,
+            lines: self.lines,
+        }
+    }
+
+    /// Entries from the complete lines appended since the last call, read
+    /// in the configured input format. A file that shrank was truncated and
+    /// is read again from the start. A file that was rotated away is read
+    /// to its end first, if it is still in the same directory, and then the
+    /// new file from its start. A file replaced by one at least as long,
+    /// as saving does by renaming a new copy over it, is read on from where
+    /// it left off. Files that can't be read from the middle,
+    /// such as binary logs, are read whole again when they change.
+    pub fn poll(&mut self) -> io::Result<Vec<LogEntry>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let metadata = file.metadata()?;
+        let current = identity(&metadata);
+        self.skipped = 0;
+        if whole(&file)? {
+            self.identity = current;
+            return self.reload(metadata.len());
+        }
+        let mut entries = Vec::new();
+        let rotated = match self.identity {
+            Some(_) if current != self.identity => self.rotated(),
+            _ => None,
+        };
+        self.rewound = if let Some(mut rotated) = rotated {
+            let mut lines = self.read(&mut rotated)?;
+            // Nothing more is written to it, so its last line is done.
+            if !self.partial.is_empty() {
+                lines.append(&mut self.partial);
+                lines.push(b'\n');
+            }
+            entries = self.parse(&lines)?;
+            true
+        } else {
+            metadata.len() < self.position
+        };
+        if self.rewound {
+            self.position = 0;
+            self.partial.clear();
+            self.lines = Some(0);
+        }
+        self.identity = current;
+        let lines = self.read(&mut file)?;
+        entries.extend(self.parse(&lines)?);
+        Ok(entries)
+    }
+
+    // Reads the file whole if its size changed; the entries after as many
+    // as were read before are new. Such files are counted in entries
+    // rather than lines, and fewer of them than before start it over.
+    fn reload(&mut self, len: u64) -> io::Result<Vec<LogEntry>> {
+        self.rewound = false;
+        if len == self.position {
+            return Ok(Vec::new());
+        }
+        let mut analyzer = LogAnalyzer::new();
+        analyzer.read_file(&self.path)?;
+        self.position = len;
+        self.skipped = analyzer.skipped.len();
+        let read = self.lines.unwrap_or(0);
+        self.rewound = analyzer.entries.len() < read;
+        self.lines = Some(analyzer.entries.len());
+        let mut entries = if self.rewound {
+            analyzer.entries
+        } else {
+            analyzer.entries.split_off(read)
+        };
+        for entry in &mut entries {
+            if let Some(source) = &mut entry.source {
+                source.file = Some(self.path.clone());
+            }
+        }
+        Ok(entries)
+    }
+
+    // The complete lines of `file` after those read so far.
+    fn read(&mut self, file: &mut File) -> io::Result<Vec<u8>> {
+        file.seek(SeekFrom::Start(self.position))?;
+        let read = file.read_to_end(&mut self.partial)?;
+        self.position += read as u64;
+        let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let rest = self.partial.split_off(end + 1);
+        Ok(std::mem::replace(&mut self.partial, rest))
+    }
+
+    fn parse(&mut self, lines: &[u8]) -> io::Result<Vec<LogEntry>> {
+        if lines.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut analyzer = LogAnalyzer::new();
+        analyzer.load_from_reader(lines)?;
+        self.skipped += analyzer.skipped.len();
+        let read = self.lines;
+        self.lines = read.map(|n| n + lines.iter().filter(|&&b| b == b'\n').count());
+        for entry in &mut analyzer.entries {
+            if let Some(source) = &mut entry.source {
+                source.file = Some(self.path.clone());
+                source.line = read.zip(source.line).map(|(read, line)| read + line);
+            }
+        }
+        Ok(analyzer.entries)
+    }
+
+    // The file that was being read, under the name it was rotated to.
+    fn rotated(&self) -> Option<File> {
+        let path = Path::new(&self.path);
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let entry = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .find(|e| e.metadata().ok().and_then(|m| identity(&m)) == self.identity)?;
+        File::open(entry.path()).ok()
+    }
+}
+
+// Whether `file` has to be read whole: a binary, encrypted or compressed
+// log.
+fn whole(mut file: &File) -> io::Result<bool> {
+    let mut head = [0; 16];
+    let read = file.read(&mut head)?;
+    let head = &head[..read];
+    Ok(head.starts_with(crate::binary::MAGIC)
+        || crate::crypt::encrypted(head)
+        || crate::export::compressed(head))
+}
+
+// The device and inode of a file, which stay the same when it is renamed;
+// without them rotation is only noticed when the file shrinks.
+#[cfg(unix)]
+fn identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(_: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Where a follower got to, so it can carry on from there after a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Offset {
+    /// Bytes read.
+    pub position: u64,
+    /// Device and inode of the file they were read from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<(u64, u64)>,
+    /// Lines read, if known.
+    #[serde(default)]
+    pub lines: Option<usize>,
+}
+
+/// The offsets of followed files, by path, kept in a JSON file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Offsets(pub BTreeMap<String, Offset>);
+
+impl Offsets {
+    /// Loads the offsets, or starts empty if the file doesn't exist yet.
+    pub fn load(filename: &str) -> io::Result<Offsets> {
+        match File::open(filename) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Offsets::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Written to a temporary file first so a crash mid-write can't leave
+    // half the offsets behind.
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let temp = format!("{}.tmp{}", filename, std::process::id());
+        let mut out = BufWriter::new(File::create(&temp)?);
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)?;
+        out.flush()?;
+        drop(out);
+        std::fs::rename(&temp, filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("logger-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn messages(entries: &[LogEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.message.as_str()).collect()
+    }
+
+    // Replaces `filename` the way saving does, through a renamed copy.
+    fn rewrite(filename: &str, text: &str) {
+        let temp = format!("{}.tmp", filename);
+        std::fs::write(&temp, text).unwrap();
+        std::fs::rename(&temp, filename).unwrap();
+    }
+
+    #[test]
+    fn rewritten_file_is_read_on() {
+        let filename = path("rewritten.log");
+        std::fs::write(&filename, "2026-01-01 00:00:00|INFO|a\n").unwrap();
+        let mut follower = Follower::from_start(&filename);
+        assert_eq!(messages(&follower.poll().unwrap()), ["a"]);
+        rewrite(
+            &filename,
+            "2026-01-01 00:00:00|INFO|a\n2026-01-01 00:00:01|INFO|b\n",
+        );
+        assert_eq!(messages(&follower.poll().unwrap()), ["b"]);
+        assert!(!follower.rewound);
+        rewrite(&filename, "2026-01-01 00:00:02|INFO|c\n");
+        assert_eq!(messages(&follower.poll().unwrap()), ["c"]);
+        assert!(follower.rewound);
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn partial_line_waits_for_its_newline() {
+        let filename = path("partial.log");
+        std::fs::write(&filename, "2026-01-01 00:00:00|INFO|a\n2026-01-01").unwrap();
+        let mut follower = Follower::from_start(&filename);
+        assert_eq!(messages(&follower.poll().unwrap()), ["a"]);
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&filename)
+            .unwrap();
+        file.write_all(b" 00:00:01|INFO|b\n").unwrap();
+        assert_eq!(messages(&follower.poll().unwrap()), ["b"]);
+        assert_eq!(follower.offset().position, file.metadata().unwrap().len());
+        std::fs::remove_file(filename).unwrap();
+    }
+}
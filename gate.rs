@@ -0,0 +1,240 @@
+use regex::Regex;
+
+use crate::alert::severity_rank;
+use crate::query::Op;
+use crate::LogEntry;
+
+pub const CONDITIONS_HELP: &str =
+    "Conditions: \"zero FATAL\", \"count ERROR < 5\", \"error rate < 1%\",
+\"ERROR+ rate < 1%\" (ERROR and worse), \"contains 'migration complete'\",
+\"not contains 'panic'\", \"not matches /OOM|out of memory/\" (a regex),
+\"ERROR+ per 1m < 10\" (the busiest minute)";
+
+/// Which entries a level condition counts. `ERROR+` means ERROR and worse.
+#[derive(Debug, Clone)]
+pub enum LevelSelector {
+    Any,
+    Exactly(String),
+    AtLeast(String),
+}
+
+impl LevelSelector {
+    pub fn parse(word: &str) -> LevelSelector {
+        match word.strip_suffix('+') {
+            Some(level) => LevelSelector::AtLeast(level.to_uppercase()),
+            None => LevelSelector::Exactly(word.to_uppercase()),
+        }
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        match self {
+            LevelSelector::Any => true,
+            LevelSelector::Exactly(level) => entry.level.eq_ignore_ascii_case(level),
+            LevelSelector::AtLeast(level) => severity_rank(&entry.level) >= severity_rank(level),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            LevelSelector::Any => "entries".to_string(),
+            LevelSelector::Exactly(level) => level.clone(),
+            LevelSelector::AtLeast(level) => format!("{}+", level),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Assertion {
+    Count(LevelSelector, Op, f64),
+    /// Percentage of all entries.
+    Rate(LevelSelector, Op, f64),
+    /// Most entries in any window of the length.
+    Peak(LevelSelector, chrono::Duration, Op, f64),
+    Contains(String, bool),
+    Matches(Regex, bool),
+}
+
+pub struct Outcome {
+    pub condition: String,
+    pub passed: bool,
+    pub actual: String,
+}
+
+pub fn parse_op(word: &str) -> Result<Op, String> {
+    match word {
+        "<" => Ok(Op::Lt),
+        "<=" => Ok(Op::Le),
+        ">" => Ok(Op::Gt),
+        ">=" => Ok(Op::Ge),
+        "=" | "==" => Ok(Op::Eq),
+        "!=" => Ok(Op::Ne),
+        _ => Err(format!("unknown operator '{}'", word)),
+    }
+}
+
+pub fn parse_number(word: &str) -> Result<f64, String> {
+    word.trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", word))
+}
+
+fn unquote(text: &str) -> String {
+    let text = text.trim();
+    for quote in ['\'', '"'] {
+        if let Some(inner) = text.strip_prefix(quote).and_then(|t| t.strip_suffix(quote)) {
+            return inner.to_string();
+        }
+    }
+    text.to_string()
+}
+
+impl Assertion {
+    /// Parses one condition:
+    /// `zero LEVEL` / `no LEVEL`, `count [LEVEL] OP N`,
+    /// `LEVEL rate OP N%`, `LEVEL per WINDOW OP N`, `contains 'text'`, `not contains 'text'`,
+    /// `matches /regex/`, `not matches /regex/`.
+    pub fn parse(condition: &str) -> Result<Assertion, String> {
+        let trimmed = condition.trim();
+        let lower = trimmed.to_lowercase();
+        for (prefix, wanted) in [("not matches ", false), ("matches ", true)] {
+            if let Some(text) = lower.strip_prefix(prefix) {
+                let pattern = unquote(&trimmed[trimmed.len() - text.len()..]);
+                let pattern = pattern
+                    .strip_prefix('/')
+                    .and_then(|p| p.strip_suffix('/'))
+                    .unwrap_or(&pattern);
+                let regex = Regex::new(pattern)
+                    .map_err(|e| format!("invalid regex in '{}': {}", condition, e))?;
+                return Ok(Assertion::Matches(regex, wanted));
+            }
+        }
+        if let Some(text) = lower.strip_prefix("not contains ") {
+            let start = trimmed.len() - text.len();
+            return Ok(Assertion::Contains(unquote(&trimmed[start..]), false));
+        }
+        if let Some(text) = lower.strip_prefix("contains ") {
+            let start = trimmed.len() - text.len();
+            return Ok(Assertion::Contains(unquote(&trimmed[start..]), true));
+        }
+
+        let words: Vec<&str> = trimmed.split_whitespace().collect();
+        let lowered: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+        let lowered: Vec<&str> = lowered.iter().map(String::as_str).collect();
+        match lowered.as_slice() {
+            ["zero" | "no", _] => Ok(Assertion::Count(
+                LevelSelector::parse(words[1]),
+                Op::Eq,
+                0.0,
+            )),
+            ["count", op, n] => Ok(Assertion::Count(
+                LevelSelector::Any,
+                parse_op(op)?,
+                parse_number(n)?,
+            )),
+            ["count", _, op, n] => Ok(Assertion::Count(
+                LevelSelector::parse(words[1]),
+                parse_op(op)?,
+                parse_number(n)?,
+            )),
+            [_, "rate", op, n] => Ok(Assertion::Rate(
+                LevelSelector::parse(words[0]),
+                parse_op(op)?,
+                parse_number(n)?,
+            )),
+            [_, "per", window, op, n] => Ok(Assertion::Peak(
+                LevelSelector::parse(words[0]),
+                crate::query::parse_interval(window)?,
+                parse_op(op)?,
+                parse_number(n)?,
+            )),
+            _ => Err(format!("cannot parse condition '{}'", condition)),
+        }
+    }
+
+    pub fn evaluate(&self, entries: &[LogEntry]) -> (bool, String) {
+        match self {
+            Assertion::Count(selector, op, expected) => {
+                let count = entries.iter().filter(|e| selector.matches(e)).count();
+                (
+                    op.holds(&(count as f64), expected),
+                    format!("{} {}", count, selector.describe()),
+                )
+            }
+            Assertion::Rate(selector, op, expected) => {
+                let count = entries.iter().filter(|e| selector.matches(e)).count();
+                let rate = if entries.is_empty() {
+                    0.0
+                } else {
+                    count as f64 * 100.0 / entries.len() as f64
+                };
+                (op.holds(&rate, expected), format!("{:.2}%", rate))
+            }
+            Assertion::Peak(selector, window, op, expected) => {
+                let peak = crate::analysis::rate(entries, selector, *window)
+                    .into_iter()
+                    .map(|(_, count)| count)
+                    .max()
+                    .unwrap_or(0);
+                (
+                    op.holds(&(peak as f64), expected),
+                    format!("{} {} at the peak", peak, selector.describe()),
+                )
+            }
+            Assertion::Contains(text, wanted) => {
+                let needle = text.to_lowercase();
+                let found = entries
+                    .iter()
+                    .filter(|e| e.message.to_lowercase().contains(&needle))
+                    .count();
+                (
+                    (found > 0) == *wanted,
+                    format!("{} matching entries", found),
+                )
+            }
+            Assertion::Matches(regex, wanted) => {
+                let found = entries
+                    .iter()
+                    .filter(|e| regex.is_match(&e.message))
+                    .count();
+                (
+                    (found > 0) == *wanted,
+                    format!("{} matching entries", found),
+                )
+            }
+        }
+    }
+}
+
+/// Evaluates every condition; all of them are checked even after a failure
+/// so the report is complete.
+pub fn evaluate_all(entries: &[LogEntry], conditions: &[String]) -> Result<Vec<Outcome>, String> {
+    let assertions = conditions
+        .iter()
+        .map(|c| Assertion::parse(c))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(conditions
+        .iter()
+        .zip(assertions)
+        .map(|(condition, assertion)| {
+            let (passed, actual) = assertion.evaluate(entries);
+            Outcome {
+                condition: condition.clone(),
+                passed,
+                actual,
+            }
+        })
+        .collect())
+}
+
+pub fn print_report(outcomes: &[Outcome]) {
+    for outcome in outcomes {
+        println!(
+            "{}  {} ({})",
+            if outcome.passed { "PASS" } else { "FAIL" },
+            outcome.condition,
+            outcome.actual
+        );
+    }
+    let passed = outcomes.iter().filter(|o| o.passed).count();
+    println!("{} of {} assertions passed", passed, outcomes.len());
+}
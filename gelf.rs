@@ -0,0 +1,156 @@
+//! GELF 1.1 messages for Graylog.
+
+use std::io::{self, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{Local, TimeZone};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::{Map, Value};
+
+use crate::sink::Sink;
+use crate::LogEntry;
+
+/// Largest UDP datagram sent; bigger messages are split into chunks.
+const DATAGRAM: usize = 8192;
+/// Magic bytes, message ID, sequence number and count.
+const CHUNK_HEADER: usize = 12;
+/// Graylog drops messages of more chunks.
+const MAX_CHUNKS: usize = 128;
+
+// Additional field names are limited to word characters, dots and dashes.
+fn field_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric() || "_.-".contains(*c))
+        .collect()
+}
+
+/// The entry as a GELF object. Fields become `_name` additional fields;
+/// GELF only takes strings and numbers, so anything else is sent as its
+/// JSON text.
+pub fn message(entry: &LogEntry, hostname: &str) -> Value {
+    let (short, full) = match entry.message.split_once('\n') {
+        Some((first, _)) => (first, Some(&entry.message)),
+        None => (entry.message.as_str(), None),
+    };
+    let mut object = Map::new();
+    object.insert("version".to_string(), Value::from("1.1"));
+    object.insert("host".to_string(), Value::from(hostname));
+    object.insert("short_message".to_string(), Value::from(short));
+    if let Some(full) = full {
+        object.insert("full_message".to_string(), Value::from(full.as_str()));
+    }
+    if let Some(time) = entry
+        .time()
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+    {
+        let seconds = time.timestamp_millis() as f64 / 1000.0;
+        object.insert("timestamp".to_string(), Value::from(seconds));
+    }
+    object.insert(
+        "level".to_string(),
+        Value::from(crate::syslog::severity(&entry.level)),
+    );
+    object.insert("_level_name".to_string(), Value::from(entry.level.as_str()));
+    for (name, value) in &entry.fields {
+        let name = field_name(name);
+        // `_id` is reserved.
+        if name.is_empty() || name == "id" {
+            continue;
+        }
+        let value = match value {
+            Value::String(_) | Value::Number(_) => value.clone(),
+            other => Value::from(other.to_string()),
+        };
+        object.insert(format!("_{}", name), value);
+    }
+    Value::Object(object)
+}
+
+enum Transport {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+/// Sends entries to a GELF input.
+pub struct Sender {
+    transport: Transport,
+    hostname: String,
+    sequence: u64,
+}
+
+impl Sender {
+    /// Connects to `address` (host:port). TCP messages are separated by NUL
+    /// bytes; UDP messages are gzipped and chunked when they do not fit one
+    /// datagram.
+    pub fn connect(address: &str, tcp: bool) -> io::Result<Sender> {
+        let transport = if tcp {
+            Transport::Tcp(TcpStream::connect(address)?)
+        } else {
+            Transport::Udp(crate::sink::udp_socket(address)?)
+        };
+        Ok(Sender {
+            transport,
+            hostname: crate::sink::hostname(),
+            sequence: 0,
+        })
+    }
+}
+
+// Unique per message, for the receiver to reassemble chunks by.
+fn message_id(sequence: &mut u64) -> [u8; 8] {
+    *sequence += 1;
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    (nanos ^ sequence.rotate_left(48) ^ u64::from(std::process::id())).to_be_bytes()
+}
+
+fn send_datagram(socket: &UdpSocket, id: [u8; 8], payload: &[u8]) -> io::Result<()> {
+    if payload.len() <= DATAGRAM {
+        crate::sink::send_datagram(socket, payload)?;
+        return Ok(());
+    }
+    let chunks: Vec<&[u8]> = payload.chunks(DATAGRAM - CHUNK_HEADER).collect();
+    if chunks.len() > MAX_CHUNKS {
+        return Err(io::Error::other(format!(
+            "GELF message of {} bytes is too large for UDP",
+            payload.len()
+        )));
+    }
+    for (sequence, chunk) in chunks.iter().enumerate() {
+        let mut datagram = Vec::with_capacity(CHUNK_HEADER + chunk.len());
+        datagram.extend_from_slice(&[0x1e, 0x0f]);
+        datagram.extend_from_slice(&id);
+        datagram.push(sequence as u8);
+        datagram.push(chunks.len() as u8);
+        datagram.extend_from_slice(chunk);
+        crate::sink::send_datagram(socket, &datagram)?;
+    }
+    Ok(())
+}
+
+impl Sink for Sender {
+    fn send(&mut self, entries: &[LogEntry]) -> io::Result<()> {
+        for entry in entries {
+            let json = message(entry, &self.hostname).to_string();
+            match &mut self.transport {
+                Transport::Tcp(stream) => {
+                    stream.write_all(json.as_bytes())?;
+                    stream.write_all(b"\0")?;
+                }
+                Transport::Udp(socket) => {
+                    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+                    gzip.write_all(json.as_bytes())?;
+                    let id = message_id(&mut self.sequence);
+                    send_datagram(socket, id, &gzip.finish()?)?;
+                }
+            }
+        }
+        if let Transport::Tcp(stream) = &mut self.transport {
+            stream.flush()?;
+        }
+        Ok(())
+    }
+}
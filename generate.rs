@@ -0,0 +1,295 @@
+//! Synthetic logs for benchmarks and pipeline tests.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{Duration, NaiveDateTime};
+use serde_json::Map;
+
+use crate::LogEntry;
+
+pub const TEMPLATES_HELP: &str = "\
+Templates may start with LEVEL: to be used only for that level and
+contain {int:MIN-MAX}, {float:MIN-MAX}, {choice:a|b|c}, {word}, {ip},
+{uuid} and {hex:N}, e.g.
+  --template 'ERROR:Connection to {ip}:{int:1000-9999} refused'
+  --template 'GET /api/{choice:users|orders} took {int:5-900}ms'";
+
+const DEFAULT_TEMPLATES: &[&str] = &[
+    "DEBUG:Cache {choice:hit|miss} for key {word}:{int:1-9999}",
+    "DEBUG:Acquired lock {hex:8} after {int:0-20}ms",
+    "INFO:GET /api/{choice:users|orders|items|search}/{int:1-5000} 200 {int:2-300}ms",
+    "INFO:POST /api/{choice:orders|login|upload} 201 {int:10-900}ms",
+    "INFO:User {int:1000-9999} logged in from {ip}",
+    "INFO:Job {uuid} finished in {float:0.1-30.0}s",
+    "WARNING:Slow query on {choice:users|orders|events} took {int:500-5000}ms",
+    "WARNING:Retrying request to {ip} (attempt {int:2-5})",
+    "WARNING:Disk usage at {int:80-95}%",
+    "ERROR:Connection to {ip}:{int:1024-65535} refused",
+    "ERROR:Timeout after {int:5-60}s waiting for {choice:db|cache|payments}",
+    "ERROR:Request {uuid} failed: {choice:invalid token|upstream 502|deadlock detected}",
+    "CRITICAL:Out of memory in worker {int:1-32}",
+];
+
+const WORDS: &[&str] = &[
+    "alpha", "bravo", "cache", "delta", "echo", "fetch", "gamma", "index", "kernel", "lambda",
+    "merge", "nexus", "omega", "proxy", "quota", "relay", "session", "token", "vector", "worker",
+];
+
+/// xorshift64*: fast, seedable and good enough for fake data.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: Option<u64>) -> Rng {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |d| d.as_nanos() as u64)
+        });
+        // A zero state would stay zero.
+        Rng((seed ^ 0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniform in [0, 1).
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n.max(1)
+    }
+}
+
+/// A message template: literal text and placeholders.
+struct Template {
+    level: Option<String>,
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Text(String),
+    Int(i64, i64),
+    Float(f64, f64),
+    Choice(Vec<String>),
+    Word,
+    Ip,
+    Uuid,
+    Hex(usize),
+}
+
+fn parse_range<T: std::str::FromStr + PartialOrd>(spec: &str) -> Option<(T, T)> {
+    // The first '-' after a digit separates the bounds, so negative
+    // minimums work.
+    let split = spec
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| c == '-')
+        .map(|(i, _)| i)?;
+    let (min, max) = (spec[..split].parse().ok()?, spec[split + 1..].parse().ok()?);
+    (min <= max).then_some((min, max))
+}
+
+impl Template {
+    fn parse(text: &str) -> Result<Template, String> {
+        let (level, body) = match text.split_once(':') {
+            Some((level, body))
+                if !level.is_empty() && level.chars().all(|c| c.is_ascii_uppercase()) =>
+            {
+                (Some(level.to_string()), body)
+            }
+            _ => (None, text),
+        };
+        let mut parts = Vec::new();
+        let mut rest = body;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map(|e| start + e)
+                .ok_or_else(|| format!("unclosed '{{' in template '{}'", text))?;
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let placeholder = &rest[start + 1..end];
+            let (name, spec) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+            let invalid = || format!("invalid placeholder {{{}}} in '{}'", placeholder, text);
+            parts.push(match name {
+                "int" => {
+                    let (min, max) = parse_range(spec).ok_or_else(invalid)?;
+                    Part::Int(min, max)
+                }
+                "float" => {
+                    let (min, max) = parse_range(spec).ok_or_else(invalid)?;
+                    Part::Float(min, max)
+                }
+                "choice" if !spec.is_empty() => {
+                    Part::Choice(spec.split('|').map(String::from).collect())
+                }
+                "word" => Part::Word,
+                "ip" => Part::Ip,
+                "uuid" => Part::Uuid,
+                "hex" => Part::Hex(spec.parse().ok().filter(|&n| n > 0).ok_or_else(invalid)?),
+                _ => return Err(invalid()),
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template { level, parts })
+    }
+
+    fn render(&self, rng: &mut Rng) -> String {
+        let mut text = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(literal) => text.push_str(literal),
+                Part::Int(min, max) => {
+                    let span = (max - min) as u64 + 1;
+                    text.push_str(&(min + rng.below(span) as i64).to_string())
+                }
+                Part::Float(min, max) => {
+                    text.push_str(&format!("{:.2}", min + rng.unit() * (max - min)))
+                }
+                Part::Choice(choices) => {
+                    text.push_str(&choices[rng.below(choices.len() as u64) as usize])
+                }
+                Part::Word => text.push_str(WORDS[rng.below(WORDS.len() as u64) as usize]),
+                Part::Ip => text.push_str(&format!(
+                    "10.{}.{}.{}",
+                    rng.below(256),
+                    rng.below(256),
+                    rng.below(254) + 1
+                )),
+                Part::Uuid => {
+                    let (a, b) = (rng.next(), rng.next());
+                    text.push_str(&format!(
+                        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+                        a >> 32,
+                        (a >> 16) & 0xffff,
+                        a & 0xfff,
+                        ((b >> 48) & 0x3fff) | 0x8000,
+                        b & 0xffff_ffff_ffff
+                    ))
+                }
+                Part::Hex(n) => {
+                    for _ in 0..*n {
+                        text.push(char::from_digit(rng.below(16) as u32, 16).unwrap());
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+/// Parses `INFO=80,WARNING=15,ERROR=5` into levels and weights.
+pub fn parse_levels(text: &str) -> Result<Vec<(String, u32)>, String> {
+    let levels = text
+        .split(',')
+        .map(|item| {
+            let (level, weight) = item
+                .split_once('=')
+                .ok_or_else(|| format!("expected LEVEL=WEIGHT, got '{}'", item))?;
+            let weight = weight
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid weight '{}'", weight))?;
+            Ok((level.trim().to_uppercase(), weight))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if levels.iter().all(|(_, weight)| *weight == 0) {
+        return Err("at least one level needs a weight above 0".to_string());
+    }
+    Ok(levels)
+}
+
+pub struct Generator {
+    rng: Rng,
+    /// Entries per second, on average.
+    rate: f64,
+    levels: Vec<(String, u32)>,
+    templates: Vec<Template>,
+    time: NaiveDateTime,
+}
+
+impl Generator {
+    /// `templates` empty means the built-in ones. Entries start at `start`
+    /// and arrive at random, `rate` a second on average.
+    pub fn new(
+        rate: f64,
+        levels: Vec<(String, u32)>,
+        templates: &[String],
+        start: NaiveDateTime,
+        seed: Option<u64>,
+    ) -> Result<Generator, String> {
+        let templates: Result<Vec<Template>, String> = if templates.is_empty() {
+            DEFAULT_TEMPLATES
+                .iter()
+                .map(|t| Template::parse(t))
+                .collect()
+        } else {
+            templates.iter().map(|t| Template::parse(t)).collect()
+        };
+        Ok(Generator {
+            rng: Rng::new(seed),
+            rate,
+            levels,
+            templates: templates?,
+            time: start,
+        })
+    }
+
+    fn level(&mut self) -> String {
+        let total: u64 = self.levels.iter().map(|(_, w)| *w as u64).sum();
+        let mut pick = self.rng.below(total);
+        for (level, weight) in &self.levels {
+            if pick < *weight as u64 {
+                return level.clone();
+            }
+            pick -= *weight as u64;
+        }
+        unreachable!("pick is below the total weight")
+    }
+
+    /// The next entry and its time. Gaps are exponentially distributed,
+    /// as for independent events.
+    pub fn next_entry(&mut self) -> LogEntry {
+        let gap = -(1.0 - self.rng.unit()).ln() / self.rate;
+        self.time += Duration::microseconds((gap * 1e6) as i64);
+        let level = self.level();
+        // Templates for this level, else the ones for any level, else all.
+        let mut candidates: Vec<&Template> = self
+            .templates
+            .iter()
+            .filter(|t| t.level.as_deref() == Some(level.as_str()))
+            .collect();
+        if candidates.is_empty() {
+            candidates = self
+                .templates
+                .iter()
+                .filter(|t| t.level.is_none())
+                .collect();
+        }
+        if candidates.is_empty() {
+            candidates = self.templates.iter().collect();
+        }
+        let template = candidates[self.rng.below(candidates.len() as u64) as usize];
+        LogEntry {
+            timestamp: self.time.format(crate::timestamp_format()).to_string(),
+            level,
+            message: template.render(&mut self.rng),
+            fields: Map::new(),
+            source: None,
+        }
+    }
+
+    pub fn time(&self) -> NaiveDateTime {
+        self.time
+    }
+}
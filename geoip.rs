@@ -0,0 +1,154 @@
+//! Country and city of the IP addresses in entries, from a MaxMind
+//! database (GeoLite2 or GeoIP2 City or Country), added as entries are
+//! read. Needs the `geoip` feature.
+
+use serde::Deserialize;
+
+use crate::query;
+
+/// The `[geoip]` table of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeoIp {
+    /// Path of the `.mmdb` file.
+    pub database: String,
+    /// Fields holding the address, e.g. [".remote_addr"]. By default the
+    /// usual address fields are tried, then the first public address in
+    /// the message.
+    pub fields: Option<Vec<String>>,
+}
+
+#[cfg(feature = "geoip")]
+pub use reader::apply;
+
+pub fn init(config: Option<&GeoIp>) -> Result<(), String> {
+    let Some(config) = config else {
+        return Ok(());
+    };
+    let fields = match &config.fields {
+        Some(names) => Some(
+            names
+                .iter()
+                .map(|name| {
+                    query::parse_field(name).ok_or_else(|| format!("invalid field '{}'", name))
+                })
+                .collect::<Result<Vec<_>, String>>()?,
+        ),
+        None => None,
+    };
+    #[cfg(feature = "geoip")]
+    return reader::open(&config.database, fields);
+    #[cfg(not(feature = "geoip"))]
+    {
+        let _ = fields;
+        Err(format!(
+            "cannot read {}: built without the geoip feature",
+            config.database
+        ))
+    }
+}
+
+#[cfg(not(feature = "geoip"))]
+pub fn apply(_: &mut crate::LogEntry) -> bool {
+    false
+}
+
+#[cfg(feature = "geoip")]
+mod reader {
+    use std::net::IpAddr;
+    use std::sync::OnceLock;
+
+    use serde_json::Value;
+
+    use crate::query::Field;
+    use crate::LogEntry;
+
+    const DEFAULT_FIELDS: [&str; 6] = ["remote_addr", "client_ip", "ip", "src_ip", "peer", "host"];
+
+    struct Lookup {
+        reader: maxminddb::Reader<Vec<u8>>,
+        fields: Option<Vec<Field>>,
+    }
+
+    static LOOKUP: OnceLock<Lookup> = OnceLock::new();
+
+    pub fn open(database: &str, fields: Option<Vec<Field>>) -> Result<(), String> {
+        let reader = maxminddb::Reader::open_readfile(database)
+            .map_err(|e| format!("{}: {}", database, e))?;
+        let _ = LOOKUP.set(Lookup { reader, fields });
+        Ok(())
+    }
+
+    // Addresses worth looking up: not private, loopback or link-local.
+    fn public(address: IpAddr) -> bool {
+        match address {
+            IpAddr::V4(v4) => {
+                !(v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified())
+            }
+            IpAddr::V6(v6) => !(v6.is_loopback() || v6.is_unspecified()),
+        }
+    }
+
+    // `203.0.113.7`, `203.0.113.7:443` or `[2001:db8::1]:443`.
+    fn parse_address(text: &str) -> Option<IpAddr> {
+        let text = text.trim();
+        text.parse()
+            .ok()
+            .or_else(|| text.parse::<std::net::SocketAddr>().ok().map(|s| s.ip()))
+    }
+
+    fn address(lookup: &Lookup, entry: &LogEntry) -> Option<IpAddr> {
+        if let Some(fields) = &lookup.fields {
+            return fields
+                .iter()
+                .find_map(|field| parse_address(&field.lookup(entry)?));
+        }
+        DEFAULT_FIELDS
+            .iter()
+            .find_map(|name| parse_address(entry.fields.get(*name)?.as_str()?))
+            .or_else(|| {
+                entry
+                    .message
+                    .split(|c: char| !(c.is_ascii_hexdigit() || c == '.' || c == ':'))
+                    .filter_map(parse_address)
+                    .find(|address| public(*address))
+            })
+    }
+
+    /// Adds `country` (ISO code) and `city` for the entry's address, leaving
+    /// fields it already has alone. Whether any were added.
+    pub fn apply(entry: &mut LogEntry) -> bool {
+        let Some(lookup) = LOOKUP.get() else {
+            return false;
+        };
+        let Some(address) = address(lookup, entry) else {
+            return false;
+        };
+        let mut added = false;
+        for (name, value) in locate(lookup, address) {
+            if !entry.fields.contains_key(name) {
+                entry.fields.insert(name.to_string(), Value::from(value));
+                added = true;
+            }
+        }
+        added
+    }
+
+    fn locate(lookup: &Lookup, address: IpAddr) -> Vec<(&'static str, String)> {
+        let mut found = Vec::new();
+        // City databases are a superset of Country ones.
+        if let Ok(city) = lookup.reader.lookup::<maxminddb::geoip2::City>(address) {
+            if let Some(code) = city.country.and_then(|c| c.iso_code) {
+                found.push(("country", code.to_string()));
+            }
+            if let Some(name) = city
+                .city
+                .and_then(|c| c.names)
+                .and_then(|names| names.get("en").copied())
+            {
+                found.push(("city", name.to_string()));
+            }
+        }
+        found
+    }
+}
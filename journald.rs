@@ -0,0 +1,128 @@
+//! The systemd journal, read through `journalctl -o json`.
+
+use std::io::{self, BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde_json::{Map, Value};
+
+use crate::LogEntry;
+
+pub struct Journal {
+    /// Only these units; all when empty.
+    pub units: Vec<String>,
+    /// Only this syslog severity and more severe ones.
+    pub priority: Option<u8>,
+    pub since: Option<NaiveDateTime>,
+    /// Keep reading entries as they are written.
+    pub follow: bool,
+}
+
+// Journal fields that become entry fields under these names. Other fields
+// starting with `_` are journal bookkeeping and are dropped.
+const FIELDS: [(&str, &str); 5] = [
+    ("_SYSTEMD_UNIT", "unit"),
+    ("SYSLOG_IDENTIFIER", "source"),
+    ("_PID", "pid"),
+    ("_HOSTNAME", "host"),
+    ("_COMM", "command"),
+];
+
+// Values that are not valid UTF-8 are exported as arrays of bytes.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes
+                .iter()
+                .filter_map(|b| b.as_u64().map(|b| b as u8))
+                .collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        other => other.to_string(),
+    }
+}
+
+/// An entry from one line of `journalctl -o json`.
+pub fn entry(line: &str) -> Option<LogEntry> {
+    let Ok(Value::Object(record)) = serde_json::from_str::<Value>(line) else {
+        return None;
+    };
+    let get = |name: &str| record.get(name).map(text);
+    let timestamp = get("__REALTIME_TIMESTAMP")
+        .and_then(|micros| micros.parse::<i64>().ok())
+        .and_then(|micros| Local.timestamp_micros(micros).single())
+        .map_or_else(crate::now_timestamp, |t| {
+            t.format(crate::timestamp_format()).to_string()
+        });
+    let level = get("PRIORITY")
+        .and_then(|p| p.parse().ok())
+        .map_or("INFO", crate::syslog::level);
+    let mut fields = Map::new();
+    for (name, field) in FIELDS {
+        if let Some(value) = get(name) {
+            fields.insert(field.to_string(), Value::from(value));
+        }
+    }
+    for (name, value) in &record {
+        if !name.starts_with('_')
+            && !matches!(name.as_str(), "MESSAGE" | "PRIORITY" | "SYSLOG_FACILITY")
+            && !FIELDS.iter().any(|(known, _)| known == name)
+        {
+            fields.insert(name.to_lowercase(), Value::from(text(value)));
+        }
+    }
+    Some(LogEntry {
+        timestamp,
+        level: level.to_string(),
+        message: get("MESSAGE").unwrap_or_default(),
+        fields,
+        source: None,
+    })
+}
+
+impl Journal {
+    /// Calls `each` with every matching entry, oldest first. With
+    /// `follow` this only returns on an error or when journalctl exits.
+    pub fn read(&self, mut each: impl FnMut(LogEntry) -> io::Result<()>) -> io::Result<()> {
+        let mut command = Command::new("journalctl");
+        command.args(["--output=json", "--no-pager", "--quiet"]);
+        for unit in &self.units {
+            command.arg(format!("--unit={}", unit));
+        }
+        if let Some(priority) = self.priority {
+            command.arg(format!("--priority={}", priority));
+        }
+        if let Some(since) = self.since {
+            command.arg(format!("--since={}", since.format("%Y-%m-%d %H:%M:%S")));
+        }
+        if self.follow {
+            command.arg("--follow");
+        }
+        let mut child = command
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("could not run journalctl: {}", e)))?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let read = BufReader::new(stdout).lines().try_for_each(|line| {
+            let line = line?;
+            match entry(&line) {
+                Some(entry) => match crate::ingest(entry, Some(&line), Vec::new()) {
+                    Some(entry) => each(entry),
+                    None => Ok(()),
+                },
+                None => Ok(()),
+            }
+        });
+        if let Err(e) = read {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("journalctl failed: {}", status)));
+        }
+        Ok(())
+    }
+}
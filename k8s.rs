@@ -0,0 +1,187 @@
+//! Pod logs from the Kubernetes API.
+
+use std::io::{self, BufRead, BufReader};
+use std::sync::mpsc;
+use std::thread;
+
+use chrono::{DateTime, Local};
+use serde_json::{Map, Value};
+
+use crate::LogEntry;
+
+/// Where `kubectl proxy` serves the API by default.
+pub const DEFAULT_SERVER: &str = "http://127.0.0.1:8001";
+
+/// The service account token inside a pod.
+const TOKEN_FILE: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+pub struct Source {
+    /// The API server, e.g. a `kubectl proxy` URL.
+    pub server: String,
+    /// Bearer token; read from the service account when running in a pod.
+    pub token: Option<String>,
+    pub namespace: String,
+    pub pod: Option<String>,
+    /// Label selector such as `app=api`, for every matching pod.
+    pub selector: Option<String>,
+    /// Only this container; all of the pod's containers otherwise.
+    pub container: Option<String>,
+    pub since_seconds: Option<i64>,
+    pub tail: Option<usize>,
+    pub follow: bool,
+}
+
+// A pod's log line, `RFC3339 message` with `timestamps=true`. Lines in a
+// format the analyzer understands keep their own level and time;
+// anything else becomes an INFO entry at the time Kubernetes recorded.
+// `None` if the ingest pipeline dropped it.
+fn entry(line: &str, pod: &str, container: &str, namespace: &str) -> Option<LogEntry> {
+    let (time, message) = line.split_once(' ').unwrap_or(("", line));
+    let time = DateTime::parse_from_rfc3339(time).ok().map(|t| {
+        t.with_timezone(&Local)
+            .format(crate::timestamp_format())
+            .to_string()
+    });
+    let message = if time.is_some() { message } else { line };
+    let mut entry = LogEntry::from_line(message).unwrap_or_else(|| LogEntry {
+        timestamp: String::new(),
+        level: "INFO".to_string(),
+        message: message.to_string(),
+        fields: Map::new(),
+        source: None,
+    });
+    if entry.timestamp.is_empty() {
+        entry.timestamp = time.unwrap_or_else(crate::now_timestamp);
+    }
+    for (name, value) in [
+        ("namespace", namespace),
+        ("pod", pod),
+        ("container", container),
+    ] {
+        entry.fields.insert(name.to_string(), Value::from(value));
+    }
+    crate::ingest(entry, Some(line), Vec::new())
+}
+
+impl Source {
+    /// The token given, else the service account's if there is one.
+    pub fn with_default_token(mut self) -> Source {
+        if self.token.is_none() {
+            self.token = std::fs::read_to_string(TOKEN_FILE)
+                .ok()
+                .map(|token| token.trim().to_string());
+        }
+        self
+    }
+
+    fn get(&self, path: &str) -> io::Result<ureq::Response> {
+        let url = format!("{}{}", self.server.trim_end_matches('/'), path);
+        let mut request = ureq::get(&url);
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        request.call().map_err(|e| match e {
+            ureq::Error::Status(code, response) => {
+                let body: Value = response
+                    .into_string()
+                    .ok()
+                    .and_then(|text| serde_json::from_str(&text).ok())
+                    .unwrap_or_default();
+                let message = body["message"].as_str().unwrap_or("request failed");
+                io::Error::other(format!("HTTP {}: {}", code, message))
+            }
+            e => io::Error::other(e),
+        })
+    }
+
+    /// `(pod, container)` pairs to read.
+    fn containers(&self) -> io::Result<Vec<(String, String)>> {
+        let path = match (&self.pod, &self.selector) {
+            (Some(pod), _) => format!("/api/v1/namespaces/{}/pods/{}", self.namespace, pod),
+            (None, selector) => format!(
+                "/api/v1/namespaces/{}/pods?labelSelector={}",
+                self.namespace,
+                crate::http::url_encode(selector.as_deref().unwrap_or(""))
+            ),
+        };
+        let text = self.get(&path)?.into_string()?;
+        let body: Value = serde_json::from_str(&text).map_err(io::Error::other)?;
+        let pods = match body["items"].as_array() {
+            Some(items) => items.clone(),
+            None => vec![body],
+        };
+        let mut containers = Vec::new();
+        for pod in &pods {
+            let name = pod["metadata"]["name"].as_str().unwrap_or_default();
+            for container in pod["spec"]["containers"].as_array().into_iter().flatten() {
+                let container = container["name"].as_str().unwrap_or_default();
+                if self.container.as_deref().is_none_or(|c| c == container) {
+                    containers.push((name.to_string(), container.to_string()));
+                }
+            }
+        }
+        Ok(containers)
+    }
+
+    fn log_path(&self, pod: &str, container: &str) -> String {
+        let mut path = format!(
+            "/api/v1/namespaces/{}/pods/{}/log?container={}&timestamps=true",
+            self.namespace, pod, container
+        );
+        if self.follow {
+            path.push_str("&follow=true");
+        }
+        if let Some(seconds) = self.since_seconds {
+            path.push_str(&format!("&sinceSeconds={}", seconds.max(1)));
+        }
+        if let Some(tail) = self.tail {
+            path.push_str(&format!("&tailLines={}", tail));
+        }
+        path
+    }
+
+    fn stream(&self, pod: &str, container: &str) -> io::Result<impl BufRead + Send + 'static> {
+        let response = self.get(&self.log_path(pod, container))?;
+        Ok(BufReader::new(response.into_reader()))
+    }
+
+    /// Calls `each` with the entries of every matching container. Without
+    /// `follow` the containers' entries are merged by time; with it they
+    /// are passed on as they arrive until every stream ends.
+    pub fn read(&self, each: impl FnMut(LogEntry) -> io::Result<()>) -> io::Result<()> {
+        let containers = self.containers()?;
+        if containers.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no matching pods or containers",
+            ));
+        }
+        if !self.follow {
+            let mut entries = Vec::new();
+            for (pod, container) in &containers {
+                for line in self.stream(pod, container)?.lines() {
+                    entries.extend(entry(&line?, pod, container, &self.namespace));
+                }
+            }
+            entries.sort_by_key(|entry| entry.time());
+            return entries.into_iter().try_for_each(each);
+        }
+        let (sender, received) = mpsc::channel();
+        for (pod, container) in containers {
+            let stream = self.stream(&pod, &container)?;
+            let (sender, namespace) = (sender.clone(), self.namespace.clone());
+            thread::spawn(move || {
+                for line in stream.lines().map_while(Result::ok) {
+                    let Some(entry) = entry(&line, &pod, &container, &namespace) else {
+                        continue;
+                    };
+                    if sender.send(entry).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        received.into_iter().try_for_each(each)
+    }
+}
@@ -0,0 +1,154 @@
+//! One name per level, so `warn`, `Warn` and `WARNING` count as the same
+//! level everywhere, plus levels of the user's own with their severity.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+/// Aliases every log gets, lowercase, to the names the analyzer uses.
+pub const BUILTIN_ALIASES: [(&str, &str); 7] = [
+    ("warn", "WARNING"),
+    ("err", "ERROR"),
+    ("fatal", "CRITICAL"),
+    ("crit", "CRITICAL"),
+    ("information", "INFO"),
+    ("notice", "INFO"),
+    ("dbg", "DEBUG"),
+];
+
+/// The levels every log has, under the names the analyzer uses.
+pub const NAMES: [&str; 7] = [
+    "TRACE", "DEBUG", "INFO", "WARNING", "ERROR", "CRITICAL", "FATAL",
+];
+
+/// The `[levels]` table of the config file, e.g.
+/// `aliases = { sev1 = "CRITICAL" }` and `custom = { AUDIT = 2 }`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Levels {
+    /// More aliases, or other targets for the built-in ones.
+    pub aliases: BTreeMap<String, String>,
+    /// Levels of their own and their severity, from 0 (DEBUG) through 1
+    /// (INFO), 2 (WARNING) and 3 (ERROR) to 4 (CRITICAL).
+    pub custom: BTreeMap<String, u8>,
+}
+
+struct Table {
+    /// Lowercase alias to level.
+    aliases: BTreeMap<String, String>,
+    /// Uppercase custom level to its severity.
+    ranks: BTreeMap<String, u8>,
+}
+
+static TABLE: OnceLock<Table> = OnceLock::new();
+
+fn table() -> &'static Table {
+    TABLE.get_or_init(|| build(&Levels::default()))
+}
+
+fn build(levels: &Levels) -> Table {
+    let mut aliases: BTreeMap<String, String> = BUILTIN_ALIASES
+        .iter()
+        .map(|(alias, level)| (alias.to_string(), level.to_string()))
+        .collect();
+    for (alias, level) in &levels.aliases {
+        aliases.insert(alias.to_lowercase(), level.to_uppercase());
+    }
+    let ranks = levels
+        .custom
+        .iter()
+        .map(|(level, rank)| (level.to_uppercase(), *rank))
+        .collect();
+    Table { aliases, ranks }
+}
+
+/// Takes the `[levels]` of the config.
+pub fn init(levels: &Levels) -> Result<(), String> {
+    if let Some((level, rank)) = levels.custom.iter().find(|(_, rank)| **rank > 4) {
+        return Err(format!("{} = {}: severity must be 0 to 4", level, rank));
+    }
+    let _ = TABLE.set(build(levels));
+    Ok(())
+}
+
+/// The name `level` is counted under: its alias target, else itself in
+/// uppercase.
+pub fn normalize(level: &str) -> String {
+    let level = level.trim();
+    match table().aliases.get(&level.to_lowercase()) {
+        Some(name) => name.clone(),
+        None => level.to_uppercase(),
+    }
+}
+
+/// The severity of a custom level, see `alert::severity_rank`.
+pub fn custom_rank(level: &str) -> Option<u8> {
+    table().ranks.get(&normalize(level)).copied()
+}
+
+/// `level` as given on the command line, normalized, if it is a built-in
+/// level, an alias or a custom level.
+pub fn parse(level: &str) -> Result<String, String> {
+    let name = normalize(level);
+    let aliased = table().aliases.contains_key(&level.trim().to_lowercase());
+    if aliased || NAMES.contains(&name.as_str()) || custom_rank(&name).is_some() {
+        Ok(name)
+    } else {
+        Err(format!(
+            "unknown level '{}': use {}, an alias or a [levels] custom level",
+            level.trim(),
+            NAMES.join(", ")
+        ))
+    }
+}
+
+/// The built-in level as severe as `level`, for sources that only know
+/// those, e.g. journald priorities.
+pub fn standard(level: &str) -> String {
+    match custom_rank(level) {
+        Some(rank) => ["DEBUG", "INFO", "WARNING", "ERROR", "CRITICAL"][rank as usize].to_string(),
+        None => normalize(level),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_aliases() {
+        assert_eq!(normalize("warn"), "WARNING");
+        assert_eq!(normalize(" Err "), "ERROR");
+        assert_eq!(normalize("fatal"), "CRITICAL");
+        assert_eq!(normalize("info"), "INFO");
+        assert_eq!(normalize("audit"), "AUDIT");
+    }
+
+    #[test]
+    fn configured_levels() {
+        let table = build(&Levels {
+            aliases: BTreeMap::from([("sev1".to_string(), "critical".to_string())]),
+            custom: BTreeMap::from([("audit".to_string(), 2)]),
+        });
+        assert_eq!(table.aliases["sev1"], "CRITICAL");
+        assert_eq!(table.aliases["warn"], "WARNING");
+        assert_eq!(table.ranks["AUDIT"], 2);
+    }
+
+    #[test]
+    fn severities_are_bounded() {
+        let levels = Levels {
+            aliases: BTreeMap::new(),
+            custom: BTreeMap::from([("PANIC".to_string(), 5)]),
+        };
+        assert!(init(&levels).is_err());
+    }
+
+    #[test]
+    fn command_line_levels() {
+        assert_eq!(parse("err"), Ok("ERROR".to_string()));
+        assert_eq!(parse("trace"), Ok("TRACE".to_string()));
+        assert!(parse("bogus").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! `logger listen`: a syslog receiver. Messages arriving over UDP, TCP or
+//! a Unix socket are parsed as they come and appended to the log file in
+//! batches.
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{Map, Value};
+
+use crate::{syslog, LogAnalyzer, LogEntry};
+
+/// Longer TCP frames are cut off; UDP datagrams cannot be longer anyway.
+pub const MAX_MESSAGE: usize = 64 * 1024;
+
+/// A message and the host it came from.
+type Received = (String, String);
+
+// A message that is not syslog is still kept: parsed like a log file line
+// if it looks like one, else as an INFO entry. `None` if the ingest
+// pipeline dropped it.
+pub fn entry(message: &str, peer: String) -> Option<LogEntry> {
+    let mut entry = syslog::parse(message)
+        .or_else(|| LogEntry::from_line(message))
+        .unwrap_or_else(|| LogEntry {
+            timestamp: crate::now_timestamp(),
+            level: "INFO".to_string(),
+            message: message.trim_end().to_string(),
+            fields: Map::new(),
+            source: None,
+        });
+    if entry.timestamp.is_empty() {
+        entry.timestamp = crate::now_timestamp();
+    }
+    if !entry.fields.contains_key("host") {
+        entry.fields.insert("host".to_string(), Value::from(peer));
+    }
+    crate::ingest(entry, Some(message), Vec::new())
+}
+
+fn receive_udp(socket: UdpSocket, received: SyncSender<Received>) {
+    let mut buffer = vec![0; MAX_MESSAGE];
+    while let Ok((len, peer)) = socket.recv_from(&mut buffer) {
+        let message = String::from_utf8_lossy(&buffer[..len]).into_owned();
+        if received.send((message, peer.ip().to_string())).is_err() {
+            return;
+        }
+    }
+}
+
+// One frame: octet-counted (`LEN message`) when it starts with a number,
+// else up to the next newline (RFC 6587).
+fn read_frame(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut frame = Vec::new();
+    if reader.fill_buf()?[0].is_ascii_digit() {
+        reader.read_until(b' ', &mut frame)?;
+        if let Ok(len) = String::from_utf8_lossy(&frame).trim_end().parse::<usize>() {
+            frame.clear();
+            reader
+                .take(len.min(MAX_MESSAGE) as u64)
+                .read_to_end(&mut frame)?;
+            return Ok(Some(String::from_utf8_lossy(&frame).into_owned()));
+        }
+    }
+    if !frame.ends_with(b"\n") {
+        reader
+            .take(MAX_MESSAGE as u64)
+            .read_until(b'\n', &mut frame)?;
+    }
+    Ok(Some(String::from_utf8_lossy(&frame).into_owned()))
+}
+
+// Reading stops while the queue is full, so a fast writer is slowed down
+// by its own socket buffer filling up instead of being dropped.
+fn receive_stream(
+    stream: impl Read,
+    peer: String,
+    received: SyncSender<Received>,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    while let Some(message) = read_frame(&mut reader)? {
+        if !message.trim().is_empty() && received.send((message, peer.clone())).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn receive_tcp(stream: TcpStream, received: SyncSender<Received>) -> io::Result<()> {
+    let peer = stream.peer_addr()?.ip().to_string();
+    receive_stream(stream, peer, received)
+}
+
+// Local daemons write a line per entry, plain or NDJSON. A socket file
+// left behind by an earlier run is replaced.
+#[cfg(unix)]
+fn listen_unix(path: &str, received: SyncSender<Received>) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let host = crate::sink::hostname();
+    thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let (host, received) = (host.clone(), received.clone());
+            thread::spawn(move || receive_stream(stream, host, received));
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen_unix(_path: &str, _received: SyncSender<Received>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unix sockets are not available on this platform",
+    ))
+}
+
+pub struct Receiver {
+    pub udp: Option<String>,
+    pub tcp: Option<String>,
+    /// Path of a Unix stream socket to create.
+    pub unix: Option<String>,
+    /// Received entries are appended this often, or as soon as this many
+    /// are waiting.
+    pub flush: Duration,
+    pub buffer: usize,
+    pub log_file: String,
+}
+
+impl Receiver {
+    /// Binds the sockets and runs until an append fails or both sockets
+    /// fail. Entries received since the last append are lost if the
+    /// process is killed.
+    pub fn run(&self) -> io::Result<()> {
+        let (sender, received) = mpsc::sync_channel::<Received>(self.buffer);
+        if let Some(address) = &self.udp {
+            let socket = UdpSocket::bind(address)?;
+            let sender = sender.clone();
+            thread::spawn(move || receive_udp(socket, sender));
+        }
+        if let Some(address) = &self.tcp {
+            let listener = TcpListener::bind(address)?;
+            let sender = sender.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().map_while(Result::ok) {
+                    let sender = sender.clone();
+                    thread::spawn(move || receive_tcp(stream, sender));
+                }
+            });
+        }
+        if let Some(path) = &self.unix {
+            listen_unix(path, sender.clone())?;
+        }
+        drop(sender);
+        let mut pending = Vec::new();
+        let mut deadline = Instant::now() + self.flush;
+        loop {
+            let disconnected =
+                match received.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok((message, peer)) => {
+                        if let Some(entry) = entry(&message, peer) {
+                            crate::emit(&entry);
+                            pending.push(entry);
+                        }
+                        false
+                    }
+                    Err(RecvTimeoutError::Timeout) => false,
+                    Err(RecvTimeoutError::Disconnected) => true,
+                };
+            if !disconnected && Instant::now() < deadline && pending.len() < self.buffer {
+                continue;
+            }
+            if !pending.is_empty() {
+                crate::rotate::before_append(&self.log_file)?;
+                LogAnalyzer::append_to_file(&self.log_file, &pending)?;
+                crate::prometheus::record(&pending, 0);
+                pending.clear();
+            }
+            if disconnected {
+                return Ok(());
+            }
+            deadline = Instant::now() + self.flush;
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Advisory locks, so two processes never write a log file at once and
+//! nobody reads one halfway through a rewrite. The lock is taken on a
+//! hidden `.FILE.lock` next to the file, since saving replaces the file
+//! itself. Writers create it; readers only lock files that have one, so
+//! reading the logs of other programs leaves nothing behind.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use fs2::FileExt;
+
+/// How long to wait for another process to finish before giving up.
+const WAIT: Duration = Duration::from_secs(2);
+
+thread_local! {
+    // The locks this thread holds and how many guards share each: a write
+    // that reads the file back first must not wait for itself.
+    static HELD: RefCell<BTreeMap<String, (File, usize)>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// A held lock, released when the last guard for the file is dropped.
+pub struct Guard(Option<String>);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let Some(name) = &self.0 else {
+            return;
+        };
+        HELD.with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some((_, count)) = held.get_mut(name) {
+                *count -= 1;
+                if *count == 0 {
+                    // Closing the file releases the lock.
+                    held.remove(name);
+                }
+            }
+        });
+    }
+}
+
+/// Locks `filename` for reading, if it has a lock file.
+pub fn shared(filename: &str) -> io::Result<Guard> {
+    acquire(filename, false)
+}
+
+/// Locks `filename` for writing, creating its lock file if needed.
+pub fn exclusive(filename: &str) -> io::Result<Guard> {
+    acquire(filename, true)
+}
+
+/// The lock file of `filename`.
+pub fn path(filename: &str) -> String {
+    let path = Path::new(filename);
+    let base = path.file_name().unwrap_or(path.as_os_str());
+    path.with_file_name(format!(".{}.lock", base.to_string_lossy()))
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn acquire(filename: &str, exclusive: bool) -> io::Result<Guard> {
+    let name = path(filename);
+    let nested = HELD.with(|held| match held.borrow_mut().get_mut(&name) {
+        Some((_, count)) => {
+            *count += 1;
+            true
+        }
+        None => false,
+    });
+    if nested {
+        return Ok(Guard(Some(name)));
+    }
+    let file = if exclusive {
+        OpenOptions::new().create(true).append(true).open(&name)?
+    } else {
+        match File::open(&name) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Guard(None)),
+            Err(e) => return Err(e),
+        }
+    };
+    let contended = fs2::lock_contended_error().kind();
+    let deadline = Instant::now() + WAIT;
+    loop {
+        let locked = if exclusive {
+            FileExt::try_lock_exclusive(&file)
+        } else {
+            FileExt::try_lock_shared(&file)
+        };
+        match locked {
+            Ok(()) => break,
+            Err(e) if e.kind() != contended => return Err(e),
+            Err(_) if Instant::now() >= deadline => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("locked by another process (see {})", name),
+                ))
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(50)),
+        }
+    }
+    HELD.with(|held| held.borrow_mut().insert(name.clone(), (file, 1)));
+    Ok(Guard(Some(name)))
+}
@@ -0,0 +1,216 @@
+use std::sync::OnceLock;
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::LogEntry;
+
+/// log4j's TTCC layout, used when `format = "log4j"` is set without a
+/// `log4j_pattern`.
+pub const DEFAULT_PATTERN: &str = "%d [%t] %-5p %c - %m%n";
+
+/// What a conversion specifier captures.
+#[derive(Debug, Clone)]
+enum Part {
+    Date(Option<String>),
+    Level,
+    Message,
+    /// Stored as a structured field; `numeric` ones are kept as numbers.
+    Field {
+        name: String,
+        numeric: bool,
+    },
+}
+
+/// A log4j/logback `PatternLayout` compiled into a line matcher.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    regex: Regex,
+    parts: Vec<Part>,
+}
+
+static LAYOUT: OnceLock<Layout> = OnceLock::new();
+
+/// Sets the layout used by the `log4j` input format.
+pub fn init(pattern: Option<&str>) -> Result<(), String> {
+    let layout = Layout::compile(pattern.unwrap_or(DEFAULT_PATTERN))?;
+    let _ = LAYOUT.set(layout);
+    Ok(())
+}
+
+/// Parses a line with the configured layout (the default one if `init`
+/// was never called).
+pub fn parse(line: &str) -> Option<LogEntry> {
+    LAYOUT
+        .get_or_init(|| Layout::compile(DEFAULT_PATTERN).expect("default pattern compiles"))
+        .parse(line)
+}
+
+// Converts a SimpleDateFormat / DateTimeFormatter pattern to strftime, for
+// the common letters. Named formats are the ones log4j and logback define.
+fn date_format(java: &str) -> String {
+    let java = match java {
+        "ISO8601" | "DEFAULT" => "yyyy-MM-dd HH:mm:ss,SSS",
+        "ISO8601_BASIC" => "yyyyMMdd'T'HHmmss,SSS",
+        "ABSOLUTE" => "HH:mm:ss,SSS",
+        "DATE" => "dd MMM yyyy HH:mm:ss,SSS",
+        "COMPACT" => "yyyyMMddHHmmssSSS",
+        other => other,
+    };
+    let chars: Vec<char> = java.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' {
+            i += 1;
+            while i < chars.len() && chars[i] != '\'' {
+                out.push(chars[i]);
+                i += 1;
+            }
+            i += 1;
+            continue;
+        }
+        let run = chars[i..].iter().take_while(|&&d| d == c).count();
+        let spec = match (c, run) {
+            ('y', 2) => "%y",
+            ('y', _) => "%Y",
+            ('M', 1 | 2) => "%m",
+            ('M', 3) => "%b",
+            ('M', _) => "%B",
+            ('d', _) => "%d",
+            ('H', _) => "%H",
+            ('h', _) => "%I",
+            ('m', _) => "%M",
+            ('s', _) => "%S",
+            ('S', _) => "%3f",
+            ('a', _) => "%p",
+            ('E', 1..=3) => "%a",
+            ('E', _) => "%A",
+            ('Z' | 'X', _) => "%z",
+            _ => {
+                out.extend(std::iter::repeat_n(c, run));
+                i += run;
+                continue;
+            }
+        };
+        out.push_str(spec);
+        i += run;
+    }
+    out
+}
+
+// The timestamp in the native format when the date text can be read,
+// otherwise as it was written.
+fn native_timestamp(text: &str, format: Option<&str>) -> String {
+    let format = date_format(format.unwrap_or("ISO8601"));
+    NaiveDateTime::parse_from_str(text, &format)
+        .or_else(|_| NaiveDateTime::parse_from_str(&text.replace(',', "."), "%Y-%m-%d %H:%M:%S%.f"))
+        .map(|t| t.format(crate::TIMESTAMP_FORMAT).to_string())
+        .unwrap_or_else(|_| text.to_string())
+}
+
+impl Layout {
+    /// Compiles a pattern such as `%d{HH:mm:ss.SSS} [%thread] %-5level
+    /// %logger{36} - %msg%n`. The logger (`%c`, `%logger`) becomes the
+    /// `source` field; thread, class, method, file, line and MDC values
+    /// (`%X{key}`) become fields too.
+    pub fn compile(pattern: &str) -> Result<Layout, String> {
+        let mut regex = String::from("^");
+        let mut parts = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                regex.push_str(&regex::escape(&c.to_string()));
+                continue;
+            }
+            if chars.next_if_eq(&'%').is_some() {
+                regex.push('%');
+                continue;
+            }
+            // Width and truncation modifiers such as -5 or .30 only pad.
+            let padded = chars.peek().is_some_and(|c| "-.0123456789".contains(*c));
+            while chars.next_if(|c| "-.0123456789".contains(*c)).is_some() {}
+            let name: String =
+                std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphabetic())).collect();
+            let option = if chars.next_if_eq(&'{').is_some() {
+                let option: String = std::iter::from_fn(|| chars.next_if(|c| *c != '}')).collect();
+                if chars.next_if_eq(&'}').is_none() {
+                    return Err(format!("unclosed {{ after %{}", name));
+                }
+                Some(option)
+            } else {
+                None
+            };
+            let field = |name: &str, numeric: bool| Part::Field {
+                name: name.to_string(),
+                numeric,
+            };
+            let (part, capture) = match name.as_str() {
+                "d" | "date" => (Part::Date(option), ".+?"),
+                "p" | "le" | "level" => (Part::Level, "[A-Za-z]+"),
+                "m" | "msg" | "message" => (Part::Message, ".*"),
+                "c" | "lo" | "logger" => (field("source", false), r"\S*"),
+                "C" | "class" => (field("class", false), r"\S*"),
+                "t" | "thread" => (field("thread", false), ".*?"),
+                "M" | "method" => (field("method", false), r"\S*"),
+                "F" | "file" => (field("file", false), r"\S*"),
+                "L" | "line" => (field("line", true), r"\d+|\?"),
+                "r" | "relative" => (field("relative", true), r"\d+"),
+                "X" | "mdc" => match option {
+                    Some(key) => (field(&key, false), ".*?"),
+                    None => (field("mdc", false), ".*?"),
+                },
+                "n" => {
+                    if chars.peek().is_some() {
+                        return Err("%n must end the pattern".to_string());
+                    }
+                    break;
+                }
+                "" => return Err("pattern ends with a lone %".to_string()),
+                other => return Err(format!("unsupported conversion %{}", other)),
+            };
+            if padded {
+                regex.push_str(&format!(r"\s*({})\s*", capture));
+            } else {
+                regex.push_str(&format!("({})", capture));
+            }
+            parts.push(part);
+        }
+        regex.push('$');
+        if !parts.iter().any(|p| matches!(p, Part::Message)) {
+            return Err("pattern has no %m".to_string());
+        }
+        let regex = Regex::new(&regex).map_err(|e| e.to_string())?;
+        Ok(Layout { regex, parts })
+    }
+
+    pub fn parse(&self, line: &str) -> Option<LogEntry> {
+        let captures = self.regex.captures(line)?;
+        let mut entry = LogEntry {
+            timestamp: String::new(),
+            level: "INFO".to_string(),
+            message: String::new(),
+            fields: Map::new(),
+            source: None,
+        };
+        for (part, text) in self.parts.iter().zip(captures.iter().skip(1)) {
+            let text = text.map_or("", |m| m.as_str()).trim();
+            match part {
+                Part::Date(format) => entry.timestamp = native_timestamp(text, format.as_deref()),
+                Part::Level => entry.level = text.to_uppercase(),
+                Part::Message => entry.message = text.to_string(),
+                Part::Field { name, numeric } => {
+                    let value = match text.parse::<i64>() {
+                        Ok(n) if *numeric => Value::from(n),
+                        _ if text.is_empty() || text == "?" => continue,
+                        _ => Value::from(text),
+                    };
+                    entry.fields.insert(name.clone(), value);
+                }
+            }
+        }
+        Some(entry)
+    }
+}
@@ -0,0 +1,126 @@
+//! Grafana Loki's push API.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use chrono::{Local, TimeZone};
+use serde_json::{json, Value};
+
+use crate::export::Compression;
+use crate::sink::{self, Options, Sink};
+use crate::LogEntry;
+
+/// Labels sent when no --label is given.
+pub const DEFAULT_LABELS: [&str; 3] = ["level", "host", "file"];
+
+enum Label {
+    /// `level`, `host` or `file`, else an entry field such as `service`
+    /// or `.http.method`.
+    From(String),
+    /// `name=value`.
+    Fixed(String, String),
+}
+
+/// Pushes entries to Loki, one stream per distinct set of label values.
+pub struct Pusher {
+    url: String,
+    headers: Vec<(String, String)>,
+    labels: Vec<Label>,
+    host: String,
+    file: String,
+    compression: Compression,
+}
+
+// Loki label names are identifiers.
+fn label_name(name: &str) -> String {
+    name.trim_start_matches('.')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+impl Pusher {
+    /// `options.to` is Loki's base URL, e.g. http://loki:3100; the push
+    /// path is added unless the URL already has it.
+    pub fn new(options: &Options) -> Pusher {
+        let url = if options.to.contains("/loki/api/") {
+            options.to.clone()
+        } else {
+            format!("{}/loki/api/v1/push", options.to.trim_end_matches('/'))
+        };
+        let labels = options
+            .labels
+            .iter()
+            .map(|label| match label.split_once('=') {
+                Some((name, value)) => Label::Fixed(label_name(name), value.to_string()),
+                None => Label::From(label.clone()),
+            })
+            .collect();
+        Pusher {
+            url,
+            headers: options.headers.clone(),
+            labels,
+            host: sink::hostname(),
+            file: options.file.clone(),
+            compression: options.compression,
+        }
+    }
+
+    fn labels(&self, entry: &LogEntry) -> BTreeMap<String, String> {
+        let mut labels = BTreeMap::new();
+        for label in &self.labels {
+            let (name, value) = match label {
+                Label::Fixed(name, value) => (name.clone(), Some(value.clone())),
+                Label::From(name) => {
+                    let value = match name.as_str() {
+                        "level" => Some(entry.level.to_lowercase()),
+                        "host" => Some(self.host.clone()),
+                        "file" => Some(self.file.clone()),
+                        field => crate::query::parse_field(&format!(
+                            ".{}",
+                            field.trim_start_matches('.')
+                        ))
+                        .and_then(|f| f.lookup(entry).map(|v| v.into_owned())),
+                    };
+                    (label_name(name), value)
+                }
+            };
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                labels.insert(name, value);
+            }
+        }
+        labels
+    }
+}
+
+// Nanoseconds since the epoch, as the string Loki expects; entries
+// without a readable time get the current one.
+fn nanos(entry: &LogEntry) -> String {
+    let time = entry
+        .time()
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .unwrap_or_else(Local::now);
+    time.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+impl Sink for Pusher {
+    fn send(&mut self, entries: &[LogEntry]) -> io::Result<()> {
+        let mut streams: BTreeMap<BTreeMap<String, String>, Vec<Value>> = BTreeMap::new();
+        for entry in entries {
+            streams
+                .entry(self.labels(entry))
+                .or_default()
+                .push(json!([nanos(entry), entry.to_line()]));
+        }
+        let streams: Vec<Value> = streams
+            .into_iter()
+            .map(|(labels, values)| json!({ "stream": labels, "values": values }))
+            .collect();
+        sink::post_compressed(
+            &self.url,
+            &self.headers,
+            &json!({ "streams": streams }),
+            self.compression,
+        )
+    }
+}
@@ -1,102 +1,1010 @@
-use std::collections::HashMap;
+extern crate alloc;
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use chrono::NaiveDateTime;
+use clap::Parser;
+use rustyline::error::ReadlineError;
+use serde_json::{Map, Value};
+
+mod access;
+#[cfg(feature = "async")]
+mod aio;
+mod alert;
+mod analysis;
+mod archive;
+mod baseline;
+mod batch;
+mod binary;
+mod capacity;
+mod catalog;
+mod chain;
+mod chart;
+mod cli;
+mod cloud;
+mod color;
+mod compare;
+mod config;
+mod correlation;
+mod crash;
+mod crypt;
+mod directives;
+mod display;
+mod elastic;
+mod encoding;
+mod entry;
+mod error;
+#[cfg(all(windows, feature = "eventlog"))]
+mod eventlog;
+mod export;
+mod extract;
+mod facade;
+mod fetch;
+mod fields;
+mod follow;
+mod gate;
+mod gelf;
+mod generate;
+mod geoip;
+mod http;
+#[cfg(feature = "journald")]
+mod journald;
+mod k8s;
+mod levels;
+mod listen;
+mod lock;
+mod log4j;
+mod loki;
+mod metrics;
+mod otlp;
+mod pager;
+mod pipeline;
+mod plugin;
+mod prometheus;
+mod provenance;
+mod query;
+mod redact;
+mod replay;
+mod report;
+mod retention;
+mod rotate;
+mod rules;
+#[cfg(feature = "s3")]
+mod s3;
+mod schedule;
+mod script;
+mod server;
+mod shared;
+mod sink;
+mod smtp;
+mod ssh;
+mod supervise;
+mod syslog;
+mod trace;
+mod tui;
+mod useragent;
+mod watch;
+#[cfg(any(feature = "s3", all(windows, feature = "eventlog")))]
+mod xml;
+
+use entry::{LogEntry, Source};
+use error::LoggerError;
 
-#[derive(Debug, Clone)]
-struct LogEntry {
-    timestamp: String,
-    level: String,
-    message: String,
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Set from the config file; new entries are stamped with it and parsing
+/// tries it before the built-in formats.
+static CUSTOM_TIMESTAMP_FORMAT: OnceLock<String> = OnceLock::new();
+
+fn timestamp_format() -> &'static str {
+    CUSTOM_TIMESTAMP_FORMAT
+        .get()
+        .map_or(TIMESTAMP_FORMAT, String::as_str)
+}
+
+/// Inputs that are read from elsewhere rather than from a local file.
+fn is_remote(filename: &str) -> bool {
+    fetch::is_url(filename)
+        || ["s3://", "ssh://"]
+            .iter()
+            .any(|scheme| filename.starts_with(scheme))
+}
+
+fn now_timestamp() -> String {
+    chrono::Local::now().format(timestamp_format()).to_string()
+}
+
+/// Set by --emit or the config: new entries are also printed to stdout as
+/// one JSON object per line, for container log collectors.
+static EMIT_STDOUT: AtomicBool = AtomicBool::new(false);
+
+fn emitting() -> bool {
+    EMIT_STDOUT.load(Ordering::Relaxed)
+}
+
+fn emit(entry: &LogEntry) {
+    if emitting() {
+        println!("{}", export::entry_json(entry));
+    }
+}
+
+/// Runs the ingest pipeline (extraction, redaction, configured stages) on
+/// a new entry read from `raw`, noting what changed it when provenance is
+/// on; `None` if a stage dropped it. Levels are normalized first, see
+/// `levels`. An entry whose stored line differs from `raw` was normalized
+/// by parsing or by its level.
+fn ingest(mut entry: LogEntry, raw: Option<&str>, mut transforms: Vec<&str>) -> Option<LogEntry> {
+    entry.level = levels::normalize(&entry.level);
+    let raw = raw.filter(|_| provenance::enabled());
+    if raw.is_some_and(|raw| raw != entry.to_line()) {
+        transforms.push("normalize");
+    }
+    let mut entry = pipeline::run(entry, &mut transforms)?;
+    if let Some(raw) = raw {
+        provenance::record(&mut entry, raw, &transforms);
+    }
+    Some(entry)
+}
+
+/// How input lines are parsed; `auto` detects JSON, pipe and logfmt per
+/// line. `log4j` uses the configured `log4j_pattern` layout; `heroku` and
+/// `cloudwatch` read what `heroku logs` and the `aws logs` commands print;
+/// `access` reads Apache and nginx access logs, which `auto` also detects;
+/// `plugin` uses the parsers of the configured WASM `plugins`, which `auto`
+/// tries last.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum InputFormat {
+    #[default]
+    Auto,
+    Pipe,
+    Json,
+    Logfmt,
+    Log4j,
+    Heroku,
+    Cloudwatch,
+    Access,
+    Plugin,
 }
 
 impl LogEntry {
+    fn parse(line: &str, format: InputFormat) -> Option<LogEntry> {
+        match format {
+            InputFormat::Auto => LogEntry::from_line(line),
+            InputFormat::Pipe => LogEntry::from_pipe(line),
+            InputFormat::Json => LogEntry::from_json(line),
+            InputFormat::Logfmt => LogEntry::from_logfmt(line),
+            InputFormat::Log4j => log4j::parse(line),
+            InputFormat::Heroku => cloud::from_heroku(line),
+            InputFormat::Cloudwatch => cloud::from_cloudwatch(line),
+            InputFormat::Access => access::parse(line),
+            InputFormat::Plugin => plugin::parse(line),
+        }
+    }
+
     fn from_line(line: &str) -> Option<LogEntry> {
-        let parts: Vec<&str> = line.splitn(3, '|').collect();
-        if parts.len() == 3 {
-            Some(LogEntry {
-                timestamp: parts[0].trim().to_string(),
-                level: parts[1].trim().to_string(),
-                message: parts[2].trim().to_string(),
-            })
-        } else {
-            None
+        if line.trim_start().starts_with('{') {
+            return LogEntry::from_json(line);
         }
+        // Heroku lines are recognizable and may contain `|` in the message.
+        cloud::from_heroku(line)
+            .or_else(|| LogEntry::from_pipe(line))
+            .or_else(|| LogEntry::from_logfmt(line))
+            .or_else(|| access::parse(line))
+            .or_else(|| plugin::parse(line))
     }
 
+    /// Parses a JSON object line. Well-known keys become the timestamp,
+    /// level and message; everything else is kept as structured fields.
+    fn from_json(line: &str) -> Option<LogEntry> {
+        let Ok(Value::Object(mut fields)) = serde_json::from_str(line) else {
+            return None;
+        };
+        let mut take = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| fields.remove(*key))
+                .map(|value| match value {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                })
+        };
+        let timestamp = take(&["timestamp", "ts", "time", "@timestamp"]).unwrap_or_default();
+        let level = take(&["level", "severity", "lvl"]).unwrap_or_else(|| "INFO".to_string());
+        let message = take(&["message", "msg"]).unwrap_or_default();
+        Some(LogEntry {
+            timestamp,
+            level,
+            message,
+            fields,
+            source: None,
+        })
+    }
+
+    /// Parses `key=value key2="quoted value"` lines. A line only counts as
+    /// logfmt when it carries a level or message key.
+    fn from_logfmt(line: &str) -> Option<LogEntry> {
+        let mut fields = logfmt_fields(line);
+        let mut take = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| fields.remove(*key))
+                .map(|value| query::render(&value))
+        };
+        let level = take(&["level", "lvl", "severity"]);
+        let message = take(&["msg", "message"]);
+        if level.is_none() && message.is_none() {
+            return None;
+        }
+        let timestamp = take(&["time", "ts", "timestamp"]).unwrap_or_default();
+        Some(LogEntry {
+            timestamp,
+            level: level.unwrap_or_else(|| "INFO".to_string()),
+            message: message.unwrap_or_default(),
+            fields,
+            source: None,
+        })
+    }
+
+    /// Entries with structured fields are stored as JSON lines so the
+    /// fields survive a save/load round trip, and so are entries whose pipe
+    /// line would not read back the same.
     fn to_line(&self) -> String {
-        format!("{}|{}|{}", self.timestamp, self.level, self.message)
+        if self.fields.is_empty() && self.pipe_safe() {
+            self.to_pipe()
+        } else {
+            export::entry_json(self).to_string()
+        }
+    }
+
+    // Line breaks, surrounding whitespace (trimmed on parse) and `|` before
+    // the message do not survive the pipe format; a leading `{` would be
+    // read as JSON.
+    fn pipe_safe(&self) -> bool {
+        let plain = |text: &str| text.trim() == text && !text.contains(['\n', '\r']);
+        plain(&self.timestamp)
+            && plain(&self.level)
+            && plain(&self.message)
+            && !self.timestamp.contains('|')
+            && !self.level.contains('|')
+            && !self.timestamp.starts_with('{')
+    }
+
+    /// Parses the native format, falling back to RFC 3339 (converted to
+    /// local time) and ISO 8601 without an offset, as found in JSON logs.
+    fn time(&self) -> Option<NaiveDateTime> {
+        parse_time(&self.timestamp)
+    }
+
+    /// Tags attached in the repl, kept in the `tags` field so they are
+    /// saved with the entry.
+    fn tags(&self) -> Vec<&str> {
+        self.fields
+            .get("tags")
+            .and_then(Value::as_array)
+            .map(|tags| tags.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags().iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Notes the line the entry was read from, with the host and pid from
+    /// the fields that parsers such as syslog and journald set.
+    fn locate(&mut self, line: Option<usize>) {
+        let pid = self.fields.get("pid").and_then(|pid| match pid {
+            Value::String(text) => text.parse().ok(),
+            other => other.as_u64(),
+        });
+        self.source = Some(Source {
+            file: None,
+            line,
+            host: self
+                .fields
+                .get("host")
+                .and_then(Value::as_str)
+                .map(String::from),
+            pid,
+        });
+    }
+
+    /// The source as `{file, line, host, pid}`, the `.source` of queries
+    /// and filters.
+    fn source_json(&self) -> Option<Value> {
+        let source = self.source.as_ref()?;
+        let mut object = Map::new();
+        let mut set = |key: &str, value: Option<Value>| {
+            if let Some(value) = value {
+                object.insert(key.to_string(), value);
+            }
+        };
+        set("file", source.file.clone().map(Value::from));
+        set("line", source.line.map(Value::from));
+        set("host", source.host.clone().map(Value::from));
+        set("pid", source.pid.map(Value::from));
+        Some(Value::Object(object))
+    }
+}
+
+fn parse_time(text: &str) -> Option<NaiveDateTime> {
+    CUSTOM_TIMESTAMP_FORMAT
+        .get()
+        .and_then(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| NaiveDateTime::parse_from_str(text, TIMESTAMP_FORMAT).ok())
+        .or_else(|| {
+            chrono::DateTime::parse_from_rfc3339(text)
+                .ok()
+                .map(|t| t.with_timezone(&chrono::Local).naive_local())
+        })
+        .or_else(|| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f").ok())
+}
+
+// The key/value pairs of a logfmt line; bare keys are `true`.
+fn logfmt_fields(line: &str) -> Map<String, Value> {
+    let mut fields = Map::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let key: String =
+            std::iter::from_fn(|| chars.next_if(|c| *c != '=' && !c.is_whitespace())).collect();
+        if key.is_empty() {
+            break;
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            fields.insert(key, Value::Bool(true));
+            continue;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    c => value.push(c),
+                }
+            }
+            fields.insert(key, Value::String(value));
+        } else {
+            value.extend(std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace())));
+            fields.insert(key, logfmt_value(value));
+        }
+    }
+    fields
+}
+
+// logfmt is untyped; keep numbers and booleans typed so they can be
+// compared and aggregated like JSON fields.
+fn logfmt_value(raw: String) -> Value {
+    if let Ok(n) = raw.parse::<i64>() {
+        return Value::from(n);
+    }
+    if let Some(n) = raw
+        .parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+    {
+        return Value::Number(n);
+    }
+    match raw.as_str() {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::String(raw),
     }
 }
 
 struct LogAnalyzer {
     entries: Vec<LogEntry>,
+    format: InputFormat,
+    /// How many leading entries are already in the log file, so `save`
+    /// only has to append the rest. `None` forces a full rewrite.
+    persisted: Option<usize>,
+    /// Write-ahead journal that added entries go to until the next save.
+    journal: Option<String>,
+    /// Entries and `persisted` from before the last destructive change.
+    undo: Option<(Vec<LogEntry>, Option<usize>)>,
+    /// Line numbers and text of lines the last load could not parse.
+    skipped: Vec<(usize, String)>,
+    /// strftime format `add_entry` stamps entries with.
+    timestamp_format: String,
+    /// Only the newest this many entries are kept.
+    max_entries: Option<usize>,
+    /// Drop a loaded entry identical to the one before it.
+    dedup: bool,
+    /// Whether loaded entries go through the ingest pipeline.
+    pipeline: bool,
 }
 
-impl LogAnalyzer {
-    fn new() -> LogAnalyzer {
+/// How a new analyzer reads and keeps entries, see `LogAnalyzer::builder`.
+struct AnalyzerBuilder {
+    format: InputFormat,
+    timestamp_format: String,
+    max_entries: Option<usize>,
+    dedup: bool,
+    pipeline: bool,
+}
+
+impl AnalyzerBuilder {
+    fn format(mut self, format: InputFormat) -> AnalyzerBuilder {
+        self.format = format;
+        self
+    }
+
+    fn timestamp_format(mut self, format: &str) -> AnalyzerBuilder {
+        self.timestamp_format = format.to_string();
+        self
+    }
+
+    /// Keeps only the newest `max` entries, dropping older ones as new
+    /// ones arrive. Meant for analyzers that are never saved, which would
+    /// lose the dropped entries from the file.
+    fn max_entries(mut self, max: usize) -> AnalyzerBuilder {
+        self.max_entries = Some(max);
+        self
+    }
+
+    /// Collapses runs of identical entries (same time, level, message and
+    /// fields) into the first.
+    fn dedup(mut self, dedup: bool) -> AnalyzerBuilder {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Whether entries go through extraction, redaction and the configured
+    /// stages as they are loaded. Off for files this program wrote, whose
+    /// entries went through it already.
+    fn pipeline(mut self, pipeline: bool) -> AnalyzerBuilder {
+        self.pipeline = pipeline;
+        self
+    }
+
+    fn build(self) -> LogAnalyzer {
         LogAnalyzer {
             entries: Vec::new(),
+            format: self.format,
+            persisted: None,
+            journal: None,
+            undo: None,
+            skipped: Vec::new(),
+            timestamp_format: self.timestamp_format,
+            max_entries: self.max_entries,
+            dedup: self.dedup,
+            pipeline: self.pipeline,
         }
     }
+}
 
-    fn load_from_file(&mut self, filename: &str) -> io::Result<()> {
-        let path = Path::new(filename);
-        if path.exists() {
-            let file = File::open(path)?;
-            let reader = BufReader::new(file);
-            
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    if let Some(entry) = LogEntry::from_line(&line) {
-                        self.entries.push(entry);
+/// Where unparseable lines are copied on load, from the config file.
+static QUARANTINE_FILE: OnceLock<String> = OnceLock::new();
+
+/// Input format from the config file, picked up by every new analyzer.
+static DEFAULT_INPUT_FORMAT: OnceLock<InputFormat> = OnceLock::new();
+
+impl LogAnalyzer {
+    fn new() -> LogAnalyzer {
+        LogAnalyzer::builder().build()
+    }
+
+    /// Starts from the input and timestamp formats of the config, with the
+    /// ingest pipeline on, no limit and no deduplication.
+    fn builder() -> AnalyzerBuilder {
+        AnalyzerBuilder {
+            format: InputFormat::Auto,
+            timestamp_format: TIMESTAMP_FORMAT.to_string(),
+            max_entries: None,
+            dedup: false,
+            pipeline: true,
+        }
+        .format(DEFAULT_INPUT_FORMAT.get().copied().unwrap_or_default())
+        .timestamp_format(timestamp_format())
+    }
+
+    // Adds a loaded entry, unless it repeats the last one and duplicates
+    // are dropped.
+    fn accept(&mut self, entry: LogEntry) {
+        let repeated = self.entries.last().is_some_and(|last| {
+            last.timestamp == entry.timestamp
+                && last.level == entry.level
+                && last.message == entry.message
+                && last.fields == entry.fields
+        });
+        if !(self.dedup && repeated) {
+            self.entries.push(entry);
+        }
+    }
+
+    // Drops the oldest entries beyond `max_entries`.
+    fn limit(&mut self) {
+        let Some(max) = self.max_entries else {
+            return;
+        };
+        if self.entries.len() > max {
+            self.entries.drain(..self.entries.len() - max);
+            self.persisted = None;
+        }
+    }
+
+    /// Adds entries read elsewhere, which went through the pipeline already.
+    fn extend(&mut self, entries: Vec<LogEntry>) {
+        for entry in entries {
+            self.accept(entry);
+        }
+        self.limit();
+    }
+
+    // Runs the pipeline on a loaded entry, if it is on.
+    fn ingest(
+        &self,
+        entry: LogEntry,
+        raw: Option<&str>,
+        transforms: Vec<&str>,
+    ) -> Option<LogEntry> {
+        if self.pipeline {
+            ingest(entry, raw, transforms)
+        } else {
+            Some(entry)
+        }
+    }
+
+    /// Loads `filename` (`-` for stdin), reporting lines that could not be
+    /// parsed on stderr and copying them to the quarantine file if one is
+    /// configured.
+    fn load_from_file(&mut self, filename: &str) -> Result<(), LoggerError> {
+        // Files read back for rewriting skip the pipeline, and retention.
+        if self.pipeline {
+            retention::enforce(filename).map_err(|e| LoggerError::io(filename, e))?;
+        }
+        self.skipped.clear();
+        let before = self.entries.len();
+        self.read_file(filename)
+            .map_err(|e| LoggerError::io(filename, e))?;
+        let source = if filename == "-" { "stdin" } else { filename };
+        for entry in &mut self.entries[before..] {
+            entry.source.get_or_insert_with(Source::default).file = Some(source.to_string());
+        }
+        self.report_skipped(filename)
+            .map_err(|e| LoggerError::io(QUARANTINE_FILE.get().map_or(filename, String::as_str), e))
+    }
+
+    fn read_file(&mut self, filename: &str) -> io::Result<()> {
+        if filename == "-" {
+            let reader = export::decompress(crypt::decrypt(io::stdin().lock())?)?;
+            self.load_stream(reader)
+        } else if fetch::is_url(filename) {
+            self.load_stream(fetch::open(filename)?)
+        } else if filename.starts_with("ssh://") {
+            let remote = ssh::Remote::parse(filename).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "expected ssh://host/path")
+            })?;
+            self.load_stream(remote.open()?)
+        } else if filename.starts_with("s3://") {
+            #[cfg(feature = "s3")]
+            return self.load_stream(s3::open(filename)?);
+            #[cfg(not(feature = "s3"))]
+            Err(io::Error::other("built without the s3 feature"))
+        } else if Path::new(filename).exists() {
+            let _lock = lock::shared(filename)?;
+            let file = File::open(filename)?;
+            let reader = export::decompress(crypt::decrypt(BufReader::new(file))?)?;
+            self.load_stream(reader)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Reads the binary format, or text in the input encoding.
+    fn load_stream(&mut self, mut reader: impl BufRead) -> io::Result<()> {
+        if !reader.fill_buf()?.starts_with(binary::MAGIC) {
+            return self.load_from_reader(encoding::decode(reader)?);
+        }
+        for (index, entry) in binary::decode(reader)?.into_iter().enumerate() {
+            let raw = provenance::enabled().then(|| entry.to_line());
+            if let Some(mut entry) = self.ingest(entry, raw.as_deref(), Vec::new()) {
+                entry.locate(Some(index + 1));
+                self.accept(entry);
+            }
+        }
+        self.limit();
+        Ok(())
+    }
+
+    fn report_skipped(&self, filename: &str) -> io::Result<()> {
+        let Some((first, _)) = self.skipped.first() else {
+            return Ok(());
+        };
+        let source = if filename == "-" { "stdin" } else { filename };
+        eprint!(
+            "Skipped {} unparseable line(s) in {}, first at line {}",
+            self.skipped.len(),
+            source,
+            first
+        );
+        let Some(quarantine) = QUARANTINE_FILE.get() else {
+            eprintln!();
+            return Ok(());
+        };
+        eprintln!("; copied to {}", quarantine);
+        let mut out = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(quarantine)?;
+        for (number, line) in &self.skipped {
+            writeln!(out, "{}:{}: {}", source, number, line)?;
+        }
+        Ok(())
+    }
+
+    // Parses line by line as the input arrives; lines that are not valid
+    // UTF-8 are decoded as described in `encoding::decode_line`, and lines
+    // that still do not parse are kept in `skipped`. CloudWatch
+    // JSON exports are one document, so that format reads all input first.
+    fn load_from_reader(&mut self, mut reader: impl BufRead) -> io::Result<()> {
+        if self.format == InputFormat::Cloudwatch {
+            let mut text = Vec::new();
+            reader.read_to_end(&mut text)?;
+            let text = encoding::decode_line(&text);
+            let entries = cloud::from_cloudwatch_export(&text).unwrap_or_else(|| {
+                let mut entries = Vec::new();
+                for (index, line) in text.lines().enumerate() {
+                    match cloud::from_cloudwatch(line) {
+                        Some(entry) => entries.push(entry),
+                        None if line.trim().is_empty() => {}
+                        None => self.skipped.push((index + 1, line.to_string())),
+                    }
+                }
+                entries
+            });
+            for entry in entries {
+                let raw = provenance::enabled().then(|| entry.to_line());
+                if let Some(mut entry) = self.ingest(entry, raw.as_deref(), Vec::new()) {
+                    entry.locate(None);
+                    self.accept(entry);
+                }
+            }
+            self.limit();
+            return Ok(());
+        }
+        let mut buf = Vec::new();
+        let mut number = 0;
+        while reader.read_until(b'\n', &mut buf)? > 0 {
+            number += 1;
+            let text = encoding::decode_line(&buf);
+            let decoded = matches!(text, Cow::Owned(_)) || encoding::converting();
+            let line = text.trim_end_matches('\n').trim_end_matches('\r');
+            let line = line.strip_prefix(encoding::BOM).unwrap_or(line);
+            match LogEntry::parse(line, self.format) {
+                Some(entry) => {
+                    let transforms = if decoded { vec!["decode"] } else { Vec::new() };
+                    if let Some(mut entry) = self.ingest(entry, Some(line), transforms) {
+                        entry.locate(Some(number));
+                        self.accept(entry);
                     }
                 }
+                None if line.trim().is_empty() => {}
+                None => self.skipped.push((number, line.to_string())),
             }
+            buf.clear();
         }
+        self.limit();
         Ok(())
     }
 
+    /// Rewrites the whole file atomically: the entries go to a temporary
+    /// file next to it, which then replaces the original, so a crash
+    /// mid-write leaves the old file intact. With encryption configured the
+    /// file is encrypted; otherwise files named `.gz` or `.zst` are written
+    /// compressed. Files named `.lgb`, or any with `--binary`, are written
+    /// in the binary format. With hash chaining on, the chain is rebuilt
+    /// from the first entry.
     fn save_to_file(&self, filename: &str) -> io::Result<()> {
-        let mut file = File::create(filename)?;
-        for entry in &self.entries {
-            writeln!(file, "{}", entry.to_line())?;
+        let _lock = lock::exclusive(filename)?;
+        let chained;
+        let entries = if chain::enabled() {
+            chained = chain::link(None, &self.entries);
+            &chained
+        } else {
+            &self.entries
+        };
+        let path = Path::new(filename);
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?;
+        let temp = path.with_file_name(format!(
+            ".{}.tmp{}",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        // Marks the file as UTF-8 for readers expecting another encoding.
+        let bom = if encoding::converting() {
+            encoding::BOM
+        } else {
+            ""
+        };
+        let binary = binary::wanted(filename);
+        let result = (|| {
+            if crypt::enabled() {
+                let text = if binary {
+                    binary::encode(entries, true)
+                } else {
+                    let mut text = bom.as_bytes().to_vec();
+                    text.extend(LogAnalyzer::lines(entries));
+                    text
+                };
+                crypt::write_new(File::create(&temp)?, &text)?;
+                File::open(&temp)?.sync_all()?;
+                return std::fs::rename(&temp, path);
+            }
+            let mut out = export::Output::new(
+                File::create(&temp)?,
+                export::Compression::from_path(filename),
+            )?;
+            if binary {
+                out.write_all(&binary::encode(entries, true))?;
+            } else {
+                write!(out, "{}", bom)?;
+                for entry in entries {
+                    writeln!(out, "{}", entry.to_line())?;
+                }
+            }
+            out.finish()?;
+            File::open(&temp)?.sync_all()?;
+            std::fs::rename(&temp, path)
+        })();
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
+
+    // The entries as they are written to a log file.
+    fn lines(entries: &[LogEntry]) -> Vec<u8> {
+        let mut text = String::new();
+        for entry in entries {
+            text.push_str(&entry.to_line());
+            text.push('\n');
+        }
+        text.into_bytes()
+    }
+
+    /// Appends `entries` to the end of the file, creating it if needed.
+    /// A `.gz` or `.zst` file gets them as a new compressed member, which
+    /// decompresses as one stream with the rest. With hash chaining on, the
+    /// new entries continue the chain from the file's last entry.
+    fn append_to_file(filename: &str, entries: &[LogEntry]) -> io::Result<()> {
+        let _lock = lock::exclusive(filename)?;
+        if encoding::needs_rewrite(filename)? || binary::needs_rewrite(filename)? {
+            // Convert the file to UTF-8, or to the format it is to be
+            // written in, once instead of mixing them.
+            let mut existing = LogAnalyzer::builder().pipeline(false).build();
+            existing.load_from_file(filename)?;
+            existing.entries.extend_from_slice(entries);
+            return existing.save_to_file(filename);
+        }
+        let chained;
+        let entries = if chain::enabled() {
+            let mut existing = LogAnalyzer::builder().pipeline(false).build();
+            existing.load_from_file(filename)?;
+            chained = chain::link(existing.entries.last(), entries);
+            &chained
+        } else {
+            entries
+        };
+        let binary = binary::wanted(filename).then(|| {
+            let empty = std::fs::metadata(filename).map_or(true, |m| m.len() == 0);
+            binary::encode(entries, empty)
+        });
+        if crypt::enabled() {
+            let text = binary.unwrap_or_else(|| LogAnalyzer::lines(entries));
+            return crypt::append(filename, &text);
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(filename)?;
+        let mut out = export::Output::new(file, export::Compression::from_path(filename))?;
+        match binary {
+            Some(bytes) => out.write_all(&bytes)?,
+            None => {
+                for entry in entries {
+                    writeln!(out, "{}", entry.to_line())?;
+                }
+            }
+        }
+        out.finish()
+    }
+
+    /// Starts journaling added entries next to `filename`. Entries left in
+    /// the journal by a session that never saved are loaded back (they are
+    /// not in the file yet, so the next save appends them); returns how many.
+    fn open_journal(&mut self, filename: &str) -> io::Result<usize> {
+        let journal = format!("{}.journal", filename);
+        // Journal lines are written here, whatever format the log is in.
+        let mut recovered = LogAnalyzer::builder()
+            .format(InputFormat::Auto)
+            .pipeline(false)
+            .build();
+        recovered.load_from_file(&journal)?;
+        let count = recovered.entries.len();
+        self.extend(recovered.entries);
+        self.journal = Some(journal);
+        Ok(count)
+    }
+
+    /// Records that the file currently holds exactly the loaded entries.
+    fn mark_saved(&mut self) {
+        self.persisted = Some(self.entries.len());
+    }
+
+    /// Timestamped name for a backup of `filename`, e.g.
+    /// `logs.txt.20240115-103000.bak`.
+    fn backup_path(filename: &str) -> String {
+        format!(
+            "{}.{}.bak",
+            filename,
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        )
+    }
+
+    /// Writes the entries currently in memory to a timestamped backup of
+    /// `filename` and returns its name.
+    fn write_backup(&self, filename: &str) -> io::Result<String> {
+        let backup = LogAnalyzer::backup_path(filename);
+        self.save_to_file(&backup)?;
+        Ok(backup)
+    }
+
+    /// Appends entries added since the last save, or rewrites the file
+    /// atomically when earlier entries may have changed. A file that is
+    /// about to be rewritten is copied to a timestamped backup first.
+    fn save(&mut self, filename: &str) -> io::Result<()> {
+        match self.persisted {
+            Some(saved) if saved <= self.entries.len() && !crypt::needs_rewrite(filename) => {
+                rotate::before_append(filename)?;
+                LogAnalyzer::append_to_file(filename, &self.entries[saved..])?
+            }
+            _ => {
+                if Path::new(filename).exists() {
+                    std::fs::copy(filename, LogAnalyzer::backup_path(filename))?;
+                }
+                self.save_to_file(filename)?
+            }
+        }
+        self.mark_saved();
+        if let Some(journal) = &self.journal {
+            match std::fs::remove_file(journal) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
         }
         Ok(())
     }
 
+    fn export_csv(&self, filename: &str) -> io::Result<()> {
+        export::write_csv(&self.entries, filename)
+    }
+
+    fn export_ndjson(&self, filename: &str) -> io::Result<()> {
+        export::write_ndjson(&self.entries, filename)
+    }
+
+    #[cfg(feature = "parquet")]
+    fn export_parquet(&self, filename: &str) -> io::Result<()> {
+        export::write_parquet(&self.entries, filename)
+    }
+
     fn add_entry(&mut self, level: String, message: String) {
-        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        self.entries.push(LogEntry {
+        let timestamp = chrono::Local::now()
+            .format(&self.timestamp_format)
+            .to_string();
+        let entry = LogEntry {
             timestamp,
             level,
             message,
-        });
+            fields: Map::new(),
+            source: None,
+        };
+        let raw = provenance::enabled().then(|| entry.to_line());
+        let Some(entry) = ingest(entry, raw.as_deref(), Vec::new()) else {
+            return;
+        };
+        emit(&entry);
+        if let Some(journal) = &self.journal {
+            if let Err(e) = LogAnalyzer::append_to_file(journal, std::slice::from_ref(&entry)) {
+                eprintln!("Could not write journal {}: {}", journal, e);
+            }
+        }
+        self.entries.push(entry);
+        self.limit();
     }
 
     fn filter_by_level(&self, level: &str) -> Vec<LogEntry> {
-        self.entries
+        self.iter_filtered(level).cloned().collect()
+    }
+
+    /// The entries of `level`, borrowed rather than copied.
+    fn iter_filtered(&self, level: &str) -> impl Iterator<Item = &LogEntry> {
+        self.iter_levels(&[level])
+    }
+
+    /// The entries of any of `levels`, in one pass over the log. Aliases
+    /// such as `warn` select the level they stand for.
+    fn filter_by_levels(&self, levels: &[&str]) -> Vec<LogEntry> {
+        self.iter_levels(levels).cloned().collect()
+    }
+
+    /// The entries of any of `levels`, borrowed rather than copied.
+    fn iter_levels(&self, levels: &[&str]) -> impl Iterator<Item = &LogEntry> {
+        let levels: Vec<String> = levels
             .iter()
-            .filter(|e| e.level.eq_ignore_ascii_case(level))
-            .cloned()
-            .collect()
+            .map(|level| levels::normalize(level))
+            .collect();
+        self.entries.iter().filter(move |e| {
+            levels
+                .iter()
+                .any(|level| e.level.eq_ignore_ascii_case(level))
+        })
     }
 
     fn search(&self, query: &str) -> Vec<LogEntry> {
+        self.iter_search(query).cloned().collect()
+    }
+
+    /// The entries whose message contains `query`, ignoring case, borrowed
+    /// rather than copied.
+    fn iter_search(&self, query: &str) -> impl Iterator<Item = &LogEntry> {
         let query_lower = query.to_lowercase();
         self.entries
             .iter()
-            .filter(|e| e.message.to_lowercase().contains(&query_lower))
-            .cloned()
+            .filter(move |e| e.message.to_lowercase().contains(&query_lower))
+    }
+
+    fn get_statistics(&self) -> analysis::Stats {
+        analysis::statistics(&self.entries)
+    }
+
+    /// Counts per level for each time bucket of the given width, oldest
+    /// first, with empty buckets kept so rises stand out.
+    fn stats_by_bucket(
+        &self,
+        bucket: chrono::Duration,
+    ) -> Vec<(NaiveDateTime, HashMap<String, usize>)> {
+        report::level_histogram(&self.entries, bucket)
+    }
+
+    /// Parses and runs a SQL query over the loaded entries.
+    fn query(&self, sql: &str) -> Result<(query::Query, query::QueryResult), LoggerError> {
+        let query = query::parse(sql).map_err(LoggerError::Query)?;
+        let result = query.run(&self.entries).map_err(LoggerError::Query)?;
+        Ok((query, result))
+    }
+
+    /// Entry counts per value of `field`; entries without the field are skipped.
+    fn group_by(&self, field: &query::Field) -> BTreeMap<String, usize> {
+        self.group_by_fields(std::slice::from_ref(field))
+            .into_iter()
+            .map(|(mut key, count)| (key.remove(0), count))
             .collect()
     }
 
-    fn get_statistics(&self) -> HashMap<String, usize> {
-        let mut stats = HashMap::new();
+    /// Counts per combination of values, e.g. host and level together.
+    /// Entries missing any of the fields are skipped.
+    fn group_by_fields(&self, fields: &[query::Field]) -> BTreeMap<Vec<String>, usize> {
+        let mut groups = BTreeMap::new();
         for entry in &self.entries {
-            *stats.entry(entry.level.clone()).or_insert(0) += 1;
+            let key: Option<Vec<String>> = fields
+                .iter()
+                .map(|f| f.lookup(entry).map(|v| v.into_owned()))
+                .collect();
+            if let Some(key) = key {
+                *groups.entry(key).or_insert(0) += 1;
+            }
         }
-        stats
+        groups
     }
 
     fn count_total(&self) -> usize {
@@ -112,23 +1020,298 @@ impl LogAnalyzer {
         self.entries[start..].to_vec()
     }
 
+    /// Entries of `level` (`LEVEL`, `LEVEL+`, or empty for all) per
+    /// `window`, over time; see `analysis::rate`.
+    fn rate(&self, level: &str, window: chrono::Duration) -> Vec<(NaiveDateTime, usize)> {
+        let selector = match level.trim() {
+            "" => gate::LevelSelector::Any,
+            level => gate::LevelSelector::parse(level),
+        };
+        analysis::rate(&self.entries, &selector, window)
+    }
+
+    fn get_first(&self, count: usize) -> Vec<LogEntry> {
+        self.entries[..count.min(self.entries.len())].to_vec()
+    }
+
+    /// The entries at `range`, cut to those there are.
+    fn get_range(&self, range: std::ops::Range<usize>) -> &[LogEntry] {
+        let end = range.end.min(self.entries.len());
+        &self.entries[range.start.min(end)..end]
+    }
+
+    /// Remembers the current state so the next destructive change can be
+    /// undone. Only one level is kept.
+    fn checkpoint(&mut self) {
+        self.undo = Some((self.entries.clone(), self.persisted));
+    }
+
+    /// Restores the state from before the last destructive change; returns
+    /// false if there is nothing to undo.
+    fn undo(&mut self) -> bool {
+        match self.undo.take() {
+            Some((entries, persisted)) => {
+                self.entries = entries;
+                self.persisted = persisted;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the entry at `index`, if there is one.
+    fn delete_at(&mut self, index: usize) -> Option<LogEntry> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        self.checkpoint();
+        self.persisted = None;
+        Some(self.entries.remove(index))
+    }
+
+    /// Removes every entry matching `predicate`; returns how many.
+    fn delete_where(&mut self, query: &query::CompiledQuery) -> usize {
+        let before = self.entries.len();
+        let undo = (self.entries.clone(), self.persisted);
+        self.entries.retain(|e| !query.matches(e));
+        let removed = before - self.entries.len();
+        if removed > 0 {
+            self.undo = Some(undo);
+            self.persisted = None;
+        }
+        removed
+    }
+
+    /// Changes the level and/or message of the entry at `index`; returns
+    /// false if there is no such entry.
+    fn edit(&mut self, index: usize, level: Option<String>, message: Option<String>) -> bool {
+        if index >= self.entries.len() {
+            return false;
+        }
+        self.checkpoint();
+        self.persisted = None;
+        let entry = &mut self.entries[index];
+        if let Some(level) = level {
+            entry.level = level;
+        }
+        if let Some(message) = message {
+            entry.message = message;
+        }
+        true
+    }
+
+    /// Adds `tag` to the entry at `index`, or removes it if the entry
+    /// already has it. Returns whether the entry is tagged now, or `None`
+    /// if there is no such entry.
+    fn toggle_tag(&mut self, index: usize, tag: &str) -> Option<bool> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        self.checkpoint();
+        self.persisted = None;
+        let entry = &mut self.entries[index];
+        let mut tags: Vec<String> = entry.tags().into_iter().map(String::from).collect();
+        let tagged = !entry.has_tag(tag);
+        if tagged {
+            tags.push(tag.to_string());
+        } else {
+            tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+        }
+        if tags.is_empty() {
+            entry.fields.remove("tags");
+        } else {
+            entry.fields.insert("tags".to_string(), Value::from(tags));
+        }
+        Some(tagged)
+    }
+
+    /// Sets the `note` field of the entry at `index`; an empty note
+    /// removes it. Returns false if there is no such entry.
+    fn annotate(&mut self, index: usize, note: &str) -> bool {
+        if index >= self.entries.len() {
+            return false;
+        }
+        self.checkpoint();
+        self.persisted = None;
+        let fields = &mut self.entries[index].fields;
+        if note.is_empty() {
+            fields.remove("note");
+        } else {
+            fields.insert("note".to_string(), Value::from(note));
+        }
+        true
+    }
+
+    fn filter_by_tag(&self, tag: &str) -> Vec<LogEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.has_tag(tag))
+            .cloned()
+            .collect()
+    }
+
     fn clear(&mut self) {
+        self.checkpoint();
         self.entries.clear();
+        self.persisted = None;
     }
 }
 
-fn read_line() -> String {
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    input.trim().to_string()
+thread_local! {
+    static EDITOR: RefCell<Option<rustyline::DefaultEditor>> =
+        RefCell::new(rustyline::DefaultEditor::new().ok());
+}
+
+static INPUT_CLOSED: AtomicBool = AtomicBool::new(false);
+
+/// Reads one trimmed line after showing `text`, with line editing and
+/// history. Ctrl-C abandons the line and returns an empty answer; Ctrl-D
+/// or the end of piped input also returns empty and marks input closed.
+fn prompt(text: &str) -> String {
+    let line = EDITOR.with(|editor| match editor.borrow_mut().as_mut() {
+        Some(editor) => match editor.readline(text) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.as_str());
+                }
+                Some(line)
+            }
+            Err(ReadlineError::Interrupted) => Some(String::new()),
+            Err(_) => None,
+        },
+        None => {
+            print!("{}", text);
+            io::stdout().flush().unwrap();
+            let mut input = String::new();
+            match io::stdin().read_line(&mut input) {
+                Ok(n) if n > 0 => Some(input),
+                _ => None,
+            }
+        }
+    });
+    match line {
+        Some(line) => line.trim().to_string(),
+        None => {
+            INPUT_CLOSED.store(true, Ordering::Relaxed);
+            String::new()
+        }
+    }
 }
 
 fn main() {
+    let cli = cli::Cli::parse();
+    let config = match config::Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Could not read config: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Some(format) = &config.timestamp_format {
+        let _ = CUSTOM_TIMESTAMP_FORMAT.set(format.clone());
+    }
+    if let Some(format) = config.format {
+        let _ = DEFAULT_INPUT_FORMAT.set(format);
+    }
+    if let Some(file) = &config.quarantine_file {
+        let _ = QUARANTINE_FILE.set(file.clone());
+    }
+    if let Err(e) = encoding::init(cli.encoding.as_deref().or(config.encoding.as_deref())) {
+        eprintln!("Invalid encoding: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = fetch::init(
+        &cli.http_headers,
+        config.http.as_deref().unwrap_or_default(),
+    ) {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = extract::init(config.extract.as_deref().unwrap_or_default()) {
+        eprintln!("Invalid extract pattern: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = geoip::init(config.geoip.as_ref()) {
+        eprintln!("Invalid geoip config: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = plugin::init(config.plugins.as_deref().unwrap_or_default()) {
+        eprintln!("Invalid plugin: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = levels::init(&config.levels.clone().unwrap_or_default()) {
+        eprintln!("Invalid [levels]: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = pipeline::init(config.pipeline.as_deref().unwrap_or_default()) {
+        eprintln!("Invalid pipeline stage: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = log4j::init(config.log4j_pattern.as_deref()) {
+        eprintln!("Invalid log4j_pattern: {}", e);
+        std::process::exit(2);
+    }
+    color::init(
+        cli.color
+            .or(config.color)
+            .unwrap_or(color::ColorChoice::Auto),
+    );
+    pager::init(!cli.no_pager && config.pager.unwrap_or(true));
+    color::show_source(cli.show_source || config.show_source.unwrap_or(false));
+    binary::force(cli.binary || config.binary.unwrap_or(false));
+    if let Err(e) = display::init(
+        cli.time_format
+            .as_deref()
+            .or(config.display_format.as_deref()),
+        cli.tz.as_deref().or(config.display_timezone.as_deref()),
+        config.log_timezone.as_deref(),
+    ) {
+        eprintln!("Invalid display setting: {}", e);
+        std::process::exit(2);
+    }
+    chain::init(config.hash_chain.unwrap_or(false));
+    provenance::init(config.provenance);
+    if let Err(e) = redact::init(config.redaction.as_ref()) {
+        eprintln!("Invalid redaction config: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = crypt::init(config.encryption.as_ref()) {
+        eprintln!("Invalid encryption config: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = retention::init(config.retention.as_ref(), &config.log_file(None)) {
+        eprintln!("Invalid retention config: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = rotate::init(config.rotation.as_ref()) {
+        eprintln!("Invalid rotation config: {}", e);
+        std::process::exit(2);
+    }
+    EMIT_STDOUT.store(cli.emit || config.emit.unwrap_or(false), Ordering::Relaxed);
+    let output = cli
+        .output
+        .or(config.output)
+        .unwrap_or(cli::OutputFormat::Text);
+
+    match cli.command {
+        cli::Command::Repl { file } => repl(&config.log_file(file)),
+        command => std::process::exit(cli::run(command, output, &config)),
+    }
+}
+
+fn repl(filename: &str) {
+    crash::install_panic_hook(filename);
     let mut analyzer = LogAnalyzer::new();
-    let filename = "logs.txt";
-    
-    if let Err(e) = analyzer.load_from_file(filename) {
-        println!("Could not load log file: {}", e);
+    let mut last_results: Vec<LogEntry> = Vec::new();
+
+    match analyzer.load_from_file(filename) {
+        Ok(()) => analyzer.mark_saved(),
+        Err(e) => println!("Could not load log file: {}", e),
+    }
+    match analyzer.open_journal(filename) {
+        Ok(0) => {}
+        Ok(recovered) => println!("Recovered {} unsaved entries from the journal", recovered),
+        Err(e) => println!("Could not read journal: {}", e),
     }
 
     loop {
@@ -141,86 +1324,838 @@ fn main() {
         println!("6. View recent logs");
         println!("7. Clear logs");
         println!("8. Save and exit");
+        println!("9. Export (CSV/NDJSON/native)");
+        println!("10. Annotate last results (GitHub/GitLab)");
+        println!("11. Open issue for last results");
+        println!("12. Page on-call for last results");
+        println!("13. Generate report (HTML/Markdown)");
+        println!("14. Run SQL query");
+        println!("15. Load another log file");
+        println!("16. Field schema");
+        println!("17. Field statistics");
+        println!("18. Statistics per time bucket");
+        println!("19. Sample entries");
+        println!("20. Top message patterns");
+        println!("21. Collapse repeated entries");
+        println!("22. Error-rate trend");
+        println!("23. Baseline anomaly detection");
+        println!("24. Detect bursts");
+        println!("25. Find gaps");
+        println!("26. New message templates");
+        println!("27. Extract metric from messages");
+        println!("28. Seasonal comparison");
+        println!("29. Group by fields");
+        println!("30. Template correlation");
+        println!("31. Undo last clear/delete/edit");
+        println!("32. Delete entries");
+        println!("33. Edit entry");
+        println!("34. Tag or annotate entry");
+        println!("35. Filter by tag");
+        println!("36. Sort last results");
+        println!("37. View first logs");
+        println!("38. View a range of logs");
+        println!("39. Entry rate over time");
 
-        print!("\nEnter choice: ");
-        io::stdout().flush().unwrap();
-        let choice = read_line();
+        println!();
+        let choice = prompt("Enter choice: ");
+        // Leaving on end of input beats spinning on empty reads forever.
+        let choice = if INPUT_CLOSED.load(Ordering::Relaxed) {
+            "8".to_string()
+        } else {
+            choice
+        };
 
         match choice.as_str() {
             "1" => {
-                print!("Level (INFO/WARNING/ERROR): ");
-                io::stdout().flush().unwrap();
-                let level = read_line().to_uppercase();
+                let level = prompt("Level (INFO/WARNING/ERROR): ").to_uppercase();
 
-                print!("Message: ");
-                io::stdout().flush().unwrap();
-                let message = read_line();
+                let message = prompt("Message: ");
 
                 analyzer.add_entry(level, message);
                 println!("Log entry added");
             }
             "2" => {
-                println!("\nAll logs:");
-                for entry in &analyzer.entries {
-                    println!("[{}] {} - {}", entry.timestamp, entry.level, entry.message);
+                let mut lines = String::from("\nAll logs:\n");
+                for (i, entry) in analyzer.entries.iter().enumerate() {
+                    lines.push_str(&format!(
+                        "{:>5}  {}\n",
+                        i + 1,
+                        color::entry_line(entry, None)
+                    ));
                 }
+                pager::page(&lines);
             }
             "3" => {
-                print!("Level: ");
-                io::stdout().flush().unwrap();
-                let level = read_line();
+                let levels = prompt("Level(s), e.g. ERROR,WARNING: ");
+                let levels: Vec<&str> = levels.split(',').map(str::trim).collect();
 
-                let filtered = analyzer.filter_by_level(&level);
+                let filtered = match levels.as_slice() {
+                    [level] => analyzer.filter_by_level(level),
+                    levels => analyzer.filter_by_levels(levels),
+                };
                 println!("\nFiltered logs:");
-                for entry in filtered {
-                    println!("[{}] {} - {}", entry.timestamp, entry.level, entry.message);
+                for entry in &filtered {
+                    println!("{}", color::entry_line(entry, None));
                 }
+                last_results = filtered;
             }
             "4" => {
-                print!("Search query: ");
-                io::stdout().flush().unwrap();
-                let query = read_line();
+                let query = prompt("Search query: ");
 
                 let results = analyzer.search(&query);
                 println!("\nSearch results:");
-                for entry in results {
-                    println!("[{}] {} - {}", entry.timestamp, entry.level, entry.message);
+                for entry in &results {
+                    println!("{}", color::entry_line(entry, Some(&query)));
                 }
+                last_results = results;
             }
             "5" => {
-                let stats = analyzer.get_statistics();
                 println!("\nStatistics:");
-                println!("Total entries: {}", analyzer.count_total());
-                for (level, count) in stats {
-                    println!("{}: {}", level, count);
+                report::print_stats(&analyzer.get_statistics());
+                report::print_histogram(&analyzer.entries, report::auto_bucket(&analyzer.entries));
+                match capacity::forecast(&analyzer.entries, filename) {
+                    Ok(forecast) => capacity::print_forecast(&forecast),
+                    Err(e) => println!("Could not estimate capacity: {}", e),
                 }
             }
             "6" => {
-                print!("Number of recent logs: ");
-                io::stdout().flush().unwrap();
-                let count = read_line().parse::<usize>().unwrap_or(10);
+                let count = prompt("Number of recent logs: ")
+                    .parse::<usize>()
+                    .unwrap_or(10);
 
                 let recent = analyzer.get_recent(count);
                 println!("\nRecent logs:");
-                for entry in recent {
-                    println!("[{}] {} - {}", entry.timestamp, entry.level, entry.message);
+                for entry in &recent {
+                    println!("{}", color::entry_line(entry, None));
                 }
+                last_results = recent;
             }
-            "7" => {
-                analyzer.clear();
-                println!("Logs cleared");
-            }
+            "7" => match analyzer.write_backup(filename) {
+                Ok(backup) => {
+                    analyzer.clear();
+                    println!("Logs cleared (backup in {}, 31 to undo)", backup);
+                }
+                Err(e) => println!("Not cleared, could not write backup: {}", e),
+            },
             "8" => {
-                if let Err(e) = analyzer.save_to_file(filename) {
+                if let Err(e) = analyzer.save(filename) {
                     println!("Error saving: {}", e);
                 } else {
                     println!("Logs saved");
                 }
                 break;
             }
+            "9" => {
+                let scope = prompt("Export (a)ll logs or (l)ast results: ").to_lowercase();
+
+                let format = prompt("Format (csv/ndjson/native/parquet): ").to_lowercase();
+
+                let mut compression = export::Compression::None;
+                if format != "parquet" {
+                    compression =
+                        export::Compression::from_name(&prompt("Compression (none/gzip/zstd): "))
+                            .unwrap_or(compression);
+                }
+
+                let path = compression.apply_to(&prompt("Output file (- for stdout): "));
+
+                let budget = prompt("Entry budget (empty for no limit): ")
+                    .parse::<usize>()
+                    .ok();
+                let budgeted = budget.map(|budget| {
+                    let selected = if scope == "l" {
+                        &last_results
+                    } else {
+                        &analyzer.entries
+                    };
+                    analysis::within_budget(selected, budget)
+                });
+                let chosen = budgeted
+                    .as_deref()
+                    .or((scope == "l").then_some(last_results.as_slice()));
+
+                let result = match (chosen, format.as_str()) {
+                    (Some(entries), "ndjson") => export::write_ndjson(entries, &path),
+                    (Some(entries), "native") => export::write_native(entries, &path),
+                    (None, "native") => export::write_native(&analyzer.entries, &path),
+                    #[cfg(feature = "parquet")]
+                    (Some(entries), "parquet") => export::write_parquet(entries, &path),
+                    #[cfg(feature = "parquet")]
+                    (None, "parquet") => analyzer.export_parquet(&path),
+                    #[cfg(not(feature = "parquet"))]
+                    (_, "parquet") => Err(io::Error::other("built without the parquet feature")),
+                    (Some(entries), _) => export::write_csv(entries, &path),
+                    (None, "ndjson") => analyzer.export_ndjson(&path),
+                    _ => analyzer.export_csv(&path),
+                };
+                match result {
+                    Ok(()) => println!("Exported to {}", path),
+                    Err(e) => println!("Error exporting: {}", e),
+                }
+            }
+            "10" => {
+                let format = prompt("Format (github/gitlab): ").to_lowercase();
+
+                if format == "gitlab" {
+                    let path = "gl-code-quality-report.json";
+                    match export::write_code_quality(&last_results, filename, path) {
+                        Ok(()) => println!("Code Quality report written to {}", path),
+                        Err(e) => println!("Error writing report: {}", e),
+                    }
+                } else {
+                    export::print_github_annotations(&last_results, filename);
+                }
+            }
+            "11" => {
+                let tracker = match alert::IssueTemplate::from_env() {
+                    Ok(tracker) => tracker,
+                    Err(e) => {
+                        println!("{}; set LOGGER_ISSUE_TRACKER (jira/github/rest) first", e);
+                        continue;
+                    }
+                };
+
+                let rule = prompt("Rule name: ");
+
+                let alert = alert::Alert::new(&rule, &last_results);
+                match tracker.create_issue(&alert) {
+                    Ok(alert::IssueOutcome::Created) => println!("Issue created"),
+                    Ok(alert::IssueOutcome::AlreadyOpen) => {
+                        println!("An open issue for {} already exists", alert.dedup_key())
+                    }
+                    Err(e) => println!("Error creating issue: {}", e),
+                }
+            }
+            "12" => {
+                let Some(pager) = alert::Pager::from_env() else {
+                    println!("Set LOGGER_PAGERDUTY_ROUTING_KEY or LOGGER_OPSGENIE_API_KEY first");
+                    continue;
+                };
+
+                let rule = prompt("Rule name: ");
+
+                // No matches means the condition cleared, so resolve instead.
+                let alert = alert::Alert::new(&rule, &last_results);
+                let result = if last_results.is_empty() {
+                    pager.resolve(&alert).map(|()| "resolved")
+                } else {
+                    pager.trigger(&alert).map(|()| "triggered")
+                };
+                match result {
+                    Ok(action) => println!("Incident {} {}", alert.dedup_key(), action),
+                    Err(e) => println!("Error paging: {}", e),
+                }
+            }
+            "13" => {
+                let markdown = prompt("Format (html/markdown): ")
+                    .to_lowercase()
+                    .starts_with('m');
+                let default = if markdown { "report.md" } else { "report.html" };
+
+                let mut path = prompt(&format!("Report file [{}]: ", default));
+                if path.is_empty() {
+                    path = default.to_string();
+                }
+
+                let result = if markdown {
+                    report::write_markdown(&analyzer.entries, filename, &path)
+                } else {
+                    let budget = prompt("Entry budget for the table (empty for no limit): ")
+                        .parse::<usize>()
+                        .ok();
+                    report::write_html(&analyzer.entries, filename, &path, budget)
+                };
+                match result {
+                    Ok(()) => println!("Report written to {}", path),
+                    Err(e) => println!("Error writing report: {}", e),
+                }
+            }
+            "14" => {
+                let sql = prompt("SQL: ");
+
+                match analyzer.query(&sql) {
+                    Ok((q, result)) => {
+                        println!("\n{}", result);
+                        last_results = q.matching(&analyzer.entries);
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "15" => {
+                let path = prompt("File: ");
+
+                let before = analyzer.count_total();
+                match analyzer.load_from_file(&path) {
+                    Ok(()) => println!("Loaded {} entries", analyzer.count_total() - before),
+                    Err(e) => println!("Could not load log file: {}", e),
+                }
+            }
+            "16" => {
+                fields::print_schema(
+                    &fields::infer_schema(&analyzer.entries),
+                    analyzer.count_total(),
+                );
+            }
+            "17" => {
+                let name = prompt("Field (level, message, ts or .path): ");
+
+                match query::parse_field(&name) {
+                    Some(field) => {
+                        fields::print_field_stats(&fields::field_stats(&analyzer.entries, &field))
+                    }
+                    None => println!("Unknown field {}", name),
+                }
+            }
+            "18" => {
+                let size = prompt("Bucket size (e.g. 5m, 1h, 1d): ");
+
+                match query::parse_interval(&size) {
+                    Ok(bucket) if bucket > chrono::Duration::zero() => {
+                        report::print_level_histogram(&analyzer.stats_by_bucket(bucket))
+                    }
+                    Ok(_) => println!("Bucket size must be positive"),
+                    Err(e) => println!("{}", e),
+                }
+            }
+            "19" => {
+                let scope = prompt("Sample from (a)ll logs or (l)ast results: ").to_lowercase();
+
+                let count = prompt("Sample size: ").parse::<usize>().unwrap_or(10);
+
+                let seed = prompt("Seed (empty for random): ").parse::<u64>().ok();
+
+                let source = if scope == "l" {
+                    &last_results
+                } else {
+                    &analyzer.entries
+                };
+                let sampled = analysis::sample(source, count, seed);
+                println!("\nSample of {} from {}:", sampled.len(), source.len());
+                for entry in &sampled {
+                    println!("{}", color::entry_line(entry, None));
+                }
+                last_results = sampled;
+            }
+            "20" => {
+                let count = prompt("Number of patterns: ")
+                    .parse::<usize>()
+                    .unwrap_or(10);
+
+                println!("\nTop message patterns:");
+                for pattern in analysis::top_patterns(&analyzer.entries, count) {
+                    println!("{:>8}  {}", pattern.count, pattern.template);
+                    println!("          e.g. {}", pattern.example);
+                }
+            }
+            "21" => {
+                let scope = prompt("Collapse (a)ll logs or (l)ast results: ").to_lowercase();
+
+                let normalized =
+                    prompt("Match normalized messages? (y/n): ").eq_ignore_ascii_case("y");
+
+                let source = if scope == "l" {
+                    &last_results
+                } else {
+                    &analyzer.entries
+                };
+                let collapsed = analysis::collapse_repeats(source, normalized);
+                println!(
+                    "\n{} entries collapsed to {}:",
+                    source.len(),
+                    collapsed.len()
+                );
+                for entry in &collapsed {
+                    println!("{}", color::entry_line(entry, None));
+                }
+                last_results = collapsed;
+            }
+            "22" => {
+                let window = match query::parse_interval(&prompt("Window size (e.g. 10m, 1h): ")) {
+                    Ok(window) if window > chrono::Duration::zero() => window,
+                    _ => {
+                        println!("Invalid window size");
+                        continue;
+                    }
+                };
+
+                let points = analysis::error_rate_windows(&analyzer.entries, window);
+                println!("\nError rate per window:");
+                for point in &points {
+                    println!(
+                        "{}  {:>6.2}%  ({} of {})",
+                        point.start.format(TIMESTAMP_FORMAT),
+                        point.ratio() * 100.0,
+                        point.errors,
+                        point.total
+                    );
+                }
+                let (trend, change) = analysis::classify_trend(&points);
+                println!("Trend: {:?} ({:+.2} percentage points)", trend, change);
+            }
+            "23" => {
+                let mode = prompt("(l)earn baseline from loaded logs or (s)core against one: ")
+                    .to_lowercase();
+
+                let mut path = prompt("Baseline file [baseline.json]: ");
+                if path.is_empty() {
+                    path = "baseline.json".to_string();
+                }
+
+                if mode == "l" {
+                    match baseline::Baseline::learn(&analyzer.entries).save(&path) {
+                        Ok(()) => println!("Baseline saved to {}", path),
+                        Err(e) => println!("Error saving baseline: {}", e),
+                    }
+                    continue;
+                }
+
+                let learned = match baseline::Baseline::load(&path) {
+                    Ok(learned) => learned,
+                    Err(e) => {
+                        println!("Could not load baseline: {}", e);
+                        continue;
+                    }
+                };
+                let threshold = prompt("Threshold in standard deviations [3]: ")
+                    .parse::<f64>()
+                    .unwrap_or(3.0);
+
+                let anomalies = learned.score(&analyzer.entries, threshold);
+                println!("\n{} unusual hours:", anomalies.len());
+                for a in &anomalies {
+                    println!(
+                        "{}  volume {} (expected {:.1}, z={:+.1})  errors {:.1}% (expected {:.1}%, z={:+.1})",
+                        a.start.format("%Y-%m-%d %H:00"),
+                        a.volume,
+                        a.expected_volume,
+                        a.volume_z,
+                        a.error_share * 100.0,
+                        a.expected_error_share * 100.0,
+                        a.error_z
+                    );
+                }
+            }
+            "24" => {
+                let window = match query::parse_interval(&prompt("Window size (e.g. 1m, 10m): ")) {
+                    Ok(window) if window > chrono::Duration::zero() => window,
+                    _ => {
+                        println!("Invalid window size");
+                        continue;
+                    }
+                };
+                let history = prompt("Windows in rolling baseline [10]: ")
+                    .parse::<usize>()
+                    .unwrap_or(10);
+                let factor = prompt("Flag windows above this multiple of the baseline [3]: ")
+                    .parse::<f64>()
+                    .unwrap_or(3.0);
+
+                let bursts = analysis::detect_bursts(&analyzer.entries, window, history, factor);
+                println!("\n{} bursts:", bursts.len());
+                for burst in &bursts {
+                    println!(
+                        "{}  {} entries ({:.1}x baseline of {:.1})",
+                        burst.start.format(TIMESTAMP_FORMAT),
+                        burst.count,
+                        burst.count as f64 / burst.baseline.max(1.0),
+                        burst.baseline
+                    );
+                    for pattern in &burst.top_messages {
+                        println!("    {:>5}  {}", pattern.count, pattern.template);
+                    }
+                }
+            }
+            "25" => {
+                let input = prompt("Report gaps longer than [5m]: ");
+                let threshold =
+                    match query::parse_interval(if input.is_empty() { "5m" } else { &input }) {
+                        Ok(threshold) => threshold,
+                        Err(e) => {
+                            println!("Invalid duration: {}", e);
+                            continue;
+                        }
+                    };
+
+                let gaps = analysis::find_gaps(&analyzer.entries, threshold);
+                println!("\n{} gaps:", gaps.len());
+                for gap in &gaps {
+                    let length = gap.length();
+                    println!(
+                        "{} -> {}  ({}h {:02}m {:02}s)",
+                        gap.from.format(TIMESTAMP_FORMAT),
+                        gap.to.format(TIMESTAMP_FORMAT),
+                        length.num_hours(),
+                        length.num_minutes() % 60,
+                        length.num_seconds() % 60
+                    );
+                }
+            }
+            "26" => {
+                let mut path = prompt("Template catalog [templates.json]: ");
+                if path.is_empty() {
+                    path = "templates.json".to_string();
+                }
+                let mut catalog = match catalog::TemplateCatalog::load(&path) {
+                    Ok(catalog) => catalog,
+                    Err(e) => {
+                        println!("Could not load catalog: {}", e);
+                        continue;
+                    }
+                };
+
+                let unseen = catalog.unseen(&analyzer.entries);
+                if catalog.templates.is_empty() {
+                    println!("Catalog is empty; every template is new.");
+                } else {
+                    println!("\n{} templates never seen before:", unseen.len());
+                    for pattern in &unseen {
+                        println!("{:>6}  {}", pattern.count, pattern.template);
+                        println!("        e.g. {}", pattern.example);
+                    }
+                }
+
+                if prompt("Add current templates to the catalog? (y/n): ").eq_ignore_ascii_case("y")
+                {
+                    let added = catalog.record(&analyzer.entries);
+                    match catalog.save(&path) {
+                        Ok(()) => println!("Added {} templates to {}", added, path),
+                        Err(e) => println!("Error saving catalog: {}", e),
+                    }
+                }
+            }
+            "27" => {
+                let regex = match metrics::compile(&prompt(
+                    "Pattern with a capture group (e.g. took (\\d+)ms): ",
+                )) {
+                    Ok(regex) => regex,
+                    Err(e) => {
+                        println!("Invalid pattern: {}", e);
+                        continue;
+                    }
+                };
+                let bucket = prompt("Bucket size (e.g. 1h, empty for none): ");
+
+                let samples = metrics::extract(&analyzer.entries, &regex);
+                let values: Vec<f64> = samples.iter().map(|(_, v)| *v).collect();
+                match metrics::summarize(&values) {
+                    Some(summary) => metrics::print_summary("\nOverall", &summary),
+                    None => {
+                        println!("No messages matched");
+                        continue;
+                    }
+                }
+                if !bucket.is_empty() {
+                    match query::parse_interval(&bucket) {
+                        Ok(bucket) if bucket > chrono::Duration::zero() => {
+                            for (start, summary) in metrics::summarize_by_bucket(&samples, bucket) {
+                                metrics::print_summary(
+                                    &start.format(TIMESTAMP_FORMAT).to_string(),
+                                    &summary,
+                                );
+                            }
+                        }
+                        _ => println!("Invalid bucket size"),
+                    }
+                }
+            }
+            "28" => {
+                let change = |value: Option<f64>| match value {
+                    Some(value) => format!("{:+.1}%", value),
+                    None => "n/a".to_string(),
+                };
+                for c in analysis::seasonal_comparisons(&analyzer.entries) {
+                    println!(
+                        "\n{} (from {}):",
+                        c.label,
+                        c.current.start.format(TIMESTAMP_FORMAT)
+                    );
+                    println!(
+                        "  Volume:     {} vs {}  ({})",
+                        c.current.total,
+                        c.previous.total,
+                        change(c.volume_change())
+                    );
+                    println!(
+                        "  Error rate: {:.2}% vs {:.2}%  ({})",
+                        c.current.ratio() * 100.0,
+                        c.previous.ratio() * 100.0,
+                        change(c.error_rate_change())
+                    );
+                }
+            }
+            "29" => {
+                let names: Vec<String> = prompt("Fields, comma separated (e.g. .host,level): ")
+                    .split(',')
+                    .map(|n| n.trim().to_string())
+                    .collect();
+                let fields: Option<Vec<query::Field>> =
+                    names.iter().map(|n| query::parse_field(n)).collect();
+                let Some(fields) = fields else {
+                    println!("Unknown field");
+                    continue;
+                };
+
+                let mut groups: Vec<(Vec<String>, usize)> = if let [field] = fields.as_slice() {
+                    analyzer
+                        .group_by(field)
+                        .into_iter()
+                        .map(|(value, count)| (vec![value], count))
+                        .collect()
+                } else {
+                    analyzer.group_by_fields(&fields).into_iter().collect()
+                };
+                groups.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                println!("\n{}  count", names.join("  "));
+                for (key, count) in &groups {
+                    println!("{}  {}", key.join("  "), count);
+                }
+            }
+            "30" => {
+                let window = match query::parse_interval(&prompt("Window size (e.g. 1m, 10m): ")) {
+                    Ok(window) if window > chrono::Duration::zero() => window,
+                    _ => {
+                        println!("Invalid window size");
+                        continue;
+                    }
+                };
+                let limit = prompt("Number of templates [8]: ")
+                    .parse::<usize>()
+                    .unwrap_or(8);
+
+                let matrix = analysis::template_correlation(&analyzer.entries, window, limit);
+                println!();
+                for (i, template) in matrix.templates.iter().enumerate() {
+                    println!("T{:<3} {}", i + 1, template);
+                }
+                print!("\n    ");
+                for i in 0..matrix.templates.len() {
+                    print!(" {:>6}", format!("T{}", i + 1));
+                }
+                println!();
+                for (i, row) in matrix.values.iter().enumerate() {
+                    print!("{:<4}", format!("T{}", i + 1));
+                    for value in row {
+                        print!(" {:>6.2}", value);
+                    }
+                    println!();
+                }
+                println!("\nStrongest pairs:");
+                for (a, b, r) in matrix.strongest_pairs(5) {
+                    println!("T{} ~ T{}  r={:+.2}", a + 1, b + 1, r);
+                }
+            }
+            "31" => {
+                if analyzer.undo() {
+                    println!("Restored {} entries", analyzer.count_total());
+                } else {
+                    println!("Nothing to undo");
+                }
+            }
+            "32" => {
+                let target = prompt("Entry number (see 2), or WHERE expression: ");
+
+                if let Ok(number) = target.parse::<usize>() {
+                    match number.checked_sub(1).and_then(|i| analyzer.delete_at(i)) {
+                        Some(entry) => {
+                            println!("Deleted {}", color::entry_line(&entry, None))
+                        }
+                        None => println!("No entry {}", number),
+                    }
+                } else {
+                    match query::CompiledQuery::compile(&target) {
+                        Ok(query) => println!("Deleted {} entries", analyzer.delete_where(&query)),
+                        Err(e) => println!("Invalid expression: {}", e),
+                    }
+                }
+            }
+            "33" => {
+                let number = prompt("Entry number (see 2): ")
+                    .parse::<usize>()
+                    .unwrap_or(0);
+                let Some(entry) = number.checked_sub(1).and_then(|i| analyzer.entries.get(i))
+                else {
+                    println!("No entry {}", number);
+                    continue;
+                };
+                println!("{}", color::entry_line(entry, None));
+                let level = prompt("New level (empty keeps it): ").to_uppercase();
+                let message = prompt("New message (empty keeps it): ");
+                let changed = |text: String| Some(text).filter(|t| !t.is_empty());
+                analyzer.edit(number - 1, changed(level), changed(message));
+                println!("Entry updated");
+            }
+            "34" => {
+                let number = prompt("Entry number (see 2): ")
+                    .parse::<usize>()
+                    .unwrap_or(0);
+                let Some(entry) = number.checked_sub(1).and_then(|i| analyzer.entries.get(i))
+                else {
+                    println!("No entry {}", number);
+                    continue;
+                };
+                println!("{}", color::entry_line(entry, None));
+                let tag = prompt("Tag to add or remove (empty for none): ");
+                if !tag.is_empty() {
+                    match analyzer.toggle_tag(number - 1, &tag) {
+                        Some(true) => println!("Tagged '{}'", tag),
+                        _ => println!("Removed tag '{}'", tag),
+                    }
+                }
+                let note = prompt("Note (empty keeps it, - removes it): ");
+                match note.as_str() {
+                    "" => {}
+                    "-" => {
+                        analyzer.annotate(number - 1, "");
+                        println!("Note removed");
+                    }
+                    _ => {
+                        analyzer.annotate(number - 1, &note);
+                        println!("Note saved");
+                    }
+                }
+            }
+            "35" => {
+                let tag = prompt("Tag: ");
+
+                let tagged = analyzer.filter_by_tag(&tag);
+                println!("\n{} entries tagged '{}':", tagged.len(), tag);
+                for entry in &tagged {
+                    println!("{}", color::entry_line(entry, None));
+                    if let Some(note) = entry.fields.get("note").and_then(Value::as_str) {
+                        println!("    note: {}", note);
+                    }
+                }
+                last_results = tagged;
+            }
+            "36" => {
+                let Some(key) =
+                    analysis::SortKey::parse(&prompt("Sort by (timestamp/level/message): "))
+                else {
+                    println!("Unknown sort key");
+                    continue;
+                };
+
+                let descending = prompt("Descending? (y/n): ").eq_ignore_ascii_case("y");
+
+                analysis::sort_by(&mut last_results, key, descending);
+                println!("\nSorted results:");
+                for entry in &last_results {
+                    println!("{}", color::entry_line(entry, None));
+                }
+            }
+            "37" => {
+                let count = prompt("Number of first logs: ")
+                    .parse::<usize>()
+                    .unwrap_or(10);
+
+                let first = analyzer.get_first(count);
+                println!("\nFirst logs:");
+                for entry in &first {
+                    println!("{}", color::entry_line(entry, None));
+                }
+                last_results = first;
+            }
+            "38" => {
+                let range = match analysis::parse_range(&prompt("Entries (e.g. 1000..2000): ")) {
+                    Ok(range) => range,
+                    Err(e) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                };
+
+                let start = range.start;
+                let slice = analyzer.get_range(range);
+                let mut lines = format!("\n{} entries:\n", slice.len());
+                for (i, entry) in slice.iter().enumerate() {
+                    lines.push_str(&format!(
+                        "{:>5}  {}\n",
+                        start + i + 1,
+                        color::entry_line(entry, None)
+                    ));
+                }
+                pager::page(&lines);
+                last_results = slice.to_vec();
+            }
+            "39" => {
+                let level = prompt("Level (e.g. ERROR, ERROR+ for worse too, empty for all): ");
+
+                let window = match query::parse_interval(&prompt("Window size (e.g. 1m, 1h): ")) {
+                    Ok(window) if window > chrono::Duration::zero() => window,
+                    _ => {
+                        println!("Invalid window size");
+                        continue;
+                    }
+                };
+
+                let series = analyzer.rate(&level, window);
+                let peak = series.iter().map(|(_, n)| *n).max().unwrap_or(0);
+                println!("\nEntries per window:");
+                for (start, count) in &series {
+                    let bar = "#".repeat((count * 50).div_ceil(peak.max(1)));
+                    println!("{}  {:>6} {}", start.format(TIMESTAMP_FORMAT), count, bar);
+                }
+                println!("Peak: {}", peak);
+            }
             _ => {
                 println!("Invalid choice");
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: &str, level: &str, message: &str) -> LogEntry {
+        LogEntry {
+            timestamp: timestamp.to_string(),
+            level: level.to_string(),
+            message: message.to_string(),
+            fields: Map::new(),
+            source: None,
+        }
+    }
+
+    fn round_trip(entry: &LogEntry) -> LogEntry {
+        LogEntry::from_line(&entry.to_line()).unwrap()
+    }
+
+    #[test]
+    fn pipe_lines_round_trip() {
+        for original in [
+            entry("2026-01-01 00:00:00", "INFO", "a|b|c"),
+            entry("2026-01-01 00:00:00", "INFO", "two\nlines"),
+            entry("2026-01-01 00:00:00", "INFO", "  padded  "),
+            entry("2026|01", "INFO", "pipe in the timestamp"),
+            entry("{not json", "INFO", "brace"),
+            entry("2026-01-01 00:00:00", "WA|RN", "pipe in the level"),
+        ] {
+            let read = round_trip(&original);
+            assert_eq!(
+                (&read.timestamp, &read.level, &read.message),
+                (&original.timestamp, &original.level, &original.message),
+                "{:?}",
+                original.to_line()
+            );
+        }
+    }
+
+    #[test]
+    fn plain_entries_stay_pipe_lines() {
+        let plain = entry("2026-01-01 00:00:00", "INFO", "started | ok");
+        assert_eq!(plain.to_line(), "2026-01-01 00:00:00|INFO|started | ok");
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let mut original = entry("2026-01-01 00:00:00", "INFO", "request");
+        original
+            .fields
+            .insert("status".to_string(), Value::from(503));
+        let read = round_trip(&original);
+        assert_eq!(read.fields, original.fields);
+        assert_eq!(read.message, "request");
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Duration, NaiveDateTime};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::query::Field;
+use crate::LogEntry;
+
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// Compiles a metric pattern. The number is taken from the group named
+/// `value`, or else from the first capture group.
+pub fn compile(pattern: &str) -> Result<Regex, String> {
+    let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+    if regex.captures_len() < 2 {
+        return Err("pattern needs a capture group, e.g. took (\\d+)ms".to_string());
+    }
+    Ok(regex)
+}
+
+/// Values captured from matching messages, with the entry's time.
+pub fn extract(entries: &[LogEntry], regex: &Regex) -> Vec<(Option<NaiveDateTime>, f64)> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let captures = regex.captures(&entry.message)?;
+            let text = captures.name("value").or_else(|| captures.get(1))?;
+            let value = text.as_str().parse::<f64>().ok()?;
+            Some((entry.time(), value))
+        })
+        .collect()
+}
+
+/// Nearest-rank percentile over sorted values.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub fn summarize(values: &[f64]) -> Option<Summary> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    Some(Summary {
+        count: sorted.len(),
+        min: sorted[0],
+        max: sorted[sorted.len() - 1],
+        avg: sorted.iter().sum::<f64>() / sorted.len() as f64,
+        p50: percentile(&sorted, 50.0),
+        p95: percentile(&sorted, 95.0),
+        p99: percentile(&sorted, 99.0),
+    })
+}
+
+/// One summary per time bucket that has samples; samples without a
+/// parseable timestamp are left out.
+pub fn summarize_by_bucket(
+    samples: &[(Option<NaiveDateTime>, f64)],
+    bucket: Duration,
+) -> Vec<(NaiveDateTime, Summary)> {
+    let width = bucket.num_seconds().max(1);
+    let mut buckets: BTreeMap<i64, Vec<f64>> = BTreeMap::new();
+    for (time, value) in samples {
+        if let Some(time) = time {
+            buckets
+                .entry(crate::report::bucket_start(*time, width))
+                .or_default()
+                .push(*value);
+        }
+    }
+    buckets
+        .into_iter()
+        .filter_map(|(start, values)| {
+            let start = chrono::DateTime::from_timestamp(start, 0)?.naive_utc();
+            Some((start, summarize(&values)?))
+        })
+        .collect()
+}
+
+pub fn print_summary(label: &str, summary: &Summary) {
+    println!(
+        "{}  n={} min={} max={} avg={:.2} p50={} p95={} p99={}",
+        label,
+        summary.count,
+        summary.min,
+        summary.max,
+        summary.avg,
+        summary.p50,
+        summary.p95,
+        summary.p99
+    );
+}
+
+/// A `[[spans]]` table of the config: start and end patterns declared
+/// once, for `logger durations --span NAME`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpanSpec {
+    pub name: String,
+    /// Regex for start events; its `id` group or first capture is the ID.
+    pub start: String,
+    pub end: String,
+    /// Take the ID from this field instead, e.g. ".request_id".
+    pub id: Option<String>,
+}
+
+/// A start event and how long until its end.
+pub struct Span {
+    pub id: String,
+    pub start: LogEntry,
+    pub seconds: f64,
+}
+
+/// Start/end events paired by ID.
+pub struct Pairing {
+    pub spans: Vec<Span>,
+    /// Starts that never ended, e.g. leaked or timed-out work.
+    pub unmatched_starts: Vec<(String, LogEntry)>,
+    pub unmatched_ends: usize,
+}
+
+impl Pairing {
+    /// Seconds between each start and its end.
+    pub fn durations(&self) -> Vec<f64> {
+        self.spans.iter().map(|span| span.seconds).collect()
+    }
+
+    /// The `limit` longest spans, longest first.
+    pub fn slowest(&self, limit: usize) -> Vec<&Span> {
+        let mut spans: Vec<&Span> = self.spans.iter().collect();
+        spans.sort_by(|a, b| b.seconds.total_cmp(&a.seconds));
+        spans.truncate(limit);
+        spans
+    }
+}
+
+// The pairing ID: the given field, else the `id` group or first capture.
+fn event_id(entry: &LogEntry, regex: &Regex, field: Option<&Field>) -> Option<String> {
+    let captures = regex.captures(&entry.message)?;
+    match field {
+        Some(field) => field.lookup(entry).map(|v| v.into_owned()),
+        None => captures
+            .name("id")
+            .or_else(|| captures.get(1))
+            .map(|m| m.as_str().to_string()),
+    }
+}
+
+/// Pairs each start with the next end carrying the same ID, in time order.
+/// A second start for an ID that is still open leaves the first unmatched.
+pub fn pair_events(
+    entries: &[LogEntry],
+    start: &Regex,
+    end: &Regex,
+    field: Option<&Field>,
+) -> Pairing {
+    let mut ordered: Vec<(NaiveDateTime, &LogEntry)> = entries
+        .iter()
+        .filter_map(|e| e.time().map(|t| (t, e)))
+        .collect();
+    ordered.sort_by_key(|(t, _)| *t);
+
+    let mut open: HashMap<String, (NaiveDateTime, &LogEntry)> = HashMap::new();
+    let mut pairing = Pairing {
+        spans: Vec::new(),
+        unmatched_starts: Vec::new(),
+        unmatched_ends: 0,
+    };
+    for (time, entry) in ordered {
+        if let Some(id) = event_id(entry, start, field) {
+            if let Some((_, previous)) = open.insert(id.clone(), (time, entry)) {
+                pairing.unmatched_starts.push((id, previous.clone()));
+            }
+        } else if let Some(id) = event_id(entry, end, field) {
+            match open.remove(&id) {
+                Some((started, start)) => pairing.spans.push(Span {
+                    id,
+                    start: start.clone(),
+                    seconds: (time - started).num_milliseconds() as f64 / 1000.0,
+                }),
+                None => pairing.unmatched_ends += 1,
+            }
+        }
+    }
+    let mut still_open: Vec<(String, LogEntry)> = open
+        .into_iter()
+        .map(|(id, (_, entry))| (id, entry.clone()))
+        .collect();
+    still_open.sort_by(|a, b| a.1.timestamp.cmp(&b.1.timestamp));
+    pairing.unmatched_starts.extend(still_open);
+    pairing
+}
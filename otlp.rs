@@ -0,0 +1,128 @@
+//! OpenTelemetry logs over OTLP/HTTP with the JSON encoding.
+
+use std::io;
+
+use chrono::{Local, TimeZone};
+use serde_json::{json, Value};
+
+use crate::export::Compression;
+use crate::sink::{self, Options, Sink};
+use crate::LogEntry;
+
+/// Sends entries as OTel LogRecords to a collector's `/v1/logs`.
+pub struct Exporter {
+    url: String,
+    headers: Vec<(String, String)>,
+    resource: Value,
+    compression: Compression,
+}
+
+/// OTel severity numbers: the first of each range of four.
+fn severity_number(level: &str) -> u8 {
+    match level.to_uppercase().as_str() {
+        "TRACE" => 1,
+        "DEBUG" => 5,
+        "WARNING" | "WARN" => 13,
+        "ERROR" => 17,
+        "FATAL" | "CRITICAL" => 21,
+        _ => 9,
+    }
+}
+
+/// A JSON value as an OTLP AnyValue; integers are strings in OTLP JSON.
+fn any_value(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({}),
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "intValue": n.to_string() }),
+        Value::Number(n) => json!({ "doubleValue": n.as_f64() }),
+        Value::String(s) => json!({ "stringValue": s }),
+        Value::Array(items) => {
+            json!({ "arrayValue": { "values": items.iter().map(any_value).collect::<Vec<_>>() } })
+        }
+        Value::Object(fields) => json!({ "kvlistValue": { "values": attributes(fields.iter()) } }),
+    }
+}
+
+fn attributes<'a>(pairs: impl Iterator<Item = (&'a String, &'a Value)>) -> Vec<Value> {
+    pairs
+        .map(|(key, value)| json!({ "key": key, "value": any_value(value) }))
+        .collect()
+}
+
+// Trace and span IDs are only carried over when they have the OTel form.
+fn hex_id(value: Option<&Value>, len: usize) -> Option<&str> {
+    value
+        .and_then(Value::as_str)
+        .filter(|id| id.len() == len && id.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// The entry as a LogRecord. `trace_id` and `span_id` fields become its
+/// trace context; the other fields become attributes.
+pub fn log_record(entry: &LogEntry) -> Value {
+    let now = Local::now().timestamp_nanos_opt().unwrap_or_default();
+    let time = entry
+        .time()
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .and_then(|t| t.timestamp_nanos_opt());
+    let trace_id = hex_id(entry.fields.get("trace_id"), 32);
+    let span_id = hex_id(entry.fields.get("span_id"), 16);
+    let mut record = json!({
+        "timeUnixNano": time.unwrap_or(0).to_string(),
+        "observedTimeUnixNano": now.to_string(),
+        "severityNumber": severity_number(&entry.level),
+        "severityText": entry.level,
+        "body": { "stringValue": entry.message },
+        "attributes": attributes(entry.fields.iter().filter(|(key, _)| {
+            !(key.as_str() == "trace_id" && trace_id.is_some()
+                || key.as_str() == "span_id" && span_id.is_some())
+        })),
+    });
+    if let Some(trace_id) = trace_id {
+        record["traceId"] = Value::from(trace_id.to_lowercase());
+    }
+    if let Some(span_id) = span_id {
+        record["spanId"] = Value::from(span_id.to_lowercase());
+    }
+    record
+}
+
+impl Exporter {
+    /// `options.to` is the collector's OTLP/HTTP URL, e.g.
+    /// http://localhost:4318. The resource gets `host.name`, a
+    /// `service.name` of "logger" unless given, and `options.resource`.
+    pub fn new(options: &Options) -> Exporter {
+        let url = if options.to.ends_with("/v1/logs") {
+            options.to.clone()
+        } else {
+            format!("{}/v1/logs", options.to.trim_end_matches('/'))
+        };
+        let mut resource = serde_json::Map::new();
+        resource.insert("service.name".to_string(), Value::from("logger"));
+        resource.insert("host.name".to_string(), Value::from(sink::hostname()));
+        for (key, value) in &options.resource {
+            resource.insert(key.clone(), Value::from(value.as_str()));
+        }
+        Exporter {
+            url,
+            headers: options.headers.clone(),
+            resource: json!({ "attributes": attributes(resource.iter()) }),
+            compression: options.compression,
+        }
+    }
+}
+
+impl Sink for Exporter {
+    fn send(&mut self, entries: &[LogEntry]) -> io::Result<()> {
+        let body = json!({
+            "resourceLogs": [{
+                "resource": self.resource,
+                "scopeLogs": [{
+                    "scope": { "name": "logger", "version": env!("CARGO_PKG_VERSION") },
+                    "logRecords": entries.iter().map(log_record).collect::<Vec<_>>(),
+                }],
+            }],
+        });
+        sink::post_compressed(&self.url, &self.headers, &body, self.compression)
+    }
+}
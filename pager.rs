@@ -0,0 +1,47 @@
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+pub fn init(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn terminal_height() -> Option<usize> {
+    terminal_size::terminal_size()
+        .map(|(_, terminal_size::Height(h))| h as usize)
+        .filter(|&h| h > 0)
+}
+
+/// Prints `text`, through $PAGER (default `less -R`) when stdout is a
+/// terminal and the text would not fit on one screen. Falls back to
+/// printing directly if the pager can't be started.
+pub fn page(text: &str) {
+    let fits = || terminal_height().is_none_or(|height| text.lines().count() < height);
+    if !ENABLED.load(Ordering::Relaxed) || !io::stdout().is_terminal() || fits() {
+        print!("{}", text);
+        return;
+    }
+
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less -R".to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or("less");
+    let child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The user quitting the pager early closes the pipe; that's fine.
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            let _ = child.wait();
+        }
+        Err(_) => print!("{}", text),
+    }
+}
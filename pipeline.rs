@@ -0,0 +1,328 @@
+//! The stages every new entry goes through as it is read or added:
+//! extraction, GeoIP, redaction and the `[[pipeline]]` stages of the
+//! config, which can also drop entries.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::plugin::Plugin;
+use crate::query::CompiledQuery;
+use crate::script::Script;
+use crate::{extract, geoip, levels, provenance, redact, LogEntry};
+
+/// One stage of the ingest pipeline.
+pub trait Transform: Send + Sync {
+    /// Noted in the provenance record of entries the stage changes.
+    fn name(&self) -> &str;
+
+    /// The entry to keep, changed or not, or `None` to drop it.
+    fn apply(&self, entry: LogEntry) -> Option<LogEntry>;
+}
+
+/// A `[[pipeline]]` table of the config file, e.g.
+/// `{ type = "drop", where = ".path = '/health'" }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Stage {
+    /// The `[[extract]]` rules.
+    Extract,
+    /// The `[geoip]` lookup.
+    Geoip,
+    /// Ingest redaction from `[redaction]`.
+    Redact,
+    /// Drops entries matching a WHERE expression.
+    Drop {
+        #[serde(rename = "where")]
+        filter: String,
+    },
+    /// Keeps only entries matching a WHERE expression.
+    Keep {
+        #[serde(rename = "where")]
+        filter: String,
+    },
+    /// Sets a field, e.g. `{ type = "set", field = "env", value = "prod" }`.
+    /// An existing value is kept unless `overwrite` is true.
+    Set {
+        field: String,
+        value: Value,
+        #[serde(default)]
+        overwrite: bool,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    Remove {
+        fields: Vec<String>,
+    },
+    /// Uppercases levels and maps aliases such as `warn` and `err` to the
+    /// names the analyzer uses, like the `[levels]` of the config does for
+    /// every entry; `map` adds or overrides aliases for this stage.
+    Levels {
+        #[serde(default)]
+        map: BTreeMap<String, String>,
+    },
+    /// A Rhai script that may change or drop each entry, see `script`.
+    Script {
+        path: String,
+    },
+    /// A WASM module exporting `transform`, see `plugin`.
+    Plugin {
+        path: String,
+    },
+}
+
+struct Extract;
+
+impl Transform for Extract {
+    fn name(&self) -> &str {
+        "extract"
+    }
+
+    fn apply(&self, mut entry: LogEntry) -> Option<LogEntry> {
+        extract::apply(&mut entry);
+        Some(entry)
+    }
+}
+
+struct GeoIp;
+
+impl Transform for GeoIp {
+    fn name(&self) -> &str {
+        "geoip"
+    }
+
+    fn apply(&self, mut entry: LogEntry) -> Option<LogEntry> {
+        geoip::apply(&mut entry);
+        Some(entry)
+    }
+}
+
+struct Redact;
+
+impl Transform for Redact {
+    fn name(&self) -> &str {
+        "redact"
+    }
+
+    fn apply(&self, mut entry: LogEntry) -> Option<LogEntry> {
+        redact::on_ingest(&mut entry);
+        Some(entry)
+    }
+}
+
+struct Filter {
+    query: CompiledQuery,
+    /// Keep matching entries rather than drop them.
+    keep: bool,
+}
+
+impl Transform for Filter {
+    fn name(&self) -> &str {
+        if self.keep {
+            "keep"
+        } else {
+            "drop"
+        }
+    }
+
+    fn apply(&self, entry: LogEntry) -> Option<LogEntry> {
+        (self.query.matches(&entry) == self.keep).then_some(entry)
+    }
+}
+
+struct Set {
+    field: String,
+    value: Value,
+    overwrite: bool,
+}
+
+impl Transform for Set {
+    fn name(&self) -> &str {
+        "set"
+    }
+
+    fn apply(&self, mut entry: LogEntry) -> Option<LogEntry> {
+        if self.overwrite || !entry.fields.contains_key(&self.field) {
+            entry.fields.insert(self.field.clone(), self.value.clone());
+        }
+        Some(entry)
+    }
+}
+
+struct Rename {
+    from: String,
+    to: String,
+}
+
+impl Transform for Rename {
+    fn name(&self) -> &str {
+        "rename"
+    }
+
+    fn apply(&self, mut entry: LogEntry) -> Option<LogEntry> {
+        if let Some(value) = entry.fields.remove(&self.from) {
+            entry.fields.insert(self.to.clone(), value);
+        }
+        Some(entry)
+    }
+}
+
+struct Remove {
+    fields: Vec<String>,
+}
+
+impl Transform for Remove {
+    fn name(&self) -> &str {
+        "remove"
+    }
+
+    fn apply(&self, mut entry: LogEntry) -> Option<LogEntry> {
+        for field in &self.fields {
+            entry.fields.remove(field);
+        }
+        Some(entry)
+    }
+}
+
+struct Levels {
+    /// Lowercase alias to level.
+    map: BTreeMap<String, String>,
+}
+
+impl Transform for Levels {
+    fn name(&self) -> &str {
+        "levels"
+    }
+
+    fn apply(&self, mut entry: LogEntry) -> Option<LogEntry> {
+        let alias = entry.level.trim().to_lowercase();
+        entry.level = match self.map.get(&alias) {
+            Some(level) => level.clone(),
+            None => alias.to_uppercase(),
+        };
+        Some(entry)
+    }
+}
+
+impl Transform for Script {
+    fn name(&self) -> &str {
+        "script"
+    }
+
+    // A failing script leaves the entry as it was rather than lose it.
+    fn apply(&self, entry: LogEntry) -> Option<LogEntry> {
+        match self.run(&entry) {
+            Ok(kept) => kept,
+            Err(e) => {
+                self.report(&e);
+                Some(entry)
+            }
+        }
+    }
+}
+
+impl Transform for Plugin {
+    fn name(&self) -> &str {
+        "plugin"
+    }
+
+    fn apply(&self, entry: LogEntry) -> Option<LogEntry> {
+        match self.transform(&entry) {
+            Ok(kept) => kept,
+            Err(e) => {
+                self.report(&e);
+                Some(entry)
+            }
+        }
+    }
+}
+
+impl Stage {
+    fn build(&self) -> Result<Box<dyn Transform>, String> {
+        Ok(match self {
+            Stage::Extract => Box::new(Extract),
+            Stage::Geoip => Box::new(GeoIp),
+            Stage::Redact => Box::new(Redact),
+            Stage::Drop { filter } | Stage::Keep { filter } => Box::new(Filter {
+                query: CompiledQuery::compile(filter).map_err(|e| format!("{}: {}", filter, e))?,
+                keep: matches!(self, Stage::Keep { .. }),
+            }),
+            Stage::Set {
+                field,
+                value,
+                overwrite,
+            } => Box::new(Set {
+                field: field.clone(),
+                value: value.clone(),
+                overwrite: *overwrite,
+            }),
+            Stage::Rename { from, to } => Box::new(Rename {
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            Stage::Remove { fields } => Box::new(Remove {
+                fields: fields.clone(),
+            }),
+            Stage::Levels { map } => {
+                let mut aliases: BTreeMap<String, String> = levels::BUILTIN_ALIASES
+                    .into_iter()
+                    .map(|(alias, level)| (alias.to_string(), level.to_string()))
+                    .collect();
+                for (alias, level) in map {
+                    aliases.insert(alias.to_lowercase(), level.clone());
+                }
+                Box::new(Levels { map: aliases })
+            }
+            Stage::Script { path } => Box::new(Script::load(path)?),
+            Stage::Plugin { path } => Box::new(Plugin::load(path, "transform")?),
+        })
+    }
+}
+
+static PIPELINE: OnceLock<Vec<Box<dyn Transform>>> = OnceLock::new();
+
+/// Builds the pipeline from the `[[pipeline]]` stages. Extraction, GeoIP
+/// and redaction run where they are listed; any that are not run first,
+/// in that order.
+pub fn init(stages: &[Stage]) -> Result<(), String> {
+    let listed = |wanted: fn(&Stage) -> bool| stages.iter().any(wanted);
+    let mut all = Vec::new();
+    if !listed(|s| matches!(s, Stage::Extract)) {
+        all.push(Stage::Extract);
+    }
+    if !listed(|s| matches!(s, Stage::Geoip)) {
+        all.push(Stage::Geoip);
+    }
+    if !listed(|s| matches!(s, Stage::Redact)) {
+        all.push(Stage::Redact);
+    }
+    all.extend(stages.iter().cloned());
+    let pipeline = all
+        .iter()
+        .map(Stage::build)
+        .collect::<Result<Vec<_>, String>>()?;
+    let _ = PIPELINE.set(pipeline);
+    Ok(())
+}
+
+/// Runs `entry` through the pipeline; `None` if a stage dropped it. With
+/// provenance on, the names of the stages that changed it are added to
+/// `transforms`.
+pub fn run(mut entry: LogEntry, transforms: &mut Vec<&str>) -> Option<LogEntry> {
+    let Some(pipeline) = PIPELINE.get() else {
+        return Some(entry);
+    };
+    let tracking = provenance::enabled();
+    for stage in pipeline {
+        let before = tracking.then(|| crate::export::entry_json(&entry));
+        entry = stage.apply(entry)?;
+        if before.is_some_and(|before| before != crate::export::entry_json(&entry)) {
+            transforms.push(stage.name());
+        }
+    }
+    Some(entry)
+}
@@ -0,0 +1,287 @@
+//! WASM plugins, so formats and destinations this crate doesn't know can
+//! ship as a module instead of a fork. Needs the `plugins` feature.
+//!
+//! A plugin exports its `memory`, `alloc(len: i32) -> i32` for the host to
+//! copy its input into, optionally `dealloc(ptr: i32, len: i32)`, which the
+//! host calls on the input and the output once it is done with them (a
+//! plugin without it must reuse its buffers), and one or more of:
+//!
+//! - `parse(ptr: i32, len: i32) -> i64`: given a line, a JSON entry
+//!   (`timestamp`, `level`, `message` and fields) as `ptr << 32 | len`, or 0
+//!   when the line is not in its format. Listed under `plugins` in the
+//!   config, it is used for `format = "plugin"` and tried by `auto` after
+//!   the built-in formats.
+//! - `transform(ptr: i32, len: i32) -> i64`: given a JSON entry, the entry
+//!   to keep, or 0 to drop it. A `{ type = "plugin", path = "x.wasm" }`
+//!   pipeline stage.
+//! - `send(ptr: i32, len: i32) -> i32`: given a JSON array of entries, 0
+//!   on success. `logger export --target plugin --to x.wasm`.
+//!
+//! Modules get no imports, so a plugin can only see what it is given.
+
+use std::io;
+use std::sync::OnceLock;
+
+use crate::LogEntry;
+
+#[cfg(feature = "plugins")]
+pub use runtime::Plugin;
+
+/// The plugins whose parsers `format = "plugin"` uses.
+static PARSERS: OnceLock<Vec<Plugin>> = OnceLock::new();
+
+/// Loads the `plugins` of the config, each of which must export `parse`.
+pub fn init(paths: &[String]) -> Result<(), String> {
+    let parsers = paths
+        .iter()
+        .map(|path| Plugin::load(path, "parse"))
+        .collect::<Result<Vec<_>, String>>()?;
+    let _ = PARSERS.set(parsers);
+    Ok(())
+}
+
+/// The entry the first plugin that knows the format makes of `line`.
+pub fn parse(line: &str) -> Option<LogEntry> {
+    PARSERS.get()?.iter().find_map(|plugin| plugin.parse(line))
+}
+
+impl crate::sink::Sink for Plugin {
+    fn send(&mut self, entries: &[LogEntry]) -> io::Result<()> {
+        Plugin::send(self, entries).map_err(io::Error::other)
+    }
+}
+
+#[cfg(feature = "plugins")]
+mod runtime {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    use wasmi::{Engine, Linker, Memory, Module, Store, TypedFunc};
+
+    use crate::export::entry_json;
+    use crate::LogEntry;
+
+    /// Instructions a call may run before it is stopped, so a plugin that
+    /// loops forever fails instead of hanging the command.
+    const FUEL: u64 = 100_000_000;
+
+    struct Instance {
+        store: Store<()>,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        dealloc: Option<TypedFunc<(i32, i32), ()>>,
+        parse: Option<TypedFunc<(i32, i32), i64>>,
+        transform: Option<TypedFunc<(i32, i32), i64>>,
+        send: Option<TypedFunc<(i32, i32), i32>>,
+    }
+
+    pub struct Plugin {
+        path: String,
+        // Calls need the store mutably, and pipeline stages are shared.
+        instance: Mutex<Instance>,
+        /// Whether a failure was reported yet; one line is enough.
+        reported: AtomicBool,
+    }
+
+    impl std::fmt::Debug for Plugin {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "Plugin({})", self.path)
+        }
+    }
+
+    impl Instance {
+        // Copies `input` into the plugin's memory, for the (ptr, len)
+        // arguments of a call.
+        fn input(&mut self, input: &[u8]) -> Result<(i32, i32), String> {
+            self.store.set_fuel(FUEL).map_err(|e| e.to_string())?;
+            let len = i32::try_from(input.len()).map_err(|_| "input too large".to_string())?;
+            let ptr = self
+                .alloc
+                .call(&mut self.store, len)
+                .map_err(|e| e.to_string())?;
+            self.memory
+                .write(&mut self.store, ptr as u32 as usize, input)
+                .map_err(|e| e.to_string())?;
+            Ok((ptr, len))
+        }
+
+        // Gives memory from `alloc` or a result back, if the plugin can
+        // take it.
+        fn free(&mut self, (ptr, len): (i32, i32)) -> Result<(), String> {
+            match self.dealloc {
+                Some(dealloc) => dealloc
+                    .call(&mut self.store, (ptr, len))
+                    .map_err(|e| e.to_string()),
+                None => Ok(()),
+            }
+        }
+
+        // The bytes at `ptr << 32 | len`, `None` for 0.
+        fn output(&self, packed: i64) -> Result<Option<Vec<u8>>, String> {
+            if packed == 0 {
+                return Ok(None);
+            }
+            let ptr = (packed >> 32) as u32 as usize;
+            let len = packed as u32 as usize;
+            // Checked before allocating, so a bad length can't take the
+            // host's memory.
+            if ptr.saturating_add(len) > self.memory.data_size(&self.store) {
+                return Err("output out of bounds".to_string());
+            }
+            let mut bytes = vec![0; len];
+            self.memory
+                .read(&self.store, ptr, &mut bytes)
+                .map_err(|e| e.to_string())?;
+            Ok(Some(bytes))
+        }
+
+        // A call taking bytes and returning bytes, like `parse`.
+        fn call(
+            &mut self,
+            func: TypedFunc<(i32, i32), i64>,
+            input: &[u8],
+        ) -> Result<Option<Vec<u8>>, String> {
+            let args = self.input(input)?;
+            let packed = func
+                .call(&mut self.store, args)
+                .map_err(|e| e.to_string())?;
+            let output = self.output(packed)?;
+            self.free(args)?;
+            if output.is_some() {
+                self.free(((packed >> 32) as i32, packed as i32))?;
+            }
+            Ok(output)
+        }
+    }
+
+    fn entry(bytes: &[u8]) -> Result<LogEntry, String> {
+        let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+        LogEntry::from_json(text).ok_or_else(|| format!("not a JSON entry: {}", text))
+    }
+
+    impl Plugin {
+        /// Loads the module at `path`, which must export `export`.
+        pub fn load(path: &str, export: &str) -> Result<Plugin, String> {
+            let failed = |e: wasmi::Error| format!("{}: {}", path, e);
+            let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            let mut config = wasmi::Config::default();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config);
+            let module = Module::new(&engine, &bytes).map_err(failed)?;
+            let mut store = Store::new(&engine, ());
+            store.set_fuel(FUEL).map_err(failed)?;
+            let instance = Linker::<()>::new(&engine)
+                .instantiate(&mut store, &module)
+                .and_then(|pre| pre.start(&mut store))
+                .map_err(failed)?;
+            let memory = instance
+                .get_memory(&store, "memory")
+                .ok_or_else(|| format!("{}: exports no memory", path))?;
+            let alloc = instance.get_typed_func(&store, "alloc").map_err(failed)?;
+            let loaded = Instance {
+                memory,
+                alloc,
+                dealloc: instance.get_typed_func(&store, "dealloc").ok(),
+                parse: instance.get_typed_func(&store, "parse").ok(),
+                transform: instance.get_typed_func(&store, "transform").ok(),
+                send: instance.get_typed_func(&store, "send").ok(),
+                store,
+            };
+            let exported = match export {
+                "parse" => loaded.parse.is_some(),
+                "transform" => loaded.transform.is_some(),
+                _ => loaded.send.is_some(),
+            };
+            if !exported {
+                return Err(format!("{}: exports no {}", path, export));
+            }
+            Ok(Plugin {
+                path: path.to_string(),
+                instance: Mutex::new(loaded),
+                reported: AtomicBool::new(false),
+            })
+        }
+
+        pub fn report(&self, error: &str) {
+            if !self.reported.swap(true, Ordering::Relaxed) {
+                eprintln!("Plugin {} failed: {}", self.path, error);
+            }
+        }
+
+        /// What the plugin makes of `line`; a failure counts as not its
+        /// format.
+        pub fn parse(&self, line: &str) -> Option<LogEntry> {
+            let mut instance = self.instance.lock().unwrap();
+            let func = instance.parse?;
+            match instance
+                .call(func, line.as_bytes())
+                .and_then(|bytes| bytes.map(|bytes| entry(&bytes)).transpose())
+            {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    self.report(&e);
+                    None
+                }
+            }
+        }
+
+        /// The entry as the plugin changed it, or `None` if it was dropped.
+        pub fn transform(&self, entry: &LogEntry) -> Result<Option<LogEntry>, String> {
+            let mut instance = self.instance.lock().unwrap();
+            let func = instance.transform.ok_or("exports no transform")?;
+            let input = entry_json(entry).to_string();
+            let Some(bytes) = instance.call(func, input.as_bytes())? else {
+                return Ok(None);
+            };
+            let mut changed = self::entry(&bytes)?;
+            changed.source = entry.source.clone();
+            Ok(Some(changed))
+        }
+
+        pub fn send(&self, entries: &[LogEntry]) -> Result<(), String> {
+            let mut instance = self.instance.lock().unwrap();
+            let func = instance.send.ok_or("exports no send")?;
+            let batch = serde_json::Value::from_iter(entries.iter().map(entry_json));
+            let args = instance.input(batch.to_string().as_bytes())?;
+            let status = func
+                .call(&mut instance.store, args)
+                .map_err(|e| e.to_string())?;
+            instance.free(args)?;
+            match status {
+                0 => Ok(()),
+                status => Err(format!("{}: send returned {}", self.path, status)),
+            }
+        }
+    }
+}
+
+/// Without the `plugins` feature no plugin can be loaded.
+#[cfg(not(feature = "plugins"))]
+#[derive(Debug)]
+pub enum Plugin {}
+
+#[cfg(not(feature = "plugins"))]
+impl Plugin {
+    pub fn load(path: &str, _: &str) -> Result<Plugin, String> {
+        Err(format!(
+            "cannot load {}: built without the plugins feature",
+            path
+        ))
+    }
+
+    pub fn parse(&self, _: &str) -> Option<LogEntry> {
+        match *self {}
+    }
+
+    pub fn transform(&self, _: &LogEntry) -> Result<Option<LogEntry>, String> {
+        match *self {}
+    }
+
+    pub fn send(&self, _: &[LogEntry]) -> Result<(), String> {
+        match *self {}
+    }
+
+    pub fn report(&self, _: &str) {
+        match *self {}
+    }
+}
@@ -0,0 +1,141 @@
+//! A Prometheus `/metrics` endpoint for long-running modes: what was
+//! ingested per level, lines that failed to parse, and the recent rate.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::io;
+use std::net::TcpListener;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{http, LogEntry};
+
+/// The ingestion rate is averaged over this window.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct Counters {
+    levels: BTreeMap<String, u64>,
+    parse_failures: u64,
+    recent: VecDeque<(Instant, usize)>,
+}
+
+static COUNTERS: Mutex<Option<Counters>> = Mutex::new(None);
+
+impl Counters {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .recent
+            .front()
+            .is_some_and(|&(at, _)| now.duration_since(at) > RATE_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+    }
+}
+
+/// Counts newly ingested entries and lines that did not parse. Does
+/// nothing unless the endpoint is being served.
+pub fn record(entries: &[LogEntry], parse_failures: usize) {
+    let mut counters = COUNTERS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(counters) = counters.as_mut() else {
+        return;
+    };
+    for entry in entries {
+        *counters
+            .levels
+            .entry(entry.level.to_uppercase())
+            .or_insert(0) += 1;
+    }
+    counters.parse_failures += parse_failures as u64;
+    let now = Instant::now();
+    counters.recent.push_back((now, entries.len()));
+    counters.prune(now);
+}
+
+// Label values escape backslashes, quotes and newlines.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The metrics in the Prometheus text format.
+pub fn render() -> String {
+    let mut counters = COUNTERS.lock().unwrap_or_else(|e| e.into_inner());
+    let counters = counters.get_or_insert_with(Counters::default);
+    counters.prune(Instant::now());
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "# HELP logger_entries_total Entries ingested, by level."
+    );
+    let _ = writeln!(text, "# TYPE logger_entries_total counter");
+    for (level, count) in &counters.levels {
+        let _ = writeln!(
+            text,
+            "logger_entries_total{{level=\"{}\"}} {}",
+            label(level),
+            count
+        );
+    }
+    let _ = writeln!(
+        text,
+        "# HELP logger_parse_failures_total Lines that could not be parsed."
+    );
+    let _ = writeln!(text, "# TYPE logger_parse_failures_total counter");
+    let _ = writeln!(
+        text,
+        "logger_parse_failures_total {}",
+        counters.parse_failures
+    );
+    let recent: usize = counters.recent.iter().map(|&(_, count)| count).sum();
+    let _ = writeln!(
+        text,
+        "# HELP logger_ingestion_rate Entries per second over the last minute."
+    );
+    let _ = writeln!(text, "# TYPE logger_ingestion_rate gauge");
+    let _ = writeln!(
+        text,
+        "logger_ingestion_rate {}",
+        recent as f64 / RATE_WINDOW.as_secs_f64()
+    );
+    text
+}
+
+/// The `/metrics` response.
+pub fn response() -> http::Response {
+    http::Response {
+        status: 200,
+        content_type: "text/plain; version=0.0.4",
+        body: render(),
+    }
+}
+
+/// Starts counting without serving the endpoint, for servers that route
+/// `/metrics` themselves.
+pub fn enable() {
+    COUNTERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(Counters::default);
+}
+
+/// Starts counting and serves `/metrics` on `address` from a background
+/// thread.
+pub fn serve(address: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    enable();
+    thread::spawn(move || {
+        http::serve(listener, |request| {
+            if request.path == "/metrics" {
+                response()
+            } else {
+                http::Response::text(404, "Not found; try /metrics\n")
+            }
+        })
+    });
+    Ok(())
+}
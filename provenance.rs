@@ -0,0 +1,84 @@
+//! Records of what ingest and export did to an entry, kept in a
+//! `provenance` field so an audit can show how a stored entry came from
+//! its input: `{"raw": "<line>", "transforms": ["normalize", "redact"]}`,
+//! or `"sha256"` in place of `"raw"`.
+
+use std::sync::OnceLock;
+
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::LogEntry;
+
+pub const FIELD: &str = "provenance";
+
+/// How much of the original line to keep (`provenance = "raw"` or
+/// `"hash"`).
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provenance {
+    /// The line as read. It is kept before redaction, so it holds whatever
+    /// redaction removed.
+    Raw,
+    /// Only the SHA-256 of the line, enough to check it against the input.
+    Hash,
+}
+
+static MODE: OnceLock<Provenance> = OnceLock::new();
+
+pub fn init(mode: Option<Provenance>) {
+    if let Some(mode) = mode {
+        let _ = MODE.set(mode);
+    }
+}
+
+pub fn enabled() -> bool {
+    MODE.get().is_some()
+}
+
+fn sha256(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn add(entry: &mut LogEntry, mode: Provenance, raw: &str, transforms: &[&str]) {
+    if transforms.is_empty() {
+        return;
+    }
+    let record = entry.fields.entry(FIELD).or_insert_with(|| {
+        let mut record = Map::new();
+        match mode {
+            Provenance::Raw => record.insert("raw".to_string(), Value::from(raw)),
+            Provenance::Hash => record.insert("sha256".to_string(), Value::from(sha256(raw))),
+        };
+        Value::Object(record)
+    });
+    let Value::Object(record) = record else {
+        return;
+    };
+    if let Value::Array(list) = record
+        .entry("transforms")
+        .or_insert_with(|| Value::Array(Vec::new()))
+    {
+        list.extend(transforms.iter().map(|&t| Value::from(t)));
+    }
+}
+
+/// Notes `transforms` on an entry read from `raw`. An entry that already
+/// has a record (read back from a file) keeps its original line and gets
+/// the transforms appended; an unchanged entry gets no record.
+pub fn record(entry: &mut LogEntry, raw: &str, transforms: &[&str]) {
+    if let Some(&mode) = MODE.get() {
+        add(entry, mode, raw, transforms);
+    }
+}
+
+/// Like `record` for a copy made for export, which would otherwise leak
+/// what export redaction removed: a new record always holds the hash.
+pub fn record_export(entry: &mut LogEntry, original: &LogEntry, transform: &str) {
+    if enabled() {
+        add(entry, Provenance::Hash, &original.to_line(), &[transform]);
+    }
+}
@@ -0,0 +1,1193 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::LogEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like,
+}
+
+impl Op {
+    pub fn holds<T: PartialOrd>(self, left: &T, right: &T) -> bool {
+        match self {
+            Op::Eq | Op::Like => left == right,
+            Op::Ne => left != right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+        }
+    }
+}
+
+/// A filter over entries. Level and message comparisons are
+/// case-insensitive, like filter_by_levels and search.
+#[derive(Debug, Clone)]
+pub enum Predicate {
+    Level(Op, String),
+    Message(Op, String),
+    Time(Op, NaiveDateTime),
+    Path(JsonPath, Op, String),
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+}
+
+// A comparison right-hand side prepared once: lowercased, parsed as a
+// number where it is one, and LIKE patterns turned into a regex.
+#[derive(Debug, Clone)]
+struct Pattern {
+    op: Op,
+    text: String,
+    number: Option<f64>,
+    like: Option<Regex>,
+}
+
+impl Pattern {
+    fn new(op: Op, pattern: &str) -> Pattern {
+        let like = (op == Op::Like).then(|| {
+            let mut regex = String::from("(?is)^");
+            for c in pattern.chars() {
+                match c {
+                    '%' => regex.push_str(".*"),
+                    '_' => regex.push('.'),
+                    c => regex.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+            regex.push('$');
+            Regex::new(&regex).expect("escaped LIKE pattern is a valid regex")
+        });
+        Pattern {
+            op,
+            text: pattern.to_lowercase(),
+            number: pattern.parse().ok(),
+            like,
+        }
+    }
+
+    fn text_matches(&self, value: &str) -> bool {
+        match &self.like {
+            Some(regex) => regex.is_match(value),
+            None => self.op.holds(&value.to_lowercase(), &self.text),
+        }
+    }
+
+    fn value_matches(&self, value: &str) -> bool {
+        match (self.number, self.like.is_none()) {
+            (Some(number), true) => match value.parse::<f64>() {
+                Ok(value) => self.op.holds(&value, &number),
+                Err(_) => self.text_matches(value),
+            },
+            _ => self.text_matches(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Level(Pattern),
+    Message(Pattern),
+    Time(Op, NaiveDateTime),
+    Path(JsonPath, Pattern),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+}
+
+impl Node {
+    fn new(predicate: &Predicate) -> Node {
+        match predicate {
+            Predicate::Level(op, value) => Node::Level(Pattern::new(*op, value)),
+            Predicate::Message(op, value) => Node::Message(Pattern::new(*op, value)),
+            Predicate::Time(op, value) => Node::Time(*op, *value),
+            Predicate::Path(path, op, value) => Node::Path(path.clone(), Pattern::new(*op, value)),
+            Predicate::And(left, right) => {
+                Node::And(Box::new(Node::new(left)), Box::new(Node::new(right)))
+            }
+            Predicate::Or(left, right) => {
+                Node::Or(Box::new(Node::new(left)), Box::new(Node::new(right)))
+            }
+            Predicate::Not(inner) => Node::Not(Box::new(Node::new(inner))),
+        }
+    }
+
+    fn matches(&self, entry: &LogEntry) -> bool {
+        match self {
+            Node::Level(pattern) => pattern.text_matches(&entry.level),
+            Node::Message(pattern) => pattern.text_matches(&entry.message),
+            Node::Time(op, value) => entry.time().is_some_and(|time| op.holds(&time, value)),
+            Node::Path(path, pattern) => path
+                .resolve(entry)
+                .is_some_and(|found| pattern.value_matches(&render(&found))),
+            Node::And(left, right) => left.matches(entry) && right.matches(entry),
+            Node::Or(left, right) => left.matches(entry) || right.matches(entry),
+            Node::Not(inner) => !inner.matches(entry),
+        }
+    }
+}
+
+/// A WHERE expression parsed and prepared once, for running against many
+/// entries: a stream being read, or snapshots of the loaded entries.
+#[derive(Debug, Clone)]
+pub struct CompiledQuery {
+    root: Node,
+}
+
+impl CompiledQuery {
+    /// Compiles a bare WHERE expression, see `parse_predicate`.
+    pub fn compile(expr: &str) -> Result<CompiledQuery, String> {
+        Ok(CompiledQuery::from_predicate(&parse_predicate(expr)?))
+    }
+
+    pub fn from_predicate(predicate: &Predicate) -> CompiledQuery {
+        CompiledQuery {
+            root: Node::new(predicate),
+        }
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.root.matches(entry)
+    }
+}
+
+// Timestamps compare as times where they parse, so mixed formats sort
+// chronologically; everything else compares as text.
+fn compare_field(field: &Field, a: &LogEntry, b: &LogEntry) -> Ordering {
+    if *field == Field::Timestamp {
+        if let (Some(a), Some(b)) = (a.time(), b.time()) {
+            return a.cmp(&b);
+        }
+    }
+    field.value(a).cmp(&field.value(b))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A jq-style path into an entry's structured fields, e.g.
+/// `.request.headers["x-request-id"]` or `.items[0].id`. The first key
+/// falls back to `timestamp`, `level` and `message` when no such field exists.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonPath {
+    text: String,
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    pub fn parse(text: &str) -> Result<JsonPath, String> {
+        let invalid = || format!("invalid path '{}'", text);
+        let chars: Vec<char> = text.chars().collect();
+        if chars.first() != Some(&'.') {
+            return Err(invalid());
+        }
+        let mut segments = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '.' if chars.get(i + 1) == Some(&'[') => i += 1,
+                '.' => {
+                    let start = i + 1;
+                    i = start;
+                    while i < chars.len() && !matches!(chars[i], '.' | '[') {
+                        i += 1;
+                    }
+                    if i == start {
+                        if i == chars.len() && segments.is_empty() {
+                            break;
+                        }
+                        return Err(invalid());
+                    }
+                    segments.push(Segment::Key(chars[start..i].iter().collect()));
+                }
+                '[' => {
+                    let close = chars[i..]
+                        .iter()
+                        .position(|c| *c == ']')
+                        .ok_or_else(invalid)?
+                        + i;
+                    let inner: String = chars[i + 1..close].iter().collect();
+                    let inner = inner.trim();
+                    let quoted = inner.len() >= 2
+                        && (inner.starts_with('"') && inner.ends_with('"')
+                            || inner.starts_with('\'') && inner.ends_with('\''));
+                    if quoted {
+                        segments.push(Segment::Key(inner[1..inner.len() - 1].to_string()));
+                    } else {
+                        segments.push(Segment::Index(inner.parse().map_err(|_| invalid())?));
+                    }
+                    i = close + 1;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(JsonPath {
+            text: text.to_string(),
+            segments,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn resolve(&self, entry: &LogEntry) -> Option<Value> {
+        let Some((first, rest)) = self.segments.split_first() else {
+            return Some(Value::Object(entry.fields.clone()));
+        };
+        let Segment::Key(key) = first else {
+            return None;
+        };
+        let root = match entry.fields.get(key) {
+            Some(value) => Cow::Borrowed(value),
+            None => Cow::Owned(match key.as_str() {
+                "timestamp" | "ts" => Value::String(entry.timestamp.clone()),
+                "level" => Value::String(entry.level.clone()),
+                "message" | "msg" => Value::String(entry.message.clone()),
+                "source" => entry.source_json()?,
+                _ => return None,
+            }),
+        };
+        let mut current: &Value = &root;
+        for segment in rest {
+            current = match segment {
+                Segment::Key(key) => current.get(key)?,
+                Segment::Index(index) => current.get(*index)?,
+            };
+        }
+        Some(current.clone())
+    }
+}
+
+/// Renders a JSON value as plain text: strings unquoted, everything else
+/// as compact JSON.
+pub fn render(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Field {
+    Timestamp,
+    Level,
+    Message,
+    Path(JsonPath),
+}
+
+impl Field {
+    /// Looks up the field's text, `None` when a path is missing.
+    pub fn lookup<'a>(&self, entry: &'a LogEntry) -> Option<Cow<'a, str>> {
+        match self {
+            Field::Timestamp => Some(Cow::Borrowed(&entry.timestamp)),
+            Field::Level => Some(Cow::Borrowed(&entry.level)),
+            Field::Message => Some(Cow::Borrowed(&entry.message)),
+            Field::Path(path) => path.resolve(entry).map(|v| Cow::Owned(render(&v))),
+        }
+    }
+
+    fn value<'a>(&self, entry: &'a LogEntry) -> Cow<'a, str> {
+        self.lookup(entry).unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Field::Timestamp => "ts",
+            Field::Level => "level",
+            Field::Message => "message",
+            Field::Path(path) => path.text(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Field(Field),
+    Count,
+    MinTime,
+    MaxTime,
+}
+
+impl Column {
+    fn is_aggregate(&self) -> bool {
+        !matches!(self, Column::Field(_))
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Column::Field(field) => field.name(),
+            Column::Count => "count",
+            Column::MinTime => "min_ts",
+            Column::MaxTime => "max_ts",
+        }
+    }
+
+    fn aggregate(&self, group: &[&LogEntry]) -> String {
+        match self {
+            Column::Field(field) => group
+                .first()
+                .map(|e| field.value(e).into_owned())
+                .unwrap_or_default(),
+            Column::Count => group.len().to_string(),
+            Column::MinTime => group
+                .iter()
+                .min_by(|a, b| compare_field(&Field::Timestamp, a, b))
+                .map(|e| e.timestamp.clone())
+                .unwrap_or_default(),
+            Column::MaxTime => group
+                .iter()
+                .max_by(|a, b| compare_field(&Field::Timestamp, a, b))
+                .map(|e| e.timestamp.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// A filter plus projection/aggregation over the loaded entries.
+///
+/// Results are deterministic: entries come back in input order (their
+/// sequence number, `seq`) unless ORDER BY says otherwise, and entries
+/// that tie on every ORDER BY key fall back to timestamp, then `seq`.
+/// Groups come back in order of first appearance, and ties between
+/// groups keep that order.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub select: Vec<Column>,
+    pub filter: Option<Predicate>,
+    pub group_by: Vec<Field>,
+    /// Column names with `true` for descending, most significant first.
+    pub order_by: Vec<(String, bool)>,
+    pub limit: Option<usize>,
+}
+
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Query {
+    fn is_aggregate(&self) -> bool {
+        !self.group_by.is_empty() || self.select.iter().any(|c| c.is_aggregate())
+    }
+
+    /// Entries passing the WHERE clause, in ORDER BY/LIMIT order when the
+    /// query is not an aggregation.
+    pub fn matching(&self, entries: &[LogEntry]) -> Vec<LogEntry> {
+        let filter = self.filter.as_ref().map(CompiledQuery::from_predicate);
+        let mut matched: Vec<(usize, &LogEntry)> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| filter.as_ref().is_none_or(|f| f.matches(e)))
+            .collect();
+        if !self.is_aggregate() {
+            if !self.order_by.is_empty() {
+                let keys: Vec<(Option<Field>, bool)> = self
+                    .order_by
+                    .iter()
+                    .map(|(name, descending)| (parse_field(name), *descending))
+                    .collect();
+                matched.sort_by(|a, b| {
+                    keys.iter()
+                        .map(|(field, descending)| {
+                            let order = match field {
+                                // `seq` is the only name parse_field rejects.
+                                None => a.0.cmp(&b.0),
+                                Some(field) => compare_field(field, a.1, b.1),
+                            };
+                            if *descending {
+                                order.reverse()
+                            } else {
+                                order
+                            }
+                        })
+                        .fold(Ordering::Equal, Ordering::then)
+                        .then_with(|| compare_field(&Field::Timestamp, a.1, b.1))
+                        .then_with(|| a.0.cmp(&b.0))
+                });
+            }
+            if let Some(limit) = self.limit {
+                matched.truncate(limit);
+            }
+        }
+        matched.into_iter().map(|(_, e)| e.clone()).collect()
+    }
+
+    pub fn run(&self, entries: &[LogEntry]) -> Result<QueryResult, String> {
+        let columns: Vec<String> = self.select.iter().map(|c| c.name().to_string()).collect();
+        let matched = self.matching(entries);
+
+        let mut rows: Vec<Vec<String>> = if self.is_aggregate() {
+            for column in &self.select {
+                if let Column::Field(field) = column {
+                    if !self.group_by.contains(field) {
+                        return Err(format!("{} must appear in GROUP BY", field.name()));
+                    }
+                }
+            }
+            let mut order: Vec<Vec<Cow<str>>> = Vec::new();
+            let mut groups: HashMap<Vec<Cow<str>>, Vec<&LogEntry>> = HashMap::new();
+            for entry in &matched {
+                let key: Vec<Cow<str>> = self.group_by.iter().map(|f| f.value(entry)).collect();
+                let group = groups.entry(key.clone()).or_default();
+                if group.is_empty() {
+                    order.push(key);
+                }
+                group.push(entry);
+            }
+            if self.group_by.is_empty() && order.is_empty() {
+                order.push(Vec::new());
+                groups.insert(Vec::new(), Vec::new());
+            }
+            order
+                .iter()
+                .map(|key| {
+                    let group = &groups[key];
+                    self.select.iter().map(|c| c.aggregate(group)).collect()
+                })
+                .collect()
+        } else {
+            matched
+                .iter()
+                .map(|entry| self.select.iter().map(|c| c.aggregate(&[entry])).collect())
+                .collect()
+        };
+
+        if self.is_aggregate() {
+            let mut keys = Vec::new();
+            for (name, descending) in &self.order_by {
+                let index = columns
+                    .iter()
+                    .position(|c| c == name)
+                    .ok_or_else(|| format!("cannot order by {}", name))?;
+                keys.push((index, self.select[index] == Column::Count, *descending));
+            }
+            // Stable, so groups that tie keep their first-appearance order.
+            rows.sort_by(|a, b| {
+                keys.iter()
+                    .map(|&(index, numeric, descending)| {
+                        let order = if numeric {
+                            let a: usize = a[index].parse().unwrap_or(0);
+                            let b: usize = b[index].parse().unwrap_or(0);
+                            a.cmp(&b)
+                        } else {
+                            a[index].cmp(&b[index])
+                        };
+                        if descending {
+                            order.reverse()
+                        } else {
+                            order
+                        }
+                    })
+                    .fold(Ordering::Equal, Ordering::then)
+            });
+            if let Some(limit) = self.limit {
+                rows.truncate(limit);
+            }
+        }
+
+        Ok(QueryResult { columns, rows })
+    }
+}
+
+impl QueryResult {
+    /// The rows as JSON objects keyed by column name.
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.rows
+                .iter()
+                .map(|row| {
+                    Value::Object(
+                        self.columns
+                            .iter()
+                            .cloned()
+                            .zip(row.iter().cloned().map(Value::String))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut widths: Vec<usize> = self.columns.iter().map(|c| c.len()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let line = |cells: &[String]| -> String {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        };
+        writeln!(f, "{}", line(&self.columns).trim_end())?;
+        writeln!(
+            f,
+            "{}",
+            widths
+                .iter()
+                .map(|w| "-".repeat(*w))
+                .collect::<Vec<_>>()
+                .join("-+-")
+        )?;
+        for row in &self.rows {
+            writeln!(f, "{}", line(row).trim_end())?;
+        }
+        write!(f, "({} rows)", self.rows.len())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Num(String),
+    Path(String),
+    Sym(&'static str),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated string literal".to_string()),
+                    // '' inside a literal is an escaped quote.
+                    Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                        value.push('\'');
+                        i += 2;
+                    }
+                    Some('\'') => {
+                        i += 1;
+                        break;
+                    }
+                    Some(c) => {
+                        value.push(*c);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(Token::Str(value));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Num(chars[start..i].iter().collect()));
+        } else if c == '.' {
+            // A path runs until whitespace or an operator outside brackets.
+            let start = i;
+            let mut depth = 0;
+            while i < chars.len() {
+                match chars[i] {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    c if depth == 0 && (c.is_whitespace() || ",=<>!();".contains(c)) => break,
+                    _ => {}
+                }
+                i += 1;
+            }
+            tokens.push(Token::Path(chars[start..i].iter().collect()));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let sym = match two.as_str() {
+                "<=" => "<=",
+                ">=" => ">=",
+                "!=" | "<>" => "!=",
+                _ => match c {
+                    '=' => "=",
+                    '<' => "<",
+                    '>' => ">",
+                    '(' => "(",
+                    ')' => ")",
+                    ',' => ",",
+                    '*' => "*",
+                    '-' => "-",
+                    '+' => "+",
+                    ';' => ";",
+                    _ => return Err(format!("unexpected character '{}'", c)),
+                },
+            };
+            // `<>` is normalized to `!=`, so count the source characters.
+            i += if sym.len() == 2 { 2 } else { 1 };
+            tokens.push(Token::Sym(sym));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.at_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(format!("expected {}", keyword.to_uppercase()))
+        }
+    }
+
+    fn eat_sym(&mut self, sym: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Sym(s)) if *s == sym) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_sym(&mut self, sym: &str) -> Result<(), String> {
+        if self.eat_sym(sym) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", sym))
+        }
+    }
+
+    fn word(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(w)) => Ok(w),
+            other => Err(format!("expected a name, found {:?}", other)),
+        }
+    }
+
+    fn path(&mut self) -> Option<Result<JsonPath, String>> {
+        if let Some(Token::Path(text)) = self.peek() {
+            let path = JsonPath::parse(text);
+            self.pos += 1;
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    fn field(&mut self) -> Result<Field, String> {
+        if let Some(path) = self.path() {
+            return path.map(Field::Path);
+        }
+        let name = self.word()?;
+        parse_field(&name).ok_or_else(|| format!("unknown column {}", name))
+    }
+
+    fn column(&mut self) -> Result<Column, String> {
+        if let Some(path) = self.path() {
+            return path.map(|p| Column::Field(Field::Path(p)));
+        }
+        let name = self.word()?.to_lowercase();
+        if self.eat_sym("(") {
+            let column = match name.as_str() {
+                "count" => {
+                    if !self.eat_sym("*") {
+                        self.word()?;
+                    }
+                    Column::Count
+                }
+                "min" | "max" => {
+                    let field = self.word()?;
+                    if parse_field(&field) != Some(Field::Timestamp) {
+                        return Err(format!("{}() only supports ts", name));
+                    }
+                    if name == "min" {
+                        Column::MinTime
+                    } else {
+                        Column::MaxTime
+                    }
+                }
+                _ => return Err(format!("unknown function {}()", name)),
+            };
+            self.expect_sym(")")?;
+            Ok(column)
+        } else {
+            parse_field(&name)
+                .map(Column::Field)
+                .ok_or_else(|| format!("unknown column {}", name))
+        }
+    }
+
+    fn select_list(&mut self) -> Result<Vec<Column>, String> {
+        if self.eat_sym("*") {
+            return Ok(vec![
+                Column::Field(Field::Timestamp),
+                Column::Field(Field::Level),
+                Column::Field(Field::Message),
+            ]);
+        }
+        let mut columns = vec![self.column()?];
+        while self.eat_sym(",") {
+            columns.push(self.column()?);
+        }
+        Ok(columns)
+    }
+
+    fn or_expr(&mut self) -> Result<Predicate, String> {
+        let mut left = self.and_expr()?;
+        while self.eat_keyword("or") {
+            left = Predicate::Or(Box::new(left), Box::new(self.and_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<Predicate, String> {
+        let mut left = self.unary()?;
+        while self.eat_keyword("and") {
+            left = Predicate::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Predicate, String> {
+        if self.eat_keyword("not") {
+            return Ok(Predicate::Not(Box::new(self.unary()?)));
+        }
+        if self.eat_sym("(") {
+            let inner = self.or_expr()?;
+            self.expect_sym(")")?;
+            return Ok(inner);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Predicate, String> {
+        let field = self.field()?;
+        let op = if self.eat_keyword("like") {
+            Op::Like
+        } else {
+            match self.next() {
+                Some(Token::Sym("=")) => Op::Eq,
+                Some(Token::Sym("!=")) => Op::Ne,
+                Some(Token::Sym("<")) => Op::Lt,
+                Some(Token::Sym("<=")) => Op::Le,
+                Some(Token::Sym(">")) => Op::Gt,
+                Some(Token::Sym(">=")) => Op::Ge,
+                other => return Err(format!("expected a comparison, found {:?}", other)),
+            }
+        };
+        match field {
+            Field::Timestamp => Ok(Predicate::Time(op, self.time_expr()?)),
+            Field::Level => Ok(Predicate::Level(op, self.string()?)),
+            Field::Message => Ok(Predicate::Message(op, self.string()?)),
+            Field::Path(path) => Ok(Predicate::Path(path, op, self.string()?)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(s),
+            Some(Token::Word(w)) | Some(Token::Num(w)) => Ok(w),
+            other => Err(format!("expected a string, found {:?}", other)),
+        }
+    }
+
+    fn time_expr(&mut self) -> Result<NaiveDateTime, String> {
+        let mut time = match self.next() {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("now") => {
+                self.expect_sym("(")?;
+                self.expect_sym(")")?;
+                chrono::Local::now().naive_local()
+            }
+            Some(Token::Str(s)) => parse_time(&s)?,
+            other => return Err(format!("expected a time, found {:?}", other)),
+        };
+        loop {
+            let sign = if self.eat_sym("-") {
+                -1
+            } else if self.eat_sym("+") {
+                1
+            } else {
+                break;
+            };
+            self.expect_keyword("interval")?;
+            let interval = match self.next() {
+                Some(Token::Str(s)) => parse_interval(&s)?,
+                other => return Err(format!("expected an interval, found {:?}", other)),
+            };
+            time = if sign < 0 {
+                time.checked_sub_signed(interval)
+            } else {
+                time.checked_add_signed(interval)
+            }
+            .ok_or("time out of range")?;
+        }
+        Ok(time)
+    }
+
+    fn limit(&mut self) -> Result<usize, String> {
+        match self.next() {
+            Some(Token::Num(n)) => n.parse().map_err(|_| format!("invalid row count {}", n)),
+            other => Err(format!("expected a row count, found {:?}", other)),
+        }
+    }
+}
+
+/// Resolves a column name (`ts`, `level`, `message`) or a `.path`.
+pub fn parse_field(name: &str) -> Option<Field> {
+    if name.starts_with('.') {
+        return JsonPath::parse(name).ok().map(Field::Path);
+    }
+    match name.to_lowercase().as_str() {
+        "ts" | "timestamp" | "time" => Some(Field::Timestamp),
+        "level" => Some(Field::Level),
+        "message" | "msg" => Some(Field::Message),
+        _ => None,
+    }
+}
+
+pub fn parse_time(value: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(value, crate::TIMESTAMP_FORMAT)
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default())
+        })
+        .map_err(|_| format!("invalid time '{}'", value))
+}
+
+/// Parses `30s`, `5m`, `1h`, `2d` or spelled-out forms like `1 hour`.
+pub fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let amount: i64 = value[..split]
+        .parse()
+        .map_err(|_| format!("invalid interval '{}'", value))?;
+    let interval = match value[split..].trim().to_lowercase().as_str() {
+        "" | "s" | "sec" | "secs" | "second" | "seconds" => Duration::try_seconds(amount),
+        "m" | "min" | "mins" | "minute" | "minutes" => Duration::try_minutes(amount),
+        "h" | "hour" | "hours" => Duration::try_hours(amount),
+        "d" | "day" | "days" => Duration::try_days(amount),
+        "w" | "week" | "weeks" => Duration::try_weeks(amount),
+        "y" | "year" | "years" => amount.checked_mul(365).and_then(Duration::try_days),
+        _ => return Err(format!("invalid interval unit in '{}'", value)),
+    };
+    interval.ok_or_else(|| format!("interval '{}' is too long", value))
+}
+
+/// The time `interval` before now, for `--since` and the like.
+pub fn ago(interval: Duration) -> Result<NaiveDateTime, String> {
+    chrono::Local::now()
+        .naive_local()
+        .checked_sub_signed(interval)
+        .ok_or_else(|| "interval is too long".to_string())
+}
+
+/// Parses `SELECT cols FROM logs [WHERE ...] [GROUP BY ...] [ORDER BY col
+/// [ASC|DESC], ...] [LIMIT n]`. ORDER BY also accepts `seq`, the input
+/// position, to pick the direction ties are broken in.
+pub fn parse(sql: &str) -> Result<Query, String> {
+    let mut parser = Parser {
+        tokens: tokenize(sql)?,
+        pos: 0,
+    };
+    let mut query = Query::default();
+
+    parser.expect_keyword("select")?;
+    query.select = parser.select_list()?;
+    parser.expect_keyword("from")?;
+    parser.word()?;
+
+    if parser.eat_keyword("where") {
+        query.filter = Some(parser.or_expr()?);
+    }
+    if parser.eat_keyword("group") {
+        parser.expect_keyword("by")?;
+        loop {
+            query.group_by.push(parser.field()?);
+            if !parser.eat_sym(",") {
+                break;
+            }
+        }
+    }
+    if parser.eat_keyword("order") {
+        parser.expect_keyword("by")?;
+        loop {
+            let name = if parser.eat_keyword("seq") {
+                if !query.group_by.is_empty() || query.select.iter().any(|c| c.is_aggregate()) {
+                    return Err("cannot order groups by seq".to_string());
+                }
+                "seq".to_string()
+            } else {
+                parser.column()?.name().to_string()
+            };
+            let descending = if parser.eat_keyword("desc") {
+                true
+            } else {
+                parser.eat_keyword("asc");
+                false
+            };
+            query.order_by.push((name, descending));
+            if !parser.eat_sym(",") {
+                break;
+            }
+        }
+    }
+    if parser.eat_keyword("limit") {
+        query.limit = Some(parser.limit()?);
+    }
+    parser.eat_sym(";");
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected {:?}", token));
+    }
+    Ok(query)
+}
+
+/// Parses a bare WHERE expression, e.g. `level = 'DEBUG' AND message LIKE
+/// '%test%'`.
+pub fn parse_predicate(expr: &str) -> Result<Predicate, String> {
+    let mut parser = Parser {
+        tokens: tokenize(expr)?,
+        pos: 0,
+    };
+    let predicate = parser.or_expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected {:?}", token));
+    }
+    Ok(predicate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(lines: &[&str]) -> Vec<LogEntry> {
+        lines
+            .iter()
+            .map(|l| LogEntry::from_pipe(l).unwrap())
+            .collect()
+    }
+
+    fn rows(sql: &str, entries: &[LogEntry]) -> Vec<Vec<String>> {
+        parse(sql).unwrap().run(entries).unwrap().rows
+    }
+
+    fn sample() -> Vec<LogEntry> {
+        entries(&[
+            "2026-01-01 00:00:00|INFO|user alice logged in",
+            "2026-01-01 00:00:01|ERROR|disk full",
+            "2026-01-01 00:00:02|error|Disk 50% full",
+            "2026-01-01 00:00:03|INFO|user bob logged in",
+        ])
+    }
+
+    #[test]
+    fn where_clauses() {
+        let found = rows(
+            "SELECT message FROM logs WHERE level = 'ERROR' AND NOT message LIKE '%50%'",
+            &sample(),
+        );
+        assert_eq!(found, [["disk full"]]);
+        let found = rows(
+            "select ts from logs where message like 'user _ob%'",
+            &sample(),
+        );
+        assert_eq!(found, [["2026-01-01 00:00:03"]]);
+        let found = rows(
+            "SELECT message FROM logs WHERE ts >= '2026-01-01 00:00:02' OR level = 'info' LIMIT 2",
+            &sample(),
+        );
+        assert_eq!(found, [["user alice logged in"], ["Disk 50% full"]]);
+    }
+
+    #[test]
+    fn like_escapes_regex() {
+        let found = rows(
+            "SELECT message FROM logs WHERE message LIKE '%(x)%'",
+            &entries(&["t|INFO|a (x) b", "t|INFO|axb"]),
+        );
+        assert_eq!(found, [["a (x) b"]]);
+    }
+
+    #[test]
+    fn groups() {
+        let found = rows(
+            "SELECT level, count(*), min(ts), max(ts) FROM logs GROUP BY level",
+            &sample(),
+        );
+        assert_eq!(
+            found[0],
+            ["INFO", "2", "2026-01-01 00:00:00", "2026-01-01 00:00:03"]
+        );
+        assert_eq!(found[1][..2], ["ERROR", "1"]);
+        assert_eq!(
+            rows("SELECT count(*) FROM logs WHERE level = 'none'", &sample()),
+            [["0"]]
+        );
+    }
+
+    #[test]
+    fn min_max_compare_times() {
+        // ISO 8601 sorts before the native format as text.
+        let logged = entries(&[
+            "2026-01-01 00:00:09|INFO|native",
+            "2026-01-01T00:00:05|INFO|iso",
+        ]);
+        let found = rows("SELECT min(ts), max(ts) FROM logs", &logged);
+        assert_eq!(found, [["2026-01-01T00:00:05", "2026-01-01 00:00:09"]]);
+    }
+
+    #[test]
+    fn json_rows() {
+        let result = parse("SELECT level, count(*) FROM logs GROUP BY level")
+            .unwrap()
+            .run(&sample())
+            .unwrap();
+        assert_eq!(
+            result.to_json(),
+            serde_json::json!([
+                { "level": "INFO", "count": "2" },
+                { "level": "ERROR", "count": "1" },
+                { "level": "error", "count": "1" },
+            ])
+        );
+    }
+
+    #[test]
+    fn paths() {
+        let mut logged = sample();
+        logged[1]
+            .fields
+            .insert("request".to_string(), serde_json::json!({ "status": 503 }));
+        let found = rows(
+            "SELECT message FROM logs WHERE .request.status >= 500",
+            &logged,
+        );
+        assert_eq!(found, [["disk full"]]);
+    }
+
+    #[test]
+    fn order_by_ties() {
+        let logged = entries(&[
+            "2026-01-01 00:00:05|WARN|third",
+            "2026-01-01 00:00:01|ERROR|second",
+            "2026-01-01 00:00:01|WARN|first",
+            "2026-01-01 00:00:01|ERROR|fourth",
+        ]);
+        let found = rows("SELECT message FROM logs ORDER BY level", &logged);
+        assert_eq!(found, [["second"], ["fourth"], ["first"], ["third"]]);
+        let found = rows(
+            "SELECT message FROM logs ORDER BY level DESC, seq DESC",
+            &logged,
+        );
+        assert_eq!(found, [["first"], ["third"], ["fourth"], ["second"]]);
+        let found = rows(
+            "SELECT message FROM logs ORDER BY seq DESC LIMIT 1",
+            &logged,
+        );
+        assert_eq!(found, [["fourth"]]);
+        assert_eq!(rows("SELECT message FROM logs", &logged)[0], ["third"]);
+    }
+
+    #[test]
+    fn group_order_is_stable() {
+        let logged = entries(&[
+            "2026-01-01 00:00:00|WARN|a",
+            "2026-01-01 00:00:00|INFO|b",
+            "2026-01-01 00:00:00|ERROR|c",
+            "2026-01-01 00:00:00|INFO|d",
+            "2026-01-01 00:00:00|ERROR|e",
+        ]);
+        let found = rows("SELECT level, count(*) FROM logs GROUP BY level", &logged);
+        assert_eq!(found, [["WARN", "1"], ["INFO", "2"], ["ERROR", "2"]]);
+        let found = rows(
+            "SELECT level, count(*) FROM logs GROUP BY level ORDER BY count(*) DESC",
+            &logged,
+        );
+        assert_eq!(found, [["INFO", "2"], ["ERROR", "2"], ["WARN", "1"]]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("SELECT FROM logs").is_err());
+        assert!(parse("SELECT * FROM logs WHERE").is_err());
+        assert!(parse("SELECT * FROM logs LIMIT ten").is_err());
+        assert!(parse("SELECT * FROM logs trailing").is_err());
+        assert!(parse("SELECT sum(ts) FROM logs").is_err());
+        assert!(parse("SELECT count(*) FROM logs ORDER BY seq").is_err());
+        let query = parse("SELECT message, count(*) FROM logs").unwrap();
+        assert!(query.run(&sample()).is_err());
+    }
+
+    #[test]
+    fn intervals() {
+        assert_eq!(parse_interval("45"), Ok(Duration::seconds(45)));
+        assert_eq!(parse_interval("90m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_interval(" 2 hours "), Ok(Duration::hours(2)));
+        assert_eq!(parse_interval("1w"), Ok(Duration::days(7)));
+        assert_eq!(parse_interval("1y"), Ok(Duration::days(365)));
+    }
+
+    #[test]
+    fn bad_intervals() {
+        assert!(parse_interval("").is_err());
+        assert!(parse_interval("5ms").is_err());
+        assert!(parse_interval("3 fortnights").is_err());
+        assert!(parse_interval("99999999999999y").is_err());
+        assert!(parse_interval("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn ago_does_not_overflow() {
+        assert!(ago(Duration::hours(1)).is_ok());
+        assert!(ago(parse_interval("100000000d").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use std::borrow::Cow;
+use std::net::Ipv6Addr;
+use std::sync::OnceLock;
+
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::LogEntry;
+
+/// The `[redaction]` table of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Redaction {
+    /// Built-in patterns to apply: "email", "ip" (v4 and v6), "card".
+    #[serde(default = "default_builtin")]
+    pub builtin: Vec<String>,
+    /// Extra `[[redaction.rule]]` patterns.
+    #[serde(default)]
+    pub rule: Vec<CustomRule>,
+    /// Redact entries as they are read and added.
+    #[serde(default)]
+    pub on_ingest: bool,
+    /// Redact exports and reports (CSV, NDJSON, Parquet, HTML, Markdown,
+    /// annotations).
+    #[serde(default = "default_on_export")]
+    pub on_export: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomRule {
+    pub name: String,
+    /// Regular expression; every match is replaced.
+    pub pattern: String,
+    /// Defaults to `[NAME]`.
+    pub replacement: Option<String>,
+}
+
+fn default_builtin() -> Vec<String> {
+    vec!["email".to_string(), "ip".to_string(), "card".to_string()]
+}
+
+fn default_on_export() -> bool {
+    true
+}
+
+// Regexes cannot check octet ranges, Luhn sums or IPv6 grammar, so each
+// match goes through a check before it is replaced.
+#[derive(Clone, Copy)]
+enum Check {
+    Always,
+    Ipv4,
+    Ipv6,
+    Luhn,
+}
+
+struct Rule {
+    regex: Regex,
+    check: Check,
+    replacement: String,
+}
+
+pub struct Redactor {
+    rules: Vec<Rule>,
+    on_ingest: bool,
+    on_export: bool,
+}
+
+static REDACTOR: OnceLock<Redactor> = OnceLock::new();
+
+fn luhn(digits: &str) -> bool {
+    let digits: Vec<u32> = digits.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+impl Check {
+    fn holds(&self, text: &str) -> bool {
+        match self {
+            Check::Always => true,
+            Check::Ipv4 => text.split('.').all(|octet| octet.parse::<u8>().is_ok()),
+            // Keeps `std::io`-like paths: a real address has a digit.
+            Check::Ipv6 => {
+                text.parse::<Ipv6Addr>().is_ok() && text.chars().any(|c| c.is_ascii_digit())
+            }
+            Check::Luhn => luhn(text),
+        }
+    }
+}
+
+impl Redactor {
+    pub fn from_config(config: &Redaction) -> Result<Redactor, String> {
+        let mut rules = Vec::new();
+        for name in &config.builtin {
+            let (patterns, replacement): (&[(&str, Check)], _) = match name.as_str() {
+                "email" => (
+                    &[(
+                        r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+                        Check::Always,
+                    )],
+                    "[EMAIL]",
+                ),
+                "ip" => (
+                    &[
+                        (r"\b(?:\d{1,3}\.){3}\d{1,3}\b", Check::Ipv4),
+                        (r"(?i)[0-9a-f]{0,4}(?::[0-9a-f]{0,4}){2,7}", Check::Ipv6),
+                    ],
+                    "[IP]",
+                ),
+                "card" => (&[(r"\b\d(?:[ -]?\d){12,18}\b", Check::Luhn)], "[CARD]"),
+                other => return Err(format!("unknown built-in redaction '{}'", other)),
+            };
+            for &(pattern, check) in patterns {
+                rules.push(Rule {
+                    regex: Regex::new(pattern).unwrap(),
+                    check,
+                    replacement: replacement.to_string(),
+                });
+            }
+        }
+        for rule in &config.rule {
+            rules.push(Rule {
+                regex: Regex::new(&rule.pattern)
+                    .map_err(|e| format!("redaction rule {}: {}", rule.name, e))?,
+                check: Check::Always,
+                replacement: rule
+                    .replacement
+                    .clone()
+                    .unwrap_or_else(|| format!("[{}]", rule.name.to_uppercase())),
+            });
+        }
+        Ok(Redactor {
+            rules,
+            on_ingest: config.on_ingest,
+            on_export: config.on_export,
+        })
+    }
+
+    pub fn redact_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for rule in &self.rules {
+            let replaced = rule.regex.replace_all(&text, |captures: &Captures| {
+                let found = &captures[0];
+                if rule.check.holds(found) {
+                    rule.replacement.clone()
+                } else {
+                    found.to_string()
+                }
+            });
+            if let Cow::Owned(replaced) = replaced {
+                text = replaced;
+            }
+        }
+        text
+    }
+
+    // Whether anything was replaced.
+    fn redact_string(&self, text: &mut String) -> bool {
+        let redacted = self.redact_text(text);
+        let changed = redacted != *text;
+        *text = redacted;
+        changed
+    }
+
+    fn redact_value(&self, value: &mut Value) -> bool {
+        match value {
+            Value::String(text) => self.redact_string(text),
+            Value::Array(items) => items
+                .iter_mut()
+                .fold(false, |changed, v| self.redact_value(v) | changed),
+            Value::Object(fields) => fields
+                .values_mut()
+                .fold(false, |changed, v| self.redact_value(v) | changed),
+            _ => false,
+        }
+    }
+
+    /// Redacts the message and every string field value in place; whether
+    /// anything was replaced.
+    pub fn redact_entry(&self, entry: &mut LogEntry) -> bool {
+        let changed = self.redact_string(&mut entry.message);
+        entry
+            .fields
+            .values_mut()
+            .fold(changed, |changed, value| self.redact_value(value) | changed)
+    }
+}
+
+pub fn init(config: Option<&Redaction>) -> Result<(), String> {
+    if let Some(config) = config {
+        let _ = REDACTOR.set(Redactor::from_config(config)?);
+    }
+    Ok(())
+}
+
+/// The configured redactor, if any.
+pub fn redactor() -> Option<&'static Redactor> {
+    REDACTOR.get()
+}
+
+/// Applies ingest-time redaction to a newly read or added entry; whether
+/// anything was replaced.
+pub fn on_ingest(entry: &mut LogEntry) -> bool {
+    REDACTOR
+        .get()
+        .filter(|r| r.on_ingest)
+        .is_some_and(|redactor| redactor.redact_entry(entry))
+}
+
+/// The entries as they may be exported: redacted copies when export
+/// redaction is configured, else the entries themselves.
+pub fn for_export(entries: &[LogEntry]) -> Cow<'_, [LogEntry]> {
+    match REDACTOR.get().filter(|r| r.on_export) {
+        Some(redactor) => Cow::Owned(
+            entries
+                .iter()
+                .map(|entry| {
+                    let mut copy = entry.clone();
+                    if redactor.redact_entry(&mut copy) {
+                        crate::provenance::record_export(&mut copy, entry, "redact:export");
+                    }
+                    copy
+                })
+                .collect(),
+        ),
+        None => Cow::Borrowed(entries),
+    }
+}
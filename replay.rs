@@ -0,0 +1,48 @@
+//! Re-emits a log with the pauses between its entries.
+
+use std::io;
+use std::thread;
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::LogEntry;
+
+pub struct Replay {
+    /// 2.0 replays twice as fast, 0.5 at half speed.
+    pub speed: f64,
+    /// Longest pause, so a quiet night in the log doesn't stall a demo.
+    pub max_gap: Option<Duration>,
+    /// Give entries the time they are replayed at instead of their own.
+    pub retime: bool,
+}
+
+impl Replay {
+    /// Calls `each` with the entries in file order, waiting as long before
+    /// each one as passed after the entry before it, scaled by the speed.
+    /// Entries without a time, or earlier than the one before, go at once.
+    pub fn run(
+        &self,
+        entries: Vec<LogEntry>,
+        mut each: impl FnMut(LogEntry) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut last: Option<NaiveDateTime> = None;
+        for mut entry in entries {
+            let time = entry.time();
+            if let (Some(last), Some(time)) = (last, time) {
+                let mut gap = time - last;
+                if let Some(max) = self.max_gap {
+                    gap = gap.min(max);
+                }
+                if let Ok(gap) = gap.to_std() {
+                    thread::sleep(gap.div_f64(self.speed));
+                }
+            }
+            last = time.or(last);
+            if self.retime {
+                entry.timestamp = crate::now_timestamp();
+            }
+            each(entry)?;
+        }
+        Ok(())
+    }
+}
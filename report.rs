@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::analysis::Stats;
+use crate::LogEntry;
+
+const TOP_MESSAGES: usize = 10;
+const RECENT_ERRORS: usize = 10;
+
+/// Picks minute, hour or day buckets so a histogram stays readable.
+pub fn auto_bucket(entries: &[LogEntry]) -> Duration {
+    let times: Vec<NaiveDateTime> = entries.iter().filter_map(|e| e.time()).collect();
+    let span = match (times.iter().min(), times.iter().max()) {
+        (Some(first), Some(last)) => *last - *first,
+        _ => Duration::zero(),
+    };
+    if span <= Duration::hours(2) {
+        Duration::minutes(1)
+    } else if span <= Duration::days(2) {
+        Duration::hours(1)
+    } else {
+        Duration::days(1)
+    }
+}
+
+pub fn bucket_label(bucket: Duration) -> String {
+    if bucket.num_days() > 0 && bucket.num_seconds() % 86_400 == 0 {
+        format!("{}d", bucket.num_days())
+    } else if bucket.num_hours() > 0 && bucket.num_seconds() % 3_600 == 0 {
+        format!("{}h", bucket.num_hours())
+    } else if bucket.num_minutes() > 0 && bucket.num_seconds() % 60 == 0 {
+        format!("{}m", bucket.num_minutes())
+    } else {
+        format!("{}s", bucket.num_seconds())
+    }
+}
+
+// Start of the bucket holding `time`, as a unix timestamp.
+pub fn bucket_start(time: NaiveDateTime, width: i64) -> i64 {
+    time.and_utc().timestamp().div_euclid(width) * width
+}
+
+/// Bucket start times from the first to the last occupied bucket.
+fn bucket_range(keys: impl Iterator<Item = i64> + Clone, width: i64) -> Vec<(i64, NaiveDateTime)> {
+    let (Some(first), Some(last)) = (keys.clone().min(), keys.max()) else {
+        return Vec::new();
+    };
+    (first..=last)
+        .step_by(width as usize)
+        .filter_map(|start| {
+            chrono::DateTime::from_timestamp(start, 0).map(|t| (start, t.naive_utc()))
+        })
+        .collect()
+}
+
+/// Counts entries per time bucket, oldest first, including empty buckets
+/// between the first and last entry. Entries whose timestamp does not
+/// parse are left out.
+pub fn time_histogram(entries: &[LogEntry], bucket: Duration) -> Vec<(NaiveDateTime, usize)> {
+    let width = bucket.num_seconds().max(1);
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for time in entries.iter().filter_map(|e| e.time()) {
+        *counts.entry(bucket_start(time, width)).or_insert(0) += 1;
+    }
+    bucket_range(counts.keys().copied(), width)
+        .into_iter()
+        .map(|(start, time)| (time, counts.get(&start).copied().unwrap_or(0)))
+        .collect()
+}
+
+/// Like time_histogram, but with a per-level breakdown of each bucket.
+pub fn level_histogram(
+    entries: &[LogEntry],
+    bucket: Duration,
+) -> Vec<(NaiveDateTime, HashMap<String, usize>)> {
+    let width = bucket.num_seconds().max(1);
+    let mut counts: HashMap<i64, HashMap<String, usize>> = HashMap::new();
+    for entry in entries {
+        if let Some(time) = entry.time() {
+            *counts
+                .entry(bucket_start(time, width))
+                .or_default()
+                .entry(entry.level.clone())
+                .or_insert(0) += 1;
+        }
+    }
+    bucket_range(counts.keys().copied(), width)
+        .into_iter()
+        .map(|(start, time)| (time, counts.remove(&start).unwrap_or_default()))
+        .collect()
+}
+
+/// Prints a bucket-per-row table with one column per level.
+pub fn print_level_histogram(buckets: &[(NaiveDateTime, HashMap<String, usize>)]) {
+    let mut totals: HashMap<&str, usize> = HashMap::new();
+    for (_, levels) in buckets {
+        for (level, count) in levels {
+            *totals.entry(level.as_str()).or_insert(0) += count;
+        }
+    }
+    let mut levels: Vec<(&str, usize)> = totals.into_iter().collect();
+    levels.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    print!("\n{:<16}", "bucket");
+    for (level, _) in &levels {
+        print!(" {:>8}", level);
+    }
+    println!();
+    for (start, counts) in buckets {
+        print!("{:<16}", start.format("%Y-%m-%d %H:%M").to_string());
+        for (level, _) in &levels {
+            print!(" {:>8}", counts.get(*level).copied().unwrap_or(0));
+        }
+        println!();
+    }
+}
+
+/// `1h 05m 09s`, or `2d 1h 05m 09s` for longer stretches.
+pub fn duration_label(duration: Duration) -> String {
+    let hours = format!(
+        "{}h {:02}m {:02}s",
+        duration.num_hours() % 24,
+        duration.num_minutes() % 60,
+        duration.num_seconds() % 60
+    );
+    match duration.num_days() {
+        0 => hours,
+        days => format!("{}d {}", days, hours),
+    }
+}
+
+/// Prints the totals of `logger stats`: levels with their share, the time
+/// span and rate, and the longest quiet period.
+pub fn print_stats(stats: &Stats) {
+    println!("Total entries: {}", stats.total);
+    for (level, count) in &stats.levels {
+        println!("{}: {} ({:.1}%)", level, count, stats.percent(*count));
+    }
+    let (Some(first), Some(last), Some(span)) = (stats.first, stats.last, stats.span()) else {
+        return;
+    };
+    println!(
+        "\nFrom {} to {} ({})",
+        first.format(crate::TIMESTAMP_FORMAT),
+        last.format(crate::TIMESTAMP_FORMAT),
+        duration_label(span)
+    );
+    if let Some(rate) = stats.per_minute() {
+        println!("Entries per minute: {:.2}", rate);
+    }
+    if let Some(quiet) = &stats.longest_quiet {
+        println!(
+            "Longest quiet period: {} from {}",
+            duration_label(quiet.length()),
+            quiet.from.format(crate::TIMESTAMP_FORMAT)
+        );
+    }
+}
+
+/// `logger stats` as JSON, also served at GET /stats.
+pub fn stats_json(stats: &Stats) -> serde_json::Value {
+    let time = |t: Option<NaiveDateTime>| t.map(|t| t.format(crate::TIMESTAMP_FORMAT).to_string());
+    let levels: serde_json::Map<String, serde_json::Value> = stats
+        .levels
+        .iter()
+        .map(|(level, count)| (level.clone(), serde_json::Value::from(*count)))
+        .collect();
+    let percents: serde_json::Map<String, serde_json::Value> = stats
+        .levels
+        .iter()
+        .map(|(level, count)| {
+            (
+                level.clone(),
+                serde_json::Value::from(stats.percent(*count)),
+            )
+        })
+        .collect();
+    serde_json::json!({
+        "total": stats.total,
+        "levels": levels,
+        "percent": percents,
+        "first": time(stats.first),
+        "last": time(stats.last),
+        "span_seconds": stats.span().map(|span| span.num_seconds()),
+        "per_minute": stats.per_minute(),
+        "longest_quiet": stats.longest_quiet.as_ref().map(|quiet| serde_json::json!({
+            "from": time(Some(quiet.from)),
+            "to": time(Some(quiet.to)),
+            "seconds": quiet.length().num_seconds(),
+        })),
+    })
+}
+
+const BAR_WIDTH: usize = 50;
+
+/// Prints one row per bucket with a bar scaled to the busiest bucket.
+pub fn print_histogram(entries: &[LogEntry], bucket: Duration) {
+    let histogram = time_histogram(entries, bucket);
+    let peak = histogram.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+    let digits = peak.to_string().len();
+    println!("\nVolume per {}:", bucket_label(bucket));
+    for (start, count) in histogram {
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(peak));
+        println!(
+            "{} {:>digits$} {}",
+            start.format("%Y-%m-%d %H:%M"),
+            count,
+            bar,
+            digits = digits
+        );
+    }
+}
+
+pub fn is_error(entry: &LogEntry) -> bool {
+    matches!(
+        entry.level.to_uppercase().as_str(),
+        "ERROR" | "FATAL" | "CRITICAL"
+    )
+}
+
+/// Most frequent messages among ERROR-and-worse entries.
+pub fn top_error_messages(entries: &[LogEntry], count: usize) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for entry in entries.iter().filter(|e| is_error(e)) {
+        *counts.entry(entry.message.as_str()).or_insert(0) += 1;
+    }
+    let mut top: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(message, n)| (message.to_string(), n))
+        .collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top.truncate(count);
+    top
+}
+
+fn level_counts(entries: &[LogEntry]) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for entry in entries {
+        *counts.entry(entry.level.as_str()).or_insert(0) += 1;
+    }
+    let mut levels: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(level, n)| (level.to_string(), n))
+        .collect();
+    levels.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    levels
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:2em}\
+td,th{border:1px solid #ccc;padding:4px 8px;text-align:left}\
+.bar{background:#4a7bd0;height:12px}\
+.ERROR,.FATAL,.CRITICAL{color:#b00020}.WARNING,.WARN{color:#a66b00}";
+
+const SCRIPT: &str = "function filterRows(){\
+var q=document.getElementById('q').value.toLowerCase();\
+var l=document.getElementById('l').value;\
+document.querySelectorAll('#entries tbody tr').forEach(function(r){\
+var ok=r.textContent.toLowerCase().indexOf(q)>=0&&(!l||r.dataset.level===l);\
+r.style.display=ok?'':'none';});}";
+
+/// Writes a standalone HTML report: totals, per-level breakdown, a time
+/// histogram, top error messages and a filterable table of all entries.
+/// With a `budget` the table holds at most about that many entries (see
+/// `analysis::within_budget`); the summaries still cover everything.
+pub fn write_html(
+    entries: &[LogEntry],
+    source: &str,
+    filename: &str,
+    budget: Option<usize>,
+) -> io::Result<()> {
+    let entries = &*crate::redact::for_export(entries);
+    let mut out = crate::export::create_output(filename)?;
+    let levels = level_counts(entries);
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>Log report: {}</title>", html_escape(source))?;
+    writeln!(out, "<style>{}</style><script>{}</script>", STYLE, SCRIPT)?;
+    writeln!(out, "</head><body>")?;
+    writeln!(out, "<h1>Log report: {}</h1>", html_escape(source))?;
+    writeln!(
+        out,
+        "<p>Generated {}. Total entries: {}</p>",
+        chrono::Local::now().format(crate::TIMESTAMP_FORMAT),
+        entries.len()
+    )?;
+
+    writeln!(
+        out,
+        "<h2>Levels</h2><table><tr><th>Level</th><th>Count</th><th>%</th></tr>"
+    )?;
+    for (level, count) in &levels {
+        writeln!(
+            out,
+            "<tr><td class=\"{0}\">{0}</td><td>{1}</td><td>{2:.1}</td></tr>",
+            html_escape(level),
+            count,
+            *count as f64 * 100.0 / entries.len() as f64
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    let bucket = auto_bucket(entries);
+    let histogram = time_histogram(entries, bucket);
+    let peak = histogram.iter().map(|(_, n)| *n).max().unwrap_or(1);
+    writeln!(
+        out,
+        "<h2>Volume over time ({} buckets)</h2><table>",
+        bucket_label(bucket)
+    )?;
+    for (start, count) in &histogram {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td style=\"width:400px\"><div class=\"bar\" style=\"width:{}%\"></div></td></tr>",
+            start.format(crate::TIMESTAMP_FORMAT),
+            count,
+            count * 100 / peak
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    writeln!(
+        out,
+        "<h2>Top error messages</h2><table><tr><th>Count</th><th>Message</th></tr>"
+    )?;
+    for (message, count) in top_error_messages(entries, TOP_MESSAGES) {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            count,
+            html_escape(&message)
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<h2>Entries</h2>")?;
+    writeln!(
+        out,
+        "<p><input id=\"q\" placeholder=\"Filter\" oninput=\"filterRows()\"> <select id=\"l\" onchange=\"filterRows()\"><option value=\"\">All levels</option>"
+    )?;
+    for (level, _) in &levels {
+        writeln!(out, "<option>{}</option>", html_escape(level))?;
+    }
+    writeln!(out, "</select></p>")?;
+    writeln!(
+        out,
+        "<table id=\"entries\"><thead><tr><th>Timestamp</th><th>Level</th><th>Message</th></tr></thead><tbody>"
+    )?;
+    let shown = match budget {
+        Some(budget) => crate::analysis::within_budget(entries, budget),
+        None => entries.to_vec(),
+    };
+    for entry in &shown {
+        writeln!(
+            out,
+            "<tr data-level=\"{1}\"><td>{0}</td><td class=\"{1}\">{1}</td><td>{2}</td></tr>",
+            html_escape(&entry.timestamp),
+            html_escape(&entry.level),
+            html_escape(&entry.message)
+        )?;
+    }
+    writeln!(out, "</tbody></table></body></html>")?;
+    out.finish()
+}
+
+// Keeps a value from breaking out of its Markdown table cell.
+fn markdown_cell(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+/// Writes a Markdown summary (level table, top error messages, recent
+/// errors) suitable for pasting into an issue or runbook. `-` writes to
+/// stdout.
+pub fn write_markdown(entries: &[LogEntry], source: &str, filename: &str) -> io::Result<()> {
+    let mut out = crate::export::create_output(filename)?;
+    out.write_all(markdown(entries, source).as_bytes())?;
+    out.finish()
+}
+
+/// The summary [`write_markdown`] writes, e.g. to post or mail it.
+pub fn markdown(entries: &[LogEntry], source: &str) -> String {
+    let mut out = Vec::new();
+    write_summary(&mut out, entries, source).expect("writing to memory cannot fail");
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn write_summary(out: &mut Vec<u8>, entries: &[LogEntry], source: &str) -> io::Result<()> {
+    let entries = &*crate::redact::for_export(entries);
+    writeln!(out, "## Log summary: {}", markdown_cell(source))?;
+    writeln!(out)?;
+    writeln!(out, "Total entries: **{}**", entries.len())?;
+    writeln!(out)?;
+    writeln!(out, "| Level | Count | % |")?;
+    writeln!(out, "|---|---:|---:|")?;
+    for (level, count) in level_counts(entries) {
+        writeln!(
+            out,
+            "| {} | {} | {:.1} |",
+            markdown_cell(&level),
+            count,
+            count as f64 * 100.0 / entries.len() as f64
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "### Top error messages")?;
+    writeln!(out)?;
+    let top = top_error_messages(entries, TOP_MESSAGES);
+    if top.is_empty() {
+        writeln!(out, "_No errors._")?;
+    } else {
+        writeln!(out, "| Count | Message |")?;
+        writeln!(out, "|---:|---|")?;
+        for (message, count) in top {
+            writeln!(out, "| {} | {} |", count, markdown_cell(&message))?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "### Recent errors")?;
+    writeln!(out)?;
+    let errors: Vec<&LogEntry> = entries.iter().filter(|e| is_error(e)).collect();
+    if errors.is_empty() {
+        writeln!(out, "_No errors._")?;
+    } else {
+        writeln!(out, "```")?;
+        for entry in &errors[errors.len().saturating_sub(RECENT_ERRORS)..] {
+            writeln!(
+                out,
+                "[{}] {} - {}",
+                entry.timestamp, entry.level, entry.message
+            )?;
+        }
+        writeln!(out, "```")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use chrono::{Duration, NaiveDateTime};
+use serde::Deserialize;
+
+use crate::{LogAnalyzer, LogEntry};
+
+/// The `[retention]` table of the config file: ages per level, e.g.
+/// `ERROR = "1y"`, `INFO = "30d"`, with `default` for every other level,
+/// and the settings below.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RetentionTable {
+    /// Keep at most this many entries, the newest.
+    pub max_entries: Option<usize>,
+    /// Move removed entries into per-day files instead of deleting them,
+    /// see `archive`.
+    pub archive: bool,
+    /// Where the day files go [default: next to the log file, named after
+    /// it].
+    pub archive_dir: Option<String>,
+    /// Apply the policy to the log file whenever it is loaded or written,
+    /// at most once a minute, instead of only on `logger retention`.
+    pub automatic: bool,
+    #[serde(flatten)]
+    pub ages: BTreeMap<String, String>,
+}
+
+/// How long entries are kept, per level, and how many. Levels without an
+/// age (and entries without a readable timestamp) are kept as long as the
+/// limit allows.
+#[derive(Debug, Clone, Default)]
+pub struct Retention {
+    pub default: Option<Duration>,
+    /// Keyed by upper-case level.
+    pub levels: BTreeMap<String, Duration>,
+    pub max_entries: Option<usize>,
+    /// Archive removed entries, to this directory if given.
+    pub archive: Option<Option<PathBuf>>,
+}
+
+static POLICY: OnceLock<Retention> = OnceLock::new();
+
+/// The log file the policy is applied to automatically, if it is.
+static AUTOMATIC: OnceLock<String> = OnceLock::new();
+
+impl Retention {
+    pub fn from_table(table: &RetentionTable) -> Result<Retention, String> {
+        let mut retention = Retention {
+            max_entries: table.max_entries,
+            archive: table
+                .archive
+                .then(|| table.archive_dir.as_ref().map(PathBuf::from)),
+            ..Retention::default()
+        };
+        for (key, value) in &table.ages {
+            let age = crate::query::parse_interval(value)
+                .map_err(|e| format!("retention for {}: {}", key, e))?;
+            if key.eq_ignore_ascii_case("default") {
+                retention.default = Some(age);
+            } else {
+                retention.levels.insert(key.to_uppercase(), age);
+            }
+        }
+        Ok(retention)
+    }
+
+    pub fn max_age(&self, level: &str) -> Option<Duration> {
+        self.levels
+            .get(&level.to_uppercase())
+            .copied()
+            .or(self.default)
+    }
+
+    pub fn is_expired(&self, entry: &LogEntry, now: NaiveDateTime) -> bool {
+        match (self.max_age(&entry.level), entry.time()) {
+            (Some(age), Some(time)) => now - time > age,
+            _ => false,
+        }
+    }
+
+    /// Takes out expired entries, then the oldest beyond the limit, and
+    /// returns them.
+    pub fn apply(&self, entries: &mut Vec<LogEntry>, now: NaiveDateTime) -> Vec<LogEntry> {
+        let (expired, kept) = std::mem::take(entries)
+            .into_iter()
+            .partition::<Vec<_>, _>(|entry| self.is_expired(entry, now));
+        *entries = kept;
+        let mut removed = expired;
+        if let Some(max) = self.max_entries {
+            let over = entries.len().saturating_sub(max);
+            removed.extend(entries.drain(..over));
+        }
+        removed
+    }
+}
+
+/// Counts per level, as `purge_file` reports them.
+fn by_level(entries: &[LogEntry]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for entry in entries {
+        *counts.entry(entry.level.to_uppercase()).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Takes the `[retention]` of the config, applied automatically to
+/// `log_file` if it says so.
+pub fn init(table: Option<&RetentionTable>, log_file: &str) -> Result<(), String> {
+    if let Some(table) = table {
+        let _ = POLICY.set(Retention::from_table(table)?);
+        if table.automatic {
+            let _ = AUTOMATIC.set(log_file.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Applies the policy to `filename` if it is the automatically kept log
+/// file and a minute has passed since the last time. Called before the
+/// file is loaded or appended to. The last time is the modification time
+/// of the file's lock file, so it holds across processes.
+pub fn enforce(filename: &str) -> io::Result<()> {
+    let (Some(policy), Some(file)) = (POLICY.get(), AUTOMATIC.get()) else {
+        return Ok(());
+    };
+    if filename != file || !std::path::Path::new(filename).exists() {
+        return Ok(());
+    }
+    let marker = crate::lock::path(filename);
+    let recent = std::fs::metadata(&marker)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age.as_secs() < 60);
+    if recent {
+        return Ok(());
+    }
+    purge_file(policy, filename, filename, false)?;
+    // Purging locked the file, so its lock file exists.
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&marker)?
+        .set_modified(SystemTime::now())
+}
+
+/// The configured policy, if any.
+pub fn policy() -> Option<&'static Retention> {
+    POLICY.get()
+}
+
+/// Rewrites `filename` without its expired entries and those beyond the
+/// limit (deleting it if none are left), after archiving them if the
+/// policy says so, and returns what was removed. `log_file` is the file it
+/// was rotated from, whose archive directory is used. With `dry_run`
+/// nothing is written.
+pub fn purge_file(
+    retention: &Retention,
+    filename: &str,
+    log_file: &str,
+    dry_run: bool,
+) -> io::Result<BTreeMap<String, usize>> {
+    // Held throughout, so nothing is appended between reading and rewriting.
+    let _lock = crate::lock::exclusive(filename)?;
+    // Entries are written back, so they must not be transformed again.
+    let mut analyzer = LogAnalyzer::builder().pipeline(false).build();
+    analyzer.load_from_file(filename)?;
+    let removed = retention.apply(&mut analyzer.entries, chrono::Local::now().naive_local());
+    if !dry_run && !removed.is_empty() {
+        if let Some(dir) = &retention.archive {
+            let dir = dir
+                .clone()
+                .unwrap_or_else(|| crate::archive::default_dir(log_file));
+            let (days, _) = crate::archive::split(removed.clone(), NaiveDateTime::MAX);
+            crate::archive::write_days(&dir, log_file, &days)?;
+        }
+        if analyzer.entries.is_empty() {
+            std::fs::remove_file(filename)?;
+        } else {
+            analyzer.save_to_file(filename)?;
+        }
+    }
+    Ok(by_level(&removed))
+}
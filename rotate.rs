@@ -0,0 +1,176 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::Deserialize;
+
+/// The `[rotation]` table of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rotation {
+    /// Rotate to logs.txt.1, logs.txt.2, ... once the file reaches this
+    /// size, e.g. "10MB".
+    pub max_size: Option<String>,
+    /// Rotate to logs-2024-05-01.txt when the file was last written on an
+    /// earlier day.
+    #[serde(default)]
+    pub daily: bool,
+    /// Rotated files to keep of each kind; older ones are deleted.
+    #[serde(default = "default_keep")]
+    pub keep: usize,
+}
+
+fn default_keep() -> usize {
+    5
+}
+
+struct Policy {
+    max_bytes: Option<u64>,
+    daily: bool,
+    keep: usize,
+}
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Parses sizes like `500`, `64K`, `10MB` or `1G` (powers of 1024).
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", text))?;
+    let shift = match unit.trim().to_uppercase().trim_end_matches('B') {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        _ => return Err(format!("invalid size unit in '{}'", text)),
+    };
+    Ok(number << shift)
+}
+
+/// Enables rotation for every later `before_append`.
+pub fn init(rotation: Option<&Rotation>) -> Result<(), String> {
+    let Some(rotation) = rotation else {
+        return Ok(());
+    };
+    let policy = Policy {
+        max_bytes: rotation.max_size.as_deref().map(parse_size).transpose()?,
+        daily: rotation.daily,
+        keep: rotation.keep.max(1),
+    };
+    let _ = POLICY.set(policy);
+    Ok(())
+}
+
+// "logs.txt" -> ("logs", ".txt")
+fn split_name(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (stem, ext)
+}
+
+// Applies the retention policy to a file rotated from `log_file`, so old
+// entries age out of rotated files the same way they do from the live one.
+fn expire(path: &Path, log_file: &str) -> io::Result<()> {
+    if let Some(retention) = crate::retention::policy() {
+        crate::retention::purge_file(retention, &path.to_string_lossy(), log_file, false)?;
+    }
+    Ok(())
+}
+
+fn rotate_by_size(filename: &str, keep: usize) -> io::Result<()> {
+    let numbered = |n: usize| format!("{}.{}", filename, n);
+    match fs::remove_file(numbered(keep)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    for n in (1..keep).rev() {
+        if Path::new(&numbered(n)).exists() {
+            fs::rename(numbered(n), numbered(n + 1))?;
+        }
+    }
+    fs::rename(filename, numbered(1))?;
+    for n in 1..=keep {
+        let path = numbered(n);
+        if Path::new(&path).exists() {
+            expire(Path::new(&path), filename)?;
+        }
+    }
+    Ok(())
+}
+
+fn rotate_by_date(path: &Path, day: NaiveDate, keep: usize) -> io::Result<()> {
+    let (stem, ext) = split_name(path);
+    let target = path.with_file_name(format!("{}-{}{}", stem, day.format("%Y-%m-%d"), ext));
+    if target.exists() {
+        // Already rotated once that day; keep everything in one file.
+        let mut out = OpenOptions::new().append(true).open(&target)?;
+        io::copy(&mut fs::File::open(path)?, &mut out)?;
+        fs::remove_file(path)?;
+    } else {
+        fs::rename(path, &target)?;
+    }
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}-", stem);
+    let mut dated: Vec<_> = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| {
+            name.strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(&ext))
+                .is_some_and(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok())
+        })
+        .collect();
+    dated.sort();
+    let (pruned, kept) = dated.split_at(dated.len().saturating_sub(keep));
+    for name in pruned {
+        fs::remove_file(dir.join(name))?;
+    }
+    for name in kept {
+        expire(&dir.join(name), &path.to_string_lossy())?;
+    }
+    Ok(())
+}
+
+/// Rotates `filename` first if it is due, so the next write starts a
+/// fresh file, and applies automatic retention to it. Does nothing unless
+/// rotation or automatic retention is configured.
+pub fn before_append(filename: &str) -> io::Result<()> {
+    crate::retention::enforce(filename)?;
+    let Some(policy) = POLICY.get() else {
+        return Ok(());
+    };
+    let _lock = crate::lock::exclusive(filename)?;
+    let path = Path::new(filename);
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if policy.daily {
+        let written: DateTime<Local> = metadata.modified()?.into();
+        if written.date_naive() < Local::now().date_naive() {
+            return rotate_by_date(path, written.date_naive(), policy.keep);
+        }
+    }
+    if policy.max_bytes.is_some_and(|max| metadata.len() >= max) {
+        rotate_by_size(filename, policy.keep)?;
+    }
+    Ok(())
+}
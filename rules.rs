@@ -0,0 +1,947 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::sync::Arc;
+
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::alert::{self, Action};
+use crate::gate::{parse_number, parse_op, LevelSelector};
+use crate::query::{self, Op};
+use crate::script::Script;
+use crate::smtp::Smtp;
+use crate::LogEntry;
+
+pub const RULES_HELP: &str = "\
+Rules file:
+  [[rule]]
+  name = \"error spike\"
+  level = \"ERROR+\"        # optional: LEVEL, or LEVEL+ for that and worse
+  contains = \"timeout\"    # optional message substring
+  matches = \"/OOM|oom-kill/\" # optional message regex
+  script = \"slow.rhai\"    # optional: entries the Rhai script keeps
+  window = \"10m\"          # default 5m
+  when = \"change >= 2x\"   # or e.g. \"count > 50\"; default \"count > 0\"
+  cooldown = \"30m\"        # optional: notify again while still firing
+  command = \"./page.sh\"   # optional: run on fire and resolve
+  webhook = \"https://...\" # optional: POST the alert as JSON
+  slack = \"https://hooks.slack.com/...\"   # optional: post a summary
+  discord = \"https://discord.com/api/webhooks/...\"
+  email = [\"oncall@example.com\"]          # optional: mail a summary
+  pagerduty_key_env = \"PD_ROUTING_KEY\"    # optional: trigger and resolve
+  opsgenie_key_env = \"OPSGENIE_API_KEY\"   # an incident per rule
+  issue = true              # optional: open a ticket, see LOGGER_ISSUE_*
+
+change compares the latest window with the one before it. Checks
+combine with all = [{ window = ..., when = ... }, ...] or any = [...].
+Commands get LOGGER_ALERT_RULE, LOGGER_ALERT_STATUS (fired/resolved),
+LOGGER_ALERT_COUNT, LOGGER_ALERT_LEVEL and LOGGER_ALERT_KEY, and the
+sample entries on stdin.
+
+  [smtp]                      # for email actions; else the config's
+  server = \"smtp.example.com:587\"
+  from = \"logger@example.com\"
+  tls = \"starttls\"            # or \"tls\" (port 465) or \"none\"
+  username = \"logger\"         # optional
+  password_env = \"SMTP_PASSWORD\"
+
+  [[silence]]
+  rules = [\"error spike\"]  # optional, all rules when omitted
+  cron = \"0 2 * * 0\"       # recurring: minute hour day month weekday
+  duration = \"1h\"
+  # or one-off: start = \"2024-01-15 02:00:00\", end = \"2024-01-15 03:00:00\"
+  reason = \"weekly deploy\"
+
+Silenced rules don't fire; what they would have reported is recorded
+in --silenced-log instead.";
+
+/// A rule as written in the rules file. A rule is either a single check
+/// (`window` and `when`, with optional `level`/`contains` filters) or a
+/// combination of nested checks in `all` or `any`:
+///
+/// ```toml
+/// [[rule]]
+/// name = "5xx spike without a deploy"
+/// all = [
+///     { contains = "status=5", window = "5m", when = "change >= 2x" },
+///     { contains = "deployment started", window = "15m", when = "count == 0" },
+/// ]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSpec {
+    /// Required for rules, optional (a label) for nested checks.
+    pub name: Option<String>,
+    pub level: Option<String>,
+    pub contains: Option<String>,
+    /// Message regex, optionally written between slashes: `/OOM/`.
+    pub matches: Option<String>,
+    /// Rhai script deciding per entry whether it counts, see `script`.
+    pub script: Option<String>,
+    pub window: Option<String>,
+    pub when: Option<String>,
+    pub all: Option<Vec<RuleSpec>>,
+    pub any: Option<Vec<RuleSpec>>,
+    /// Rules only: while the rule keeps firing, notify again after this
+    /// long. Without it a rule notifies once per firing.
+    pub cooldown: Option<String>,
+    /// Rules only: shell command run when the rule fires or resolves.
+    pub command: Option<String>,
+    /// Rules only: URL the alert is POSTed to when it fires or resolves.
+    pub webhook: Option<String>,
+    /// Rules only: Slack incoming webhook for a summary of the alert.
+    pub slack: Option<String>,
+    /// Rules only: Discord webhook for a summary of the alert.
+    pub discord: Option<String>,
+    /// Rules only: addresses mailed a summary through `[smtp]`.
+    pub email: Option<Vec<String>>,
+    /// Rules only: environment variable with the PagerDuty routing key of
+    /// the service incidents are opened and resolved on.
+    pub pagerduty_key_env: Option<String>,
+    /// Rules only: environment variable with the Opsgenie API key.
+    pub opsgenie_key_env: Option<String>,
+    /// Rules only: open a ticket when the rule fires, in the tracker the
+    /// LOGGER_ISSUE_* variables describe.
+    pub issue: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleSpec>,
+    #[serde(default)]
+    silence: Vec<SilenceSpec>,
+    smtp: Option<Smtp>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SilenceSpec {
+    pub rules: Option<Vec<String>>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub cron: Option<String>,
+    pub duration: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// Matching entries in the latest window.
+    Count(Op, f64),
+    /// Latest window's count as a multiple of the window before it.
+    Change(Op, f64),
+}
+
+/// A count of matching entries over a window, compared with a condition.
+#[derive(Debug, Clone)]
+pub struct Threshold {
+    pub label: Option<String>,
+    pub level: LevelSelector,
+    pub contains: Option<String>,
+    pub matches: Option<Regex>,
+    pub script: Option<Arc<Script>>,
+    pub window: Duration,
+    pub condition: Condition,
+}
+
+#[derive(Debug, Clone)]
+pub enum Check {
+    Threshold(Threshold),
+    All(Vec<Check>),
+    Any(Vec<Check>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub check: Check,
+    pub cooldown: Option<Duration>,
+    pub actions: Vec<Action>,
+}
+
+/// Outcome of one threshold.
+pub struct CheckResult {
+    pub description: String,
+    pub held: bool,
+    pub current: usize,
+    pub previous: usize,
+}
+
+pub struct Evaluation {
+    pub fired: bool,
+    /// Every threshold of the rule, in the order written.
+    pub checks: Vec<CheckResult>,
+    /// Matching entries of the latest window of thresholds that held.
+    pub matches: Vec<LogEntry>,
+}
+
+impl Evaluation {
+    /// One-line summary of the thresholds, e.g. for the silenced log.
+    pub fn summary(&self) -> String {
+        self.checks
+            .iter()
+            .map(|c| c.description.as_str())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+// `/pattern/` or a bare pattern.
+fn parse_regex(text: &str) -> Result<Regex, String> {
+    let pattern = text
+        .strip_prefix('/')
+        .and_then(|rest| rest.strip_suffix('/'))
+        .unwrap_or(text);
+    Regex::new(pattern).map_err(|e| format!("invalid matches '{}': {}", text, e))
+}
+
+fn parse_condition(text: &str) -> Result<Condition, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let [metric, op, value] = words.as_slice() else {
+        return Err(format!(
+            "expected 'count <op> N' or 'change <op> Nx', got '{}'",
+            text
+        ));
+    };
+    let op = parse_op(op)?;
+    match metric.to_lowercase().as_str() {
+        "count" => Ok(Condition::Count(op, parse_number(value)?)),
+        "change" => Ok(Condition::Change(
+            op,
+            parse_number(value.trim_end_matches(['x', 'X']))?,
+        )),
+        _ => Err(format!("unknown metric '{}'", metric)),
+    }
+}
+
+impl Check {
+    fn from_spec(spec: RuleSpec) -> Result<Check, String> {
+        let nested = |specs: Vec<RuleSpec>| -> Result<Vec<Check>, String> {
+            if specs.is_empty() {
+                return Err("all/any needs at least one check".to_string());
+            }
+            specs.into_iter().map(Check::from_spec).collect()
+        };
+        if spec.cooldown.is_some()
+            || spec.command.is_some()
+            || spec.webhook.is_some()
+            || spec.slack.is_some()
+            || spec.discord.is_some()
+            || spec.email.is_some()
+            || spec.pagerduty_key_env.is_some()
+            || spec.opsgenie_key_env.is_some()
+            || spec.issue.is_some()
+        {
+            return Err("cooldown and actions can only be set on a rule".to_string());
+        }
+        let filtered = spec.level.is_some()
+            || spec.contains.is_some()
+            || spec.matches.is_some()
+            || spec.script.is_some();
+        match (spec.all, spec.any, spec.window, spec.when) {
+            (Some(all), None, None, None) if !filtered => Ok(Check::All(nested(all)?)),
+            (None, Some(any), None, None) if !filtered => Ok(Check::Any(nested(any)?)),
+            (None, None, window, when) if filtered || window.is_some() || when.is_some() => {
+                let window = query::parse_interval(window.as_deref().unwrap_or("5m"))?;
+                if window <= Duration::zero() {
+                    return Err("window must be positive".to_string());
+                }
+                Ok(Check::Threshold(Threshold {
+                    label: spec.name,
+                    level: spec
+                        .level
+                        .as_deref()
+                        .map_or(LevelSelector::Any, LevelSelector::parse),
+                    contains: spec.contains.map(|c| c.to_lowercase()),
+                    matches: spec.matches.as_deref().map(parse_regex).transpose()?,
+                    script: match spec.script.as_deref() {
+                        Some(path) => Some(Arc::new(Script::load(path)?)),
+                        None => None,
+                    },
+                    window,
+                    condition: parse_condition(when.as_deref().unwrap_or("count > 0"))?,
+                }))
+            }
+            _ => Err(
+                "expected a check (level, contains, matches, script, window or when), or just all or any"
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// How far back this check looks from the time it is evaluated at.
+    fn horizon(&self) -> Duration {
+        match self {
+            Check::Threshold(threshold) => threshold.window * 2,
+            Check::All(checks) | Check::Any(checks) => checks
+                .iter()
+                .map(Check::horizon)
+                .max()
+                .unwrap_or_else(Duration::zero),
+        }
+    }
+
+    fn evaluate(&self, entries: &[LogEntry], now: NaiveDateTime, out: &mut Evaluation) -> bool {
+        match self {
+            Check::Threshold(threshold) => threshold.evaluate(entries, now, out),
+            // Every nested check is evaluated, so the report shows them all.
+            Check::All(checks) => {
+                let held: Vec<bool> = checks
+                    .iter()
+                    .map(|c| c.evaluate(entries, now, out))
+                    .collect();
+                held.iter().all(|&h| h)
+            }
+            Check::Any(checks) => {
+                let held: Vec<bool> = checks
+                    .iter()
+                    .map(|c| c.evaluate(entries, now, out))
+                    .collect();
+                held.iter().any(|&h| h)
+            }
+        }
+    }
+}
+
+impl Threshold {
+    fn selects(&self, entry: &LogEntry) -> bool {
+        self.level.matches(entry)
+            && self
+                .contains
+                .as_ref()
+                .is_none_or(|c| entry.message.to_lowercase().contains(c))
+            && self
+                .matches
+                .as_ref()
+                .is_none_or(|m| m.is_match(&entry.message))
+            && self.script.as_ref().is_none_or(|s| s.matches(entry))
+    }
+
+    fn describe(&self, current: usize, previous: usize) -> String {
+        let mut text = match (&self.label, &self.contains, &self.matches) {
+            (Some(label), _, _) => label.clone(),
+            (None, Some(contains), _) => {
+                format!("{} with '{}'", self.level.describe(), contains)
+            }
+            (None, None, Some(matches)) => {
+                format!("{} matching /{}/", self.level.describe(), matches.as_str())
+            }
+            (None, None, None) => self.level.describe(),
+        };
+        text.push_str(&format!(
+            ": {} in last {}",
+            current,
+            crate::report::bucket_label(self.window)
+        ));
+        if let Condition::Change(..) = self.condition {
+            text.push_str(&format!(", {} in the window before", previous));
+        }
+        text
+    }
+
+    /// Evaluates the window ending at `now` (and, for change conditions,
+    /// the one before it). A previous window with no matches counts as one,
+    /// so a single stray entry isn't an infinite increase.
+    fn evaluate(&self, entries: &[LogEntry], now: NaiveDateTime, out: &mut Evaluation) -> bool {
+        let start = now
+            .checked_sub_signed(self.window)
+            .unwrap_or(NaiveDateTime::MIN);
+        let before = start
+            .checked_sub_signed(self.window)
+            .unwrap_or(NaiveDateTime::MIN);
+        let mut matches = Vec::new();
+        let mut previous = 0;
+        for entry in entries.iter().filter(|e| self.selects(e)) {
+            match entry.time() {
+                Some(t) if t > start && t <= now => matches.push(entry.clone()),
+                Some(t) if t > before && t <= start => previous += 1,
+                _ => {}
+            }
+        }
+        let current = matches.len();
+        let fired = match self.condition {
+            Condition::Count(op, value) => op.holds(&(current as f64), &value),
+            Condition::Change(op, factor) => {
+                op.holds(&(current as f64 / previous.max(1) as f64), &factor)
+            }
+        };
+        out.checks.push(CheckResult {
+            description: self.describe(current, previous),
+            held: fired,
+            current,
+            previous,
+        });
+        if fired {
+            out.matches.extend(matches);
+        }
+        fired
+    }
+}
+
+impl Rule {
+    /// `smtp` is the server for `email` actions.
+    pub fn from_spec(mut spec: RuleSpec, smtp: Option<&Smtp>) -> Result<Rule, String> {
+        let name = spec
+            .name
+            .take()
+            .ok_or_else(|| "every rule needs a name".to_string())?;
+        let cooldown = spec
+            .cooldown
+            .take()
+            .map(|c| query::parse_interval(&c))
+            .transpose()
+            .map_err(|e| format!("rule '{}': {}", name, e))?;
+        let email = match (spec.email.take(), smtp) {
+            (Some(to), Some(smtp)) => Some(Action::Email(Box::new(smtp.clone()), to)),
+            (Some(_), None) => return Err(format!("rule '{}': email needs an [smtp] table", name)),
+            (None, _) => None,
+        };
+        let issue = match spec.issue.take() {
+            Some(true) => {
+                alert::IssueTemplate::from_env()
+                    .map_err(|e| format!("rule '{}': issue: {}", name, e))?;
+                Some(Action::Issue)
+            }
+            _ => None,
+        };
+        let actions = [
+            spec.command.take().map(Action::Command),
+            spec.webhook.take().map(Action::Webhook),
+            spec.slack.take().map(Action::Slack),
+            spec.discord.take().map(Action::Discord),
+            email,
+            spec.pagerduty_key_env.take().map(Action::PagerDuty),
+            spec.opsgenie_key_env.take().map(Action::Opsgenie),
+            issue,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let check = Check::from_spec(spec).map_err(|e| format!("rule '{}': {}", name, e))?;
+        Ok(Rule {
+            name,
+            check,
+            cooldown,
+            actions,
+        })
+    }
+
+    /// How far back from the evaluation time entries can still matter.
+    pub fn horizon(&self) -> Duration {
+        self.check.horizon()
+    }
+
+    pub fn evaluate(&self, entries: &[LogEntry], now: NaiveDateTime) -> Evaluation {
+        let mut evaluation = Evaluation {
+            fired: false,
+            checks: Vec::new(),
+            matches: Vec::new(),
+        };
+        evaluation.fired = self.check.evaluate(entries, now, &mut evaluation);
+        evaluation
+    }
+}
+
+/// A cron time specification: minute, hour, day of month, month and day
+/// of week, each `*`, a number, a range `a-b` or a list of those, with an
+/// optional `/step`.
+#[derive(Debug, Clone)]
+pub struct CronSpec {
+    // Bit n is set when value n matches.
+    minute: u64,
+    hour: u64,
+    day: u64,
+    month: u64,
+    weekday: u64,
+    // Cron matches either day field when both are restricted.
+    day_restricted: bool,
+    weekday_restricted: bool,
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let number = |text: &str| {
+        text.parse::<u32>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(|| format!("'{}' is not in {}-{}", text, min, max))
+    };
+    let mut bits = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|&s| s > 0)
+                    .ok_or_else(|| format!("invalid step '{}'", step))?,
+            ),
+            None => (item, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (number(from)?, number(to)?),
+                None => {
+                    let n = number(range)?;
+                    (n, if step > 1 { max } else { n })
+                }
+            },
+        };
+        for n in (from..=to).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+impl CronSpec {
+    pub fn parse(text: &str) -> Result<CronSpec, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+            return Err(format!("expected 5 cron fields, got '{}'", text));
+        };
+        let mut weekdays = parse_cron_field(weekday, 0, 7)?;
+        // 7 is another name for Sunday.
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(CronSpec {
+            minute: parse_cron_field(minute, 0, 59)?,
+            hour: parse_cron_field(hour, 0, 23)?,
+            day: parse_cron_field(day, 1, 31)?,
+            month: parse_cron_field(month, 1, 12)?,
+            weekday: weekdays,
+            day_restricted: *day != "*",
+            weekday_restricted: *weekday != "*",
+        })
+    }
+
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        let bit = |mask: u64, n: u32| mask & (1 << n) != 0;
+        let day = bit(self.day, time.day());
+        let weekday = bit(self.weekday, time.weekday().num_days_from_sunday());
+        let day_matches = if self.day_restricted && self.weekday_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        };
+        bit(self.minute, time.minute())
+            && bit(self.hour, time.hour())
+            && bit(self.month, time.month())
+            && day_matches
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    Once(NaiveDateTime, NaiveDateTime),
+    /// Starts whenever the spec matches and lasts for the duration.
+    Recurring(CronSpec, Duration),
+}
+
+#[derive(Debug, Clone)]
+pub struct Silence {
+    /// Rule names covered; empty means every rule.
+    pub rules: Vec<String>,
+    pub schedule: Schedule,
+    pub reason: String,
+}
+
+impl Silence {
+    pub fn from_spec(spec: SilenceSpec) -> Result<Silence, String> {
+        let time =
+            |text: &str| crate::parse_time(text).ok_or_else(|| format!("invalid time '{}'", text));
+        let schedule = match (spec.start, spec.end, spec.cron, spec.duration) {
+            (Some(start), Some(end), None, None) => Schedule::Once(time(&start)?, time(&end)?),
+            (None, None, Some(cron), Some(duration)) => {
+                let duration = query::parse_interval(&duration)?;
+                if duration <= Duration::zero() || duration > Duration::days(7) {
+                    return Err("silence duration must be between 1s and 7d".to_string());
+                }
+                Schedule::Recurring(CronSpec::parse(&cron)?, duration)
+            }
+            _ => {
+                return Err("a silence needs either start and end, or cron and duration".to_string())
+            }
+        };
+        Ok(Silence {
+            rules: spec.rules.unwrap_or_default(),
+            schedule,
+            reason: spec.reason.unwrap_or_else(|| "maintenance".to_string()),
+        })
+    }
+
+    pub fn covers(&self, rule: &str) -> bool {
+        self.rules.is_empty() || self.rules.iter().any(|r| r == rule)
+    }
+
+    pub fn active_at(&self, now: NaiveDateTime) -> bool {
+        match &self.schedule {
+            Schedule::Once(start, end) => *start <= now && now < *end,
+            Schedule::Recurring(cron, duration) => {
+                // Walk back over every minute a window still open now could
+                // have started in.
+                let mut minute = now.with_second(0).unwrap().with_nanosecond(0).unwrap();
+                let earliest = now
+                    .checked_sub_signed(*duration)
+                    .unwrap_or(NaiveDateTime::MIN);
+                while minute > earliest {
+                    if cron.matches(minute) {
+                        return true;
+                    }
+                    minute -= Duration::minutes(1);
+                }
+                false
+            }
+        }
+    }
+}
+
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+    pub silences: Vec<Silence>,
+}
+
+impl RuleSet {
+    /// `smtp` is used for email actions when the file has no `[smtp]`.
+    pub fn load(filename: &str, smtp: Option<&Smtp>) -> io::Result<RuleSet> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let text = fs::read_to_string(filename)?;
+        let file: RulesFile = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        Ok(RuleSet {
+            rules: file
+                .rule
+                .into_iter()
+                .map(|spec| Rule::from_spec(spec, file.smtp.as_ref().or(smtp)))
+                .collect::<Result<_, _>>()
+                .map_err(invalid)?,
+            silences: file
+                .silence
+                .into_iter()
+                .map(Silence::from_spec)
+                .collect::<Result<_, _>>()
+                .map_err(invalid)?,
+        })
+    }
+
+    /// The silence suppressing `rule` at `now`, if any.
+    pub fn silenced(&self, rule: &Rule, now: NaiveDateTime) -> Option<&Silence> {
+        self.silences
+            .iter()
+            .find(|s| s.covers(&rule.name) && s.active_at(now))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleState {
+    pub firing: bool,
+    /// When the rule last started firing or resolved.
+    pub since: String,
+    /// When a notification last went out for the current firing.
+    pub notified: Option<String>,
+}
+
+/// How a rule's state changed in this evaluation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transition {
+    /// Started firing, or is still firing and its cooldown ran out: notify.
+    Fired,
+    /// Still firing and already notified.
+    Firing,
+    /// Stopped firing since the last evaluation: notify the resolution.
+    Resolved,
+    Ok,
+}
+
+impl Transition {
+    pub fn name(self) -> &'static str {
+        match self {
+            Transition::Fired => "fired",
+            Transition::Firing => "firing",
+            Transition::Resolved => "resolved",
+            Transition::Ok => "ok",
+        }
+    }
+}
+
+/// Firing state of every rule, kept between runs so an evaluation that
+/// restarts doesn't notify again about alerts that are already open.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AlertState {
+    pub rules: BTreeMap<String, RuleState>,
+}
+
+impl AlertState {
+    /// Loads the state, or starts empty if the file doesn't exist yet.
+    pub fn load(filename: &str) -> io::Result<AlertState> {
+        match File::open(filename) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(AlertState::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Written to a temporary file first so a crash mid-write can't leave
+    // half a state file behind.
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let temp = format!("{}.tmp{}", filename, std::process::id());
+        let mut out = BufWriter::new(File::create(&temp)?);
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)?;
+        out.flush()?;
+        drop(out);
+        fs::rename(&temp, filename)
+    }
+
+    /// Records whether `rule` fired at `now` and returns what changed.
+    pub fn update(&mut self, rule: &Rule, fired: bool, now: NaiveDateTime) -> Transition {
+        let stamp = now.format(crate::TIMESTAMP_FORMAT).to_string();
+        let previous = self.rules.get(&rule.name);
+        let transition = match (previous.filter(|s| s.firing), fired) {
+            (None, false) => return Transition::Ok,
+            (None, true) => Transition::Fired,
+            (Some(_), false) => Transition::Resolved,
+            (Some(state), true) => {
+                let last = state.notified.as_deref().and_then(crate::parse_time);
+                match (rule.cooldown, last) {
+                    (Some(cooldown), Some(last)) if now - last >= cooldown => Transition::Fired,
+                    _ => Transition::Firing,
+                }
+            }
+        };
+        let state = self
+            .rules
+            .entry(rule.name.clone())
+            .or_insert_with(|| RuleState {
+                firing: false,
+                since: stamp.clone(),
+                notified: None,
+            });
+        if state.firing != fired {
+            state.since = stamp.clone();
+        }
+        state.firing = fired;
+        match transition {
+            Transition::Fired => state.notified = Some(stamp),
+            Transition::Resolved => state.notified = None,
+            _ => {}
+        }
+        transition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(spec: &str) -> Result<Rule, String> {
+        Rule::from_spec(toml::from_str(spec).unwrap(), None)
+    }
+
+    #[test]
+    fn pager_actions() {
+        let rule = rule(
+            "name = \"errors\"\nlevel = \"ERROR\"\n\
+             pagerduty_key_env = \"PD_KEY\"\nopsgenie_key_env = \"OG_KEY\"",
+        )
+        .unwrap();
+        assert!(matches!(
+            rule.actions.as_slice(),
+            [Action::PagerDuty(pd), Action::Opsgenie(og)] if pd == "PD_KEY" && og == "OG_KEY"
+        ));
+    }
+
+    fn at(text: &str) -> NaiveDateTime {
+        crate::parse_time(text).unwrap()
+    }
+
+    fn errors(times: &[&str]) -> Vec<LogEntry> {
+        times
+            .iter()
+            .map(|t| LogEntry::from_pipe(&format!("{}|ERROR|boom", t)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn change_compares_windows() {
+        let rule = rule("name = \"spike\"\nwindow = \"1m\"\nwhen = \"change >= 2x\"").unwrap();
+        let entries = errors(&[
+            "2026-01-01 00:00:30",
+            "2026-01-01 00:01:10",
+            "2026-01-01 00:01:20",
+            "2026-01-01 00:01:50",
+        ]);
+        let evaluation = rule.evaluate(&entries, at("2026-01-01 00:02:00"));
+        assert!(evaluation.fired);
+        assert_eq!(
+            (evaluation.checks[0].current, evaluation.checks[0].previous),
+            (3, 1)
+        );
+        assert_eq!(evaluation.matches.len(), 3);
+        // Two entries after an empty window double the count of one.
+        let evaluation = rule.evaluate(&entries[1..3], at("2026-01-01 00:02:00"));
+        assert!(evaluation.fired);
+        let evaluation = rule.evaluate(&entries[..2], at("2026-01-01 00:02:00"));
+        assert!(!evaluation.fired);
+        assert!(evaluation.matches.is_empty());
+    }
+
+    #[test]
+    fn change_falls_as_well_as_rises() {
+        let rule = rule("name = \"drop\"\nwindow = \"1m\"\nwhen = \"change <= 0.5x\"").unwrap();
+        let entries = errors(&[
+            "2026-01-01 00:00:10",
+            "2026-01-01 00:00:20",
+            "2026-01-01 00:01:30",
+        ]);
+        assert!(rule.evaluate(&entries, at("2026-01-01 00:02:00")).fired);
+        assert!(
+            !rule
+                .evaluate(&entries[2..], at("2026-01-01 00:02:00"))
+                .fired
+        );
+    }
+
+    #[test]
+    fn combined_checks() {
+        let rule = rule(
+            "name = \"5xx without deploy\"\nall = [\n\
+             { level = \"ERROR\", window = \"1m\", when = \"count >= 2\" },\n\
+             { contains = \"deploy\", window = \"5m\", when = \"count == 0\" },\n]",
+        )
+        .unwrap();
+        let mut entries = errors(&["2026-01-01 00:09:10", "2026-01-01 00:09:20"]);
+        let evaluation = rule.evaluate(&entries, at("2026-01-01 00:10:00"));
+        assert!(evaluation.fired);
+        assert_eq!(evaluation.checks.len(), 2);
+        entries.push(LogEntry::from_pipe("2026-01-01 00:08:00|INFO|deploy started").unwrap());
+        let evaluation = rule.evaluate(&entries, at("2026-01-01 00:10:00"));
+        assert!(!evaluation.fired);
+        assert!(evaluation.checks[0].held && !evaluation.checks[1].held);
+        assert_eq!(rule.horizon(), Duration::minutes(10));
+    }
+
+    #[test]
+    fn bad_conditions() {
+        assert!(rule("name = \"x\"\nwhen = \"rate > 1\"").is_err());
+        assert!(rule("name = \"x\"\nwhen = \"count >\"").is_err());
+        assert!(rule("name = \"x\"\nwindow = \"0s\"").is_err());
+        assert!(rule("name = \"x\"\nall = []").is_err());
+        assert!(rule("level = \"ERROR\"").is_err());
+    }
+
+    fn silence(spec: &str) -> Result<Silence, String> {
+        Silence::from_spec(toml::from_str(spec).unwrap())
+    }
+
+    #[test]
+    fn cron_fields() {
+        let cron = CronSpec::parse("*/15 9-17 * * 1-5").unwrap();
+        // 2026-01-05 is a Monday.
+        assert!(cron.matches(at("2026-01-05 09:45:00")));
+        assert!(!cron.matches(at("2026-01-05 09:50:00")));
+        assert!(!cron.matches(at("2026-01-05 18:00:00")));
+        assert!(!cron.matches(at("2026-01-04 10:00:00")));
+        let sunday = CronSpec::parse("0 2 * * 7").unwrap();
+        assert!(sunday.matches(at("2026-01-04 02:00:00")));
+        // Restricting both day fields matches either.
+        let either = CronSpec::parse("0 0 1 * 1").unwrap();
+        assert!(either.matches(at("2026-01-01 00:00:00")));
+        assert!(either.matches(at("2026-01-05 00:00:00")));
+        assert!(!either.matches(at("2026-01-06 00:00:00")));
+        assert!(CronSpec::parse("0 24 * * *").is_err());
+        assert!(CronSpec::parse("*/0 * * * *").is_err());
+        assert!(CronSpec::parse("0 0 * *").is_err());
+    }
+
+    #[test]
+    fn recurring_silences() {
+        let silence =
+            silence("rules = [\"errors\"]\ncron = \"0 2 * * 0\"\nduration = \"1h\"").unwrap();
+        assert!(silence.active_at(at("2026-01-04 02:00:00")));
+        assert!(silence.active_at(at("2026-01-04 02:59:59")));
+        assert!(!silence.active_at(at("2026-01-04 03:00:00")));
+        assert!(!silence.active_at(at("2026-01-04 01:59:00")));
+        assert!(silence.covers("errors"));
+        assert!(!silence.covers("latency"));
+        assert_eq!(silence.reason, "maintenance");
+    }
+
+    #[test]
+    fn one_off_silences() {
+        let upgrade = silence(
+            "start = \"2026-01-15 02:00:00\"\nend = \"2026-01-15 03:00:00\"\nreason = \"upgrade\"",
+        )
+        .unwrap();
+        assert!(upgrade.active_at(at("2026-01-15 02:30:00")));
+        assert!(!upgrade.active_at(at("2026-01-15 03:00:00")));
+        assert!(upgrade.covers("anything"));
+        assert!(silence("cron = \"0 2 * * 0\"").is_err());
+        assert!(silence("cron = \"0 2 * * 0\"\nduration = \"8d\"").is_err());
+        assert!(silence("start = \"2026-01-15 02:00:00\"\nduration = \"1h\"").is_err());
+    }
+
+    #[test]
+    fn state_transitions() {
+        let rule = rule("name = \"errors\"\nlevel = \"ERROR\"\ncooldown = \"30m\"").unwrap();
+        let mut state = AlertState::default();
+        let mut update = |fired, time| state.update(&rule, fired, at(time));
+        assert_eq!(update(false, "2026-01-01 00:00:00"), Transition::Ok);
+        assert_eq!(update(true, "2026-01-01 00:01:00"), Transition::Fired);
+        assert_eq!(update(true, "2026-01-01 00:10:00"), Transition::Firing);
+        assert_eq!(update(true, "2026-01-01 00:31:00"), Transition::Fired);
+        assert_eq!(update(true, "2026-01-01 00:40:00"), Transition::Firing);
+        assert_eq!(update(false, "2026-01-01 00:41:00"), Transition::Resolved);
+        assert_eq!(update(false, "2026-01-01 00:42:00"), Transition::Ok);
+        assert_eq!(update(true, "2026-01-01 00:43:00"), Transition::Fired);
+        let errors = &state.rules["errors"];
+        assert!(errors.firing);
+        assert_eq!(errors.since, "2026-01-01 00:43:00");
+    }
+
+    #[test]
+    fn no_cooldown_notifies_once() {
+        let rule = rule("name = \"errors\"\nlevel = \"ERROR\"").unwrap();
+        let mut state = AlertState::default();
+        assert_eq!(
+            state.update(&rule, true, at("2026-01-01 00:00:00")),
+            Transition::Fired
+        );
+        assert_eq!(
+            state.update(&rule, true, at("2026-01-02 00:00:00")),
+            Transition::Firing
+        );
+        assert_eq!(state.rules["errors"].since, "2026-01-01 00:00:00");
+    }
+
+    #[test]
+    fn state_survives_a_restart() {
+        let path = std::env::temp_dir().join(format!("logger-{}-state.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let rule = rule("name = \"errors\"\nlevel = \"ERROR\"").unwrap();
+        let mut state = AlertState::load(path).unwrap();
+        assert!(state.rules.is_empty());
+        state.update(&rule, true, at("2026-01-01 00:00:00"));
+        state.save(path).unwrap();
+        let mut restarted = AlertState::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(
+            restarted.update(&rule, true, at("2026-01-01 00:01:00")),
+            Transition::Firing
+        );
+        assert_eq!(
+            restarted.update(&rule, false, at("2026-01-01 00:02:00")),
+            Transition::Resolved
+        );
+    }
+
+    #[test]
+    fn actions_only_on_rules() {
+        let nested = "name = \"x\"\nany = [{ level = \"ERROR\", pagerduty_key_env = \"K\" }]";
+        assert!(rule(nested).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! `s3://bucket/key` input. Requests are signed with AWS Signature
+//! Version 4 from the usual `AWS_*` environment variables; without
+//! credentials they are sent unsigned, for public buckets.
+//! `AWS_ENDPOINT_URL` points at S3-compatible stores such as MinIO.
+
+use std::collections::VecDeque;
+use std::env;
+use std::io::{self, BufRead, BufReader, Read};
+
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+
+use crate::xml::unescape;
+use crate::{crypt, export};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    const BLOCK: usize = 64;
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<u8>>();
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .to_vec()
+}
+
+// URI-encoding as SigV4 defines it; `/` is kept in paths.
+fn encode(text: &str, keep_slash: bool) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b'/' if keep_slash => "/".to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+struct Credentials {
+    key_id: String,
+    secret: String,
+    token: Option<String>,
+}
+
+struct Bucket {
+    name: String,
+    region: String,
+    /// Set for S3-compatible stores, which are addressed path-style.
+    endpoint: Option<String>,
+    credentials: Option<Credentials>,
+}
+
+impl Bucket {
+    fn new(name: &str) -> Bucket {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let credentials = match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+            (Some(key_id), Some(secret)) => Some(Credentials {
+                key_id,
+                secret,
+                token: var("AWS_SESSION_TOKEN"),
+            }),
+            _ => None,
+        };
+        Bucket {
+            name: name.to_string(),
+            region: var("AWS_REGION")
+                .or_else(|| var("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|| "us-east-1".to_string()),
+            endpoint: var("AWS_ENDPOINT_URL").map(|e| e.trim_end_matches('/').to_string()),
+            credentials,
+        }
+    }
+
+    // The scheme, host and path of `key`.
+    fn location(&self, key: &str) -> (String, String, String) {
+        let key = encode(key, true);
+        match &self.endpoint {
+            Some(endpoint) => {
+                let (scheme, host) = endpoint.split_once("://").unwrap_or(("https", endpoint));
+                (
+                    scheme.to_string(),
+                    host.to_string(),
+                    format!("/{}/{}", encode(&self.name, false), key),
+                )
+            }
+            None => (
+                "https".to_string(),
+                format!("{}.s3.{}.amazonaws.com", self.name, self.region),
+                format!("/{}", key),
+            ),
+        }
+    }
+
+    /// GETs `key` with the query parameters, which must be sorted.
+    fn get(&self, key: &str, query: &[(&str, &str)]) -> io::Result<ureq::Response> {
+        let (scheme, host, path) = self.location(key);
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", encode(name, false), encode(value, false)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let url = if query.is_empty() {
+            format!("{}://{}{}", scheme, host, path)
+        } else {
+            format!("{}://{}{}?{}", scheme, host, path, query)
+        };
+        let mut request = ureq::get(&url);
+        if let Some(credentials) = &self.credentials {
+            let now = Utc::now();
+            let time = now.format("%Y%m%dT%H%M%SZ").to_string();
+            let date = now.format("%Y%m%d").to_string();
+            // GET requests have an empty body.
+            let payload = hex(&Sha256::digest(b""));
+            let mut headers = vec![
+                ("host", host.clone()),
+                ("x-amz-content-sha256", payload.clone()),
+                ("x-amz-date", time.clone()),
+            ];
+            if let Some(token) = &credentials.token {
+                headers.push(("x-amz-security-token", token.clone()));
+            }
+            let signed: Vec<&str> = headers.iter().map(|(name, _)| *name).collect();
+            let canonical = format!(
+                "GET\n{}\n{}\n{}\n{}\n{}",
+                path,
+                query,
+                headers
+                    .iter()
+                    .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+                    .collect::<String>(),
+                signed.join(";"),
+                payload
+            );
+            let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+            let to_sign = format!(
+                "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+                time,
+                scope,
+                hex(&Sha256::digest(canonical.as_bytes()))
+            );
+            let signing_key = [date.as_str(), &self.region, "s3", "aws4_request"]
+                .iter()
+                .fold(
+                    format!("AWS4{}", credentials.secret).into_bytes(),
+                    |key, part| hmac(&key, part.as_bytes()),
+                );
+            let signature = hex(&hmac(&signing_key, to_sign.as_bytes()));
+            for (name, value) in &headers[1..] {
+                request = request.set(name, value);
+            }
+            request = request.set(
+                "Authorization",
+                &format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    credentials.key_id,
+                    scope,
+                    signed.join(";"),
+                    signature
+                ),
+            );
+        }
+        request.call().map_err(|e| match e {
+            ureq::Error::Status(code, response) => {
+                let body = response.into_string().unwrap_or_default();
+                let message = tag(&body, "Message").unwrap_or("request failed");
+                io::Error::other(format!("{}: HTTP {}: {}", key, code, message))
+            }
+            e => io::Error::other(e),
+        })
+    }
+
+    /// The keys under `prefix`, in S3's lexicographic order.
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = Vec::new();
+            if let Some(token) = &token {
+                query.push(("continuation-token", token.as_str()));
+            }
+            query.extend([("list-type", "2"), ("prefix", prefix)]);
+            let body = self.get("", &query)?.into_string()?;
+            keys.extend(
+                body.split("<Key>")
+                    .skip(1)
+                    .filter_map(|item| item.split_once("</Key>"))
+                    .map(|(key, _)| unescape(key))
+                    .filter(|key| !key.ends_with('/')),
+            );
+            match tag(&body, "NextContinuationToken") {
+                Some(next) if tag(&body, "IsTruncated") == Some("true") => {
+                    token = Some(unescape(next))
+                }
+                _ => return Ok(keys),
+            }
+        }
+    }
+}
+
+fn tag<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", name))?;
+    Some(&xml[start..start + end])
+}
+
+/// The objects one after another, each fetched when the one before it has
+/// been read and decompressed or decrypted on its own.
+struct Objects {
+    bucket: Bucket,
+    keys: VecDeque<String>,
+    current: Option<Box<dyn BufRead>>,
+}
+
+impl Objects {
+    fn current(&mut self) -> io::Result<Option<&mut Box<dyn BufRead>>> {
+        loop {
+            if self.current.is_none() {
+                let Some(key) = self.keys.pop_front() else {
+                    return Ok(None);
+                };
+                let body = BufReader::new(self.bucket.get(&key, &[])?.into_reader());
+                self.current = Some(export::decompress(crypt::decrypt(body)?)?);
+            }
+            if !self
+                .current
+                .as_mut()
+                .expect("just set")
+                .fill_buf()?
+                .is_empty()
+            {
+                return Ok(self.current.as_mut());
+            }
+            self.current = None;
+        }
+    }
+}
+
+impl Read for Objects {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.current()? {
+            Some(reader) => reader.read(buf),
+            None => Ok(0),
+        }
+    }
+}
+
+impl BufRead for Objects {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self.current()? {
+            Some(reader) => reader.fill_buf(),
+            None => Ok(&[]),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if let Some(reader) = &mut self.current {
+            reader.consume(amount);
+        }
+    }
+}
+
+/// Opens `s3://bucket/key`. A key ending in `/` or `*`, or no key, is a
+/// prefix: every object under it is read, in key order.
+pub fn open(url: &str) -> io::Result<Box<dyn BufRead>> {
+    let path = url.strip_prefix("s3://").unwrap_or(url);
+    let (name, key) = path.split_once('/').unwrap_or((path, ""));
+    if name.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: no bucket", url),
+        ));
+    }
+    let bucket = Bucket::new(name);
+    let keys = if key.is_empty() || key.ends_with('/') || key.ends_with('*') {
+        bucket.list(key.trim_end_matches('*'))?
+    } else {
+        vec![key.to_string()]
+    };
+    Ok(Box::new(Objects {
+        bucket,
+        keys: keys.into(),
+        current: None,
+    }))
+}
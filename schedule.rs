@@ -0,0 +1,138 @@
+//! Stats reports every interval while serving or listening, each covering
+//! the entries added since the one before.
+
+use std::fmt::Write as _;
+use std::io;
+use std::thread;
+
+use chrono::{Local, NaiveDateTime};
+use serde_json::json;
+
+use crate::follow::Follower;
+use crate::smtp::Smtp;
+use crate::{report, LogEntry};
+
+/// Where a report goes.
+pub enum Destination {
+    /// A Markdown file; strftime sequences such as `%Y-%m-%d-%H%M` in the
+    /// path are filled in with the report time. `-` is stdout.
+    File(String),
+    /// `http(s)://`: POSTed as JSON with the Markdown in `text`, as Slack
+    /// incoming webhooks expect, or `content` for Discord.
+    Webhook(String),
+    /// `mailto:a@example.com,b@example.com`, through the config's `[smtp]`.
+    Email(Vec<String>),
+}
+
+impl Destination {
+    pub fn parse(text: &str) -> Destination {
+        if let Some(addresses) = text.strip_prefix("mailto:") {
+            Destination::Email(addresses.split(',').map(|a| a.trim().to_string()).collect())
+        } else if crate::fetch::is_url(text) {
+            Destination::Webhook(text.to_string())
+        } else {
+            Destination::File(text.to_string())
+        }
+    }
+}
+
+pub struct Reports {
+    pub every: chrono::Duration,
+    /// The log file whose new entries are reported.
+    pub log_file: String,
+    pub to: Vec<Destination>,
+    pub smtp: Option<Smtp>,
+}
+
+impl Reports {
+    /// Checks that every destination can be used.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.every < chrono::Duration::minutes(1) {
+            return Err("reports need an interval of at least 1m".to_string());
+        }
+        for destination in &self.to {
+            match destination {
+                Destination::Email(_) if self.smtp.is_none() => {
+                    return Err("mailto: needs an [smtp] table in the config".to_string())
+                }
+                Destination::File(path) => {
+                    let mut name = String::new();
+                    if write!(name, "{}", Local::now().format(path)).is_err() {
+                        return Err(format!("invalid % sequence in '{}'", path));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&self, destination: &Destination, text: &str, entries: &[LogEntry]) -> io::Result<()> {
+        match destination {
+            Destination::File(path) => {
+                let path = Local::now().format(path).to_string();
+                let mut out = crate::export::create_output(&path)?;
+                io::Write::write_all(&mut out, text.as_bytes())?;
+                out.finish()
+            }
+            Destination::Webhook(url) => {
+                let body = if url.contains("discord.com/") || url.contains("discordapp.com/") {
+                    json!({ "content": text.chars().take(2000).collect::<String>() })
+                } else {
+                    json!({
+                        "text": text,
+                        "entries": entries.len(),
+                        "errors": entries.iter().filter(|e| report::is_error(e)).count(),
+                    })
+                };
+                ureq::post(url)
+                    .set("Content-Type", "application/json")
+                    .send_string(&body.to_string())
+                    .map_err(io::Error::other)?;
+                Ok(())
+            }
+            Destination::Email(to) => {
+                let smtp = self.smtp.as_ref().expect("checked by validate");
+                let subject = format!(
+                    "[logger] Report for {}: {} entries",
+                    self.log_file,
+                    entries.len()
+                );
+                smtp.send(to, &subject, text)
+            }
+        }
+    }
+
+    fn report(&self, entries: &[LogEntry], since: NaiveDateTime, until: NaiveDateTime) {
+        let source = format!(
+            "{}, {} to {}",
+            self.log_file,
+            since.format(crate::timestamp_format()),
+            until.format(crate::timestamp_format())
+        );
+        let text = report::markdown(entries, &source);
+        for destination in &self.to {
+            if let Err(e) = self.send(destination, &text, entries) {
+                eprintln!("Could not send the report: {}", e);
+            }
+        }
+    }
+
+    /// Reports in a background thread from now on.
+    pub fn start(self) {
+        thread::spawn(move || {
+            let mut follower = Follower::from_end(&self.log_file);
+            let mut since = Local::now().naive_local();
+            let every = self.every.to_std().expect("validated as positive");
+            loop {
+                thread::sleep(every);
+                let until = Local::now().naive_local();
+                match follower.poll() {
+                    Ok(entries) => self.report(&entries, since, until),
+                    Err(e) => eprintln!("Could not read {} for the report: {}", self.log_file, e),
+                }
+                since = until;
+            }
+        });
+    }
+}
@@ -0,0 +1,142 @@
+//! Rhai scripts for filters, pipeline transforms and alert conditions,
+//! so custom logic needs no recompiling. Needs the `scripting` feature.
+//!
+//! A script sees the entry as `entry` (`entry.timestamp`, `entry.level`,
+//! `entry.message` and `entry.fields`) and may change it. Returning
+//! `false` drops the entry, or doesn't count it for a filter or alert;
+//! returning a map replaces it; anything else keeps it as the script left
+//! it:
+//!
+//! ```rhai
+//! if entry.fields.path == "/health" { return false; }
+//! entry.fields.slow = entry.fields.response_time > 1.0;
+//! ```
+
+#[cfg(feature = "scripting")]
+pub use engine::Script;
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use rhai::{Dynamic, Engine, Scope, AST};
+    use serde_json::{Map, Value};
+
+    use crate::LogEntry;
+
+    pub struct Script {
+        path: String,
+        engine: Engine,
+        ast: AST,
+        /// Whether a failure was reported yet; one line is enough.
+        reported: AtomicBool,
+    }
+
+    impl std::fmt::Debug for Script {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "Script({})", self.path)
+        }
+    }
+
+    fn text(map: &rhai::Map, key: &str) -> String {
+        map.get(key).map(|v| v.to_string()).unwrap_or_default()
+    }
+
+    impl Script {
+        pub fn load(path: &str) -> Result<Script, String> {
+            let mut engine = Engine::new();
+            // Scripts run once per entry; a runaway loop should fail, not hang.
+            engine.set_max_operations(1_000_000);
+            let ast = engine
+                .compile_file(path.into())
+                .map_err(|e| format!("{}: {}", path, e))?;
+            Ok(Script {
+                path: path.to_string(),
+                engine,
+                ast,
+                reported: AtomicBool::new(false),
+            })
+        }
+
+        /// The entry as the script left it, or `None` if it returned false.
+        pub fn run(&self, entry: &LogEntry) -> Result<Option<LogEntry>, String> {
+            let mut input = rhai::Map::new();
+            input.insert("timestamp".into(), entry.timestamp.clone().into());
+            input.insert("level".into(), entry.level.clone().into());
+            input.insert("message".into(), entry.message.clone().into());
+            let fields = rhai::serde::to_dynamic(&entry.fields).map_err(|e| e.to_string())?;
+            input.insert("fields".into(), fields);
+            let mut scope = Scope::new();
+            scope.push("entry", input);
+            let result: Dynamic = self
+                .engine
+                .eval_ast_with_scope(&mut scope, &self.ast)
+                .map_err(|e| e.to_string())?;
+            if result.as_bool() == Ok(false) {
+                return Ok(None);
+            }
+            let output = match result.try_cast::<rhai::Map>() {
+                Some(map) => map,
+                None => scope
+                    .get_value::<rhai::Map>("entry")
+                    .ok_or("`entry` must stay a map")?,
+            };
+            let fields: Map<String, Value> = match output.get("fields") {
+                Some(fields) => rhai::serde::from_dynamic(fields).map_err(|e| e.to_string())?,
+                None => Map::new(),
+            };
+            Ok(Some(LogEntry {
+                timestamp: text(&output, "timestamp"),
+                level: text(&output, "level"),
+                message: text(&output, "message"),
+                fields,
+                source: entry.source.clone(),
+            }))
+        }
+
+        /// Whether the script keeps `entry`. A failing script keeps
+        /// nothing, and says why once.
+        pub fn matches(&self, entry: &LogEntry) -> bool {
+            match self.run(entry) {
+                Ok(kept) => kept.is_some(),
+                Err(e) => {
+                    self.report(&e);
+                    false
+                }
+            }
+        }
+
+        pub fn report(&self, error: &str) {
+            if !self.reported.swap(true, Ordering::Relaxed) {
+                eprintln!("Script {} failed: {}", self.path, error);
+            }
+        }
+    }
+}
+
+/// Without the `scripting` feature no script can be loaded.
+#[cfg(not(feature = "scripting"))]
+#[derive(Debug)]
+pub enum Script {}
+
+#[cfg(not(feature = "scripting"))]
+impl Script {
+    pub fn load(path: &str) -> Result<Script, String> {
+        Err(format!(
+            "cannot run {}: built without the scripting feature",
+            path
+        ))
+    }
+
+    pub fn run(&self, _: &crate::LogEntry) -> Result<Option<crate::LogEntry>, String> {
+        match *self {}
+    }
+
+    pub fn matches(&self, _: &crate::LogEntry) -> bool {
+        match *self {}
+    }
+
+    pub fn report(&self, _: &str) {
+        match *self {}
+    }
+}
@@ -0,0 +1,148 @@
+//! `logger serve`: the analyzer over HTTP, for dashboards and scripts.
+//!
+//! - `GET /entries?level=ERROR&since=1h&q=timeout&filter=app=debug&limit=100`
+//! - `GET /stats`
+//! - `POST /entries` with a JSON entry, or an array of them
+//! - `GET /metrics`
+
+use std::io;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::directives::Directives;
+use crate::http::{self, Request, Response};
+use crate::shared::{SharedAnalyzer, Tail};
+use crate::{export, prometheus, query, redact, report, LogAnalyzer, LogEntry};
+
+/// Entries returned by `GET /entries` when no limit is given.
+const DEFAULT_LIMIT: usize = 1000;
+
+pub struct Server {
+    file: String,
+    // Appends from concurrent requests must not interleave.
+    writing: Mutex<()>,
+    // Kept in step with the file by the tail, so requests don't read it.
+    analyzer: SharedAnalyzer,
+    tail: Arc<Mutex<Tail>>,
+}
+
+impl Server {
+    /// Reads `file`, which may not exist yet, and starts following it.
+    /// With `max_entries`, only that many of the newest are answered from.
+    pub fn new(file: String, max_entries: Option<usize>) -> io::Result<Server> {
+        let mut builder = LogAnalyzer::builder();
+        if let Some(max) = max_entries {
+            builder = builder.max_entries(max);
+        }
+        let analyzer = SharedAnalyzer::new(builder.build());
+        let mut tail = analyzer.tail(&file);
+        tail.poll()?;
+        Ok(Server {
+            file,
+            writing: Mutex::new(()),
+            analyzer,
+            tail: tail.spawn(Duration::from_secs(1)),
+        })
+    }
+
+    /// Serves requests on `listener` until the process ends.
+    pub fn run(self, listener: TcpListener) {
+        prometheus::enable();
+        http::serve(listener, move |request| self.respond(request));
+    }
+
+    fn respond(&self, request: &Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/entries") => self.entries(request),
+            ("POST", "/entries") => self.append(request),
+            ("GET", "/stats") => self.stats(),
+            ("GET", "/metrics") => prometheus::response(),
+            (_, "/entries" | "/stats" | "/metrics") => Response::error(405, "method not allowed"),
+            _ => Response::error(404, "not found; try /entries, /stats or /metrics"),
+        }
+    }
+
+    fn entries(&self, request: &Request) -> Response {
+        let param = |name: &str| request.query.get(name).map(String::as_str);
+        let since =
+            match param("since").map(|since| query::parse_interval(since).and_then(query::ago)) {
+                Some(Ok(since)) => Some(since),
+                Some(Err(e)) => return Response::error(400, &format!("invalid since: {}", e)),
+                None => None,
+            };
+        let filter = match param("filter").map(Directives::parse) {
+            Some(Ok(filter)) => Some(filter),
+            Some(Err(e)) => return Response::error(400, &format!("invalid filter: {}", e)),
+            None => None,
+        };
+        let limit = match param("limit").map(str::parse) {
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => return Response::error(400, "invalid limit"),
+            None => DEFAULT_LIMIT,
+        };
+        let matches: Vec<LogEntry> = self.analyzer.read(|analyzer| {
+            analyzer
+                .iter_search(param("q").unwrap_or(""))
+                .filter(|entry| {
+                    param("level").is_none_or(|l| entry.level.eq_ignore_ascii_case(l))
+                        && since.is_none_or(|s| entry.time().is_some_and(|t| t >= s))
+                        && filter.as_ref().is_none_or(|f| f.matches(entry))
+                })
+                .cloned()
+                .collect()
+        });
+        // The newest entries when there are more than the limit.
+        let start = matches.len().saturating_sub(limit);
+        let entries = redact::for_export(&matches[start..]);
+        Response::json(
+            200,
+            &Value::Array(entries.iter().map(export::entry_json).collect()),
+        )
+    }
+
+    fn stats(&self) -> Response {
+        let stats = self.analyzer.read(LogAnalyzer::get_statistics);
+        Response::json(200, &report::stats_json(&stats))
+    }
+
+    /// Entries without a timestamp are stamped with the current time.
+    fn append(&self, request: &Request) -> Response {
+        let values = match serde_json::from_str(&request.body) {
+            Ok(Value::Array(values)) => values,
+            Ok(value @ Value::Object(_)) => vec![value],
+            _ => return Response::error(400, "expected a JSON object or array of objects"),
+        };
+        let mut entries = Vec::new();
+        for value in values {
+            let Some(mut entry) = LogEntry::from_json(&value.to_string()) else {
+                return Response::error(400, "expected a JSON object or array of objects");
+            };
+            if entry.message.is_empty() {
+                return Response::error(400, "an entry needs a message");
+            }
+            if entry.timestamp.is_empty() {
+                entry.timestamp = crate::now_timestamp();
+            }
+            entry.level = entry.level.to_uppercase();
+            if let Some(entry) = crate::ingest(entry, None, Vec::new()) {
+                crate::emit(&entry);
+                entries.push(entry);
+            }
+        }
+        let _writing = self.writing.lock().unwrap_or_else(|e| e.into_inner());
+        let written = crate::rotate::before_append(&self.file)
+            .and_then(|()| LogAnalyzer::append_to_file(&self.file, &entries))
+            // Read them back now, so the next request sees them.
+            .and_then(|()| self.tail.lock().unwrap_or_else(|e| e.into_inner()).poll());
+        match written {
+            Ok(_) => {
+                prometheus::record(&entries, 0);
+                Response::json(201, &json!({ "added": entries.len() }))
+            }
+            Err(e) => Response::error(500, &format!("could not write {}: {}", self.file, e)),
+        }
+    }
+}
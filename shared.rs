@@ -0,0 +1,90 @@
+//! A `LogAnalyzer` that several threads can use at once: one following a
+//! file while others query it, as `logger serve` does.
+
+use std::io;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::follow::Follower;
+use crate::{LogAnalyzer, LogEntry};
+
+/// A cheap-to-clone handle; every clone sees the same entries. Queries take
+/// a read lock, so any number run together and only wait while new entries
+/// are being added.
+#[derive(Clone)]
+pub struct SharedAnalyzer(Arc<RwLock<LogAnalyzer>>);
+
+impl SharedAnalyzer {
+    pub fn new(analyzer: LogAnalyzer) -> SharedAnalyzer {
+        SharedAnalyzer(Arc::new(RwLock::new(analyzer)))
+    }
+
+    /// Runs `query` on the analyzer as it is now.
+    pub fn read<T>(&self, query: impl FnOnce(&LogAnalyzer) -> T) -> T {
+        // A panicking writer leaves whole entries behind, so carry on.
+        query(&self.0.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Runs `change` with the analyzer to itself.
+    pub fn write<T>(&self, change: impl FnOnce(&mut LogAnalyzer) -> T) -> T {
+        change(&mut self.0.write().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub fn extend(&self, entries: Vec<LogEntry>) {
+        if !entries.is_empty() {
+            self.write(|analyzer| analyzer.extend(entries));
+        }
+    }
+
+    /// Keeps the analyzer in step with `path`, from its start: see `Tail`.
+    pub fn tail(&self, path: &str) -> Tail {
+        Tail {
+            follower: Follower::from_start(path),
+            analyzer: self.clone(),
+        }
+    }
+}
+
+/// Adds the lines appended to a file to a shared analyzer. When the file
+/// is truncated or replaced by a shorter one, the analyzer starts over
+/// with what the file holds now.
+pub struct Tail {
+    follower: Follower,
+    analyzer: SharedAnalyzer,
+}
+
+impl Tail {
+    /// Reads what was appended since the last call; returns how many
+    /// entries were added.
+    pub fn poll(&mut self) -> io::Result<usize> {
+        let entries = self.follower.poll()?;
+        let added = entries.len();
+        if self.follower.rewound {
+            self.analyzer.write(|analyzer| {
+                analyzer.entries.clear();
+                analyzer.extend(entries);
+            });
+        } else {
+            self.analyzer.extend(entries);
+        }
+        Ok(added)
+    }
+
+    /// Polls every `interval` on a thread of its own until reading fails.
+    /// The tail stays usable through the mutex, e.g. to catch up right
+    /// after writing to the file.
+    pub fn spawn(self, interval: Duration) -> Arc<Mutex<Tail>> {
+        let tail = Arc::new(Mutex::new(self));
+        let polled = Arc::clone(&tail);
+        thread::spawn(move || loop {
+            let result = polled.lock().unwrap_or_else(|e| e.into_inner()).poll();
+            if let Err(e) = result {
+                eprintln!("Stopped following: {}", e);
+                return;
+            }
+            thread::sleep(interval);
+        });
+        tail
+    }
+}
@@ -0,0 +1,178 @@
+//! Sending entries to log servers, once or while following a file.
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use crate::export::Compression;
+use crate::follow::{Follower, Offsets};
+use crate::plugin::Plugin;
+use crate::{elastic, gelf, loki, otlp, syslog, LogEntry};
+
+/// Where `logger export` sends entries.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Target {
+    /// RFC 5424 syslog; --to is host:port
+    Syslog,
+    /// Graylog GELF input; --to is host:port
+    Gelf,
+    /// Grafana Loki push API; --to is Loki's URL
+    Loki,
+    /// Elasticsearch bulk API; --to is the cluster URL
+    Elasticsearch,
+    /// OpenTelemetry collector, OTLP/HTTP; --to is its URL
+    Otlp,
+    /// A WASM plugin exporting `send`; --to is the module's path
+    Plugin,
+}
+
+/// A log server entries are sent to, a batch at a time.
+pub trait Sink {
+    fn send(&mut self, entries: &[LogEntry]) -> io::Result<()>;
+}
+
+pub struct Options {
+    pub to: String,
+    /// Stream over TCP instead of sending UDP datagrams.
+    pub tcp: bool,
+    /// Extra HTTP headers, e.g. authorization.
+    pub headers: Vec<(String, String)>,
+    /// Loki labels.
+    pub labels: Vec<String>,
+    /// The file the entries come from, for labels.
+    pub file: String,
+    /// Elasticsearch index name, with strftime fields.
+    pub index: String,
+    /// How often to resend a failed request.
+    pub retries: u32,
+    /// OpenTelemetry resource attributes.
+    pub resource: Vec<(String, String)>,
+    /// How HTTP targets compress request bodies.
+    pub compression: Compression,
+}
+
+pub fn open(target: Target, options: &Options) -> io::Result<Box<dyn Sink>> {
+    Ok(match target {
+        Target::Syslog => Box::new(syslog::Forwarder::connect(&options.to, options.tcp)?),
+        Target::Gelf => Box::new(gelf::Sender::connect(&options.to, options.tcp)?),
+        Target::Loki => Box::new(loki::Pusher::new(options)),
+        Target::Elasticsearch => Box::new(elastic::Indexer::new(options)),
+        Target::Otlp => Box::new(otlp::Exporter::new(options)),
+        Target::Plugin => Box::new(Plugin::load(&options.to, "send").map_err(io::Error::other)?),
+    })
+}
+
+/// A UDP socket sending to `address` (host:port).
+pub fn udp_socket(address: &str) -> io::Result<UdpSocket> {
+    let server = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::other(format!("{} does not resolve", address)))?;
+    let local = if server.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(server)?;
+    Ok(socket)
+}
+
+pub fn post_json(
+    url: &str,
+    headers: &[(String, String)],
+    body: &serde_json::Value,
+) -> io::Result<()> {
+    post_compressed(url, headers, body, Compression::None)
+}
+
+/// Like `post_json`, with the body compressed as `compression` says.
+pub fn post_compressed(
+    url: &str,
+    headers: &[(String, String)],
+    body: &serde_json::Value,
+    compression: Compression,
+) -> io::Result<()> {
+    let mut request = ureq::post(url).set("Content-Type", "application/json");
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    let (request, body) = encode_body(request, &body.to_string(), compression)?;
+    request.send_bytes(&body).map_err(io::Error::other)?;
+    Ok(())
+}
+
+/// Compresses `body` as `compression` says and sets the request's
+/// Content-Encoding to match.
+pub fn encode_body(
+    request: ureq::Request,
+    body: &str,
+    compression: Compression,
+) -> io::Result<(ureq::Request, Vec<u8>)> {
+    let body = compression.compress(body.as_bytes())?;
+    Ok(match compression.encoding() {
+        Some(encoding) => (request.set("Content-Encoding", encoding), body),
+        None => (request, body),
+    })
+}
+
+/// Sends one datagram. UDP is fire-and-forget: a refusal reported for an
+/// earlier datagram, while the server was down, is not an error.
+pub fn send_datagram(socket: &UdpSocket, datagram: &[u8]) -> io::Result<()> {
+    match socket.send(datagram) {
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => Ok(()),
+        result => result.map(|_| ()),
+    }
+}
+
+/// The machine's name for the `host` of sent entries.
+pub fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+/// Sends `entries` in batches of `batch`, redacted like other exports;
+/// returns how many were sent.
+pub fn send_all(sink: &mut dyn Sink, entries: &[LogEntry], batch: usize) -> io::Result<usize> {
+    let entries = &*crate::redact::for_export(entries);
+    for chunk in entries.chunks(batch.max(1)) {
+        sink.send(chunk)?;
+    }
+    Ok(entries.len())
+}
+
+/// Sends the entries of `path`, then every line appended to it, checking
+/// each second. With an `offsets` file, starts where the last run left off
+/// and records there what has been sent. Only returns on an error.
+pub fn follow(
+    sink: &mut dyn Sink,
+    path: &str,
+    batch: usize,
+    offsets: Option<&str>,
+) -> io::Result<()> {
+    let mut saved = match offsets {
+        Some(offsets) => Offsets::load(offsets)?,
+        None => Offsets::default(),
+    };
+    let mut follower = Follower::resume(path, saved.0.get(path));
+    loop {
+        let entries = follower.poll()?;
+        crate::prometheus::record(&entries, follower.skipped);
+        if entries.is_empty() {
+            thread::sleep(Duration::from_secs(1));
+            continue;
+        }
+        send_all(sink, &entries, batch)?;
+        if let Some(offsets) = offsets {
+            saved.0.insert(path.to_string(), follower.offset());
+            saved.save(offsets)?;
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Plain-text mail over SMTP, for alert notifications.
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConnection, StreamOwned};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    /// Upgrade a plain connection with STARTTLS, as on port 587.
+    #[default]
+    Starttls,
+    /// TLS from the start, as on port 465.
+    Tls,
+    /// No encryption, e.g. for a relay on localhost.
+    None,
+}
+
+/// The `[smtp]` table of the rules file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Smtp {
+    /// `host:port`; the port defaults to 465 with `tls = "tls"`, else 587.
+    pub server: String,
+    pub from: String,
+    #[serde(default)]
+    pub tls: Security,
+    pub username: Option<String>,
+    /// The environment variable holding the password.
+    pub password_env: Option<String>,
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+fn tls(stream: TcpStream, host: &str) -> io::Result<Stream> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string()).map_err(io::Error::other)?;
+    let connection = ClientConnection::new(Arc::new(config), name).map_err(io::Error::other)?;
+    Ok(Stream::Tls(Box::new(StreamOwned::new(connection, stream))))
+}
+
+// Message headers allow only ASCII; anything else is encoded.
+fn header_text(text: &str) -> String {
+    if text.is_ascii() {
+        text.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", BASE64.encode(text))
+    }
+}
+
+struct Session {
+    stream: Stream,
+}
+
+impl Session {
+    // Read a byte at a time: replies are short, and nothing may be left
+    // buffered when the connection switches to TLS.
+    fn reply(&mut self) -> io::Result<(u16, String)> {
+        let mut text = String::new();
+        loop {
+            let mut line = Vec::new();
+            let mut byte = [0u8];
+            while !line.ends_with(b"\n") {
+                if self.stream.read(&mut byte)? == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "the server closed the connection",
+                    ));
+                }
+                line.push(byte[0]);
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end();
+            let code = line
+                .get(..3)
+                .and_then(|code| code.parse().ok())
+                .ok_or_else(|| io::Error::other(format!("invalid reply '{}'", line)))?;
+            text.push_str(line.get(4..).unwrap_or(""));
+            // `250-...` continues, `250 ...` is the last line.
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text));
+            }
+            text.push('\n');
+        }
+    }
+
+    /// Sends `command` and expects a reply in the class of `expected`,
+    /// e.g. 2 for any 2xx.
+    fn command(&mut self, command: &str, expected: u16) -> io::Result<String> {
+        self.stream.write_all(command.as_bytes())?;
+        self.stream.write_all(b"\r\n")?;
+        self.stream.flush()?;
+        self.expect(expected, command.split(' ').next().unwrap_or(command))
+    }
+
+    fn expect(&mut self, expected: u16, step: &str) -> io::Result<String> {
+        let (code, text) = self.reply()?;
+        if code / 100 == expected {
+            Ok(text)
+        } else {
+            Err(io::Error::other(format!("{}: {} {}", step, code, text)))
+        }
+    }
+}
+
+impl Smtp {
+    /// Sends a plain-text message to every address in `to`.
+    pub fn send(&self, to: &[String], subject: &str, body: &str) -> io::Result<()> {
+        let (host, port) = match self.server.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid port in '{}'", self.server),
+                    )
+                })?,
+            ),
+            None if self.tls == Security::Tls => (self.server.as_str(), 465),
+            None => (self.server.as_str(), 587),
+        };
+        let password = match &self.password_env {
+            Some(var) => Some(std::env::var(var).map_err(|_| {
+                io::Error::new(io::ErrorKind::NotFound, format!("${} is not set", var))
+            })?),
+            None => None,
+        };
+        let tcp = TcpStream::connect((host, port))?;
+        tcp.set_read_timeout(Some(Duration::from_secs(30)))?;
+        tcp.set_write_timeout(Some(Duration::from_secs(30)))?;
+        let stream = match self.tls {
+            Security::Tls => tls(tcp, host)?,
+            _ => Stream::Plain(tcp),
+        };
+        let mut session = Session { stream };
+        session.expect(2, "connect")?;
+        let hello = format!("EHLO {}", crate::sink::hostname());
+        session.command(&hello, 2)?;
+        if self.tls == Security::Starttls {
+            session.command("STARTTLS", 2)?;
+            let Stream::Plain(tcp) = session.stream else {
+                unreachable!("STARTTLS is only sent on a plain connection");
+            };
+            session.stream = tls(tcp, host)?;
+            session.command(&hello, 2)?;
+        }
+        if let Some(username) = &self.username {
+            let credentials = format!("\0{}\0{}", username, password.unwrap_or_default());
+            session.command(&format!("AUTH PLAIN {}", BASE64.encode(credentials)), 2)?;
+        }
+        session.command(&format!("MAIL FROM:<{}>", self.from), 2)?;
+        for address in to {
+            session.command(&format!("RCPT TO:<{}>", address), 2)?;
+        }
+        session.command("DATA", 3)?;
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            self.from,
+            to.join(", "),
+            header_text(subject),
+            chrono::Local::now().to_rfc2822()
+        );
+        for line in body.lines() {
+            // Lines starting with a dot are escaped with another.
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message.push_str(".\r\n");
+        session.stream.write_all(message.as_bytes())?;
+        session.stream.flush()?;
+        session.expect(2, "message")?;
+        session.command("QUIT", 2)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+//! Files on other machines, read through the `ssh` command so the usual
+//! keys, agent and `~/.ssh/config` apply.
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use crate::{crypt, export, LogAnalyzer, LogEntry};
+
+/// `ssh://[user@]host[:port]/path`, or scp-style `ssh://host:/path` and
+/// `host:/path`.
+pub struct Remote {
+    host: String,
+    port: Option<u16>,
+    path: String,
+}
+
+// Single-quoted for the remote shell.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+impl Remote {
+    pub fn parse(url: &str) -> Option<Remote> {
+        let rest = url.strip_prefix("ssh://").unwrap_or(url);
+        let slash = rest.find('/')?;
+        let (authority, path) = rest.split_at(slash);
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, "")) => (host, None),
+            Some((host, port)) => (host, Some(port.parse().ok()?)),
+            None if url.starts_with("ssh://") => (authority, None),
+            None => return None,
+        };
+        // A host starting with `-` would be read by ssh as an option.
+        let valid = !host.is_empty() && !host.starts_with('-') && !path.is_empty();
+        valid.then(|| Remote {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn spawn(&self, command: &str) -> io::Result<Output> {
+        let mut ssh = Command::new("ssh");
+        if let Some(port) = self.port {
+            ssh.arg("-p").arg(port.to_string());
+        }
+        let mut child = ssh
+            .arg("--")
+            .arg(&self.host)
+            .arg(format!("{} -- {}", command, quote(&self.path)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("could not run ssh: {}", e)))?;
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        Ok(Output {
+            child,
+            stdout,
+            finished: false,
+        })
+    }
+
+    /// The file's contents, decrypted and decompressed like a local file.
+    pub fn open(&self) -> io::Result<Box<dyn BufRead>> {
+        export::decompress(crypt::decrypt(self.spawn("cat")?)?)
+    }
+
+    /// Calls `each` with the file's entries and then with those of lines
+    /// appended to it, following it across rotation like `tail -F`.
+    pub fn follow(&self, mut each: impl FnMut(LogEntry) -> io::Result<()>) -> io::Result<()> {
+        for line in self.spawn("tail -F -n +1")?.lines() {
+            let mut analyzer = LogAnalyzer::new();
+            analyzer.load_from_reader(line?.as_bytes())?;
+            for mut entry in analyzer.entries {
+                // Every line is read on its own, so they would all be line 1.
+                if let Some(source) = &mut entry.source {
+                    source.line = None;
+                }
+                each(entry)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The remote command's output; a failed command, such as one for a
+/// missing file, is an error at the end of the output.
+struct Output {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    finished: bool,
+}
+
+impl Output {
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        let status = self.child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("ssh failed: {}", status)))
+        }
+    }
+}
+
+impl Read for Output {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.stdout.read(buf)?;
+        if read == 0 {
+            self.finish()?;
+        }
+        Ok(read)
+    }
+}
+
+impl BufRead for Output {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.stdout.fill_buf()?.is_empty() {
+            self.finish()?;
+        }
+        self.stdout.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.stdout.consume(amount);
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::io::{self, BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::LogEntry;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum RestartPolicy {
+    Never,
+    OnFailure,
+    Always,
+}
+
+pub struct Supervisor {
+    pub command: Vec<String>,
+    pub policy: RestartPolicy,
+    /// Give up after this many restarts; `None` keeps going forever.
+    pub max_restarts: Option<u32>,
+    pub log_file: String,
+}
+
+// A line of child output. Lines in a format the analyzer understands keep
+// their own level and fields; anything else becomes an INFO entry.
+fn output_entry(line: &str, stream: &str) -> LogEntry {
+    let mut entry = LogEntry::from_line(line).unwrap_or_else(|| LogEntry {
+        timestamp: crate::now_timestamp(),
+        level: "INFO".to_string(),
+        message: line.to_string(),
+        fields: Map::new(),
+        source: None,
+    });
+    entry
+        .fields
+        .insert("stream".to_string(), Value::from(stream));
+    entry
+}
+
+fn capture(
+    reader: impl io::Read + Send + 'static,
+    stream: &'static str,
+    sender: mpsc::Sender<LogEntry>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            // Through the pipeline like other ingested lines, so redaction
+            // and the configured stages apply before it is logged.
+            let entry = crate::ingest(output_entry(&line, stream), Some(&line), Vec::new());
+            if !crate::emitting() {
+                if stream == "stderr" {
+                    eprintln!("{}", line);
+                } else {
+                    println!("{}", line);
+                }
+            }
+            let Some(entry) = entry else {
+                continue;
+            };
+            if crate::emitting() {
+                crate::emit(&entry);
+            }
+            if sender.send(entry).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+impl Supervisor {
+    fn append(&self, entry: &LogEntry) -> io::Result<()> {
+        // Output lines were emitted as they were read.
+        if entry.fields.get("stream").is_none() {
+            crate::emit(entry);
+        }
+        crate::rotate::before_append(&self.log_file)?;
+        crate::LogAnalyzer::append_to_file(&self.log_file, std::slice::from_ref(entry))
+    }
+
+    /// Runs the child once, logging its output and, through the `tracing`
+    /// layer installed for the log file, its lifecycle in an `attempt`
+    /// span. Returns the exit code, or `None` if it was killed by a signal.
+    fn run_once(&self, attempt: u32) -> io::Result<Option<i32>> {
+        let mut child = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let span = tracing::info_span!(
+            target: "supervise",
+            "attempt",
+            attempt,
+            command = self.command.join(" "),
+            pid = child.id()
+        );
+        let _attempt = span.enter();
+        tracing::info!(
+            target: "supervise",
+            event = "start",
+            "Started {} (pid {})",
+            self.command[0],
+            child.id()
+        );
+
+        let (sender, receiver) = mpsc::channel();
+        let readers = [
+            capture(child.stdout.take().unwrap(), "stdout", sender.clone()),
+            capture(child.stderr.take().unwrap(), "stderr", sender),
+        ];
+        // Ends once both pipes are closed, i.e. the child is gone.
+        for entry in receiver {
+            self.append(&entry)?;
+        }
+        for reader in readers {
+            let _ = reader.join();
+        }
+        let status = child.wait()?;
+
+        let code = status.code();
+        match code {
+            Some(0) => tracing::info!(
+                target: "supervise", event = "exit", exit_code = 0,
+                "{} exited with code 0", self.command[0]
+            ),
+            Some(exit) => tracing::error!(
+                target: "supervise", event = "exit", exit_code = exit,
+                "{} exited with code {}", self.command[0], exit
+            ),
+            None => tracing::error!(
+                target: "supervise", event = "exit",
+                "{} was killed by a signal", self.command[0]
+            ),
+        }
+        Ok(status.code())
+    }
+
+    /// Supervises the child according to the restart policy, backing off
+    /// from one second up to 30 between restarts. Returns the last exit code.
+    pub fn run(&self) -> io::Result<i32> {
+        let mut restarts = 0;
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            let code = self.run_once(restarts + 1)?;
+            let failed = code != Some(0);
+            let restart = match self.policy {
+                RestartPolicy::Never => false,
+                RestartPolicy::OnFailure => failed,
+                RestartPolicy::Always => true,
+            };
+            if !restart || self.max_restarts.is_some_and(|max| restarts >= max) {
+                return Ok(code.unwrap_or(1));
+            }
+
+            restarts += 1;
+            tracing::warn!(
+                target: "supervise", event = "restart", restarts,
+                "Restarting {} in {}s (restart {})",
+                self.command[0],
+                backoff.as_secs(),
+                restarts
+            );
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(Duration::from_secs(30));
+        }
+    }
+}
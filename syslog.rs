@@ -0,0 +1,263 @@
+//! Syslog messages: RFC 5424 out, RFC 5424 and RFC 3164 in.
+
+use std::io::{self, Write};
+use std::net::{TcpStream, UdpSocket};
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone};
+use serde_json::{Map, Value};
+
+use crate::sink::Sink;
+use crate::LogEntry;
+
+/// The `user-level messages` facility.
+const FACILITY: u8 = 1;
+
+/// Example enterprise number RFC 5612 sets aside, for the fields' SD-ID.
+const SD_ID: &str = "fields@32473";
+
+/// The syslog severity for `level`; custom levels get that of the built-in
+/// level as severe.
+pub fn severity(level: &str) -> u8 {
+    match crate::levels::standard(level).as_str() {
+        "EMERGENCY" | "EMERG" => 0,
+        "ALERT" => 1,
+        "FATAL" | "CRITICAL" | "CRIT" => 2,
+        "ERROR" | "ERR" => 3,
+        "WARNING" | "WARN" => 4,
+        "NOTICE" => 5,
+        "DEBUG" | "TRACE" => 7,
+        _ => 6,
+    }
+}
+
+/// The level for a syslog severity.
+pub fn level(severity: u8) -> &'static str {
+    match severity {
+        0 | 1 => "FATAL",
+        2 => "CRITICAL",
+        3 => "ERROR",
+        4 => "WARNING",
+        7 => "DEBUG",
+        _ => "INFO",
+    }
+}
+
+// Header fields are printable ASCII without spaces, at most `max` long;
+// "-" when empty.
+fn header_field(text: &str, max: usize) -> String {
+    let text: String = text
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max)
+        .collect();
+    if text.is_empty() {
+        "-".to_string()
+    } else {
+        text
+    }
+}
+
+// SD-NAMEs also exclude '=', ']' and '"'.
+fn param_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_graphic() && !"=]\"".contains(*c))
+        .take(32)
+        .collect()
+}
+
+fn param_value(value: &Value) -> String {
+    crate::query::render(value)
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace(']', "\\]")
+}
+
+/// The entry as a syslog message. The app name and process ID come from
+/// the `source` and `pid` fields, the other fields become structured data.
+pub fn format(entry: &LogEntry, hostname: &str) -> String {
+    let time = entry
+        .time()
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .map_or_else(
+            || "-".to_string(),
+            |t| t.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, false),
+        );
+    let field = |name: &str| entry.fields.get(name).map(crate::query::render);
+    let app = field("source").unwrap_or_else(|| "logger".to_string());
+    let pid = field("pid").unwrap_or_default();
+    let params: Vec<String> = entry
+        .fields
+        .iter()
+        .filter(|(name, _)| !matches!(name.as_str(), "source" | "pid"))
+        .map(|(name, value)| (param_name(name), value))
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, value)| format!(" {}=\"{}\"", name, param_value(value)))
+        .collect();
+    let data = if params.is_empty() {
+        "-".to_string()
+    } else {
+        format!("[{}{}]", SD_ID, params.concat())
+    };
+    format!(
+        "<{}>1 {} {} {} {} - {} {}",
+        FACILITY * 8 + severity(&entry.level),
+        time,
+        header_field(hostname, 255),
+        header_field(&app, 48),
+        header_field(&pid, 128),
+        data,
+        entry.message
+    )
+}
+
+enum Transport {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+/// Forwards entries to a syslog server.
+pub struct Forwarder {
+    transport: Transport,
+    hostname: String,
+}
+
+impl Forwarder {
+    /// Connects to `address` (host:port), over TCP with octet-counting
+    /// framing (RFC 6587) or as one UDP datagram per message.
+    pub fn connect(address: &str, tcp: bool) -> io::Result<Forwarder> {
+        let transport = if tcp {
+            Transport::Tcp(TcpStream::connect(address)?)
+        } else {
+            Transport::Udp(crate::sink::udp_socket(address)?)
+        };
+        Ok(Forwarder {
+            transport,
+            hostname: crate::sink::hostname(),
+        })
+    }
+}
+
+impl Sink for Forwarder {
+    fn send(&mut self, entries: &[LogEntry]) -> io::Result<()> {
+        for entry in entries {
+            let message = format(entry, &self.hostname);
+            match &mut self.transport {
+                Transport::Udp(socket) => {
+                    crate::sink::send_datagram(socket, message.as_bytes())?;
+                }
+                Transport::Tcp(stream) => write!(stream, "{} {}", message.len(), message)?,
+            }
+        }
+        if let Transport::Tcp(stream) = &mut self.transport {
+            stream.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn stamp(time: DateTime<Local>) -> String {
+    time.format(crate::timestamp_format()).to_string()
+}
+
+// `[id name="value" ...][id2 ...]` and the rest of the message. Escaped
+// `\"`, `\\` and `\]` are unescaped.
+fn structured_data(mut text: &str, fields: &mut Map<String, Value>) -> Option<String> {
+    if let Some(rest) = text.strip_prefix("- ") {
+        return Some(rest.to_string());
+    }
+    if text == "-" {
+        return Some(String::new());
+    }
+    while let Some(rest) = text.strip_prefix('[') {
+        let mut params = &rest[rest.find([' ', ']'])?..];
+        loop {
+            params = params.trim_start_matches(' ');
+            if let Some(rest) = params.strip_prefix(']') {
+                text = rest;
+                break;
+            }
+            let (name, rest) = params.split_once("=\"")?;
+            let mut value = String::new();
+            let mut chars = rest.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (_, '\\') => value.extend(chars.next().map(|(_, c)| c)),
+                    (i, '"') => break i,
+                    (_, c) => value.push(c),
+                }
+            };
+            fields.insert(name.to_string(), Value::from(value));
+            params = &rest[end + 1..];
+        }
+    }
+    Some(text.strip_prefix(' ').unwrap_or(text).to_string())
+}
+
+/// Parses a received syslog message, RFC 5424 or the older BSD format
+/// (RFC 3164). The hostname, app name and process ID become `host`,
+/// `source` and `pid` fields, and structured data becomes fields too.
+/// Messages without a timestamp are stamped with the current time.
+pub fn parse(message: &str) -> Option<LogEntry> {
+    let message = message.trim_end_matches(['\r', '\n', '\0']);
+    let (pri, rest) = message.strip_prefix('<')?.split_once('>')?;
+    let pri: u8 = pri.parse().ok().filter(|&pri| pri < 192)?;
+    let mut fields = Map::new();
+    fields.insert("facility".to_string(), Value::from(pri / 8));
+    let mut field = |name: &str, value: &str| {
+        if value != "-" && !value.is_empty() {
+            fields.insert(name.to_string(), Value::from(value));
+        }
+    };
+    let (timestamp, message) = if let Some(rest) = rest.strip_prefix("1 ") {
+        let mut parts = rest.splitn(6, ' ');
+        let time = parts.next()?;
+        field("host", parts.next()?);
+        field("source", parts.next()?);
+        field("pid", parts.next()?);
+        field("msgid", parts.next()?);
+        let time = DateTime::parse_from_rfc3339(time)
+            .map(|t| t.with_timezone(&Local))
+            .unwrap_or_else(|_| Local::now());
+        let message = structured_data(parts.next().unwrap_or("-"), &mut fields)?;
+        (stamp(time), message)
+    } else {
+        // `Mmm dd hh:mm:ss host tag[pid]: message`, without a year.
+        let year = Local::now().year();
+        let time = rest
+            .get(..15)
+            .and_then(|t| {
+                NaiveDateTime::parse_from_str(&format!("{} {}", year, t), "%Y %b %e %H:%M:%S").ok()
+            })
+            .and_then(|t| Local.from_local_datetime(&t).earliest());
+        let rest = match time {
+            Some(_) => rest[15..].trim_start(),
+            None => rest,
+        };
+        let (host, rest) = match time {
+            Some(_) => rest.split_once(' ').unwrap_or((rest, "")),
+            None => ("", rest),
+        };
+        field("host", host);
+        let rest = match rest.split_once(": ") {
+            Some((tag, message)) if !tag.contains(' ') => {
+                match tag.strip_suffix(']').and_then(|t| t.split_once('[')) {
+                    Some((app, pid)) => {
+                        field("source", app);
+                        field("pid", pid);
+                    }
+                    None => field("source", tag),
+                }
+                message
+            }
+            _ => rest,
+        };
+        (stamp(time.unwrap_or_else(Local::now)), rest.to_string())
+    };
+    Some(LogEntry {
+        timestamp,
+        level: level(pri % 8).to_string(),
+        message,
+        fields,
+        source: None,
+    })
+}
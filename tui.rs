@@ -0,0 +1,275 @@
+use std::collections::BTreeSet;
+use std::io;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::alert::severity_rank;
+use crate::{export, LogEntry};
+
+/// Level groups toggled with F1..F4, by severity rank.
+const LEVEL_GROUPS: [(&str, &[u8]); 4] = [
+    ("ERROR+", &[3, 4]),
+    ("WARNING", &[2]),
+    ("INFO", &[1]),
+    ("DEBUG", &[0]),
+];
+
+struct App<'a> {
+    entries: &'a [LogEntry],
+    filter: String,
+    shown_levels: [bool; 4],
+    /// Indices into `entries` marked with Ctrl-B.
+    bookmarks: BTreeSet<usize>,
+    only_bookmarks: bool,
+    /// Indices into `entries` passing the filter and level toggles.
+    visible: Vec<usize>,
+    list: ListState,
+}
+
+impl<'a> App<'a> {
+    fn new(entries: &'a [LogEntry]) -> App<'a> {
+        let mut app = App {
+            entries,
+            filter: String::new(),
+            shown_levels: [true; 4],
+            bookmarks: BTreeSet::new(),
+            only_bookmarks: false,
+            visible: Vec::new(),
+            list: ListState::default(),
+        };
+        app.refilter();
+        app
+    }
+
+    fn refilter(&mut self) {
+        let needle = self.filter.to_lowercase();
+        self.visible = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.only_bookmarks || self.bookmarks.contains(i))
+            .filter(|(_, e)| {
+                let rank = severity_rank(&e.level);
+                let level_shown = LEVEL_GROUPS
+                    .iter()
+                    .zip(self.shown_levels)
+                    .any(|((_, ranks), shown)| shown && ranks.contains(&rank));
+                level_shown && (needle.is_empty() || e.message.to_lowercase().contains(&needle))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let selected = self.list.selected().unwrap_or(0);
+        self.list.select(if self.visible.is_empty() {
+            None
+        } else {
+            Some(selected.min(self.visible.len() - 1))
+        });
+    }
+
+    fn selected(&self) -> Option<&LogEntry> {
+        self.list
+            .selected()
+            .and_then(|i| self.visible.get(i))
+            .map(|&i| &self.entries[i])
+    }
+
+    fn toggle_bookmark(&mut self) {
+        if let Some(&index) = self.list.selected().and_then(|i| self.visible.get(i)) {
+            if !self.bookmarks.remove(&index) {
+                self.bookmarks.insert(index);
+            }
+            if self.only_bookmarks {
+                self.refilter();
+            }
+        }
+    }
+
+    /// Selects the next (or previous) visible bookmark, wrapping around.
+    fn jump_bookmark(&mut self, forward: bool) {
+        let marked: Vec<usize> = (0..self.visible.len())
+            .filter(|&i| self.bookmarks.contains(&self.visible[i]))
+            .collect();
+        let current = self.list.selected().unwrap_or(0);
+        let target = if forward {
+            marked.iter().find(|&&i| i > current).or(marked.first())
+        } else {
+            marked
+                .iter()
+                .rev()
+                .find(|&&i| i < current)
+                .or(marked.last())
+        };
+        if let Some(&i) = target {
+            self.list.select(Some(i));
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let current = self.list.selected().unwrap_or(0) as isize;
+        let last = self.visible.len() as isize - 1;
+        self.list
+            .select(Some((current + delta).clamp(0, last) as usize));
+    }
+}
+
+fn level_color(level: &str) -> Style {
+    match severity_rank(level) {
+        4 => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        3 => Style::default().fg(Color::Red),
+        2 => Style::default().fg(Color::Yellow),
+        _ => Style::default().add_modifier(Modifier::DIM),
+    }
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [main, sidebar] =
+        Layout::horizontal([Constraint::Min(40), Constraint::Length(30)]).areas(frame.area());
+    let [filter_area, list_area, detail_area] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(5),
+        Constraint::Length(10),
+    ])
+    .areas(main);
+
+    let filter = Paragraph::new(format!("{}_", app.filter)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Filter (type to search, Esc quits) "),
+    );
+    frame.render_widget(filter, filter_area);
+
+    let items: Vec<ListItem> = app
+        .visible
+        .iter()
+        .map(|&i| {
+            let e = &app.entries[i];
+            let mark = if app.bookmarks.contains(&i) {
+                "* "
+            } else {
+                "  "
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(mark, Style::default().fg(Color::Cyan)),
+                Span::raw(format!("[{}] ", crate::display::timestamp(e))),
+                Span::styled(format!("{:<7}", e.level), level_color(&e.level)),
+                Span::raw(format!(" {}", e.message)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            " Entries ({} of {}) ",
+            app.visible.len(),
+            app.entries.len()
+        )))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, list_area, &mut app.list);
+
+    let detail = match app.selected() {
+        Some(entry) => serde_json::to_string_pretty(&export::entry_json(entry)).unwrap(),
+        None => "No entry selected".to_string(),
+    };
+    frame.render_widget(
+        Paragraph::new(detail)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title(" Detail ")),
+        detail_area,
+    );
+
+    let mut lines = vec![
+        Line::from(format!("Total:  {}", app.entries.len())),
+        Line::from(format!("Shown:  {}", app.visible.len())),
+        Line::from(""),
+    ];
+    for (i, ((name, ranks), shown)) in LEVEL_GROUPS.iter().zip(app.shown_levels).enumerate() {
+        let count = app
+            .visible
+            .iter()
+            .filter(|&&e| ranks.contains(&severity_rank(&app.entries[e].level)))
+            .count();
+        let mark = if shown { "x" } else { " " };
+        lines.push(Line::from(format!(
+            "F{} [{}] {:<8} {}",
+            i + 1,
+            mark,
+            name,
+            count
+        )));
+    }
+    let mark = if app.only_bookmarks { "x" } else { " " };
+    lines.push(Line::from(format!(
+        "F5 [{}] Bookmarks {}",
+        mark,
+        app.bookmarks.len()
+    )));
+    lines.push(Line::from(""));
+    lines.push(Line::from("Up/Down, PgUp/PgDn, Home/End"));
+    lines.push(Line::from("Ctrl-B mark, Tab/S-Tab jump"));
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Stats ")),
+        sidebar,
+    );
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, app))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.toggle_bookmark()
+            }
+            KeyCode::Tab => app.jump_bookmark(true),
+            KeyCode::BackTab => app.jump_bookmark(false),
+            KeyCode::F(5) => {
+                app.only_bookmarks = !app.only_bookmarks;
+                app.refilter();
+            }
+            KeyCode::Up => app.move_by(-1),
+            KeyCode::Down => app.move_by(1),
+            KeyCode::PageUp => app.move_by(-20),
+            KeyCode::PageDown => app.move_by(20),
+            KeyCode::Home => app.move_by(isize::MIN / 2),
+            KeyCode::End => app.move_by(isize::MAX / 2),
+            KeyCode::F(n @ 1..=4) => {
+                let group = n as usize - 1;
+                app.shown_levels[group] = !app.shown_levels[group];
+                app.refilter();
+            }
+            KeyCode::Backspace => {
+                app.filter.pop();
+                app.refilter();
+            }
+            KeyCode::Char(c) => {
+                app.filter.push(c);
+                app.refilter();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Full-screen browser: live filter, level toggles, bookmarks, detail pane
+/// and stats.
+pub fn run(entries: &[LogEntry]) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let mut app = App::new(entries);
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,135 @@
+//! Browser, operating system and bot detection from User-Agent headers,
+//! for the traffic breakdown of access logs.
+
+/// What a User-Agent says about the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agent {
+    /// `Chrome 120`, `Googlebot`, `curl`, or `Other`.
+    pub browser: String,
+    /// `Windows 10`, `Android 14`, `iOS`, ... or `Other`.
+    pub os: String,
+    /// Crawlers, monitors and command-line clients.
+    pub bot: bool,
+}
+
+// Substrings (lowercase) of clients that are not people browsing.
+const BOT_MARKERS: [&str; 16] = [
+    "bot",
+    "crawler",
+    "spider",
+    "slurp",
+    "curl/",
+    "wget/",
+    "python-requests",
+    "python-urllib",
+    "go-http-client",
+    "okhttp",
+    "java/",
+    "libwww",
+    "headlesschrome",
+    "pingdom",
+    "uptimerobot",
+    "facebookexternalhit",
+];
+
+// The version after `name/`, up to the first dot: `Chrome/120.0` is 120.
+fn major(ua: &str, name: &str) -> Option<String> {
+    let rest = &ua[ua.find(name)? + name.len()..];
+    let version: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    (!version.is_empty()).then_some(version)
+}
+
+fn named(name: &str, version: Option<String>) -> String {
+    match version {
+        Some(version) => format!("{} {}", name, version),
+        None => name.to_string(),
+    }
+}
+
+// The product token naming a bot, e.g. `Googlebot` in
+// `Mozilla/5.0 (compatible; Googlebot/2.1; +http://...)`.
+fn bot_name(ua: &str) -> Option<String> {
+    ua.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .find(|token| {
+            let lower = token.to_lowercase();
+            lower.ends_with("bot") || lower.ends_with("crawler") || lower.ends_with("spider")
+        })
+        .map(String::from)
+}
+
+fn browser(ua: &str, bot: bool) -> String {
+    let tokens: [(&str, &str); 10] = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("SamsungBrowser/", "Samsung Internet"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("CriOS/", "Chrome"),
+        ("FxiOS/", "Firefox"),
+        ("curl/", "curl"),
+        ("Wget/", "Wget"),
+        ("python-requests/", "Python Requests"),
+    ];
+    if let Some(name) = bot.then(|| bot_name(ua)).flatten() {
+        return name;
+    }
+    if let Some((token, name)) = tokens.iter().find(|(token, _)| ua.contains(token)) {
+        return named(name, major(ua, token));
+    }
+    if ua.contains("Safari/") {
+        return named("Safari", major(ua, "Version/"));
+    }
+    if ua.contains("Trident/") || ua.contains("MSIE ") {
+        return named(
+            "Internet Explorer",
+            major(ua, "MSIE ").or_else(|| major(ua, "rv:")),
+        );
+    }
+    "Other".to_string()
+}
+
+fn os(ua: &str) -> String {
+    if let Some(start) = ua.find("Windows NT ") {
+        let version: String = ua[start + 11..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == '.')
+            .collect();
+        let release = match version.as_str() {
+            "10.0" => "10",
+            "6.3" => "8.1",
+            "6.2" => "8",
+            "6.1" => "7",
+            other => other,
+        };
+        return format!("Windows {}", release).trim_end().to_string();
+    }
+    if ua.contains("Windows") {
+        return "Windows".to_string();
+    }
+    if ua.contains("Android") {
+        return named("Android", major(ua, "Android "));
+    }
+    if ua.contains("iPhone") || ua.contains("iPad") || ua.contains("iPod") {
+        return named("iOS", major(ua, "OS "));
+    }
+    if ua.contains("CrOS") {
+        return "ChromeOS".to_string();
+    }
+    if ua.contains("Mac OS X") || ua.contains("Macintosh") {
+        return "macOS".to_string();
+    }
+    if ua.contains("Linux") || ua.contains("X11") {
+        return "Linux".to_string();
+    }
+    "Other".to_string()
+}
+
+pub fn parse(ua: &str) -> Agent {
+    let lower = ua.to_lowercase();
+    let bot = ua.trim().is_empty() || BOT_MARKERS.iter().any(|marker| lower.contains(marker));
+    Agent {
+        browser: browser(ua, bot),
+        os: os(ua),
+        bot,
+    }
+}
@@ -0,0 +1,129 @@
+//! `logger watch`: a stats screen redrawn every few seconds while a file
+//! is followed, like `watch -n2` over `logger stats`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, IsTerminal, Write};
+use std::thread;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+
+use crate::follow::Follower;
+use crate::{color, LogEntry};
+
+pub struct Options {
+    /// Seconds between redraws.
+    pub interval: u64,
+    /// How far back the per-level rates look.
+    pub window: chrono::Duration,
+    /// How many of the newest errors to show.
+    pub errors: usize,
+}
+
+/// What the screen shows, kept up to date as entries arrive; entries
+/// themselves are not kept, so a long watch stays small.
+struct Totals {
+    levels: BTreeMap<String, usize>,
+    /// Time and level of the entries still inside the window.
+    recent: VecDeque<(NaiveDateTime, String)>,
+    errors: VecDeque<LogEntry>,
+    started: NaiveDateTime,
+}
+
+impl Totals {
+    fn add(&mut self, entries: Vec<LogEntry>, options: &Options) {
+        let now = chrono::Local::now().naive_local();
+        for entry in entries {
+            *self.levels.entry(entry.level.clone()).or_insert(0) += 1;
+            // Lines without a timestamp count as arriving now.
+            self.recent
+                .push_back((entry.time().unwrap_or(now), entry.level.clone()));
+            if crate::report::is_error(&entry) {
+                self.errors.push_back(entry);
+                if self.errors.len() > options.errors {
+                    self.errors.pop_front();
+                }
+            }
+        }
+        let start = now
+            .checked_sub_signed(options.window)
+            .unwrap_or(NaiveDateTime::MIN);
+        self.recent.retain(|(time, _)| *time > start);
+    }
+
+    fn draw(&self, path: &str, options: &Options, out: &mut impl Write) -> io::Result<()> {
+        let now = chrono::Local::now().naive_local();
+        if io::stdout().is_terminal() {
+            // Home the cursor and clear, so the screen redraws in place.
+            write!(out, "\x1b[H\x1b[2J")?;
+        } else {
+            writeln!(out)?;
+        }
+        writeln!(
+            out,
+            "{}  every {}s  {}  (watching since {})",
+            path,
+            options.interval,
+            now.format(crate::TIMESTAMP_FORMAT),
+            self.started.format("%H:%M:%S")
+        )?;
+        let total: usize = self.levels.values().sum();
+        let minutes = (options.window.num_seconds().max(1) as f64) / 60.0;
+        writeln!(out, "\nTotal entries: {}", total)?;
+        writeln!(
+            out,
+            "{:<10} {:>10} {:>12} {:>10}",
+            "level",
+            "total",
+            format!("last {}", short(options.window)),
+            "per min"
+        )?;
+        for (level, count) in &self.levels {
+            let recent = self.recent.iter().filter(|(_, l)| l == level).count();
+            writeln!(
+                out,
+                "{:<10} {:>10} {:>12} {:>10.1}",
+                level,
+                count,
+                recent,
+                recent as f64 / minutes
+            )?;
+        }
+        if !self.errors.is_empty() {
+            writeln!(out, "\nLast errors:")?;
+            for entry in &self.errors {
+                writeln!(out, "{}", color::entry_line(entry, None))?;
+            }
+        }
+        out.flush()
+    }
+}
+
+// `90s`, `5m` or `2h`, whichever unit divides the window.
+fn short(window: chrono::Duration) -> String {
+    let seconds = window.num_seconds();
+    if seconds % 3600 == 0 {
+        format!("{}h", seconds / 3600)
+    } else if seconds % 60 == 0 {
+        format!("{}m", seconds / 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Follows `path` from its start and redraws the screen every
+/// `options.interval` seconds, until reading fails or the user stops it.
+pub fn run(path: &str, options: &Options) -> io::Result<()> {
+    let mut follower = Follower::from_start(path);
+    let mut totals = Totals {
+        levels: BTreeMap::new(),
+        recent: VecDeque::new(),
+        errors: VecDeque::new(),
+        started: chrono::Local::now().naive_local(),
+    };
+    loop {
+        totals.add(follower.poll()?, options);
+        totals.draw(path, options, &mut io::stdout().lock())?;
+        thread::sleep(Duration::from_secs(options.interval.max(1)));
+    }
+}